@@ -14,6 +14,7 @@ use http_body_util::{BodyExt, Full, StreamBody};
 use tokio::sync::oneshot;
 
 use hyper::body::Frame;
+use hyper::rt::TokioIo;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::Response;
@@ -38,10 +39,11 @@ macro_rules! bench_server {
                 rt.spawn(async move {
                     loop {
                         let (stream, _) = listener.accept().await.expect("accept");
+                        let io = TokioIo::new(stream);
 
                         http1::Builder::new()
                             .serve_connection(
-                                stream,
+                                io,
                                 service_fn(|_| async {
                                     Ok::<_, hyper::Error>(
                                         Response::builder()
@@ -1,2 +1 @@
-mod tokiort;
-pub use tokiort::{TokioExecutor, TokioTimer};
+pub use hyper::rt::{TokioExecutor, TokioTimer};
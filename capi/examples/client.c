@@ -190,6 +190,9 @@ int main(int argc, char *argv[]) {
     // In case a task errors...
     hyper_error *err;
 
+    // The body of the response, freed once its data was printed.
+    hyper_body *resp_body = NULL;
+
     // The polling state machine!
     while (1) {
         // Poll all ready tasks and act on them...
@@ -260,7 +263,7 @@ int main(int argc, char *argv[]) {
                 hyper_headers_foreach(headers, print_each_header, NULL);
                 printf("\n");
 
-                hyper_body *resp_body = hyper_response_body(resp);
+                resp_body = hyper_response_body(resp);
                 hyper_task *foreach = hyper_body_foreach(resp_body, print_each_chunk, NULL);
                 hyper_task_set_userdata(foreach, (void *)EXAMPLE_RESP_BODY);
                 hyper_executor_push(exec, foreach);
@@ -283,6 +286,7 @@ int main(int argc, char *argv[]) {
 
                 // Cleaning up before exiting
                 hyper_task_free(task);
+                hyper_body_free(resp_body);
                 hyper_executor_free(exec);
                 free_conn_data(conn);
 
@@ -0,0 +1,352 @@
+#include <stdlib.h>
+#include <stdio.h>
+#include <unistd.h>
+#include <fcntl.h>
+#include <errno.h>
+#include <sys/select.h>
+#include <assert.h>
+
+#include <sys/types.h>
+#include <sys/socket.h>
+#include <netdb.h>
+#include <string.h>
+
+#include "hyper.h"
+
+#define MAX_CONNS 64
+
+static const char *RESPONSE_BODY = "Hello, World!\n";
+
+struct conn_data {
+    int fd;
+    hyper_waker *read_waker;
+    hyper_waker *write_waker;
+    int body_sent;
+};
+
+static size_t read_cb(void *userdata, hyper_context *ctx, uint8_t *buf, size_t buf_len) {
+    struct conn_data *conn = (struct conn_data *)userdata;
+    ssize_t ret = read(conn->fd, buf, buf_len);
+
+    if (ret >= 0) {
+        return ret;
+    }
+
+    if (errno != EAGAIN) {
+        // kaboom
+        return HYPER_IO_ERROR;
+    }
+
+    // would block, register interest
+    if (conn->read_waker != NULL) {
+        hyper_waker_free(conn->read_waker);
+    }
+    conn->read_waker = hyper_context_waker(ctx);
+    return HYPER_IO_PENDING;
+}
+
+static size_t write_cb(void *userdata, hyper_context *ctx, const uint8_t *buf, size_t buf_len) {
+    struct conn_data *conn = (struct conn_data *)userdata;
+    ssize_t ret = write(conn->fd, buf, buf_len);
+
+    if (ret >= 0) {
+        return ret;
+    }
+
+    if (errno != EAGAIN) {
+        // kaboom
+        return HYPER_IO_ERROR;
+    }
+
+    // would block, register interest
+    if (conn->write_waker != NULL) {
+        hyper_waker_free(conn->write_waker);
+    }
+    conn->write_waker = hyper_context_waker(ctx);
+    return HYPER_IO_PENDING;
+}
+
+static void free_conn_data(struct conn_data *conn) {
+    if (conn->read_waker) {
+        hyper_waker_free(conn->read_waker);
+        conn->read_waker = NULL;
+    }
+    if (conn->write_waker) {
+        hyper_waker_free(conn->write_waker);
+        conn->write_waker = NULL;
+    }
+
+    close(conn->fd);
+    free(conn);
+}
+
+static int listen_on(const char *host, const char *port) {
+    struct addrinfo hints;
+    memset(&hints, 0, sizeof(struct addrinfo));
+    hints.ai_family = AF_UNSPEC;
+    hints.ai_socktype = SOCK_STREAM;
+    hints.ai_flags = AI_PASSIVE;
+
+    struct addrinfo *result, *rp;
+    if (getaddrinfo(host, port, &hints, &result) != 0) {
+        printf("dns failed for %s\n", host);
+        return -1;
+    }
+
+    int sfd;
+    for (rp = result; rp != NULL; rp = rp->ai_next) {
+        sfd = socket(rp->ai_family, rp->ai_socktype, rp->ai_protocol);
+        if (sfd == -1) {
+            continue;
+        }
+
+        int reuse = 1;
+        setsockopt(sfd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse));
+
+        if (bind(sfd, rp->ai_addr, rp->ai_addrlen) == 0 && listen(sfd, 16) == 0) {
+            break;
+        }
+
+        close(sfd);
+    }
+
+    freeaddrinfo(result);
+
+    // no address succeeded
+    if (rp == NULL) {
+        printf("listen failed for %s\n", host);
+        return -1;
+    }
+
+    return sfd;
+}
+
+static int print_each_header(void *userdata,
+                             const uint8_t *name,
+                             size_t name_len,
+                             const uint8_t *value,
+                             size_t value_len) {
+    printf("%.*s: %.*s\n", (int) name_len, name, (int) value_len, value);
+    return HYPER_ITER_CONTINUE;
+}
+
+static int poll_resp_body(void *userdata, hyper_context *ctx, hyper_buf **chunk) {
+    struct conn_data *conn = (struct conn_data *)userdata;
+
+    if (conn->body_sent) {
+        // all sent, end the body
+        *chunk = NULL;
+    } else {
+        *chunk = hyper_buf_copy((const uint8_t *)RESPONSE_BODY, strlen(RESPONSE_BODY));
+        conn->body_sent = 1;
+    }
+
+    return HYPER_POLL_READY;
+}
+
+#define STR_ARG(XX) (uint8_t *)XX, strlen(XX)
+
+static void handle_request(void *userdata, hyper_request *req, hyper_response_channel *channel) {
+    struct conn_data *conn = (struct conn_data *)userdata;
+
+    const uint8_t *method, *path;
+    size_t method_len, path_len;
+    hyper_request_method(req, &method, &method_len);
+    hyper_request_uri_parts(req, NULL, NULL, NULL, NULL, &path, &path_len);
+
+    printf("\nRequest on fd %d: %.*s %.*s\n", conn->fd,
+           (int) method_len, method, (int) path_len, path);
+    hyper_headers *req_headers = hyper_request_headers(req);
+    hyper_headers_foreach(req_headers, print_each_header, NULL);
+
+    // Done with the request, this example doesn't read its body
+    hyper_request_free(req);
+
+    // Prepare the response
+    hyper_response *resp = hyper_response_new();
+    hyper_response_set_status(resp, 200);
+
+    hyper_headers *resp_headers = hyper_response_headers(resp);
+    hyper_headers_set(resp_headers, STR_ARG("Content-Type"), STR_ARG("text/plain"));
+
+    conn->body_sent = 0;
+    hyper_body *body = hyper_body_new();
+    hyper_body_set_userdata(body, (void *)conn);
+    hyper_body_set_data_func(body, poll_resp_body);
+    hyper_response_set_body(resp, body);
+
+    // Send it!
+    hyper_response_channel_send(channel, resp);
+}
+
+int main(int argc, char *argv[]) {
+    const char *host = argc > 1 ? argv[1] : "127.0.0.1";
+    const char *port = argc > 2 ? argv[2] : "1234";
+    printf("listening on port %s on %s...\n", port, host);
+
+    int listen_fd = listen_on(host, port);
+    if (listen_fd < 0) {
+        return 1;
+    }
+
+    if (fcntl(listen_fd, F_SETFL, O_NONBLOCK) != 0) {
+        printf("failed to set socket to non-blocking\n");
+        return 1;
+    }
+
+    printf("serving with hyper v%s ...\n", hyper_version());
+
+    fd_set fds_read;
+    fd_set fds_write;
+    fd_set fds_excep;
+
+    struct conn_data *conns[MAX_CONNS] = { NULL };
+
+    // We need an executor generally to poll futures
+    const hyper_executor *exec = hyper_executor_new();
+
+    // Prepare server options, they are shared by all connections
+    hyper_serverconn_options *opts = hyper_serverconn_options_new();
+    hyper_serverconn_options_exec(opts, exec);
+
+    // The polling state machine!
+    while (1) {
+        // Poll all ready tasks and act on them...
+        while (1) {
+            hyper_task *task = hyper_executor_poll(exec);
+            if (!task) {
+                break;
+            }
+
+            struct conn_data *conn = (struct conn_data *)hyper_task_userdata(task);
+            if (!conn) {
+                // A background task for hyper completed...
+                hyper_task_free(task);
+                continue;
+            }
+
+            // A connection task completed, it's closed now.
+            if (hyper_task_type(task) == HYPER_TASK_ERROR) {
+                hyper_error *err = hyper_task_value(task);
+                char errbuf [256];
+                size_t errlen = hyper_error_print(err, (uint8_t *)errbuf, sizeof(errbuf));
+                printf("connection on fd %d failed: %.*s\n", conn->fd, (int) errlen, errbuf);
+                hyper_error_free(err);
+            } else {
+                assert(hyper_task_type(task) == HYPER_TASK_EMPTY);
+                printf("connection on fd %d closed\n", conn->fd);
+            }
+            hyper_task_free(task);
+
+            for (int i = 0; i < MAX_CONNS; i++) {
+                if (conns[i] == conn) {
+                    conns[i] = NULL;
+                }
+            }
+            free_conn_data(conn);
+        }
+
+        // All futures are pending on IO work, so select on the fds.
+
+        FD_ZERO(&fds_read);
+        FD_ZERO(&fds_write);
+        FD_ZERO(&fds_excep);
+
+        FD_SET(listen_fd, &fds_read);
+        int max_fd = listen_fd;
+
+        for (int i = 0; i < MAX_CONNS; i++) {
+            struct conn_data *conn = conns[i];
+            if (!conn) {
+                continue;
+            }
+            if (conn->read_waker) {
+                FD_SET(conn->fd, &fds_read);
+            }
+            if (conn->write_waker) {
+                FD_SET(conn->fd, &fds_write);
+            }
+            if (conn->fd > max_fd) {
+                max_fd = conn->fd;
+            }
+        }
+
+        int sel_ret = select(max_fd + 1, &fds_read, &fds_write, &fds_excep, NULL);
+
+        if (sel_ret < 0) {
+            printf("select() error\n");
+            return 1;
+        }
+
+        for (int i = 0; i < MAX_CONNS; i++) {
+            struct conn_data *conn = conns[i];
+            if (!conn) {
+                continue;
+            }
+            if (FD_ISSET(conn->fd, &fds_read)) {
+                hyper_waker_wake(conn->read_waker);
+                conn->read_waker = NULL;
+            }
+            if (FD_ISSET(conn->fd, &fds_write)) {
+                hyper_waker_wake(conn->write_waker);
+                conn->write_waker = NULL;
+            }
+        }
+
+        if (!FD_ISSET(listen_fd, &fds_read)) {
+            continue;
+        }
+
+        // Accept the new connections
+        while (1) {
+            int fd = accept(listen_fd, NULL, NULL);
+            if (fd < 0) {
+                if (errno != EAGAIN && errno != EWOULDBLOCK) {
+                    printf("accept() error\n");
+                }
+                break;
+            }
+
+            int slot = -1;
+            for (int i = 0; i < MAX_CONNS; i++) {
+                if (!conns[i]) {
+                    slot = i;
+                    break;
+                }
+            }
+            if (slot < 0 || fcntl(fd, F_SETFL, O_NONBLOCK) != 0) {
+                printf("dropping connection on fd %d\n", fd);
+                close(fd);
+                continue;
+            }
+
+            struct conn_data *conn = malloc(sizeof(struct conn_data));
+
+            conn->fd = fd;
+            conn->read_waker = NULL;
+            conn->write_waker = NULL;
+            conn->body_sent = 0;
+            conns[slot] = conn;
+
+            // Hookup the IO
+            hyper_io *io = hyper_io_new();
+            hyper_io_set_userdata(io, (void *)conn);
+            hyper_io_set_read(io, read_cb);
+            hyper_io_set_write(io, write_cb);
+
+            // And the service handling its requests
+            hyper_service *service = hyper_service_new(handle_request);
+            hyper_service_set_userdata(service, (void *)conn);
+
+            printf("serving connection on fd %d ...\n", fd);
+            hyper_task *serve = hyper_serverconn_serve(io, opts, service);
+            hyper_task_set_userdata(serve, (void *)conn);
+            hyper_executor_push(exec, serve);
+        }
+    }
+
+    hyper_serverconn_options_free(opts);
+    hyper_executor_free(exec);
+
+    return 0;
+}
@@ -105,6 +105,80 @@ typedef enum hyper_code {
    The peer sent an HTTP message that could not be parsed.
    */
   HYPERE_INVALID_PEER_MESSAGE,
+  /*
+   A timeout elapsed before the operation could complete.
+   */
+  HYPERE_TIMEOUT,
+  /*
+   The operation was canceled before it could complete.
+
+   This typically means a request was queued on a connection that closed
+   before it could be sent.
+   */
+  HYPERE_CANCELED,
+  /*
+   The connection, or the channel the operation was waiting on, is
+   closed.
+   */
+  HYPERE_CLOSED,
+  /*
+   Reading from or writing to the IO transport failed.
+   */
+  HYPERE_IO,
+  /*
+   The peer sent a message, or some bytes, when none was expected.
+   */
+  HYPERE_UNEXPECTED_MESSAGE,
+  /*
+   The head of a received message was larger than the maximum buffer
+   size.
+   */
+  HYPERE_MESSAGE_HEAD_TOO_LARGE,
+  /*
+   The URI of a received request was too long.
+   */
+  HYPERE_URI_TOO_LONG,
+  /*
+   Reading the body of a received message failed.
+   */
+  HYPERE_BODY_READ,
+  /*
+   Writing the body of an outgoing message to the connection failed.
+   */
+  HYPERE_BODY_WRITE,
+  /*
+   The sender of an outgoing body aborted it before it was complete.
+   */
+  HYPERE_BODY_WRITE_ABORTED,
+  /*
+   The body given to hyper for an outgoing message returned an error.
+   */
+  HYPERE_USER_BODY,
+  /*
+   The service handling a request returned an error.
+   */
+  HYPERE_USER_SERVICE,
+  /*
+   A message given to hyper cannot be sent as it is.
+
+   For example, a response with an unsupported status code, or with both
+   a `content-length` and a `transfer-encoding` header.
+   */
+  HYPERE_INVALID_USER_MESSAGE,
+  /*
+   An upgrade was awaited on a message that has none.
+   */
+  HYPERE_NO_UPGRADE,
+  /*
+   Shutting down the IO transport failed.
+   */
+  HYPERE_SHUTDOWN,
+  /*
+   An HTTP/2 error, such as a reset stream.
+
+   The reason code can be retrieved with `hyper_error_http2_reason`.
+   */
+  HYPERE_HTTP2,
 } hyper_code;
 
 /*
@@ -131,6 +205,10 @@ typedef enum hyper_task_return_type {
    The value of this task is `hyper_buf *`.
    */
   HYPER_TASK_BUF,
+  /*
+   The value of this task is `hyper_upgraded *`.
+   */
+  HYPER_TASK_UPGRADED,
 } hyper_task_return_type;
 
 /*
@@ -194,11 +272,48 @@ typedef struct hyper_request hyper_request;
  */
 typedef struct hyper_response hyper_response;
 
+/*
+ A channel to send the response to a request with.
+
+ It is given to the `hyper_service` callback with each request, and is
+ consumed by `hyper_response_channel_send`.
+ */
+typedef struct hyper_response_channel hyper_response_channel;
+
+/*
+ An options builder to configure an HTTP server connection.
+ */
+typedef struct hyper_serverconn_options hyper_serverconn_options;
+
+/*
+ A service that handles the requests received on a server connection.
+
+ The service callback is called with each request, and a
+ `hyper_response_channel *` to send the response on.
+ */
+typedef struct hyper_service hyper_service;
+
 /*
  An async task.
  */
 typedef struct hyper_task hyper_task;
 
+/*
+ A timer used to time background tasks, such as HTTP/2 keep-alive pings.
+
+ hyper keeps track of the deadlines, the timer only has to wake the tasks
+ waiting on them in time.
+ */
+typedef struct hyper_timer hyper_timer;
+
+/*
+ A connection that was upgraded to some other protocol.
+
+ Any bytes the peer sent right after agreeing to the upgrade, which were
+ already read by hyper, are returned by the first reads.
+ */
+typedef struct hyper_upgraded hyper_upgraded;
+
 /*
  A waker that is saved and used to waken a pending task.
  */
@@ -208,6 +323,12 @@ typedef int (*hyper_body_foreach_callback)(void*, const struct hyper_buf*);
 
 typedef int (*hyper_body_data_callback)(void*, struct hyper_context*, struct hyper_buf**);
 
+typedef int (*hyper_body_trailers_callback)(void*, struct hyper_context*, struct hyper_headers*);
+
+typedef void (*hyper_buf_release_callback)(void*);
+
+typedef int (*hyper_error_foreach_callback)(void*, const uint8_t*, size_t);
+
 typedef void (*hyper_request_on_informational_callback)(void*, struct hyper_response*);
 
 typedef int (*hyper_headers_foreach_callback)(void*, const uint8_t*, size_t, const uint8_t*, size_t);
@@ -216,6 +337,12 @@ typedef size_t (*hyper_io_read_callback)(void*, struct hyper_context*, uint8_t*,
 
 typedef size_t (*hyper_io_write_callback)(void*, struct hyper_context*, const uint8_t*, size_t);
 
+typedef void (*hyper_service_callback)(void*, struct hyper_request*, struct hyper_response_channel*);
+
+typedef void (*hyper_executor_notify_callback)(void*);
+
+typedef void (*hyper_timer_sleep_callback)(void*, struct hyper_context*, uint64_t);
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
@@ -246,6 +373,12 @@ void hyper_body_free(struct hyper_body *body);
  - `HYPER_TASK_ERROR`: An error retrieving the data.
  - `HYPER_TASK_EMPTY`: The body has finished streaming data.
 
+ The `hyper_buf *` refers to the bytes hyper received, without copying
+ them.
+
+ Trailers received after the data are kept in the body, and can be
+ retrieved with `hyper_body_trailers` once the data has finished.
+
  This does not consume the `hyper_body *`, so it may be used to again.
  However, it MUST NOT be used or freed until the related task completes.
  */
@@ -256,17 +389,35 @@ struct hyper_task *hyper_body_data(struct hyper_body *body);
  body chunk that is received.
 
  The `hyper_buf` pointer is only a borrowed reference, it cannot live outside
- the execution of the callback. You must make a copy to retain it.
+ the execution of the callback. You must make a copy to retain it, such
+ as with `hyper_buf_clone`.
 
  The callback should return `HYPER_ITER_CONTINUE` to continue iterating
  chunks as they are received, or `HYPER_ITER_BREAK` to cancel.
 
- This will consume the `hyper_body *`, you shouldn't use it anymore or free it.
+ Trailers received after the data are kept in the body, and can be
+ retrieved with `hyper_body_trailers` once the task completes.
+
+ This does not consume the `hyper_body *`, it must be freed with
+ `hyper_body_free` once done with it. However, it MUST NOT be used or
+ freed until the related task completes.
  */
 struct hyper_task *hyper_body_foreach(struct hyper_body *body,
                                       hyper_body_foreach_callback func,
                                       void *userdata);
 
+/*
+ Get the trailers that were received after the data of this body.
+
+ Trailers are only known once `hyper_body_data` has returned that the
+ body finished, or the task of `hyper_body_foreach` has completed. If
+ none were received, this returns `NULL`.
+
+ The returned `hyper_headers *` is borrowed from the body, and is valid
+ until the body is freed.
+ */
+const struct hyper_headers *hyper_body_trailers(const struct hyper_body *body);
+
 /*
  Set userdata on this body, which will be passed to callback functions.
  */
@@ -295,6 +446,28 @@ void hyper_body_set_userdata(struct hyper_body *body, void *userdata);
  */
 void hyper_body_set_data_func(struct hyper_body *body, hyper_body_data_callback func);
 
+/*
+ Set the trailers callback for this body.
+
+ The callback is called once the data callback has completed all data.
+ It is passed the value from `hyper_body_set_userdata`, and an empty
+ `hyper_headers *` to add the trailers to.
+
+ If the trailers are ready, `HYPER_POLL_READY` should be returned. The
+ headers are only sent if this is returned, and if any were added.
+
+ If they aren't known yet, a `hyper_waker` should be saved from the
+ `hyper_context *` argument, and `HYPER_POLL_PENDING` returned. The
+ callback is then called again with new empty headers once woken.
+
+ If some error has occurred, you can return `HYPER_POLL_ERROR` to abort
+ the body.
+
+ HTTP/1 only sends trailers in chunked messages, and only those named
+ in the `trailer` header of the message.
+ */
+void hyper_body_set_trailers_func(struct hyper_body *body, hyper_body_trailers_callback func);
+
 /*
  Create a new `hyper_buf *` by copying the provided bytes.
 
@@ -305,6 +478,31 @@ void hyper_body_set_data_func(struct hyper_body *body, hyper_body_data_callback
  */
 struct hyper_buf *hyper_buf_copy(const uint8_t *buf, size_t len);
 
+/*
+ Create a new `hyper_buf *` that refers to the provided bytes, without
+ copying them.
+
+ The bytes must not be changed or freed until hyper is done with them,
+ at which point the `release` callback is called with the `userdata`.
+ This may happen on any thread, and may be later than the request or
+ response the bytes were sent with has completed.
+
+ If this returns `NULL`, `release` is not called.
+ */
+struct hyper_buf *hyper_buf_from_owned(const uint8_t *buf,
+                                       size_t len,
+                                       hyper_buf_release_callback release,
+                                       void *userdata);
+
+/*
+ Create a new `hyper_buf *` that refers to the same bytes as `buf`,
+ without copying them.
+
+ This can be used to keep a buffer that is only borrowed, such as in
+ the callback of `hyper_body_foreach`.
+ */
+struct hyper_buf *hyper_buf_clone(const struct hyper_buf *buf);
+
 /*
  Get a pointer to the bytes in this buffer.
 
@@ -334,6 +532,10 @@ void hyper_buf_free(struct hyper_buf *buf);
 
  The returned `hyper_task *` must be polled with an executor until the
  handshake completes, at which point the value can be taken.
+
+ Returns NULL if HTTP/2 keep-alive was enabled with
+ `hyper_clientconn_options_http2_keep_alive_interval`, but no timer was
+ set with `hyper_clientconn_options_timer`.
  */
 struct hyper_task *hyper_clientconn_handshake(struct hyper_io *io,
                                               struct hyper_clientconn_options *options);
@@ -402,6 +604,123 @@ enum hyper_code hyper_clientconn_options_http2(struct hyper_clientconn_options *
 enum hyper_code hyper_clientconn_options_http1_allow_multiline_headers(struct hyper_clientconn_options *opts,
                                                                        int enabled);
 
+/*
+ Set whether HTTP/1 connections will accept HTTP/0.9 responses.
+
+ Pass `0` to disable (default), `1` to enable.
+ */
+enum hyper_code hyper_clientconn_options_http1_http09_responses(struct hyper_clientconn_options *opts,
+                                                                int enabled);
+
+/*
+ Set whether HTTP/1 connections should try to use vectored writes, or
+ always flatten into a single buffer.
+
+ Pass `0` to always flatten, `1` to use vectored writes. The default
+ is to pick based on the IO transport.
+ */
+enum hyper_code hyper_clientconn_options_http1_writev(struct hyper_clientconn_options *opts,
+                                                      int enabled);
+
+/*
+ Set the maximum buffer size for HTTP/1 connections.
+
+ The default is ~400kb. This unsets the exact read buffer size.
+
+ Returns `HYPERE_INVALID_ARG` if `max_buf_size` is less than 8192.
+ */
+enum hyper_code hyper_clientconn_options_http1_max_buf_size(struct hyper_clientconn_options *opts,
+                                                            size_t max_buf_size);
+
+/*
+ Set the exact size of the read buffer HTTP/1 connections always use.
+
+ Pass `0` to use an adaptive read buffer (default). This unsets the
+ maximum buffer size.
+ */
+enum hyper_code hyper_clientconn_options_http1_read_buf_exact_size(struct hyper_clientconn_options *opts,
+                                                                   size_t size);
+
+/*
+ Set the timer used by connections, such as for HTTP/2 keep-alive.
+
+ This does not consume the `options` or the `timer`.
+ */
+enum hyper_code hyper_clientconn_options_timer(struct hyper_clientconn_options *opts,
+                                               const struct hyper_timer *timer);
+
+/*
+ Set the initial HTTP/2 stream-level flow control window size.
+
+ This disables the adaptive window.
+ */
+enum hyper_code hyper_clientconn_options_http2_initial_stream_window_size(struct hyper_clientconn_options *opts,
+                                                                          uint32_t size);
+
+/*
+ Set the initial HTTP/2 connection-level flow control window size.
+
+ This disables the adaptive window.
+ */
+enum hyper_code hyper_clientconn_options_http2_initial_connection_window_size(struct hyper_clientconn_options *opts,
+                                                                              uint32_t size);
+
+/*
+ Set whether HTTP/2 connections use an adaptive flow control window,
+ overriding the initial window sizes.
+
+ Pass `0` to disable (default), `1` to enable.
+ */
+enum hyper_code hyper_clientconn_options_http2_adaptive_window(struct hyper_clientconn_options *opts,
+                                                               int enabled);
+
+/*
+ Set the maximum HTTP/2 frame size.
+
+ Returns `HYPERE_INVALID_ARG` if `size` isn't within the range of
+ 16,384 to 16,777,215.
+ */
+enum hyper_code hyper_clientconn_options_http2_max_frame_size(struct hyper_clientconn_options *opts,
+                                                              uint32_t size);
+
+/*
+ Set the interval at which HTTP/2 Ping frames are sent to keep the
+ connection alive, in milliseconds.
+
+ Pass `0` to disable keep-alive (default). Keep-alive requires a timer
+ to also be set with `hyper_clientconn_options_timer`, before or after
+ this call, otherwise the handshake fails.
+ */
+enum hyper_code hyper_clientconn_options_http2_keep_alive_interval(struct hyper_clientconn_options *opts,
+                                                                   uint64_t interval_ms);
+
+/*
+ Set how long to wait for the acknowledgement of a keep-alive ping, in
+ milliseconds, before closing the connection.
+
+ The default is 20 seconds.
+ */
+enum hyper_code hyper_clientconn_options_http2_keep_alive_timeout(struct hyper_clientconn_options *opts,
+                                                                  uint64_t timeout_ms);
+
+/*
+ Set whether HTTP/2 keep-alive pings are also sent while the
+ connection has no open streams.
+
+ Pass `0` to disable (default), `1` to enable.
+ */
+enum hyper_code hyper_clientconn_options_http2_keep_alive_while_idle(struct hyper_clientconn_options *opts,
+                                                                     int enabled);
+
+/*
+ Set the maximum write buffer size for each HTTP/2 stream.
+
+ The default is 1MB. Returns `HYPERE_INVALID_ARG` if `size` is larger
+ than `UINT32_MAX`.
+ */
+enum hyper_code hyper_clientconn_options_http2_max_send_buf_size(struct hyper_clientconn_options *opts,
+                                                                 size_t size);
+
 /*
  Frees a `hyper_error`.
  */
@@ -422,13 +741,40 @@ enum hyper_code hyper_error_code(const struct hyper_error *err);
  */
 size_t hyper_error_print(const struct hyper_error *err, uint8_t *dst, size_t dst_len);
 
+/*
+ Get the HTTP/2 reason code of this error.
+
+ If the error was caused by an HTTP/2 reset stream or GOAWAY frame, the
+ reason code is written to `reason` and `HYPERE_OK` is returned.
+ Otherwise `reason` is left untouched, and `HYPERE_ERROR` is returned.
+ */
+enum hyper_code hyper_error_http2_reason(const struct hyper_error *err, uint32_t *reason);
+
+/*
+ Iterates the causes of this error, passing the message of each to the
+ callback.
+
+ The first cause is the one that this error was directly caused by, the
+ next one is what caused that, and so on. The message of a cause may
+ also include the messages of its own causes.
+
+ The `userdata` pointer is also passed to the callback.
+
+ The callback should return `HYPER_ITER_CONTINUE` to keep iterating, or
+ `HYPER_ITER_BREAK` to stop.
+ */
+void hyper_error_foreach_source(const struct hyper_error *err,
+                                hyper_error_foreach_callback func,
+                                void *userdata);
+
 /*
  Construct a new HTTP request.
  */
 struct hyper_request *hyper_request_new(void);
 
 /*
- Free an HTTP request if not going to send it on a client.
+ Free an HTTP request if not going to send it on a client, or once done
+ with a request received by a `hyper_service`.
  */
 void hyper_request_free(struct hyper_request *req);
 
@@ -486,6 +832,50 @@ enum hyper_code hyper_request_set_uri_parts(struct hyper_request *req,
  */
 enum hyper_code hyper_request_set_version(struct hyper_request *req, int version);
 
+/*
+ Get the method of this request.
+
+ The `method` and `method_len` arguments are set to the method's
+ buffer, which isn't null-terminated. It is owned by the request, and
+ should not be used after the request has been freed.
+ */
+enum hyper_code hyper_request_method(const struct hyper_request *req,
+                                     const uint8_t **method,
+                                     size_t *method_len);
+
+/*
+ Get the URI of this request as separate scheme, authority, and
+ path/query strings.
+
+ Each of `scheme`, `authority`, and `path_and_query` may be null, to
+ skip that component. Otherwise, it and its corresponding `len` are set
+ to the component's buffer, which isn't null-terminated, or to null and
+ `0` if the URI has no such component. Requests received by a server
+ typically only have a path and query.
+
+ The buffers are owned by the request, and should not be used after the
+ request has been freed.
+ */
+enum hyper_code hyper_request_uri_parts(const struct hyper_request *req,
+                                        const uint8_t **scheme,
+                                        size_t *scheme_len,
+                                        const uint8_t **authority,
+                                        size_t *authority_len,
+                                        const uint8_t **path_and_query,
+                                        size_t *path_and_query_len);
+
+/*
+ Get the HTTP version of this request.
+
+ The returned value could be:
+
+ - `HYPER_HTTP_VERSION_1_0`
+ - `HYPER_HTTP_VERSION_1_1`
+ - `HYPER_HTTP_VERSION_2`
+ - `HYPER_HTTP_VERSION_NONE` if newer (or older).
+ */
+int hyper_request_version(const struct hyper_request *req);
+
 /*
  Gets a reference to the HTTP headers of this request
 
@@ -525,11 +915,39 @@ enum hyper_code hyper_request_on_informational(struct hyper_request *req,
                                                hyper_request_on_informational_callback callback,
                                                void *data);
 
+/*
+ Construct a new HTTP response.
+
+ The response has a `200 OK` status and an empty body, until changed.
+ */
+struct hyper_response *hyper_response_new(void);
+
 /*
  Free an HTTP response after using it.
  */
 void hyper_response_free(struct hyper_response *resp);
 
+/*
+ Set the HTTP-Status code of this response.
+
+ Returns `HYPERE_INVALID_ARG` if the code isn't within the range of
+ 100-999.
+ */
+enum hyper_code hyper_response_set_status(struct hyper_response *resp, uint16_t status);
+
+/*
+ Set the reason-phrase of this response.
+
+ By default, the canonical reason of the status code is used. The
+ reason-phrase is only sent on HTTP/1 connections.
+
+ Returns `HYPERE_INVALID_ARG` if the reason contains a byte that isn't
+ allowed, such as `\r` or `\n`.
+ */
+enum hyper_code hyper_response_set_reason_phrase(struct hyper_response *resp,
+                                                 const uint8_t *reason,
+                                                 size_t reason_len);
+
 /*
  Get the HTTP-Status code of this response.
 
@@ -584,6 +1002,16 @@ struct hyper_headers *hyper_response_headers(struct hyper_response *resp);
  */
 struct hyper_body *hyper_response_body(struct hyper_response *resp);
 
+/*
+ Set the body of the response.
+
+ The default is an empty body.
+
+ This takes ownership of the `hyper_body *`, you must not use it or
+ free it after setting it on the response.
+ */
+enum hyper_code hyper_response_set_body(struct hyper_response *resp, struct hyper_body *body);
+
 /*
  Iterates the headers passing each name and value pair to the callback.
 
@@ -679,11 +1107,143 @@ void hyper_io_set_read(struct hyper_io *io, hyper_io_read_callback func);
  */
 void hyper_io_set_write(struct hyper_io *io, hyper_io_write_callback func);
 
+/*
+ Serves an HTTP connection on the provided IO transport, calling the
+ service for each request.
+
+ The `io` and the `service` are consumed in this function call, but the
+ `options` are not, so they can be used for more connections.
+
+ The returned `hyper_task *` must be polled with an executor until the
+ connection is closed. Its value is empty, or a `hyper_error *` if the
+ connection failed.
+ */
+struct hyper_task *hyper_serverconn_serve(struct hyper_io *io,
+                                          const struct hyper_serverconn_options *options,
+                                          struct hyper_service *service);
+
+/*
+ Creates a new set of HTTP serverconn options to be used when serving
+ connections.
+ */
+struct hyper_serverconn_options *hyper_serverconn_options_new(void);
+
+/*
+ Free a `hyper_serverconn_options *`.
+ */
+void hyper_serverconn_options_free(struct hyper_serverconn_options *opts);
+
+/*
+ Set the server background task executor.
+
+ It is used to drive the streams of HTTP/2 connections.
+
+ This does not consume the `options` or the `exec`.
+ */
+void hyper_serverconn_options_exec(struct hyper_serverconn_options *opts,
+                                   const struct hyper_executor *exec);
+
+/*
+ Set whether to accept HTTP/2 connections.
+
+ When enabled, the version is detected from the first bytes the client
+ sends. Connections starting with the HTTP/2 preface are served as
+ HTTP/2, and others as HTTP/1. An executor must be set with
+ `hyper_serverconn_options_exec` for HTTP/2 to make progress.
+
+ Pass `0` to disable, `1` to enable.
+ */
+enum hyper_code hyper_serverconn_options_http2(struct hyper_serverconn_options *opts, int enabled);
+
+/*
+ Set whether HTTP/1 connections should support half-closures.
+
+ Pass `0` to disable (default), `1` to enable.
+ */
+enum hyper_code hyper_serverconn_options_http1_half_close(struct hyper_serverconn_options *opts,
+                                                          int enabled);
+
+/*
+ Set whether HTTP/1 connections are kept alive after a response.
+
+ Pass `0` to disable, `1` to enable (default).
+ */
+enum hyper_code hyper_serverconn_options_http1_keep_alive(struct hyper_serverconn_options *opts,
+                                                          int enabled);
+
+/*
+ Set whether HTTP/1 connections will write header names as title case.
+
+ Pass `0` to disable (default), `1` to enable.
+ */
+enum hyper_code hyper_serverconn_options_http1_title_case_headers(struct hyper_serverconn_options *opts,
+                                                                  int enabled);
+
+/*
+ Set whether HTTP/1 connections will preserve the original case of
+ header names.
+
+ Pass `0` to allow lowercase normalization (default), `1` to retain
+ original case.
+ */
+enum hyper_code hyper_serverconn_options_http1_preserve_header_case(struct hyper_serverconn_options *opts,
+                                                                    int enabled);
+
+/*
+ Set the maximum buffer size for HTTP/1 connections.
+
+ The default is ~400kb. Returns `HYPERE_INVALID_ARG` if `max_buf_size`
+ is less than 8192.
+ */
+enum hyper_code hyper_serverconn_options_http1_max_buf_size(struct hyper_serverconn_options *opts,
+                                                            size_t max_buf_size);
+
+/*
+ Create a new service from a callback.
+
+ The callback is called with the userdata set by
+ `hyper_service_set_userdata`, the received `hyper_request *`, and a
+ `hyper_response_channel *`.
+
+ The callback owns the request, and must free it once done with it. The
+ response, which may be sent later on, must be sent with
+ `hyper_response_channel_send`. If the connection is closed before
+ then, the response is dropped.
+ */
+struct hyper_service *hyper_service_new(hyper_service_callback func);
+
+/*
+ Set the user data pointer passed to the service callback.
+ */
+void hyper_service_set_userdata(struct hyper_service *service, void *userdata);
+
+/*
+ Free a `hyper_service *` that wasn't used to serve a connection.
+ */
+void hyper_service_free(struct hyper_service *service);
+
+/*
+ Send a response on the channel.
+
+ Both the `channel` and the `response` are consumed.
+ */
+enum hyper_code hyper_response_channel_send(struct hyper_response_channel *channel,
+                                            struct hyper_response *response);
+
 /*
  Creates a new task executor.
  */
 const struct hyper_executor *hyper_executor_new(void);
 
+/*
+ Creates a new task executor that can be polled from several threads.
+
+ Each thread calling `hyper_executor_poll` polls a different task, so
+ tasks can make progress at the same time. The IO, body and other
+ callbacks of the tasks may then be called from any of those threads.
+ */
+const struct hyper_executor *hyper_executor_new_multi_thread(void);
+
 /*
  Frees an executor and any incomplete tasks still part of it.
  */
@@ -697,6 +1257,21 @@ void hyper_executor_free(const struct hyper_executor *exec);
  */
 enum hyper_code hyper_executor_push(const struct hyper_executor *exec, struct hyper_task *task);
 
+/*
+ Set a callback to be called when tasks of the executor are ready to
+ make progress, so `hyper_executor_poll` should be called.
+
+ The callback is passed the `userdata`. It may be called from any
+ thread, including from within `hyper_executor_poll`, and only needs to
+ signal the thread that polls the executor, such as by writing to an
+ eventfd.
+
+ This does not consume the `exec`.
+ */
+void hyper_executor_set_notify(const struct hyper_executor *exec,
+                               hyper_executor_notify_callback func,
+                               void *userdata);
+
 /*
  Polls the executor, trying to make progress on any tasks that have notified
  that they are ready again.
@@ -704,6 +1279,9 @@ enum hyper_code hyper_executor_push(const struct hyper_executor *exec, struct hy
  If ready, returns a task from the executor that has completed.
 
  If there are no ready tasks, this returns `NULL`.
+
+ Executors created with `hyper_executor_new_multi_thread` can be polled
+ from several threads at the same time.
  */
 struct hyper_task *hyper_executor_poll(const struct hyper_executor *exec);
 
@@ -757,6 +1335,96 @@ void hyper_waker_free(struct hyper_waker *waker);
  */
 void hyper_waker_wake(struct hyper_waker *waker);
 
+/*
+ Create a new timer.
+
+ The sleep function of this timer should be set with
+ `hyper_timer_set_sleep`.
+ */
+struct hyper_timer *hyper_timer_new(void);
+
+/*
+ Free a `hyper_timer *`.
+
+ Options the timer was set on keep their own reference to the sleep
+ function and user data, so the timer can be freed right after.
+ */
+void hyper_timer_free(struct hyper_timer *timer);
+
+/*
+ Set the user data pointer for this timer to some value.
+
+ This value is passed as an argument to the sleep callback.
+ */
+void hyper_timer_set_userdata(struct hyper_timer *timer, void *data);
+
+/*
+ Set the sleep function for this timer.
+
+ The callback is called when a task has to wait until some deadline,
+ with a `hyper_context *` and the number of milliseconds until that
+ deadline. A waker should be claimed from the `ctx` with
+ `hyper_context_waker`, and woken once at least that many milliseconds
+ have passed.
+
+ Waking it earlier is allowed, the callback is then called again with
+ the time that is left.
+ */
+void hyper_timer_set_sleep(struct hyper_timer *timer, hyper_timer_sleep_callback func);
+
+/*
+ Return a task that waits for the connection of this response to be
+ upgraded.
+
+ This is used with the response to a `CONNECT` request, or to a request
+ with an `Upgrade` header, once its status shows that the server agreed
+ to it.
+
+ The task value may have different types depending on the outcome:
+
+ - `HYPER_TASK_UPGRADED`: Success, the value is a `hyper_upgraded *`.
+ - `HYPER_TASK_ERROR`: The connection couldn't be upgraded.
+
+ This does not consume the `hyper_response *`.
+ */
+struct hyper_task *hyper_response_upgrade(struct hyper_response *resp);
+
+/*
+ Return a task that reads the next bytes from the upgraded connection.
+
+ The task value may have different types depending on the outcome:
+
+ - `HYPER_TASK_BUF`: Success, and some bytes were read.
+ - `HYPER_TASK_ERROR`: An error reading from the connection.
+ - `HYPER_TASK_EMPTY`: The connection was closed by the peer.
+
+ This does not consume the `hyper_upgraded *`. A read and a write may
+ be in progress at the same time.
+ */
+struct hyper_task *hyper_upgraded_read(const struct hyper_upgraded *upgraded);
+
+/*
+ Return a task that writes all of the bytes to the upgraded connection,
+ and flushes it.
+
+ The bytes are copied, so the `buf` can be freed right away. The task
+ value is empty on success, or a `hyper_error *` if writing failed.
+
+ This does not consume the `hyper_upgraded *`. A read and a write may
+ be in progress at the same time.
+ */
+struct hyper_task *hyper_upgraded_write(const struct hyper_upgraded *upgraded,
+                                        const uint8_t *buf,
+                                        size_t buf_len);
+
+/*
+ Free a `hyper_upgraded *`.
+
+ The connection is closed once the tasks still reading or writing it
+ have completed.
+ */
+void hyper_upgraded_free(struct hyper_upgraded *upgraded);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif // __cplusplus
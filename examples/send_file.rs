@@ -2,6 +2,7 @@
 
 use std::net::SocketAddr;
 
+use hyper::rt::TokioIo;
 use hyper::server::conn::http1;
 use tokio::net::TcpListener;
 
@@ -27,7 +28,7 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 
         tokio::task::spawn(async move {
             if let Err(err) = http1::Builder::new()
-                .serve_connection(stream, service_fn(response_examples))
+                .serve_connection(TokioIo::new(stream), service_fn(response_examples))
                 .await
             {
                 println!("Failed to serve connection: {:?}", err);
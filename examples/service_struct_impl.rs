@@ -1,5 +1,6 @@
 use bytes::Bytes;
 use http_body_util::Full;
+use hyper::rt::TokioIo;
 use hyper::server::conn::http1;
 use hyper::service::Service;
 use hyper::{body::Incoming as IncomingBody, Request, Response};
@@ -23,7 +24,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
         tokio::task::spawn(async move {
             if let Err(err) = http1::Builder::new()
-                .serve_connection(stream, Svc { counter: 81818 })
+                .serve_connection(TokioIo::new(stream), Svc { counter: 81818 })
                 .await
             {
                 println!("Failed to serve connection: {:?}", err);
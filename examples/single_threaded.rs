@@ -7,6 +7,7 @@ use std::rc::Rc;
 use tokio::net::TcpListener;
 
 use hyper::body::{Body as HttpBody, Bytes, Frame};
+use hyper::rt::TokioIo;
 use hyper::service::service_fn;
 use hyper::{Error, Response};
 use std::marker::PhantomData;
@@ -77,7 +78,7 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
 
         tokio::task::spawn_local(async move {
             if let Err(err) = http2::Builder::new(LocalExec)
-                .serve_connection(stream, service)
+                .serve_connection(TokioIo::new(stream), service)
                 .await
             {
                 println!("Error serving connection: {:?}", err);
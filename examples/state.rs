@@ -8,6 +8,7 @@ use std::sync::{
 
 use bytes::Bytes;
 use http_body_util::Full;
+use hyper::rt::TokioIo;
 use hyper::{server::conn::http1, service::service_fn};
 use hyper::{Error, Response};
 use tokio::net::TcpListener;
@@ -47,7 +48,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         });
 
         if let Err(err) = http1::Builder::new()
-            .serve_connection(stream, service)
+            .serve_connection(TokioIo::new(stream), service)
             .await
         {
             println!("Error serving connection: {:?}", err);
@@ -11,6 +11,7 @@ use tokio::sync::watch;
 use bytes::Bytes;
 use http_body_util::Empty;
 use hyper::header::{HeaderValue, UPGRADE};
+use hyper::rt::TokioIo;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::upgrade::Upgraded;
@@ -20,10 +21,11 @@ use hyper::{Request, Response, StatusCode};
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
 /// Handle server-side I/O after HTTP upgraded.
-async fn server_upgraded_io(mut upgraded: Upgraded) -> Result<()> {
+async fn server_upgraded_io(upgraded: Upgraded) -> Result<()> {
     // we have an upgraded connection that we can read and
-    // write on directly.
-    //
+    // write on directly, once wrapped to use Tokio's IO helpers.
+    let mut upgraded = TokioIo::new(upgraded);
+
     // since we completely control this example, we know exactly
     // how many bytes the client will write, so just read exact...
     let mut vec = vec![0; 7];
@@ -75,9 +77,10 @@ async fn server_upgrade(mut req: Request<hyper::body::Incoming>) -> Result<Respo
 }
 
 /// Handle client-side I/O after HTTP upgraded.
-async fn client_upgraded_io(mut upgraded: Upgraded) -> Result<()> {
+async fn client_upgraded_io(upgraded: Upgraded) -> Result<()> {
     // We've gotten an upgraded connection that we can read
     // and write directly on. Let's start out 'foobar' protocol.
+    let mut upgraded = TokioIo::new(upgraded);
     upgraded.write_all(b"foo=bar").await?;
     println!("client[foobar] sent");
 
@@ -97,7 +100,7 @@ async fn client_upgrade_request(addr: SocketAddr) -> Result<()> {
         .unwrap();
 
     let stream = TcpStream::connect(addr).await?;
-    let (mut sender, conn) = hyper::client::conn::http1::handshake(stream).await?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
 
     tokio::task::spawn(async move {
         if let Err(err) = conn.await {
@@ -149,7 +152,7 @@ async fn main() {
 
                     let mut rx = rx.clone();
                     tokio::task::spawn(async move {
-                        let conn = http1::Builder::new().serve_connection(stream, service_fn(server_upgrade));
+                        let conn = http1::Builder::new().serve_connection(TokioIo::new(stream), service_fn(server_upgrade));
 
                         // Don't forget to enable upgrades on the connection.
                         let mut conn = conn.with_upgrades();
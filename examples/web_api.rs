@@ -4,6 +4,7 @@ use std::net::SocketAddr;
 
 use bytes::{Buf, Bytes};
 use http_body_util::{BodyExt, Full};
+use hyper::rt::TokioIo;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{body::Incoming as IncomingBody, header, Method, Request, Response, StatusCode};
@@ -31,7 +32,7 @@ async fn client_request_response() -> Result<Response<BoxBody>> {
     let port = req.uri().port_u16().expect("uri has no port");
     let stream = TcpStream::connect(format!("{}:{}", host, port)).await?;
 
-    let (mut sender, conn) = hyper::client::conn::http1::handshake(stream).await?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
 
     tokio::task::spawn(async move {
         if let Err(err) = conn.await {
@@ -114,7 +115,7 @@ async fn main() -> Result<()> {
             let service = service_fn(move |req| response_examples(req));
 
             if let Err(err) = http1::Builder::new()
-                .serve_connection(stream, service)
+                .serve_connection(TokioIo::new(stream), service)
                 .await
             {
                 println!("Failed to serve connection: {:?}", err);
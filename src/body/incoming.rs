@@ -1,3 +1,4 @@
+use std::error::Error as StdError;
 use std::fmt;
 
 use bytes::Bytes;
@@ -8,10 +9,16 @@ use http::HeaderMap;
 use http_body::{Body, Frame, SizeHint};
 
 use super::DecodedLength;
+#[cfg(all(feature = "http2", feature = "server"))]
+use crate::common::rate::DataRate;
 use crate::common::Future;
 use crate::common::{task, watch, Pin, Poll};
 #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
+use crate::observe::BodyObserver;
+#[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
 use crate::proto::h2::ping;
+#[cfg(all(feature = "http2", feature = "server"))]
+use crate::proto::h2::server::ResetSignal;
 
 type BodySender = mpsc::Sender<Result<Bytes, crate::Error>>;
 type TrailersSender = oneshot::Sender<HeaderMap>;
@@ -37,12 +44,57 @@ enum Kind {
         data_done: bool,
         ping: ping::Recorder,
         recv: h2::RecvStream,
+        options: Option<Box<H2Options>>,
     },
     #[cfg(feature = "ffi")]
     Ffi(crate::ffi::UserBody),
 }
 
-/// A sender half created through [`Body::channel()`].
+/// Limits enforced while receiving an HTTP/2 body, and its observer.
+///
+/// Boxed in `Kind::H2`, since most bodies have none.
+#[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
+#[derive(Default)]
+struct H2Options {
+    /// How many more bytes may be received.
+    remaining: Option<u64>,
+    /// Whether the body went over its maximum size.
+    too_large: bool,
+    /// Asks the server to reset the stream once the body is too large.
+    #[cfg(feature = "server")]
+    reset: Option<ResetSignal>,
+    #[cfg(feature = "server")]
+    data_rate: Option<DataRate>,
+    observer: Option<BodyObserver>,
+}
+
+#[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
+impl H2Options {
+    /// Counts `len` received bytes against the maximum size, returning
+    /// whether they fit.
+    fn receive(&mut self, len: usize) -> bool {
+        let remaining = match self.remaining {
+            Some(ref mut remaining) => remaining,
+            None => return true,
+        };
+        match remaining.checked_sub(len as u64) {
+            Some(left) => {
+                *remaining = left;
+                true
+            }
+            None => {
+                self.too_large = true;
+                #[cfg(feature = "server")]
+                if let Some(ref reset) = self.reset {
+                    reset.reset();
+                }
+                false
+            }
+        }
+    }
+}
+
+/// A sender half created through [`Incoming::channel()`].
 ///
 /// Useful when wanting to stream chunks from another thread.
 ///
@@ -53,10 +105,10 @@ enum Kind {
 /// connection with an incomplete response (e.g. in the case of an error during asynchronous
 /// processing), call the [`Sender::abort()`] method to abort the body in an abnormal fashion.
 ///
-/// [`Body::channel()`]: struct.Body.html#method.channel
+/// [`Incoming::channel()`]: struct.Incoming.html#method.channel
 /// [`Sender::abort()`]: struct.Sender.html#method.abort
 #[must_use = "Sender does nothing unless sent on"]
-pub(crate) struct Sender {
+pub struct Sender {
     want_rx: watch::Receiver,
     data_tx: BodySender,
     trailers_tx: Option<TrailersSender>,
@@ -69,12 +121,36 @@ impl Incoming {
     /// Create a `Body` stream with an associated sender half.
     ///
     /// Useful when wanting to stream chunks from another thread.
+    ///
+    /// The sender will only accept a new chunk once the previous one has
+    /// been polled from the body, so a slow reader applies back-pressure to
+    /// the producer.
     #[inline]
-    #[allow(unused)]
-    pub(crate) fn channel() -> (Sender, Incoming) {
+    pub fn channel() -> (Sender, Incoming) {
         Self::new_channel(DecodedLength::CHUNKED, /*wanter =*/ false)
     }
 
+    /// Create a `Body` stream with an associated sender half, declaring the
+    /// exact length of the body.
+    ///
+    /// The length is reported by the body's `size_hint`, which allows it to
+    /// be sent with a `content-length` instead of chunked encoding. It is up
+    /// to the sender to send exactly this many bytes. If it sends more, or
+    /// is dropped after sending fewer, the body yields an error for which
+    /// [`Error::is_body_write_aborted`](crate::Error::is_body_write_aborted)
+    /// returns `true`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `content_length` is larger than the maximum length hyper can
+    /// represent (`u64::MAX - 2`).
+    #[inline]
+    pub fn channel_with_length(content_length: u64) -> (Sender, Incoming) {
+        let content_length =
+            DecodedLength::checked_new(content_length).expect("content length too large");
+        Self::new_channel(content_length, /*wanter =*/ false)
+    }
+
     pub(crate) fn new_channel(content_length: DecodedLength, wanter: bool) -> (Sender, Incoming) {
         let (data_tx, data_rx) = mpsc::channel(0);
         let (trailers_tx, trailers_rx) = oneshot::channel();
@@ -130,11 +206,61 @@ impl Incoming {
             ping,
             content_length,
             recv,
+            options: None,
         });
 
         body
     }
 
+    /// Enforce a maximum size on an HTTP/2 body.
+    #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
+    pub(crate) fn with_max_size(mut self, max: u64) -> Self {
+        if let Kind::H2 {
+            ref mut options, ..
+        } = self.kind
+        {
+            options.get_or_insert_with(Default::default).remaining = Some(max);
+        }
+        self
+    }
+
+    /// Signal the server to reset the stream when the body goes over its
+    /// maximum size.
+    #[cfg(all(feature = "http2", feature = "server"))]
+    pub(crate) fn with_reset_signal(mut self, reset: ResetSignal) -> Self {
+        if let Kind::H2 {
+            ref mut options, ..
+        } = self.kind
+        {
+            options.get_or_insert_with(Default::default).reset = Some(reset);
+        }
+        self
+    }
+
+    /// Enforce a minimum receive rate on an HTTP/2 request body.
+    #[cfg(all(feature = "http2", feature = "server"))]
+    pub(crate) fn with_data_rate(mut self, rate: DataRate) -> Self {
+        if let Kind::H2 {
+            ref mut options, ..
+        } = self.kind
+        {
+            options.get_or_insert_with(Default::default).data_rate = Some(rate);
+        }
+        self
+    }
+
+    /// Report the progress of an HTTP/2 body to an observer.
+    #[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
+    pub(crate) fn with_observer(mut self, observer: BodyObserver) -> Self {
+        if let Kind::H2 {
+            ref mut options, ..
+        } = self.kind
+        {
+            options.get_or_insert_with(Default::default).observer = Some(observer);
+        }
+        self
+    }
+
     #[cfg(feature = "ffi")]
     pub(crate) fn as_ffi_mut(&mut self) -> &mut crate::ffi::UserBody {
         match self.kind {
@@ -172,11 +298,24 @@ impl Body for Incoming {
                 if !data_rx.is_terminated() {
                     match ready!(Pin::new(data_rx).poll_next(cx)?) {
                         Some(chunk) => {
+                            if len.into_opt().map_or(false, |rem| chunk.len() as u64 > rem) {
+                                *len = DecodedLength::ZERO;
+                                return Poll::Ready(Some(Err(length_mismatch(
+                                    "sent more bytes than the declared length",
+                                ))));
+                            }
                             len.sub_if(chunk.len() as u64);
                             return Poll::Ready(Some(Ok(Frame::data(chunk))));
                         }
-                        // fall through to trailers
-                        None => (),
+                        None => {
+                            if len.into_opt().map_or(false, |rem| rem > 0) {
+                                *len = DecodedLength::ZERO;
+                                return Poll::Ready(Some(Err(length_mismatch(
+                                    "sent fewer bytes than the declared length",
+                                ))));
+                            }
+                            // fall through to trailers
+                        }
                     }
                 }
 
@@ -192,31 +331,48 @@ impl Body for Incoming {
                 ref ping,
                 recv: ref mut h2,
                 content_length: ref mut len,
+                ref mut options,
             } => {
-                if !*data_done {
-                    match ready!(h2.poll_data(cx)) {
-                        Some(Ok(bytes)) => {
-                            let _ = h2.flow_control().release_capacity(bytes.len());
-                            len.sub_if(bytes.len() as u64);
-                            ping.record_data(bytes.len());
-                            return Poll::Ready(Some(Ok(Frame::data(bytes))));
-                        }
-                        Some(Err(e)) => return Poll::Ready(Some(Err(crate::Error::new_body(e)))),
-                        None => {
-                            *data_done = true;
-                            // fall through to trailers
-                        }
+                let options = match options {
+                    Some(options) => options,
+                    None => return poll_h2(cx, data_done, ping, h2, len, None),
+                };
+
+                if options.too_large {
+                    return Poll::Ready(Some(Err(crate::Error::new_body_too_large())));
+                }
+
+                #[cfg(feature = "server")]
+                if let Some(ref rate) = options.data_rate {
+                    if rate.is_expired() {
+                        return Poll::Ready(Some(Err(crate::Error::new_min_data_rate())));
                     }
+                    // Time counts while waiting on the client, until a frame
+                    // is handed back to the service.
+                    rate.resume();
+                }
+
+                let ret = ready!(poll_h2(cx, data_done, ping, h2, len, Some(options)));
+                let data_len = match ret {
+                    Some(Ok(ref frame)) => frame.data_ref().map_or(0, |data| data.len()),
+                    _ => 0,
+                };
+
+                #[cfg(feature = "server")]
+                if let Some(ref rate) = options.data_rate {
+                    rate.record(data_len);
+                    rate.pause();
                 }
 
-                // after data, check trailers
-                match ready!(h2.poll_trailers(cx)) {
-                    Ok(t) => {
-                        ping.record_non_data();
-                        Poll::Ready(Ok(t.map(Frame::trailers)).transpose())
+                if let Some(ref mut observer) = options.observer {
+                    match ret {
+                        Some(Ok(ref frame)) if frame.is_data() => observer.data(data_len),
+                        Some(Ok(_)) | None => observer.end(),
+                        Some(Err(_)) => {}
                     }
-                    Err(e) => Poll::Ready(Some(Err(crate::Error::new_h2(e)))),
                 }
+
+                Poll::Ready(ret)
             }
 
             #[cfg(feature = "ffi")]
@@ -259,6 +415,46 @@ impl Body for Incoming {
     }
 }
 
+#[cfg(all(feature = "http2", any(feature = "client", feature = "server")))]
+fn poll_h2(
+    cx: &mut task::Context<'_>,
+    data_done: &mut bool,
+    ping: &ping::Recorder,
+    h2: &mut h2::RecvStream,
+    len: &mut DecodedLength,
+    options: Option<&mut H2Options>,
+) -> Poll<Option<Result<Frame<Bytes>, crate::Error>>> {
+    if !*data_done {
+        match ready!(h2.poll_data(cx)) {
+            Some(Ok(bytes)) => {
+                // Checked before releasing capacity, so a peer over the
+                // limit can't send any more.
+                if !options.map_or(true, |options| options.receive(bytes.len())) {
+                    return Poll::Ready(Some(Err(crate::Error::new_body_too_large())));
+                }
+                let _ = h2.flow_control().release_capacity(bytes.len());
+                len.sub_if(bytes.len() as u64);
+                ping.record_data(bytes.len());
+                return Poll::Ready(Some(Ok(Frame::data(bytes))));
+            }
+            Some(Err(e)) => return Poll::Ready(Some(Err(crate::Error::new_body(e)))),
+            None => {
+                *data_done = true;
+                // fall through to trailers
+            }
+        }
+    }
+
+    // after data, check trailers
+    match ready!(h2.poll_trailers(cx)) {
+        Ok(t) => {
+            ping.record_non_data();
+            Poll::Ready(Ok(t.map(Frame::trailers)).transpose())
+        }
+        Err(e) => Poll::Ready(Some(Err(crate::Error::new_h2(e)))),
+    }
+}
+
 impl fmt::Debug for Incoming {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         #[derive(Debug)]
@@ -278,7 +474,7 @@ impl fmt::Debug for Incoming {
 
 impl Sender {
     /// Check to see if this `Sender` can send more data.
-    pub(crate) fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
+    pub fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
         // Check if the receiver end has tried polling for the body yet
         ready!(self.poll_want(cx)?);
         self.data_tx
@@ -300,8 +496,7 @@ impl Sender {
     }
 
     /// Send data on data channel when it is ready.
-    #[allow(unused)]
-    pub(crate) async fn send_data(&mut self, chunk: Bytes) -> crate::Result<()> {
+    pub async fn send_data(&mut self, chunk: Bytes) -> crate::Result<()> {
         self.ready().await?;
         self.data_tx
             .try_send(Ok(chunk))
@@ -309,8 +504,9 @@ impl Sender {
     }
 
     /// Send trailers on trailers channel.
-    #[allow(unused)]
-    pub(crate) async fn send_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
+    ///
+    /// Trailers are sent after all data has been received by the body.
+    pub async fn send_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
         let tx = match self.trailers_tx.take() {
             Some(tx) => tx,
             None => return Err(crate::Error::new_closed()),
@@ -330,21 +526,58 @@ impl Sender {
     /// This is mostly useful for when trying to send from some other thread
     /// that doesn't have an async context. If in an async context, prefer
     /// `send_data()` instead.
-    #[cfg(feature = "http1")]
-    pub(crate) fn try_send_data(&mut self, chunk: Bytes) -> Result<(), Bytes> {
+    pub fn try_send_data(&mut self, chunk: Bytes) -> Result<(), Bytes> {
         self.data_tx
             .try_send(Ok(chunk))
             .map_err(|err| err.into_inner().expect("just sent Ok"))
     }
 
+    /// Try to send trailers on this channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(Some(HeaderMap))` if the trailers receiver was dropped,
+    /// or `Err(None)` if trailers were already sent.
+    #[cfg(feature = "http1")]
+    pub(crate) fn try_send_trailers(
+        &mut self,
+        trailers: HeaderMap,
+    ) -> Result<(), Option<HeaderMap>> {
+        let tx = match self.trailers_tx.take() {
+            Some(tx) => tx,
+            None => return Err(None),
+        };
+
+        tx.send(trailers).map_err(Some)
+    }
+
     /// Aborts the body in an abnormal fashion.
-    #[allow(unused)]
-    pub(crate) fn abort(self) {
+    ///
+    /// The body will yield an error for which
+    /// [`Error::is_body_write_aborted`](crate::Error::is_body_write_aborted)
+    /// returns `true`, even if the channel buffer is currently full.
+    pub fn abort(self) {
+        self.abort_err(crate::Error::new_body_write_aborted());
+    }
+
+    /// Aborts the body in an abnormal fashion, because of an error.
+    ///
+    /// Like with [`abort`](Sender::abort), the body will yield an error for
+    /// which [`Error::is_body_write_aborted`](crate::Error::is_body_write_aborted)
+    /// returns `true`. Its source is the given `err`.
+    pub fn abort_with<E>(self, err: E)
+    where
+        E: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        self.abort_err(crate::Error::new_body_write_aborted().with(err));
+    }
+
+    fn abort_err(self, err: crate::Error) {
         let _ = self
             .data_tx
             // clone so the send works even if buffer is full
             .clone()
-            .try_send(Err(crate::Error::new_body_write_aborted()));
+            .try_send(Err(err));
     }
 
     #[cfg(feature = "http1")]
@@ -353,6 +586,10 @@ impl Sender {
     }
 }
 
+fn length_mismatch(msg: &'static str) -> crate::Error {
+    crate::Error::new_body_write_aborted().with(msg)
+}
+
 impl fmt::Debug for Sender {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         #[derive(Debug)]
@@ -372,6 +609,7 @@ impl fmt::Debug for Sender {
 
 #[cfg(test)]
 mod tests {
+    use std::error::Error;
     use std::mem;
     use std::task::Poll;
 
@@ -384,7 +622,12 @@ mod tests {
         // the size by too much.
 
         let body_size = mem::size_of::<Incoming>();
-        let body_expected_size = mem::size_of::<u64>() * 5;
+        // h2 bodies may carry boxed options
+        let body_expected_size = if cfg!(feature = "http2") {
+            mem::size_of::<u64>() * 6
+        } else {
+            mem::size_of::<u64>() * 5
+        };
         assert!(
             body_size <= body_expected_size,
             "Body size = {} <= {}",
@@ -424,6 +667,50 @@ mod tests {
             SizeHint::with_exact(4),
             "channel with length",
         );
+
+        eq(
+            Incoming::channel_with_length(7).1,
+            SizeHint::with_exact(7),
+            "public channel with length",
+        );
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn channel_with_length_ends_stream() {
+        let (mut tx, mut rx) = Incoming::channel_with_length(5);
+
+        assert!(!rx.is_end_stream());
+
+        let send = tokio::spawn(async move { tx.send_data("hello".into()).await });
+
+        let chunk = rx.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(chunk, "hello");
+        assert!(rx.is_end_stream());
+
+        send.await.unwrap().expect("send_data");
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn channel_trailers() {
+        let (mut tx, rx) = Incoming::channel();
+
+        let send = tokio::spawn(async move {
+            tx.send_data("chunk 1".into()).await?;
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("chunky-trailer", "header data".parse().unwrap());
+            tx.send_trailers(trailers).await
+        });
+
+        let collected = rx.collect().await.expect("collect");
+        send.await.unwrap().expect("send");
+
+        assert_eq!(
+            collected.trailers().expect("trailers")["chunky-trailer"],
+            "header data"
+        );
+        assert_eq!(collected.to_bytes(), "chunk 1");
     }
 
     #[cfg(not(miri))]
@@ -437,7 +724,37 @@ mod tests {
         assert!(err.is_body_write_aborted(), "{:?}", err);
     }
 
-    #[cfg(all(not(miri), feature = "http1"))]
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn channel_abort_with() {
+        let (tx, mut rx) = Incoming::channel();
+
+        tx.abort_with("upstream reset");
+
+        let err = rx.frame().await.unwrap().unwrap_err();
+        assert!(err.is_body_write_aborted(), "{:?}", err);
+        assert_eq!(err.source().unwrap().to_string(), "upstream reset");
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn channel_with_length_mismatch() {
+        let (mut tx, mut rx) = Incoming::channel_with_length(5);
+        tx.try_send_data("hello world".into()).unwrap();
+        let err = rx.frame().await.unwrap().unwrap_err();
+        assert!(err.is_body_write_aborted(), "{:?}", err);
+
+        let (mut tx, mut rx) = Incoming::channel_with_length(5);
+        tx.try_send_data("hell".into()).unwrap();
+        drop(tx);
+        let chunk = rx.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(chunk, "hell");
+        let err = rx.frame().await.unwrap().unwrap_err();
+        assert!(err.is_body_write_aborted(), "{:?}", err);
+        assert!(rx.frame().await.is_none());
+    }
+
+    #[cfg(not(miri))]
     #[tokio::test]
     async fn channel_abort_when_buffer_is_full() {
         let (mut tx, mut rx) = Incoming::channel();
@@ -459,7 +776,6 @@ mod tests {
         assert!(err.is_body_write_aborted(), "{:?}", err);
     }
 
-    #[cfg(feature = "http1")]
     #[test]
     fn channel_buffers_one() {
         let (mut tx, _rx) = Incoming::channel();
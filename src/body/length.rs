@@ -14,7 +14,6 @@ impl From<Option<u64>> for DecodedLength {
     }
 }
 
-#[cfg(any(feature = "http1", feature = "http2", test))]
 const MAX_LEN: u64 = std::u64::MAX - 2;
 
 impl DecodedLength {
@@ -48,7 +47,6 @@ impl DecodedLength {
     }
 
     /// Checks the `u64` is within the maximum allowed for content-length.
-    #[cfg(any(feature = "http1", feature = "http2"))]
     pub(crate) fn checked_new(len: u64) -> Result<Self, crate::error::Parse> {
         use tracing::warn;
 
@@ -12,17 +12,16 @@
 //!   applications to have fine-grained control over their streaming.
 //! - **The [`Incoming`](Incoming) concrete type**, which is an implementation of
 //!   `Body`, and returned by hyper as a "receive stream" (so, for server
-//!   requests and client responses).
+//!   requests and client responses). It can also be created with
+//!   [`Incoming::channel()`](Incoming::channel), to stream a body from
+//!   another task through a [`Sender`](Sender).
 
 pub use bytes::{Buf, Bytes};
 pub use http_body::Body;
 pub use http_body::Frame;
 pub use http_body::SizeHint;
 
-pub use self::incoming::Incoming;
-
-#[cfg(feature = "http1")]
-pub(crate) use self::incoming::Sender;
+pub use self::incoming::{Incoming, Sender};
 pub(crate) use self::length::DecodedLength;
 
 mod incoming;
@@ -34,4 +33,5 @@ fn _assert_send_sync() {
 
     _assert_send::<Incoming>();
     _assert_sync::<Incoming>();
+    _assert_send::<Sender>();
 }
@@ -2,18 +2,22 @@
 
 use std::error::Error as StdError;
 use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
-use http::{Request, Response};
+use http::{Request, Response, Version};
 use httparse::ParserConfig;
-use tokio::io::{AsyncRead, AsyncWrite};
 
 use super::super::dispatch;
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::time::Time;
 use crate::common::{
     task, Future, Pin, Poll,
 };
+use crate::observe::{Observe, Observer};
 use crate::proto;
+use crate::rt::{Read, Timer, Write};
 use crate::upgrade::Upgraded;
 
 type Dispatcher<T, B> =
@@ -51,7 +55,7 @@ pub struct Parts<T> {
 #[must_use = "futures do nothing unless polled"]
 pub struct Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + Send + 'static,
+    T: Read + Write + Send + 'static,
     B: Body + 'static,
 {
     inner: Option<Dispatcher<T, B>>,
@@ -59,7 +63,7 @@ where
 
 impl<T, B> Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    T: Read + Write + Send + Unpin + 'static,
     B: Body + 'static,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
@@ -99,6 +103,8 @@ where
 /// After setting options, the builder is used to create a handshake future.
 #[derive(Clone, Debug)]
 pub struct Builder {
+    timer: Time,
+    observer: Observe,
     h09_responses: bool,
     h1_parser_config: ParserConfig,
     h1_writev: Option<bool>,
@@ -106,8 +112,11 @@ pub struct Builder {
     h1_preserve_header_case: bool,
     #[cfg(feature = "ffi")]
     h1_preserve_header_order: bool,
+    h1_max_headers: Option<usize>,
+    h1_max_body_size: Option<u64>,
     h1_read_buf_exact_size: Option<usize>,
     h1_max_buf_size: Option<usize>,
+    h1_expect_continue_timeout: Option<Duration>,
 }
 
 /// Returns a handshake future over some IO.
@@ -116,7 +125,7 @@ pub struct Builder {
 /// See [`client::conn`](crate::client::conn) for more.
 pub async fn handshake<T, B>(io: T) -> crate::Result<(SendRequest<B>, Connection<T, B>)>
 where
-    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: Read + Write + Unpin + Send + 'static,
     B: Body + 'static,
     B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -240,7 +249,7 @@ impl<B> fmt::Debug for SendRequest<B> {
 
 impl<T, B> fmt::Debug for Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + fmt::Debug + Send + 'static,
+    T: Read + Write + fmt::Debug + Send + 'static,
     B: Body + 'static,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -250,7 +259,7 @@ where
 
 impl<T, B> Future for Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: Read + Write + Unpin + Send + 'static,
     B: Body + Send + 'static,
     B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -282,6 +291,8 @@ impl Builder {
     #[inline]
     pub fn new() -> Builder {
         Builder {
+            timer: Time::Empty,
+            observer: Observe::default(),
             h09_responses: false,
             h1_writev: None,
             h1_read_buf_exact_size: None,
@@ -290,7 +301,10 @@ impl Builder {
             h1_preserve_header_case: false,
             #[cfg(feature = "ffi")]
             h1_preserve_header_order: false,
+            h1_max_headers: None,
+            h1_max_body_size: None,
             h1_max_buf_size: None,
+            h1_expect_continue_timeout: None,
         }
     }
 
@@ -437,6 +451,37 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of headers.
+    ///
+    /// When a response is received, the parser will reserve a buffer to store headers for optimal
+    /// performance.
+    ///
+    /// If client receives more headers than the buffer size, the connection
+    /// fails with a parse error.
+    ///
+    /// Headers are stored on the stack while this value is at most 100. Setting
+    /// a larger value moves the storage to the heap, which means an allocation
+    /// for each response parsed.
+    ///
+    /// Default is 100.
+    pub fn max_headers(&mut self, val: usize) -> &mut Builder {
+        self.h1_max_headers = Some(val);
+        self
+    }
+
+    /// Set the maximum size of a response body, in bytes.
+    ///
+    /// A response declaring a larger `Content-Length` fails with an error
+    /// instead of being returned. If a body without a declared length grows
+    /// past the limit, reading it returns an error and the connection is
+    /// closed.
+    ///
+    /// Default is None.
+    pub fn max_response_body_size(&mut self, max: u64) -> &mut Builder {
+        self.h1_max_body_size = Some(max);
+        self
+    }
+
     /// Sets the exact size of the read buffer to *always* use.
     ///
     /// Note that setting this option unsets the `max_buf_size` option.
@@ -468,6 +513,44 @@ impl Builder {
         self
     }
 
+    /// Set how long to wait for `100 Continue` before sending the body of a
+    /// request with an `Expect: 100-continue` header.
+    ///
+    /// The body is withheld until the server responds with `100 Continue`
+    /// or the timeout elapses. If a final response arrives first, the body
+    /// is never sent, and the connection is closed after that response.
+    ///
+    /// This requires a [`timer`](Builder::timer) to be set, otherwise the
+    /// handshake fails.
+    ///
+    /// Default is to send the body right away.
+    pub fn expect_continue_timeout(&mut self, timeout: Duration) -> &mut Builder {
+        self.h1_expect_continue_timeout = Some(timeout);
+        self
+    }
+
+    /// Provide a timer, used by the
+    /// [`expect_continue_timeout`](Builder::expect_continue_timeout).
+    pub fn timer<M>(&mut self, timer: M) -> &mut Builder
+    where
+        M: Timer + Send + Sync + 'static,
+    {
+        self.timer = Time::Timer(Arc::new(timer));
+        self
+    }
+
+    /// Set an observer for the events of connections, such as requests
+    /// being sent and responses being received.
+    ///
+    /// See the [`observe`](crate::observe) module for the events reported.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Builder
+    where
+        O: Observer + 'static,
+    {
+        self.observer = Observe::new(observer);
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::client::conn) for more.
     ///
@@ -478,7 +561,7 @@ impl Builder {
         io: T,
     ) -> impl Future<Output = crate::Result<(SendRequest<B>, Connection<T, B>)>>
     where
-        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: Read + Write + Unpin + Send + 'static,
         B: Body + 'static,
         B::Data: Send,
         B::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -488,8 +571,15 @@ impl Builder {
         async move {
             tracing::trace!("client handshake HTTP/1");
 
+            // The wait for `100 Continue` can't be bounded without a timer.
+            if opts.h1_expect_continue_timeout.is_some() && matches!(opts.timer, Time::Empty) {
+                return Err(crate::Error::new_user_missing_timer());
+            }
+
             let (tx, rx) = dispatch::channel();
             let mut conn = proto::Conn::new(io);
+            conn.set_timer(opts.timer);
+            conn.set_observer(opts.observer.open(Version::HTTP_11));
             conn.set_h1_parser_config(opts.h1_parser_config);
             if let Some(writev) = opts.h1_writev {
                 if writev {
@@ -513,12 +603,22 @@ impl Builder {
                 conn.set_h09_responses();
             }
 
+            if let Some(max_headers) = opts.h1_max_headers {
+                conn.set_http1_max_headers(max_headers);
+            }
+            if let Some(max) = opts.h1_max_body_size {
+                conn.set_http1_max_body_size(max);
+            }
+
             if let Some(sz) = opts.h1_read_buf_exact_size {
                 conn.set_read_buf_exact_size(sz);
             }
             if let Some(max) = opts.h1_max_buf_size {
                 conn.set_max_buf_size(max);
             }
+            if let Some(timeout) = opts.h1_expect_continue_timeout {
+                conn.set_http1_expect_continue_timeout(timeout);
+            }
             let cd = proto::h1::dispatch::Client::new(rx);
             let proto = proto::h1::Dispatcher::new(cd, conn);
 
@@ -7,7 +7,6 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use http::{Request, Response};
-use tokio::io::{AsyncRead, AsyncWrite};
 
 use super::super::dispatch;
 use crate::body::{Body, Incoming as IncomingBody};
@@ -16,8 +15,9 @@ use crate::common::{
     exec::{BoxSendFuture, Exec},
     task, Future, Pin, Poll,
 };
+use crate::observe::{Observe, Observer};
 use crate::proto;
-use crate::rt::{Executor, Timer};
+use crate::rt::{Executor, Read, Timer, Write};
 
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
@@ -37,7 +37,7 @@ impl<B> Clone for SendRequest<B> {
 #[must_use = "futures do nothing unless polled"]
 pub struct Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + Send + 'static,
+    T: Read + Write + Send + 'static,
     B: Body + 'static,
 {
     inner: (PhantomData<T>, proto::h2::ClientTask<B>),
@@ -48,7 +48,7 @@ where
 /// After setting options, the builder is used to create a handshake future.
 #[derive(Clone, Debug)]
 pub struct Builder {
-    pub(super) exec: Exec,
+    pub(crate) exec: Exec,
     pub(super) timer: Time,
     h2_builder: proto::h2::client::Config,
 }
@@ -63,7 +63,7 @@ pub async fn handshake<E, T, B>(
 ) -> crate::Result<(SendRequest<B>, Connection<T, B>)>
 where
     E: Executor<BoxSendFuture> + Send + Sync + 'static,
-    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: Read + Write + Unpin + Send + 'static,
     B: Body + 'static,
     B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -191,7 +191,7 @@ impl<B> fmt::Debug for SendRequest<B> {
 
 impl<T, B> Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: Read + Write + Unpin + Send + 'static,
     B: Body + Unpin + Send + 'static,
     B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -212,7 +212,7 @@ where
 
 impl<T, B> fmt::Debug for Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + fmt::Debug + Send + 'static,
+    T: Read + Write + fmt::Debug + Send + 'static,
     B: Body + 'static,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -222,7 +222,7 @@ where
 
 impl<T, B> Future for Connection<T, B>
 where
-    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: Read + Write + Unpin + Send + 'static,
     B: Body + Send + 'static,
     B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -263,6 +263,18 @@ impl Builder {
         self
     }
 
+    /// Set an observer for the events of connections, such as requests
+    /// being sent and responses being received.
+    ///
+    /// See the [`observe`](crate::observe) module for the events reported.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Builder
+    where
+        O: Observer + 'static,
+    {
+        self.h2_builder.observer = Observe::new(observer);
+        self
+    }
+
     /// Sets the [`SETTINGS_INITIAL_WINDOW_SIZE`][spec] option for HTTP2
     /// stream-level flow control.
     ///
@@ -387,6 +399,18 @@ impl Builder {
         self
     }
 
+    /// Set the maximum size of a response body, in bytes.
+    ///
+    /// A response declaring a larger `Content-Length` fails with an error
+    /// and its stream is reset. If a body without a declared length grows
+    /// past the limit, reading it returns an error.
+    ///
+    /// Default is None.
+    pub fn max_response_body_size(&mut self, max: u64) -> &mut Self {
+        self.h2_builder.max_response_body_size = Some(max);
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::client::conn) for more.
     ///
@@ -397,7 +421,7 @@ impl Builder {
         io: T,
     ) -> impl Future<Output = crate::Result<(SendRequest<B>, Connection<T, B>)>>
     where
-        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: Read + Write + Unpin + Send + 'static,
         B: Body + 'static,
         B::Data: Send,
         B::Error: Into<Box<dyn StdError + Send + Sync>>,
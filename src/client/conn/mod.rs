@@ -17,11 +17,12 @@
 //! use http::{Request, StatusCode};
 //! use http_body_util::Empty;
 //! use hyper::client::conn;
+//! use hyper::rt::TokioIo;
 //! use tokio::net::TcpStream;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     let target_stream = TcpStream::connect("example.com:80").await?;
+//!     let target_stream = TokioIo::new(TcpStream::connect("example.com:80").await?);
 //!
 //!     let (mut request_sender, connection) = conn::http1::handshake(target_stream).await?;
 //!
@@ -1,6 +1,7 @@
 //! HTTP Client
 //!
 //! hyper provides HTTP over a single connection. See the [`conn`](conn) module.
+//! Connections can be reused across requests with the [`pool`](pool) module.
 //!
 //! ## Example
 //!
@@ -15,4 +16,5 @@ cfg_feature! {
 
     pub mod conn;
     pub(super) mod dispatch;
+    pub mod pool;
 }
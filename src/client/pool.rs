@@ -0,0 +1,732 @@
+//! A pooled HTTP client
+//!
+//! The [`Client`] is built on the connections of [`client::conn`], and keeps
+//! them around to be reused by later requests to the same scheme and
+//! authority:
+//!
+//! - HTTP/1 connections are reused once the previous response has been
+//!   read, and closed after staying idle longer than the idle timeout.
+//! - HTTP/2 connections are shared by all requests to the same host.
+//!
+//! Connections are created with a [`Connect`], so that any transport can be
+//! used.
+//!
+//! [`client::conn`]: crate::client::conn
+//!
+//! ## Example
+//!
+//! ```no_run
+//! # #[cfg(all(feature = "http1", feature = "runtime"))]
+//! # mod rt {
+//! use std::future::Future;
+//! use std::pin::Pin;
+//!
+//! use bytes::Bytes;
+//! use http::{Request, Uri};
+//! use http_body_util::Empty;
+//! use hyper::client::pool::{Client, Connect};
+//! use hyper::rt::{TokioExecutor, TokioIo};
+//! use tokio::net::TcpStream;
+//!
+//! struct TcpConnector;
+//!
+//! impl Connect for TcpConnector {
+//!     type Io = TokioIo<TcpStream>;
+//!     type Error = std::io::Error;
+//!     type Future = Pin<Box<dyn Future<Output = std::io::Result<Self::Io>> + Send>>;
+//!
+//!     fn connect(&self, dst: &Uri) -> Self::Future {
+//!         let addr = format!("{}:{}", dst.host().unwrap(), dst.port_u16().unwrap_or(80));
+//!         Box::pin(async move { TcpStream::connect(addr).await.map(TokioIo::new) })
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let client = Client::builder(TokioExecutor).build(TcpConnector);
+//!
+//!     for _ in 0..2 {
+//!         // the second request reuses the connection of the first one
+//!         let req = Request::get("http://example.com/").body(Empty::<Bytes>::new())?;
+//!         let res = client.request(req).await?;
+//!         println!("status: {}", res.status());
+//!     }
+//!     Ok(())
+//! }
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(feature = "http1")]
+use std::time::Instant;
+
+#[cfg(feature = "http1")]
+use http::header::{HeaderValue, HOST};
+use http::uri::{Authority, Scheme};
+#[cfg(feature = "http1")]
+use http::Method;
+use http::{Request, Response, Uri};
+use tokio::sync::oneshot;
+use tracing::{debug, trace};
+
+#[cfg(feature = "http1")]
+use super::conn::http1;
+#[cfg(feature = "http2")]
+use super::conn::http2;
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::exec::{BoxSendFuture, Exec};
+use crate::common::time::Time;
+use crate::common::{task, Future, Pin, Poll};
+use crate::rt::{Executor, Read, Timer, Write};
+
+/// Connects to a remote address, for a [`Client`].
+///
+/// This allows the client to be used with any transport, for instance a
+/// TCP stream of some runtime, wrapped in TLS or not.
+pub trait Connect {
+    /// The IO object returned once connected.
+    type Io: Read + Write + Unpin + Send + 'static;
+    /// The error returned if connecting fails.
+    type Error: Into<Box<dyn StdError + Send + Sync>>;
+    /// The future connecting to `dst`.
+    type Future: Future<Output = Result<Self::Io, Self::Error>> + Send + 'static;
+
+    /// Connect to the scheme and authority of `dst`.
+    fn connect(&self, dst: &Uri) -> Self::Future;
+}
+
+/// A client that pools connections by scheme and authority.
+///
+/// Cloning a `Client` is cheap, and clones share the same pool.
+pub struct Client<C, B> {
+    inner: Arc<Inner<C, B>>,
+}
+
+struct Inner<C, B> {
+    connector: C,
+    config: Config,
+    pool: Arc<Mutex<Pool<B>>>,
+}
+
+/// A builder to configure a [`Client`].
+#[derive(Clone, Debug)]
+pub struct Builder {
+    config: Config,
+}
+
+#[derive(Clone, Debug)]
+struct Config {
+    exec: Exec,
+    timer: Time,
+    idle_timeout: Option<Duration>,
+    max_idle_per_host: usize,
+    max_connections_per_host: usize,
+    #[cfg(feature = "http1")]
+    http1: http1::Builder,
+    #[cfg(feature = "http2")]
+    http2: http2::Builder,
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    http2_only: bool,
+}
+
+/// A future resolving to the response of a request sent with a [`Client`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ResponseFuture {
+    inner: Pin<Box<dyn Future<Output = crate::Result<Response<IncomingBody>>> + Send>>,
+}
+
+type Key = (Scheme, Authority);
+
+struct Pool<B> {
+    hosts: HashMap<Key, Host<B>>,
+}
+
+struct Host<B> {
+    #[cfg(feature = "http1")]
+    idle: Vec<Idle<B>>,
+    #[cfg(feature = "http2")]
+    http2: Option<http2::SendRequest<B>>,
+    #[cfg(feature = "http2")]
+    http2_connecting: bool,
+    // Connections that are open or being connected, idle or not.
+    connections: usize,
+    waiters: VecDeque<oneshot::Sender<()>>,
+}
+
+#[cfg(feature = "http1")]
+struct Idle<B> {
+    tx: http1::SendRequest<B>,
+    since: Instant,
+}
+
+enum Checkout<B> {
+    #[cfg(feature = "http1")]
+    Http1(http1::SendRequest<B>),
+    #[cfg(feature = "http2")]
+    Http2(http2::SendRequest<B>),
+    Connect,
+    Wait(oneshot::Receiver<()>),
+}
+
+// ===== impl Client =====
+
+impl Client<(), ()> {
+    /// Creates a [`Builder`] to configure a client.
+    ///
+    /// The executor is used to drive the connections in the background.
+    pub fn builder<E>(exec: E) -> Builder
+    where
+        E: Executor<BoxSendFuture> + Clone + Send + Sync + 'static,
+    {
+        Builder::new(exec)
+    }
+}
+
+impl<C, B> Client<C, B>
+where
+    C: Connect + Send + Sync + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Sends a `Request` on a pooled connection.
+    ///
+    /// The request URI must be in absolute-form, since its scheme and
+    /// authority select the connection. For HTTP/1, the URI is sent in
+    /// origin-form, and a `Host` header is added if missing.
+    pub fn request(&self, req: Request<B>) -> ResponseFuture {
+        let inner = self.inner.clone();
+        ResponseFuture {
+            inner: Box::pin(async move { inner.send(req).await }),
+        }
+    }
+}
+
+impl<C, B> Clone for Client<C, B> {
+    fn clone(&self) -> Client<C, B> {
+        Client {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C, B> fmt::Debug for Client<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client").finish()
+    }
+}
+
+impl<C, B> Inner<C, B>
+where
+    C: Connect + Send + Sync + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    async fn send(&self, req: Request<B>) -> crate::Result<Response<IncomingBody>> {
+        let key = match (req.uri().scheme(), req.uri().authority()) {
+            (Some(scheme), Some(authority)) => (scheme.clone(), authority.clone()),
+            _ => return Err(crate::Error::new_user_absolute_uri_required()),
+        };
+
+        loop {
+            let checkout = lock(&self.pool).checkout(&key, &self.config);
+            match checkout {
+                #[cfg(feature = "http1")]
+                Checkout::Http1(tx) => {
+                    trace!("reusing idle connection for {:?}", key);
+                    return self.send_http1(key, tx, req).await;
+                }
+                #[cfg(feature = "http2")]
+                Checkout::Http2(mut tx) => {
+                    trace!("reusing HTTP/2 connection for {:?}", key);
+                    return tx.send_request(req).await;
+                }
+                Checkout::Connect => {
+                    let guard = ConnectingGuard {
+                        pool: &self.pool,
+                        key: &key,
+                    };
+                    let io = self
+                        .connector
+                        .connect(req.uri())
+                        .await
+                        .map_err(crate::Error::new_connect)?;
+
+                    #[cfg(feature = "http2")]
+                    if self.config.is_http2_only() {
+                        let (mut tx, conn) = self.config.http2.handshake(io).await?;
+                        guard.connected();
+                        self.spawn_connection(key.clone(), conn);
+                        lock(&self.pool).put_http2(&key, tx.clone());
+                        return tx.send_request(req).await;
+                    }
+
+                    #[cfg(feature = "http1")]
+                    {
+                        let (tx, conn) = self.config.http1.handshake(io).await?;
+                        guard.connected();
+                        self.spawn_connection(key.clone(), conn);
+                        return self.send_http1(key, tx, req).await;
+                    }
+
+                    #[cfg(not(feature = "http1"))]
+                    unreachable!("HTTP/2 is always used without http1");
+                }
+                Checkout::Wait(rx) => {
+                    // A connection was released, or HTTP/2 finished
+                    // connecting. Either way, try again.
+                    trace!("waiting for a connection to {:?}", key);
+                    let _ = rx.await;
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "http1")]
+    async fn send_http1(
+        &self,
+        key: Key,
+        mut tx: http1::SendRequest<B>,
+        mut req: Request<B>,
+    ) -> crate::Result<Response<IncomingBody>> {
+        set_host(&mut req);
+        if req.method() != Method::CONNECT {
+            let path = req
+                .uri()
+                .path_and_query()
+                .map_or("/", |path| path.as_str())
+                .parse()
+                .expect("path is a valid uri");
+            *req.uri_mut() = path;
+        }
+
+        let res = tx.send_request(req).await;
+
+        // Once the response has been read, the connection can be reused.
+        let pool = Arc::downgrade(&self.pool);
+        let config = self.config.clone();
+        self.config.exec.execute(async move {
+            if futures_util::future::poll_fn(|cx| tx.poll_ready(cx))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            if let Some(pool) = pool.upgrade() {
+                lock(&pool).put_idle(key.clone(), tx, &config);
+                schedule_idle_eviction(&pool, key, &config);
+            }
+        });
+
+        res
+    }
+
+    fn spawn_connection<F>(&self, key: Key, conn: F)
+    where
+        F: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        let pool = Arc::downgrade(&self.pool);
+        self.config.exec.execute(async move {
+            if let Err(e) = conn.await {
+                debug!("client connection error: {}", e);
+            }
+            trace!("connection to {:?} closed", key);
+            if let Some(pool) = pool.upgrade() {
+                lock(&pool).closed(&key);
+            }
+        });
+    }
+}
+
+fn lock<B>(pool: &Mutex<Pool<B>>) -> std::sync::MutexGuard<'_, Pool<B>> {
+    pool.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(feature = "http1")]
+fn set_host<B>(req: &mut Request<B>) {
+    if req.headers().contains_key(HOST) {
+        return;
+    }
+    let uri = req.uri();
+    let host = uri.host().expect("authority has a host");
+    let is_default_port = matches!(
+        (uri.scheme_str(), uri.port_u16()),
+        (_, None) | (Some("http"), Some(80)) | (Some("https"), Some(443))
+    );
+    let value = if is_default_port {
+        HeaderValue::from_str(host)
+    } else {
+        HeaderValue::from_str(&format!("{}:{}", host, uri.port_u16().unwrap()))
+    };
+    if let Ok(value) = value {
+        req.headers_mut().insert(HOST, value);
+    }
+}
+
+#[cfg(feature = "http1")]
+fn schedule_idle_eviction<B: Send + 'static>(
+    pool: &Arc<Mutex<Pool<B>>>,
+    key: Key,
+    config: &Config,
+) {
+    let timeout = match (config.idle_timeout, &config.timer) {
+        (Some(timeout), Time::Timer(_)) => timeout,
+        _ => return,
+    };
+    let pool = Arc::downgrade(pool);
+    let timer = config.timer.clone();
+    let sleep = timer.sleep(timeout);
+    config.exec.execute(async move {
+        sleep.await;
+        if let Some(pool) = pool.upgrade() {
+            lock(&pool).evict_idle(&key, timeout, &timer);
+        }
+    });
+}
+
+/// Releases the connection slot taken for connecting, unless connecting
+/// succeeded.
+struct ConnectingGuard<'a, B> {
+    pool: &'a Mutex<Pool<B>>,
+    key: &'a Key,
+}
+
+impl<B> ConnectingGuard<'_, B> {
+    fn connected(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl<B> Drop for ConnectingGuard<'_, B> {
+    fn drop(&mut self) {
+        trace!("connecting to {:?} failed", self.key);
+        lock(self.pool).connect_failed(self.key);
+    }
+}
+
+// ===== impl Pool =====
+
+impl<B> Pool<B> {
+    fn checkout(&mut self, key: &Key, config: &Config) -> Checkout<B> {
+        let host = self.hosts.entry(key.clone()).or_insert_with(|| Host {
+            #[cfg(feature = "http1")]
+            idle: Vec::new(),
+            #[cfg(feature = "http2")]
+            http2: None,
+            #[cfg(feature = "http2")]
+            http2_connecting: false,
+            connections: 0,
+            waiters: VecDeque::new(),
+        });
+
+        #[cfg(feature = "http2")]
+        if config.is_http2_only() {
+            if let Some(ref tx) = host.http2 {
+                if !tx.is_closed() {
+                    return Checkout::Http2(tx.clone());
+                }
+                host.http2 = None;
+            }
+            if host.http2_connecting {
+                return host.wait();
+            }
+            if host.connections < config.max_connections_per_host {
+                host.http2_connecting = true;
+                host.connections += 1;
+                return Checkout::Connect;
+            }
+            return host.wait();
+        }
+
+        #[cfg(feature = "http1")]
+        while let Some(idle) = host.idle.pop() {
+            let expired = config
+                .idle_timeout
+                .map_or(false, |timeout| idle.idle_for(&config.timer) >= timeout);
+            if !expired && idle.tx.is_ready() {
+                return Checkout::Http1(idle.tx);
+            }
+            trace!("removing idle connection to {:?}", key);
+        }
+
+        if host.connections < config.max_connections_per_host {
+            host.connections += 1;
+            Checkout::Connect
+        } else {
+            host.wait()
+        }
+    }
+
+    #[cfg(feature = "http1")]
+    fn put_idle(&mut self, key: Key, tx: http1::SendRequest<B>, config: &Config) {
+        let host = match self.hosts.get_mut(&key) {
+            Some(host) => host,
+            None => return,
+        };
+        if host.idle.len() >= config.max_idle_per_host {
+            trace!("max idle per host for {:?}, dropping connection", key);
+            return;
+        }
+        host.idle.push(Idle {
+            tx,
+            since: config.timer.now(),
+        });
+        host.notify();
+    }
+
+    #[cfg(feature = "http1")]
+    fn evict_idle(&mut self, key: &Key, timeout: Duration, timer: &Time) {
+        if let Some(host) = self.hosts.get_mut(key) {
+            host.idle.retain(|idle| idle.idle_for(timer) < timeout);
+        }
+    }
+
+    #[cfg(feature = "http2")]
+    fn put_http2(&mut self, key: &Key, tx: http2::SendRequest<B>) {
+        if let Some(host) = self.hosts.get_mut(key) {
+            host.http2 = Some(tx);
+            host.http2_connecting = false;
+            host.notify();
+        }
+    }
+
+    /// Connecting to `key` failed, so another connection may be tried.
+    fn connect_failed(&mut self, key: &Key) {
+        #[cfg(feature = "http2")]
+        if let Some(host) = self.hosts.get_mut(key) {
+            host.http2_connecting = false;
+        }
+        self.closed(key);
+    }
+
+    /// A connection to `key` closed, or failed to connect.
+    ///
+    /// This leaves an HTTP/2 connection that is still connecting alone, it is
+    /// only done once `put_http2` or `connect_failed` is called for it.
+    fn closed(&mut self, key: &Key) {
+        let host = match self.hosts.get_mut(key) {
+            Some(host) => host,
+            None => return,
+        };
+        host.connections -= 1;
+        #[cfg(feature = "http2")]
+        if host.http2.as_ref().map_or(false, |tx| tx.is_closed()) {
+            host.http2 = None;
+        }
+        host.notify();
+        if host.connections == 0 && host.waiters.is_empty() {
+            self.hosts.remove(key);
+        }
+    }
+}
+
+#[cfg(feature = "http1")]
+impl<B> Idle<B> {
+    fn idle_for(&self, timer: &Time) -> Duration {
+        timer.now().saturating_duration_since(self.since)
+    }
+}
+
+impl<B> Host<B> {
+    fn wait(&mut self) -> Checkout<B> {
+        let (tx, rx) = oneshot::channel();
+        self.waiters.push_back(tx);
+        Checkout::Wait(rx)
+    }
+
+    // Waiters try to check out a connection again.
+    fn notify(&mut self) {
+        for waiter in self.waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+}
+
+// ===== impl Builder =====
+
+impl Builder {
+    /// Creates a new client builder.
+    ///
+    /// The executor is used to drive the connections in the background.
+    pub fn new<E>(exec: E) -> Builder
+    where
+        E: Executor<BoxSendFuture> + Clone + Send + Sync + 'static,
+    {
+        Builder {
+            config: Config {
+                #[cfg(feature = "http2")]
+                http2: http2::Builder::new(exec.clone()),
+                exec: Exec::new(exec),
+                timer: Time::Empty,
+                idle_timeout: Some(Duration::from_secs(90)),
+                max_idle_per_host: usize::MAX,
+                max_connections_per_host: usize::MAX,
+                #[cfg(feature = "http1")]
+                http1: http1::Builder::new(),
+                #[cfg(all(feature = "http1", feature = "http2"))]
+                http2_only: false,
+            },
+        }
+    }
+
+    /// Provide a timer, used to close connections that stay idle longer
+    /// than the [idle timeout](Builder::pool_idle_timeout).
+    ///
+    /// It is also used by the HTTP/1 and HTTP/2 connections.
+    pub fn timer<M>(&mut self, timer: M) -> &mut Self
+    where
+        M: Timer + Clone + Send + Sync + 'static,
+    {
+        #[cfg(feature = "http1")]
+        self.config.http1.timer(timer.clone());
+        #[cfg(feature = "http2")]
+        self.config.http2.timer(timer.clone());
+        self.config.timer = Time::Timer(Arc::new(timer));
+        self
+    }
+
+    /// Set how long an idle HTTP/1 connection is kept in the pool.
+    ///
+    /// Expired connections are never reused. They are only closed right
+    /// away if a [`timer`](Builder::timer) is provided, otherwise they are
+    /// closed when found by a later request.
+    ///
+    /// Pass `None` to keep idle connections forever.
+    ///
+    /// Default is 90 seconds.
+    pub fn pool_idle_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.config.idle_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of idle HTTP/1 connections kept per host.
+    ///
+    /// Default is `usize::MAX` (no limit).
+    pub fn pool_max_idle_per_host(&mut self, max: usize) -> &mut Self {
+        self.config.max_idle_per_host = max;
+        self
+    }
+
+    /// Set the maximum number of connections open to the same host, idle or
+    /// in use.
+    ///
+    /// Once reached, requests wait for a connection to be released.
+    ///
+    /// Default is `usize::MAX` (no limit).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if 0 is passed.
+    pub fn pool_max_connections_per_host(&mut self, max: usize) -> &mut Self {
+        assert!(max > 0, "max connections per host must be greater than 0");
+        self.config.max_connections_per_host = max;
+        self
+    }
+
+    /// Returns the builder used to configure HTTP/1 connections.
+    #[cfg(feature = "http1")]
+    pub fn http1(&mut self) -> &mut http1::Builder {
+        &mut self.config.http1
+    }
+
+    /// Returns the builder used to configure HTTP/2 connections.
+    #[cfg(feature = "http2")]
+    pub fn http2(&mut self) -> &mut http2::Builder {
+        &mut self.config.http2
+    }
+
+    /// Set whether the connections only speak HTTP/2.
+    ///
+    /// A single HTTP/2 connection is then shared by all requests to a host.
+    ///
+    /// Default is `false`.
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    pub fn http2_only(&mut self, enabled: bool) -> &mut Self {
+        self.config.http2_only = enabled;
+        self
+    }
+
+    /// Combine the configuration of this builder with a connector to create
+    /// a `Client`.
+    pub fn build<C, B>(&self, connector: C) -> Client<C, B>
+    where
+        C: Connect + Send + Sync + 'static,
+        B: Body + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        Client {
+            inner: Arc::new(Inner {
+                connector,
+                config: self.config.clone(),
+                pool: Arc::new(Mutex::new(Pool {
+                    hosts: HashMap::new(),
+                })),
+            }),
+        }
+    }
+}
+
+impl Config {
+    #[cfg(feature = "http2")]
+    fn is_http2_only(&self) -> bool {
+        #[cfg(feature = "http1")]
+        return self.http2_only;
+        #[cfg(not(feature = "http1"))]
+        return true;
+    }
+}
+
+// ===== impl ResponseFuture =====
+
+impl Future for ResponseFuture {
+    type Output = crate::Result<Response<IncomingBody>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+impl fmt::Debug for ResponseFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Future<Response>")
+    }
+}
+
+#[cfg(all(test, feature = "http1", feature = "http2"))]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct NoopExec;
+
+    impl<F> Executor<F> for NoopExec {
+        fn execute(&self, _fut: F) {}
+    }
+
+    #[test]
+    fn http2_connecting_outlives_other_connections() {
+        let mut builder = Builder::new(NoopExec);
+        builder.http2_only(true);
+        let config = builder.config;
+        let key: Key = (Scheme::HTTP, Authority::from_static("example.local"));
+        let mut pool = Pool::<crate::body::Incoming> {
+            hosts: HashMap::new(),
+        };
+
+        assert!(matches!(pool.checkout(&key, &config), Checkout::Connect));
+        // an older connection to the host, which closes while the HTTP/2
+        // connection is still connecting
+        pool.hosts.get_mut(&key).unwrap().connections += 1;
+        pool.closed(&key);
+        assert!(matches!(pool.checkout(&key, &config), Checkout::Wait(_)));
+
+        pool.connect_failed(&key);
+        assert!(matches!(pool.checkout(&key, &config), Checkout::Connect));
+    }
+}
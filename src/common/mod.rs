@@ -14,6 +14,9 @@ pub(crate) mod date;
 pub(crate) mod exec;
 pub(crate) mod io;
 mod never;
+#[cfg(any(feature = "http2", all(feature = "server", feature = "http1")))]
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub(crate) mod rate;
 pub(crate) mod task;
 #[cfg(any(feature = "http1", feature = "http2", feature = "server"))]
 pub(crate) mod time;
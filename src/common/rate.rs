@@ -0,0 +1,178 @@
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+use super::time::Time;
+use super::{task, Future, Pin, Poll};
+use crate::rt::Sleep;
+
+/// A minimum rate at which data must be transferred.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MinDataRate {
+    bytes_per_second: u64,
+    grace_period: Duration,
+}
+
+/// Tracks a transfer against a `MinDataRate`.
+///
+/// Time only counts while the transfer is waiting on the peer, it is
+/// paused while waiting on the local side. Clones share their state, so
+/// one task can record progress while another polls for expiry.
+#[derive(Clone)]
+pub(crate) struct DataRate {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    rate: MinDataRate,
+    timer: Time,
+    // Pushed forward by the time spent paused.
+    start: Instant,
+    bytes: u64,
+    paused_at: Option<Instant>,
+    expired: bool,
+    // The byte count the sleep's deadline was computed for.
+    armed_for: Option<u64>,
+    sleep: Option<Pin<Box<dyn Sleep>>>,
+    waker: Option<Waker>,
+}
+
+impl MinDataRate {
+    pub(crate) fn new(bytes_per_second: u64, grace_period: Duration) -> MinDataRate {
+        assert!(
+            bytes_per_second > 0,
+            "minimum data rate must be larger than 0"
+        );
+        MinDataRate {
+            bytes_per_second,
+            grace_period,
+        }
+    }
+
+    /// How long a transfer of `bytes` may take.
+    fn allowed(&self, bytes: u64) -> Duration {
+        let nanos = u128::from(bytes) * 1_000_000_000 / u128::from(self.bytes_per_second);
+        let allowed = Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX));
+        allowed.max(self.grace_period)
+    }
+}
+
+impl DataRate {
+    /// Starts tracking a transfer, initially paused.
+    pub(crate) fn new(rate: MinDataRate, timer: Time) -> DataRate {
+        let now = timer.now();
+        DataRate {
+            inner: Arc::new(Mutex::new(Inner {
+                rate,
+                timer,
+                start: now,
+                bytes: 0,
+                paused_at: Some(now),
+                expired: false,
+                armed_for: None,
+                sleep: None,
+                waker: None,
+            })),
+        }
+    }
+
+    pub(crate) fn record(&self, bytes: usize) {
+        self.inner.lock().unwrap().bytes += bytes as u64;
+    }
+
+    /// Stops counting time, the transfer is waiting on the local side.
+    pub(crate) fn pause(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.paused_at.is_none() {
+            inner.paused_at = Some(inner.timer.now());
+        }
+    }
+
+    /// Counts time again, the transfer is waiting on the peer.
+    pub(crate) fn resume(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(paused_at) = inner.paused_at.take() {
+            let paused_for = inner.timer.now().saturating_duration_since(paused_at);
+            inner.start += paused_for;
+            inner.armed_for = None;
+            if let Some(waker) = inner.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn is_expired(&self) -> bool {
+        self.inner.lock().unwrap().expired
+    }
+
+    /// Resolves once the transfer has fallen below the minimum rate.
+    pub(crate) fn poll_expired(&self, cx: &mut task::Context<'_>) -> Poll<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        if inner.expired {
+            return Poll::Ready(());
+        }
+        if inner.paused_at.is_some() {
+            inner.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        if inner.armed_for != Some(inner.bytes) {
+            let deadline = inner.start + inner.rate.allowed(inner.bytes);
+            match inner.sleep {
+                Some(ref mut sleep) => inner.timer.reset(sleep, deadline),
+                None => inner.sleep = Some(inner.timer.sleep_until(deadline)),
+            }
+            inner.armed_for = Some(inner.bytes);
+        }
+
+        let sleep = inner.sleep.as_mut().expect("sleep armed");
+        if Future::poll(sleep.as_mut(), cx).is_pending() {
+            inner.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        // Nothing arrived between arming the sleep and its deadline.
+        inner.expired = true;
+        Poll::Ready(())
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use futures_util::future::poll_fn;
+
+    use super::{DataRate, MinDataRate};
+    use crate::common::time::Time;
+    use crate::rt::TokioTimer;
+
+    #[tokio::test]
+    async fn data_rate_expires_without_progress() {
+        let rate = DataRate::new(
+            MinDataRate::new(1000, Duration::from_millis(50)),
+            Time::Timer(Arc::new(TokioTimer)),
+        );
+
+        // time spent paused doesn't count
+        let paused = tokio::time::timeout(
+            Duration::from_millis(100),
+            poll_fn(|cx| rate.poll_expired(cx)),
+        );
+        assert!(paused.await.is_err());
+
+        // 100 bytes buy 100ms, more than the grace period
+        let start = Instant::now();
+        rate.resume();
+        rate.record(100);
+        tokio::time::timeout(Duration::from_secs(1), poll_fn(|cx| rate.poll_expired(cx)))
+            .await
+            .expect("data rate should expire");
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        assert!(rate.is_expired());
+    }
+}
@@ -55,6 +55,15 @@ impl<F> Future for HyperTimeout<F> where F: Future {
 */
 
 impl Time {
+    /// The current instant of the timer's clock, or of the system clock if
+    /// there is no timer.
+    pub(crate) fn now(&self) -> Instant {
+        match *self {
+            Time::Empty => Instant::now(),
+            Time::Timer(ref t) => t.now(),
+        }
+    }
+
     pub(crate) fn sleep(&self, duration: Duration) -> Pin<Box<dyn Sleep>> {
         match *self {
             Time::Empty => {
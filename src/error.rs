@@ -31,6 +31,9 @@ pub(super) enum Kind {
     Canceled,
     /// Indicates a channel (client or body sender) is closed.
     ChannelClosed,
+    /// Error from a `Connect` of a pooled client.
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    Connect,
     /// An `io::Error` that occurred while trying to read or write to a network stream.
     #[cfg(any(feature = "http1", feature = "http2"))]
     Io,
@@ -40,13 +43,22 @@ pub(super) enum Kind {
     /// User took too long to send headers
     #[cfg(all(feature = "http1", feature = "server"))]
     HeaderTimeout,
+    /// A kept-alive connection received no new request in time
+    #[cfg(all(feature = "http1", feature = "server"))]
+    IdleTimeout,
+    /// A peer transferred body data slower than the configured minimum rate
+    #[cfg(any(feature = "http2", all(feature = "server", feature = "http1")))]
+    MinDataRate,
+    /// A body was larger than the configured maximum size.
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    BodyTooLarge,
     /// Error while reading a body from connection.
     #[cfg(any(feature = "http1", feature = "http2"))]
     Body,
     /// Error while writing a body to connection.
     #[cfg(any(feature = "http1", feature = "http2"))]
     BodyWrite,
-    /// Error calling AsyncWrite::shutdown()
+    /// Error calling Write::poll_shutdown()
     #[cfg(feature = "http1")]
     Shutdown,
 
@@ -102,6 +114,16 @@ pub(super) enum User {
     #[cfg(feature = "http1")]
     #[cfg(feature = "server")]
     UnsupportedStatusCode,
+    /// User tried to send an informational response without a 1xx status,
+    /// or with 101.
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(feature = "server")]
+    InformationalStatus,
+    /// User tried to send an informational response on a connection that
+    /// can't send them.
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(feature = "server")]
+    InformationalUnsupported,
 
     /// User tried polling for an upgrade that doesn't exist.
     NoUpgrade,
@@ -114,6 +136,15 @@ pub(super) enum User {
     #[cfg(feature = "client")]
     DispatchGone,
 
+    /// A client connection was configured with an option that needs a timer,
+    /// but no timer was set.
+    #[cfg(all(feature = "client", feature = "http1"))]
+    MissingTimer,
+
+    /// A pooled client was given a request without a scheme and authority.
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    AbsoluteUriRequired,
+
     /// User aborted in an FFI callback.
     #[cfg(feature = "ffi")]
     AbortedByCallback,
@@ -158,6 +189,12 @@ impl Error {
         matches!(self.inner.kind, Kind::ChannelClosed)
     }
 
+    /// Returns true if this was an error from `Connect`.
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    pub fn is_connect(&self) -> bool {
+        matches!(self.inner.kind, Kind::Connect)
+    }
+
     /// Returns true if the connection closed before a message could complete.
     pub fn is_incomplete_message(&self) -> bool {
         matches!(self.inner.kind, Kind::IncompleteMessage)
@@ -254,11 +291,21 @@ impl Error {
         Error::new(Kind::ChannelClosed)
     }
 
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_connect<E: Into<Cause>>(cause: E) -> Error {
+        Error::new(Kind::Connect).with(cause)
+    }
+
     #[cfg(any(feature = "http1", feature = "http2"))]
     pub(super) fn new_body<E: Into<Cause>>(cause: E) -> Error {
         Error::new(Kind::Body).with(cause)
     }
 
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    pub(super) fn new_body_too_large() -> Error {
+        Error::new(Kind::BodyTooLarge)
+    }
+
     #[cfg(any(feature = "http1", feature = "http2"))]
     pub(super) fn new_body_write<E: Into<Cause>>(cause: E) -> Error {
         Error::new(Kind::BodyWrite).with(cause)
@@ -283,12 +330,34 @@ impl Error {
         Error::new(Kind::HeaderTimeout)
     }
 
+    #[cfg(all(feature = "http1", feature = "server"))]
+    pub(super) fn new_idle_timeout() -> Error {
+        Error::new(Kind::IdleTimeout).with(TimedOut)
+    }
+
+    #[cfg(any(feature = "http2", all(feature = "server", feature = "http1")))]
+    pub(super) fn new_min_data_rate() -> Error {
+        Error::new(Kind::MinDataRate).with(TimedOut)
+    }
+
     #[cfg(feature = "http1")]
     #[cfg(feature = "server")]
     pub(super) fn new_user_unsupported_status_code() -> Error {
         Error::new_user(User::UnsupportedStatusCode)
     }
 
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(feature = "server")]
+    pub(super) fn new_user_informational_status() -> Error {
+        Error::new_user(User::InformationalStatus)
+    }
+
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    #[cfg(feature = "server")]
+    pub(super) fn new_user_informational_unsupported() -> Error {
+        Error::new_user(User::InformationalUnsupported)
+    }
+
     pub(super) fn new_user_no_upgrade() -> Error {
         Error::new_user(User::NoUpgrade)
     }
@@ -323,6 +392,16 @@ impl Error {
         Error::new(Kind::User(User::DispatchGone))
     }
 
+    #[cfg(all(feature = "client", feature = "http1"))]
+    pub(super) fn new_user_missing_timer() -> Error {
+        Error::new_user(User::MissingTimer)
+    }
+
+    #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+    pub(super) fn new_user_absolute_uri_required() -> Error {
+        Error::new_user(User::AbsoluteUriRequired)
+    }
+
     #[cfg(feature = "http2")]
     pub(super) fn new_h2(cause: ::h2::Error) -> Error {
         if cause.is_io() {
@@ -368,10 +447,18 @@ impl Error {
             Kind::UnexpectedMessage => "received unexpected message from connection",
             Kind::ChannelClosed => "channel closed",
             Kind::Canceled => "operation was canceled",
+            #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+            Kind::Connect => "error trying to connect",
             #[cfg(all(feature = "server", feature = "tcp"))]
             Kind::Listen => "error creating server listener",
             #[cfg(all(feature = "http1", feature = "server"))]
             Kind::HeaderTimeout => "read header from client timeout",
+            #[cfg(all(feature = "http1", feature = "server"))]
+            Kind::IdleTimeout => "idle connection timed out waiting for a request",
+            #[cfg(any(feature = "http2", all(feature = "server", feature = "http1")))]
+            Kind::MinDataRate => "data transfer rate below the configured minimum",
+            #[cfg(any(feature = "http1", feature = "http2"))]
+            Kind::BodyTooLarge => "message body is larger than the configured maximum",
             #[cfg(any(feature = "http1", feature = "http2"))]
             Kind::Body => "error reading a body from connection",
             #[cfg(any(feature = "http1", feature = "http2"))]
@@ -396,11 +483,25 @@ impl Error {
             Kind::User(User::UnsupportedStatusCode) => {
                 "response has 1xx status code, not supported by server"
             }
+            #[cfg(any(feature = "http1", feature = "http2"))]
+            #[cfg(feature = "server")]
+            Kind::User(User::InformationalStatus) => {
+                "informational response has a non-1xx or 101 status code"
+            }
+            #[cfg(any(feature = "http1", feature = "http2"))]
+            #[cfg(feature = "server")]
+            Kind::User(User::InformationalUnsupported) => {
+                "informational responses are not supported by the connection"
+            }
             Kind::User(User::NoUpgrade) => "no upgrade available",
             #[cfg(feature = "http1")]
             Kind::User(User::ManualUpgrade) => "upgrade expected but low level API in use",
             #[cfg(feature = "client")]
             Kind::User(User::DispatchGone) => "dispatch task is gone",
+            #[cfg(all(feature = "client", feature = "http1"))]
+            Kind::User(User::MissingTimer) => "the connection options require a timer",
+            #[cfg(all(feature = "client", any(feature = "http1", feature = "http2")))]
+            Kind::User(User::AbsoluteUriRequired) => "client requires absolute-form URIs",
             #[cfg(feature = "ffi")]
             Kind::User(User::AbortedByCallback) => "operation aborted by an application callback",
         }
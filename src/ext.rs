@@ -8,8 +8,14 @@ use http::header::{IntoHeaderName, ValueIter};
 use http::HeaderMap;
 #[cfg(feature = "ffi")]
 use std::collections::HashMap;
+#[cfg(all(feature = "server", feature = "http1"))]
+use std::collections::VecDeque;
 #[cfg(feature = "http2")]
 use std::fmt;
+#[cfg(all(feature = "server", feature = "http1"))]
+use std::sync::{Arc, Mutex};
+#[cfg(all(feature = "server", feature = "http1"))]
+use std::task::{Context, Poll, Waker};
 
 #[cfg(any(feature = "http1", feature = "ffi"))]
 mod h1_reason_phrase;
@@ -73,6 +79,114 @@ impl fmt::Debug for Protocol {
     }
 }
 
+/// A handle to send informational (1xx) responses to a request.
+///
+/// Servers insert it into the extensions of each request, so that the
+/// service can send any number of interim responses, such as
+/// `103 Early Hints`, before its final response.
+///
+/// On HTTP/1.1 connections, the responses are written as soon as possible.
+/// HTTP/1.0 clients don't understand them, and HTTP/2 connections can't send
+/// them yet, so sending fails on those.
+#[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+#[derive(Clone, Debug)]
+pub struct InformationalSender {
+    #[cfg(feature = "http1")]
+    queue: Option<(Arc<InformationalQueue>, u64)>,
+}
+
+/// The informational responses sent by the requests of a connection.
+///
+/// Only the request currently being answered can send, so a connection
+/// shares one queue between all its requests.
+#[cfg(all(feature = "server", feature = "http1"))]
+#[derive(Debug, Default)]
+pub(crate) struct InformationalQueue {
+    state: Mutex<QueueState>,
+}
+
+#[cfg(all(feature = "server", feature = "http1"))]
+#[derive(Debug, Default)]
+struct QueueState {
+    // Identifies the request allowed to send.
+    request: u64,
+    responses: VecDeque<http::Response<()>>,
+    waker: Option<Waker>,
+}
+
+#[cfg(all(feature = "server", any(feature = "http1", feature = "http2")))]
+impl InformationalSender {
+    pub(crate) fn unsupported() -> Self {
+        Self {
+            #[cfg(feature = "http1")]
+            queue: None,
+        }
+    }
+
+    /// Sends an informational response.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the status of `res` isn't 1xx, or is `101 Switching
+    /// Protocols`, which is sent by upgrades instead. Also fails if the
+    /// final response was already sent, or if the connection doesn't
+    /// support informational responses.
+    pub fn send(&self, res: http::Response<()>) -> crate::Result<()> {
+        let status = res.status();
+        if !status.is_informational() || status == http::StatusCode::SWITCHING_PROTOCOLS {
+            return Err(crate::Error::new_user_informational_status());
+        }
+        #[cfg(feature = "http1")]
+        if let Some((ref queue, request)) = self.queue {
+            return queue.push(request, res);
+        }
+        Err(crate::Error::new_user_informational_unsupported())
+    }
+}
+
+#[cfg(all(feature = "server", feature = "http1"))]
+impl InformationalQueue {
+    /// Returns a sender for the request currently being answered.
+    pub(crate) fn sender(self: &Arc<Self>) -> InformationalSender {
+        let request = self.state.lock().unwrap().request;
+        InformationalSender {
+            queue: Some((self.clone(), request)),
+        }
+    }
+
+    fn push(&self, request: u64, res: http::Response<()>) -> crate::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.request != request {
+            return Err(crate::Error::new_closed());
+        }
+        state.responses.push_back(res);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<http::Response<()>> {
+        let mut state = self.state.lock().unwrap();
+        match state.responses.pop_front() {
+            Some(res) => Poll::Ready(res),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Stops accepting responses from the senders of the current request.
+    ///
+    /// Responses already queued can still be received.
+    pub(crate) fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.request = state.request.wrapping_add(1);
+        state.waker = None;
+    }
+}
+
 /// A map from header names to their original casing as received in an HTTP message.
 ///
 /// If an HTTP/1 response `res` is parsed on a connection whose option
@@ -6,19 +6,35 @@ use std::task::{Context, Poll};
 use http_body_util::BodyExt as _;
 use libc::{c_int, size_t};
 
+use super::http_types::hyper_headers;
 use super::task::{hyper_context, hyper_task, hyper_task_return_type, AsTaskType};
 use super::{UserDataPointer, HYPER_ITER_CONTINUE};
 use crate::body::{Bytes, Frame, Incoming as IncomingBody};
 
 /// A streaming HTTP body.
-pub struct hyper_body(pub(super) IncomingBody);
+pub struct hyper_body {
+    pub(super) body: IncomingBody,
+    /// The trailers received after the data, if any.
+    trailers: Option<hyper_headers>,
+}
 
 /// A buffer of bytes that is sent or received on a `hyper_body`.
 pub struct hyper_buf(pub(crate) Bytes);
 
 pub(crate) struct UserBody {
     data_func: hyper_body_data_callback,
+    trailers_func: Option<hyper_body_trailers_callback>,
     userdata: *mut c_void,
+    /// Whether the data callback has ended the data.
+    data_done: bool,
+}
+
+/// Bytes owned by the application, released once hyper is done with them.
+struct ForeignBytes {
+    ptr: *const u8,
+    len: usize,
+    release: hyper_buf_release_callback,
+    userdata: UserDataPointer,
 }
 
 // ===== Body =====
@@ -28,12 +44,26 @@ type hyper_body_foreach_callback = extern "C" fn(*mut c_void, *const hyper_buf)
 type hyper_body_data_callback =
     extern "C" fn(*mut c_void, *mut hyper_context<'_>, *mut *mut hyper_buf) -> c_int;
 
+type hyper_body_trailers_callback =
+    extern "C" fn(*mut c_void, *mut hyper_context<'_>, *mut hyper_headers) -> c_int;
+
+type hyper_buf_release_callback = extern "C" fn(*mut c_void);
+
+impl hyper_body {
+    pub(super) fn wrap(body: IncomingBody) -> hyper_body {
+        hyper_body {
+            body,
+            trailers: None,
+        }
+    }
+}
+
 ffi_fn! {
     /// Create a new "empty" body.
     ///
     /// If not configured, this body acts as an empty payload.
     fn hyper_body_new() -> *mut hyper_body {
-        Box::into_raw(Box::new(hyper_body(IncomingBody::ffi())))
+        Box::into_raw(Box::new(hyper_body::wrap(IncomingBody::ffi())))
     } ?= ptr::null_mut()
 }
 
@@ -53,6 +83,12 @@ ffi_fn! {
     /// - `HYPER_TASK_ERROR`: An error retrieving the data.
     /// - `HYPER_TASK_EMPTY`: The body has finished streaming data.
     ///
+    /// The `hyper_buf *` refers to the bytes hyper received, without copying
+    /// them.
+    ///
+    /// Trailers received after the data are kept in the body, and can be
+    /// retrieved with `hyper_body_trailers` once the data has finished.
+    ///
     /// This does not consume the `hyper_body *`, so it may be used to again.
     /// However, it MUST NOT be used or freed until the related task completes.
     fn hyper_body_data(body: *mut hyper_body) -> *mut hyper_task {
@@ -61,12 +97,16 @@ ffi_fn! {
 
         Box::into_raw(hyper_task::boxed(async move {
             loop {
-                match body.0.frame().await {
+                match body.body.frame().await {
                     Some(Ok(frame)) => {
-                        if let Ok(data) = frame.into_data() {
-                            return Ok(Some(hyper_buf(data)));
-                        } else {
-                            continue;
+                        match frame.into_data() {
+                            Ok(data) => return Ok(Some(hyper_buf(data))),
+                            Err(frame) => {
+                                if let Ok(trailers) = frame.into_trailers() {
+                                    body.trailers = Some(hyper_headers::new(trailers));
+                                }
+                                continue;
+                            }
                         }
                     },
                     Some(Err(e)) => return Err(e),
@@ -82,23 +122,36 @@ ffi_fn! {
     /// body chunk that is received.
     ///
     /// The `hyper_buf` pointer is only a borrowed reference, it cannot live outside
-    /// the execution of the callback. You must make a copy to retain it.
+    /// the execution of the callback. You must make a copy to retain it, such
+    /// as with `hyper_buf_clone`.
     ///
     /// The callback should return `HYPER_ITER_CONTINUE` to continue iterating
     /// chunks as they are received, or `HYPER_ITER_BREAK` to cancel.
     ///
-    /// This will consume the `hyper_body *`, you shouldn't use it anymore or free it.
+    /// Trailers received after the data are kept in the body, and can be
+    /// retrieved with `hyper_body_trailers` once the task completes.
+    ///
+    /// This does not consume the `hyper_body *`, it must be freed with
+    /// `hyper_body_free` once done with it. However, it MUST NOT be used or
+    /// freed until the related task completes.
     fn hyper_body_foreach(body: *mut hyper_body, func: hyper_body_foreach_callback, userdata: *mut c_void) -> *mut hyper_task {
-        let mut body = non_null!(Box::from_raw(body) ?= ptr::null_mut());
+        // This doesn't take ownership of the Body, so don't allow destructor
+        let mut body = ManuallyDrop::new(non_null!(Box::from_raw(body) ?= ptr::null_mut()));
         let userdata = UserDataPointer(userdata);
 
         Box::into_raw(hyper_task::boxed(async move {
-            while let Some(item) = body.0.frame().await {
-                let frame = item?;
-                if let Ok(chunk) = frame.into_data() {
-                    if HYPER_ITER_CONTINUE != func(userdata.0, &hyper_buf(chunk)) {
-                        return Err(crate::Error::new_user_aborted_by_callback());
+            while let Some(item) = body.body.frame().await {
+                let frame = match item?.into_data() {
+                    Ok(chunk) => {
+                        if HYPER_ITER_CONTINUE != func(userdata.0, &hyper_buf(chunk)) {
+                            return Err(crate::Error::new_user_aborted_by_callback());
+                        }
+                        continue;
                     }
+                    Err(frame) => frame,
+                };
+                if let Ok(trailers) = frame.into_trailers() {
+                    body.trailers = Some(hyper_headers::new(trailers));
                 }
             }
             Ok(())
@@ -106,11 +159,28 @@ ffi_fn! {
     } ?= ptr::null_mut()
 }
 
+ffi_fn! {
+    /// Get the trailers that were received after the data of this body.
+    ///
+    /// Trailers are only known once `hyper_body_data` has returned that the
+    /// body finished, or the task of `hyper_body_foreach` has completed. If
+    /// none were received, this returns `NULL`.
+    ///
+    /// The returned `hyper_headers *` is borrowed from the body, and is valid
+    /// until the body is freed.
+    fn hyper_body_trailers(body: *const hyper_body) -> *const hyper_headers {
+        match non_null!(&*body ?= ptr::null()).trailers {
+            Some(ref trailers) => trailers,
+            None => ptr::null(),
+        }
+    } ?= ptr::null()
+}
+
 ffi_fn! {
     /// Set userdata on this body, which will be passed to callback functions.
     fn hyper_body_set_userdata(body: *mut hyper_body, userdata: *mut c_void) {
         let b = non_null!(&mut *body ?= ());
-        b.0.as_ffi_mut().userdata = userdata;
+        b.body.as_ffi_mut().userdata = userdata;
     }
 }
 
@@ -136,7 +206,32 @@ ffi_fn! {
     /// the body.
     fn hyper_body_set_data_func(body: *mut hyper_body, func: hyper_body_data_callback) {
         let b = non_null!{ &mut *body ?= () };
-        b.0.as_ffi_mut().data_func = func;
+        b.body.as_ffi_mut().data_func = func;
+    }
+}
+
+ffi_fn! {
+    /// Set the trailers callback for this body.
+    ///
+    /// The callback is called once the data callback has completed all data.
+    /// It is passed the value from `hyper_body_set_userdata`, and an empty
+    /// `hyper_headers *` to add the trailers to.
+    ///
+    /// If the trailers are ready, `HYPER_POLL_READY` should be returned. The
+    /// headers are only sent if this is returned, and if any were added.
+    ///
+    /// If they aren't known yet, a `hyper_waker` should be saved from the
+    /// `hyper_context *` argument, and `HYPER_POLL_PENDING` returned. The
+    /// callback is then called again with new empty headers once woken.
+    ///
+    /// If some error has occurred, you can return `HYPER_POLL_ERROR` to abort
+    /// the body.
+    ///
+    /// HTTP/1 only sends trailers in chunked messages, and only those named
+    /// in the `trailer` header of the message.
+    fn hyper_body_set_trailers_func(body: *mut hyper_body, func: hyper_body_trailers_callback) {
+        let b = non_null!{ &mut *body ?= () };
+        b.body.as_ffi_mut().trailers_func = Some(func);
     }
 }
 
@@ -146,7 +241,9 @@ impl UserBody {
     pub(crate) fn new() -> UserBody {
         UserBody {
             data_func: data_noop,
+            trailers_func: None,
             userdata: std::ptr::null_mut(),
+            data_done: false,
         }
     }
 
@@ -154,11 +251,16 @@ impl UserBody {
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<Option<crate::Result<Frame<Bytes>>>> {
+        if self.data_done {
+            return self.poll_trailers(cx);
+        }
+
         let mut out = std::ptr::null_mut();
         match (self.data_func)(self.userdata, hyper_context::wrap(cx), &mut out) {
             super::task::HYPER_POLL_READY => {
                 if out.is_null() {
-                    Poll::Ready(None)
+                    self.data_done = true;
+                    self.poll_trailers(cx)
                 } else {
                     let buf = unsafe { Box::from_raw(out) };
                     Poll::Ready(Some(Ok(Frame::data(buf.0))))
@@ -174,6 +276,34 @@ impl UserBody {
             ))))),
         }
     }
+
+    fn poll_trailers(&mut self, cx: &mut Context<'_>) -> Poll<Option<crate::Result<Frame<Bytes>>>> {
+        let func = match self.trailers_func {
+            Some(func) => func,
+            None => return Poll::Ready(None),
+        };
+
+        let mut trailers = hyper_headers::default();
+        match func(self.userdata, hyper_context::wrap(cx), &mut trailers) {
+            super::task::HYPER_POLL_READY => {
+                // Only call it once.
+                self.trailers_func = None;
+                if trailers.headers.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(Ok(Frame::trailers(trailers.headers))))
+                }
+            }
+            super::task::HYPER_POLL_PENDING => Poll::Pending,
+            super::task::HYPER_POLL_ERROR => {
+                Poll::Ready(Some(Err(crate::Error::new_body_write_aborted())))
+            }
+            unexpected => Poll::Ready(Some(Err(crate::Error::new_body_write(format!(
+                "unexpected hyper_body_trailers_func return code {}",
+                unexpected
+            ))))),
+        }
+    }
 }
 
 /// cbindgen:ignore
@@ -205,6 +335,42 @@ ffi_fn! {
     } ?= ptr::null_mut()
 }
 
+ffi_fn! {
+    /// Create a new `hyper_buf *` that refers to the provided bytes, without
+    /// copying them.
+    ///
+    /// The bytes must not be changed or freed until hyper is done with them,
+    /// at which point the `release` callback is called with the `userdata`.
+    /// This may happen on any thread, and may be later than the request or
+    /// response the bytes were sent with has completed.
+    ///
+    /// If this returns `NULL`, `release` is not called.
+    fn hyper_buf_from_owned(buf: *const u8, len: size_t, release: hyper_buf_release_callback, userdata: *mut c_void) -> *mut hyper_buf {
+        if buf.is_null() && len != 0 {
+            return ptr::null_mut();
+        }
+        let bytes = Bytes::from_owner(ForeignBytes {
+            ptr: buf,
+            len,
+            release,
+            userdata: UserDataPointer(userdata),
+        });
+        Box::into_raw(Box::new(hyper_buf(bytes)))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Create a new `hyper_buf *` that refers to the same bytes as `buf`,
+    /// without copying them.
+    ///
+    /// This can be used to keep a buffer that is only borrowed, such as in
+    /// the callback of `hyper_body_foreach`.
+    fn hyper_buf_clone(buf: *const hyper_buf) -> *mut hyper_buf {
+        let buf = non_null!(&*buf ?= ptr::null_mut());
+        Box::into_raw(Box::new(hyper_buf(buf.0.clone())))
+    } ?= ptr::null_mut()
+}
+
 ffi_fn! {
     /// Get a pointer to the bytes in this buffer.
     ///
@@ -232,8 +398,191 @@ ffi_fn! {
     }
 }
 
+// ===== impl ForeignBytes =====
+
+impl AsRef<[u8]> for ForeignBytes {
+    fn as_ref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for ForeignBytes {
+    fn drop(&mut self) {
+        (self.release)(self.userdata.0);
+    }
+}
+
+// The application promised to not touch the bytes until released.
+unsafe impl Send for ForeignBytes {}
+unsafe impl Sync for ForeignBytes {}
+
 unsafe impl AsTaskType for hyper_buf {
     fn as_task_type(&self) -> hyper_task_return_type {
         hyper_task_return_type::HYPER_TASK_BUF
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::http_types::hyper_headers_set;
+    use crate::ffi::task::{
+        hyper_executor_free, hyper_executor_new, hyper_executor_poll, hyper_executor_push,
+        hyper_task_free, hyper_task_type, hyper_task_value,
+    };
+    use futures_util::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_buf_from_owned_released_once() {
+        extern "C" fn release(userdata: *mut c_void) {
+            let released = unsafe { &*(userdata as *const AtomicUsize) };
+            released.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let data = b"zero copy";
+        let released = AtomicUsize::new(0);
+        let buf = hyper_buf_from_owned(
+            data.as_ptr(),
+            data.len(),
+            release,
+            &released as *const AtomicUsize as *mut c_void,
+        );
+        assert_eq!(hyper_buf_bytes(buf), data.as_ptr());
+
+        let clone = hyper_buf_clone(buf);
+        hyper_buf_free(buf);
+        assert_eq!(released.load(Ordering::SeqCst), 0);
+        assert_eq!(unsafe { &(*clone).0[..] }, data);
+
+        hyper_buf_free(clone);
+        assert_eq!(released.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_body_sends_trailers() {
+        extern "C" fn data(
+            userdata: *mut c_void,
+            _: *mut hyper_context<'_>,
+            chunk: *mut *mut hyper_buf,
+        ) -> c_int {
+            let sent = unsafe { &mut *(userdata as *mut bool) };
+            if !*sent {
+                *sent = true;
+                unsafe { *chunk = hyper_buf_copy(b"data".as_ptr(), 4) };
+            }
+            super::super::task::HYPER_POLL_READY
+        }
+
+        extern "C" fn trailers(
+            _: *mut c_void,
+            _: *mut hyper_context<'_>,
+            headers: *mut hyper_headers,
+        ) -> c_int {
+            let (name, value) = (b"grpc-status", b"0");
+            hyper_headers_set(
+                headers,
+                name.as_ptr(),
+                name.len(),
+                value.as_ptr(),
+                value.len(),
+            );
+            super::super::task::HYPER_POLL_READY
+        }
+
+        let mut sent = false;
+        let body = hyper_body_new();
+        hyper_body_set_userdata(body, &mut sent as *mut bool as *mut c_void);
+        hyper_body_set_data_func(body, data);
+        hyper_body_set_trailers_func(body, trailers);
+        let mut body = unsafe { Box::from_raw(body) }.body;
+
+        let frame = body.frame().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "data");
+        let frame = body.frame().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(frame.into_trailers().unwrap()["grpc-status"], "0");
+        assert!(body.frame().now_or_never().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_body_receives_trailers() {
+        let (mut tx, rx) = IncomingBody::channel();
+        let body = Box::into_raw(Box::new(hyper_body::wrap(rx)));
+        assert!(hyper_body_trailers(body).is_null());
+
+        tx.try_send_data(Bytes::from_static(b"data")).unwrap();
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        tx.try_send_trailers(trailers).unwrap();
+        drop(tx);
+
+        let exec = hyper_executor_new();
+        let mut poll = |task| {
+            hyper_executor_push(exec, task);
+            let task = hyper_executor_poll(exec);
+            assert!(!task.is_null());
+            let ty = hyper_task_type(task);
+            let value = hyper_task_value(task);
+            hyper_task_free(task);
+            (ty, value)
+        };
+
+        let (ty, buf) = poll(hyper_body_data(body));
+        assert!(matches!(ty, hyper_task_return_type::HYPER_TASK_BUF));
+        let buf = unsafe { Box::from_raw(buf as *mut hyper_buf) };
+        assert_eq!(buf.0, "data");
+
+        let (ty, _) = poll(hyper_body_data(body));
+        assert!(matches!(ty, hyper_task_return_type::HYPER_TASK_EMPTY));
+
+        let trailers = hyper_body_trailers(body);
+        assert!(!trailers.is_null());
+        assert_eq!(unsafe { &(*trailers).headers["grpc-status"] }, "0");
+
+        hyper_body_free(body);
+        hyper_executor_free(exec);
+    }
+
+    #[test]
+    fn test_body_foreach_receives_trailers() {
+        extern "C" fn count(userdata: *mut c_void, chunk: *const hyper_buf) -> c_int {
+            let len = unsafe { &mut *(userdata as *mut usize) };
+            *len += unsafe { (*chunk).0.len() };
+            HYPER_ITER_CONTINUE
+        }
+
+        let (mut tx, rx) = IncomingBody::channel();
+        let body = Box::into_raw(Box::new(hyper_body::wrap(rx)));
+
+        tx.try_send_data(Bytes::from_static(b"data")).unwrap();
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        tx.try_send_trailers(trailers).unwrap();
+        drop(tx);
+
+        let mut len = 0usize;
+        let exec = hyper_executor_new();
+        hyper_executor_push(
+            exec,
+            hyper_body_foreach(body, count, &mut len as *mut usize as *mut c_void),
+        );
+        let task = hyper_executor_poll(exec);
+        assert!(!task.is_null());
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_EMPTY
+        ));
+        hyper_task_free(task);
+        assert_eq!(len, 4);
+
+        let trailers = hyper_body_trailers(body);
+        assert!(!trailers.is_null());
+        assert_eq!(unsafe { &(*trailers).headers["grpc-status"] }, "0");
+
+        hyper_body_free(body);
+        hyper_executor_free(exec);
+    }
+}
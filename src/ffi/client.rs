@@ -1,7 +1,9 @@
 use std::ptr;
 use std::sync::Arc;
+#[cfg(feature = "http2")]
+use std::time::Duration;
 
-use libc::c_int;
+use libc::{c_int, size_t};
 
 use crate::client::conn;
 use crate::rt::Executor as _;
@@ -10,13 +12,17 @@ use super::error::hyper_code;
 use super::http_types::{hyper_request, hyper_response};
 use super::io::hyper_io;
 use super::task::{hyper_executor, hyper_task, hyper_task_return_type, AsTaskType, WeakExec};
+use super::time::hyper_timer;
 
 /// An options builder to configure an HTTP client connection.
 pub struct hyper_clientconn_options {
-    http1_allow_obsolete_multiline_headers_in_responses: bool,
-    http1_preserve_header_case: bool,
-    http1_preserve_header_order: bool,
+    http1: conn::http1::Builder,
+    #[cfg(feature = "http2")]
+    http2_builder: conn::http2::Builder,
     http2: bool,
+    #[cfg(feature = "http2")]
+    http2_keep_alive: bool,
+    timer: Option<hyper_timer>,
     /// Use a `Weak` to prevent cycles.
     exec: WeakExec,
 }
@@ -47,15 +53,31 @@ ffi_fn! {
     ///
     /// The returned `hyper_task *` must be polled with an executor until the
     /// handshake completes, at which point the value can be taken.
+    ///
+    /// Returns NULL if HTTP/2 keep-alive was enabled with
+    /// `hyper_clientconn_options_http2_keep_alive_interval`, but no timer was
+    /// set with `hyper_clientconn_options_timer`.
     fn hyper_clientconn_handshake(io: *mut hyper_io, options: *mut hyper_clientconn_options) -> *mut hyper_task {
-        let options = non_null! { Box::from_raw(options) ?= ptr::null_mut() };
+        let mut options = non_null! { Box::from_raw(options) ?= ptr::null_mut() };
         let io = non_null! { Box::from_raw(io) ?= ptr::null_mut() };
 
+        // Keep-alive pings can't be scheduled without a timer.
+        #[cfg(feature = "http2")]
+        if options.http2 && options.http2_keep_alive && options.timer.is_none() {
+            return ptr::null_mut();
+        }
+
+        if let Some(timer) = options.timer.take() {
+            #[cfg(feature = "http2")]
+            options.http2_builder.timer(timer.clone());
+            options.http1.timer(timer);
+        }
+
         Box::into_raw(hyper_task::boxed(async move {
             #[cfg(feature = "http2")]
             {
             if options.http2 {
-                return conn::http2::Builder::new(options.exec.clone())
+                return options.http2_builder
                     .handshake::<_, crate::body::Incoming>(io)
                     .await
                     .map(|(tx, conn)| {
@@ -67,10 +89,7 @@ ffi_fn! {
                 }
             }
 
-            conn::http1::Builder::new()
-                .allow_obsolete_multiline_headers_in_responses(options.http1_allow_obsolete_multiline_headers_in_responses)
-                .preserve_header_case(options.http1_preserve_header_case)
-                .preserve_header_order(options.http1_preserve_header_order)
+            options.http1
                 .handshake::<_, crate::body::Incoming>(io)
                 .await
                 .map(|(tx, conn)| {
@@ -126,10 +145,13 @@ ffi_fn! {
     /// Creates a new set of HTTP clientconn options to be used in a handshake.
     fn hyper_clientconn_options_new() -> *mut hyper_clientconn_options {
         Box::into_raw(Box::new(hyper_clientconn_options {
-            http1_allow_obsolete_multiline_headers_in_responses: false,
-            http1_preserve_header_case: false,
-            http1_preserve_header_order: false,
+            http1: conn::http1::Builder::new(),
+            #[cfg(feature = "http2")]
+            http2_builder: conn::http2::Builder::new(WeakExec::new()),
             http2: false,
+            #[cfg(feature = "http2")]
+            http2_keep_alive: false,
+            timer: None,
             exec: WeakExec::new(),
         }))
     } ?= std::ptr::null_mut()
@@ -141,7 +163,7 @@ ffi_fn! {
     /// Pass `0` to allow lowercase normalization (default), `1` to retain original case.
     fn hyper_clientconn_options_set_preserve_header_case(opts: *mut hyper_clientconn_options, enabled: c_int) {
         let opts = non_null! { &mut *opts ?= () };
-        opts.http1_preserve_header_case = enabled != 0;
+        opts.http1.preserve_header_case(enabled != 0);
     }
 }
 
@@ -151,7 +173,7 @@ ffi_fn! {
     /// Pass `0` to allow reordering (default), `1` to retain original ordering.
     fn hyper_clientconn_options_set_preserve_header_order(opts: *mut hyper_clientconn_options, enabled: c_int) {
         let opts = non_null! { &mut *opts ?= () };
-        opts.http1_preserve_header_order = enabled != 0;
+        opts.http1.preserve_header_order(enabled != 0);
     }
 }
 
@@ -173,6 +195,10 @@ ffi_fn! {
         let weak_exec = hyper_executor::downgrade(&exec);
         std::mem::forget(exec);
 
+        #[cfg(feature = "http2")]
+        {
+            opts.http2_builder.exec = crate::common::exec::Exec::new(weak_exec.clone());
+        }
         opts.exec = weak_exec;
     }
 }
@@ -206,7 +232,273 @@ ffi_fn! {
     ///
     fn hyper_clientconn_options_http1_allow_multiline_headers(opts: *mut hyper_clientconn_options, enabled: c_int) -> hyper_code {
         let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
-        opts.http1_allow_obsolete_multiline_headers_in_responses = enabled != 0;
+        opts.http1.allow_obsolete_multiline_headers_in_responses(enabled != 0);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/1 connections will accept HTTP/0.9 responses.
+    ///
+    /// Pass `0` to disable (default), `1` to enable.
+    fn hyper_clientconn_options_http1_http09_responses(opts: *mut hyper_clientconn_options, enabled: c_int) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1.http09_responses(enabled != 0);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/1 connections should try to use vectored writes, or
+    /// always flatten into a single buffer.
+    ///
+    /// Pass `0` to always flatten, `1` to use vectored writes. The default
+    /// is to pick based on the IO transport.
+    fn hyper_clientconn_options_http1_writev(opts: *mut hyper_clientconn_options, enabled: c_int) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1.writev(enabled != 0);
         hyper_code::HYPERE_OK
     }
 }
+
+ffi_fn! {
+    /// Set the maximum buffer size for HTTP/1 connections.
+    ///
+    /// The default is ~400kb. This unsets the exact read buffer size.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if `max_buf_size` is less than 8192.
+    fn hyper_clientconn_options_http1_max_buf_size(opts: *mut hyper_clientconn_options, max_buf_size: size_t) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        if max_buf_size < crate::proto::h1::MINIMUM_MAX_BUFFER_SIZE {
+            return hyper_code::HYPERE_INVALID_ARG;
+        }
+        opts.http1.max_buf_size(max_buf_size);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set the exact size of the read buffer HTTP/1 connections always use.
+    ///
+    /// Pass `0` to use an adaptive read buffer (default). This unsets the
+    /// maximum buffer size.
+    fn hyper_clientconn_options_http1_read_buf_exact_size(opts: *mut hyper_clientconn_options, size: size_t) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1.read_buf_exact_size(if size == 0 { None } else { Some(size) });
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set the timer used by connections, such as for HTTP/2 keep-alive.
+    ///
+    /// This does not consume the `options` or the `timer`.
+    fn hyper_clientconn_options_timer(opts: *mut hyper_clientconn_options, timer: *const hyper_timer) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        let timer = non_null! { &*timer ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.timer = Some(timer.clone());
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set the initial HTTP/2 stream-level flow control window size.
+    ///
+    /// This disables the adaptive window.
+    fn hyper_clientconn_options_http2_initial_stream_window_size(opts: *mut hyper_clientconn_options, size: u32) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_builder.initial_stream_window_size(size);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, size);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the initial HTTP/2 connection-level flow control window size.
+    ///
+    /// This disables the adaptive window.
+    fn hyper_clientconn_options_http2_initial_connection_window_size(opts: *mut hyper_clientconn_options, size: u32) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_builder.initial_connection_window_size(size);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, size);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/2 connections use an adaptive flow control window,
+    /// overriding the initial window sizes.
+    ///
+    /// Pass `0` to disable (default), `1` to enable.
+    fn hyper_clientconn_options_http2_adaptive_window(opts: *mut hyper_clientconn_options, enabled: c_int) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_builder.adaptive_window(enabled != 0);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, enabled);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the maximum HTTP/2 frame size.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if `size` isn't within the range of
+    /// 16,384 to 16,777,215.
+    fn hyper_clientconn_options_http2_max_frame_size(opts: *mut hyper_clientconn_options, size: u32) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            if !(16_384..=16_777_215).contains(&size) {
+                return hyper_code::HYPERE_INVALID_ARG;
+            }
+            opts.http2_builder.max_frame_size(size);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, size);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the interval at which HTTP/2 Ping frames are sent to keep the
+    /// connection alive, in milliseconds.
+    ///
+    /// Pass `0` to disable keep-alive (default). Keep-alive requires a timer
+    /// to also be set with `hyper_clientconn_options_timer`, before or after
+    /// this call, otherwise the handshake fails.
+    fn hyper_clientconn_options_http2_keep_alive_interval(opts: *mut hyper_clientconn_options, interval_ms: u64) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            if interval_ms == 0 {
+                opts.http2_keep_alive = false;
+                opts.http2_builder.keep_alive_interval(None);
+            } else {
+                opts.http2_keep_alive = true;
+                opts.http2_builder.keep_alive_interval(Duration::from_millis(interval_ms));
+            }
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, interval_ms);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set how long to wait for the acknowledgement of a keep-alive ping, in
+    /// milliseconds, before closing the connection.
+    ///
+    /// The default is 20 seconds.
+    fn hyper_clientconn_options_http2_keep_alive_timeout(opts: *mut hyper_clientconn_options, timeout_ms: u64) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_builder.keep_alive_timeout(Duration::from_millis(timeout_ms));
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, timeout_ms);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/2 keep-alive pings are also sent while the
+    /// connection has no open streams.
+    ///
+    /// Pass `0` to disable (default), `1` to enable.
+    fn hyper_clientconn_options_http2_keep_alive_while_idle(opts: *mut hyper_clientconn_options, enabled: c_int) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2_builder.keep_alive_while_idle(enabled != 0);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, enabled);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the maximum write buffer size for each HTTP/2 stream.
+    ///
+    /// The default is 1MB. Returns `HYPERE_INVALID_ARG` if `size` is larger
+    /// than `UINT32_MAX`.
+    fn hyper_clientconn_options_http2_max_send_buf_size(opts: *mut hyper_clientconn_options, size: size_t) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            if size > std::u32::MAX as usize {
+                return hyper_code::HYPERE_INVALID_ARG;
+            }
+            opts.http2_builder.max_send_buf_size(size);
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, size);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+#[cfg(all(test, feature = "http2"))]
+mod tests {
+    use super::*;
+    use crate::ffi::io::hyper_io_new;
+
+    #[test]
+    fn test_http2_keep_alive_requires_timer() {
+        let opts = hyper_clientconn_options_new();
+        assert!(matches!(
+            hyper_clientconn_options_http2(opts, 1),
+            hyper_code::HYPERE_OK
+        ));
+        assert!(matches!(
+            hyper_clientconn_options_http2_keep_alive_interval(opts, 1_000),
+            hyper_code::HYPERE_OK
+        ));
+
+        let task = hyper_clientconn_handshake(hyper_io_new(), opts);
+        assert!(task.is_null());
+    }
+}
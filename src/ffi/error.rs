@@ -1,4 +1,8 @@
-use libc::size_t;
+use std::ffi::c_void;
+
+use libc::{c_int, size_t};
+
+use super::HYPER_ITER_CONTINUE;
 
 /// A more detailed error object returned by some hyper functions.
 pub struct hyper_error(crate::Error);
@@ -24,20 +28,102 @@ pub enum hyper_code {
     HYPERE_FEATURE_NOT_ENABLED,
     /// The peer sent an HTTP message that could not be parsed.
     HYPERE_INVALID_PEER_MESSAGE,
+    /// A timeout elapsed before the operation could complete.
+    HYPERE_TIMEOUT,
+    /// The operation was canceled before it could complete.
+    ///
+    /// This typically means a request was queued on a connection that closed
+    /// before it could be sent.
+    HYPERE_CANCELED,
+    /// The connection, or the channel the operation was waiting on, is
+    /// closed.
+    HYPERE_CLOSED,
+    /// Reading from or writing to the IO transport failed.
+    HYPERE_IO,
+    /// The peer sent a message, or some bytes, when none was expected.
+    HYPERE_UNEXPECTED_MESSAGE,
+    /// The head of a received message was larger than the maximum buffer
+    /// size.
+    HYPERE_MESSAGE_HEAD_TOO_LARGE,
+    /// The URI of a received request was too long.
+    HYPERE_URI_TOO_LONG,
+    /// Reading the body of a received message failed.
+    HYPERE_BODY_READ,
+    /// Writing the body of an outgoing message to the connection failed.
+    HYPERE_BODY_WRITE,
+    /// The sender of an outgoing body aborted it before it was complete.
+    HYPERE_BODY_WRITE_ABORTED,
+    /// The body given to hyper for an outgoing message returned an error.
+    HYPERE_USER_BODY,
+    /// The service handling a request returned an error.
+    HYPERE_USER_SERVICE,
+    /// A message given to hyper cannot be sent as it is.
+    ///
+    /// For example, a response with an unsupported status code, or with both
+    /// a `content-length` and a `transfer-encoding` header.
+    HYPERE_INVALID_USER_MESSAGE,
+    /// An upgrade was awaited on a message that has none.
+    HYPERE_NO_UPGRADE,
+    /// Shutting down the IO transport failed.
+    HYPERE_SHUTDOWN,
+    /// An HTTP/2 error, such as a reset stream.
+    ///
+    /// The reason code can be retrieved with `hyper_error_http2_reason`.
+    #[cfg_attr(not(feature = "http2"), allow(unused))]
+    HYPERE_HTTP2,
 }
 
+type hyper_error_foreach_callback = extern "C" fn(*mut c_void, *const u8, size_t) -> c_int;
+
 // ===== impl hyper_error =====
 
 impl hyper_error {
     fn code(&self) -> hyper_code {
         use crate::error::Kind as ErrorKind;
-        use crate::error::User;
+        use crate::error::{Parse, User};
+
+        if self.0.is_timeout() {
+            return hyper_code::HYPERE_TIMEOUT;
+        }
 
         match self.0.kind() {
+            ErrorKind::Parse(Parse::TooLarge) => hyper_code::HYPERE_MESSAGE_HEAD_TOO_LARGE,
+            ErrorKind::Parse(Parse::UriTooLong) => hyper_code::HYPERE_URI_TOO_LONG,
             ErrorKind::Parse(_) => hyper_code::HYPERE_INVALID_PEER_MESSAGE,
             ErrorKind::IncompleteMessage => hyper_code::HYPERE_UNEXPECTED_EOF,
+            ErrorKind::UnexpectedMessage => hyper_code::HYPERE_UNEXPECTED_MESSAGE,
+            ErrorKind::Canceled => hyper_code::HYPERE_CANCELED,
+            ErrorKind::ChannelClosed => hyper_code::HYPERE_CLOSED,
+            ErrorKind::Io => hyper_code::HYPERE_IO,
+            #[cfg(feature = "server")]
+            ErrorKind::HeaderTimeout => hyper_code::HYPERE_TIMEOUT,
+            #[cfg(feature = "server")]
+            ErrorKind::IdleTimeout => hyper_code::HYPERE_TIMEOUT,
+            #[cfg(any(feature = "http2", all(feature = "server", feature = "http1")))]
+            ErrorKind::MinDataRate => hyper_code::HYPERE_TIMEOUT,
+            ErrorKind::BodyTooLarge | ErrorKind::Body => hyper_code::HYPERE_BODY_READ,
+            ErrorKind::BodyWrite => hyper_code::HYPERE_BODY_WRITE,
+            ErrorKind::Shutdown => hyper_code::HYPERE_SHUTDOWN,
+            #[cfg(feature = "http2")]
+            ErrorKind::Http2 => hyper_code::HYPERE_HTTP2,
+            ErrorKind::User(User::Body) => hyper_code::HYPERE_USER_BODY,
+            ErrorKind::User(User::BodyWriteAborted) => hyper_code::HYPERE_BODY_WRITE_ABORTED,
+            ErrorKind::User(User::Service) => hyper_code::HYPERE_USER_SERVICE,
+            #[cfg(feature = "server")]
+            ErrorKind::User(User::UnexpectedHeader)
+            | ErrorKind::User(User::UnsupportedStatusCode)
+            | ErrorKind::User(User::InformationalStatus) => hyper_code::HYPERE_INVALID_USER_MESSAGE,
+            #[cfg(feature = "client")]
+            ErrorKind::User(User::AbsoluteUriRequired) => hyper_code::HYPERE_INVALID_USER_MESSAGE,
+            ErrorKind::User(User::NoUpgrade) | ErrorKind::User(User::ManualUpgrade) => {
+                hyper_code::HYPERE_NO_UPGRADE
+            }
+            #[cfg(feature = "client")]
+            ErrorKind::User(User::DispatchGone) => hyper_code::HYPERE_CLOSED,
             ErrorKind::User(User::AbortedByCallback) => hyper_code::HYPERE_ABORTED_BY_CALLBACK,
-            // TODO: add more variants
+            // Only used by the pooled client and the server listener, which
+            // the C API doesn't have.
+            #[allow(unreachable_patterns)]
             _ => hyper_code::HYPERE_ERROR,
         }
     }
@@ -83,3 +169,133 @@ ffi_fn! {
         non_null!(&*err ?= 0).print_to(dst)
     }
 }
+
+ffi_fn! {
+    /// Get the HTTP/2 reason code of this error.
+    ///
+    /// If the error was caused by an HTTP/2 reset stream or GOAWAY frame, the
+    /// reason code is written to `reason` and `HYPERE_OK` is returned.
+    /// Otherwise `reason` is left untouched, and `HYPERE_ERROR` is returned.
+    fn hyper_error_http2_reason(err: *const hyper_error, reason: *mut u32) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let err = non_null!(&*err ?= hyper_code::HYPERE_INVALID_ARG);
+            if reason.is_null() {
+                return hyper_code::HYPERE_INVALID_ARG;
+            }
+
+            match err.0.find_source::<h2::Error>().and_then(|e| e.reason()) {
+                Some(r) => {
+                    unsafe { *reason = r.into() };
+                    hyper_code::HYPERE_OK
+                }
+                None => hyper_code::HYPERE_ERROR,
+            }
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (err, reason);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Iterates the causes of this error, passing the message of each to the
+    /// callback.
+    ///
+    /// The first cause is the one that this error was directly caused by, the
+    /// next one is what caused that, and so on. The message of a cause may
+    /// also include the messages of its own causes.
+    ///
+    /// The `userdata` pointer is also passed to the callback.
+    ///
+    /// The callback should return `HYPER_ITER_CONTINUE` to keep iterating, or
+    /// `HYPER_ITER_BREAK` to stop.
+    fn hyper_error_foreach_source(err: *const hyper_error, func: hyper_error_foreach_callback, userdata: *mut c_void) {
+        use std::error::Error as _;
+
+        let err = non_null!(&*err ?= ());
+        let mut source = err.0.source();
+        while let Some(cause) = source {
+            let msg = cause.to_string();
+            if HYPER_ITER_CONTINUE != func(userdata, msg.as_ptr(), msg.len()) {
+                return;
+            }
+            source = cause.source();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        let code = |err: crate::Error| hyper_error(err).code();
+
+        assert!(matches!(
+            code(crate::Error::new_canceled()),
+            hyper_code::HYPERE_CANCELED
+        ));
+        assert!(matches!(
+            code(crate::Error::new_closed()),
+            hyper_code::HYPERE_CLOSED
+        ));
+        assert!(matches!(
+            code(crate::Error::new_body_write_aborted()),
+            hyper_code::HYPERE_BODY_WRITE_ABORTED
+        ));
+        assert!(matches!(
+            code(crate::Error::new_too_large()),
+            hyper_code::HYPERE_MESSAGE_HEAD_TOO_LARGE
+        ));
+        assert!(matches!(
+            code(crate::Error::new_io(std::io::ErrorKind::BrokenPipe.into())),
+            hyper_code::HYPERE_IO
+        ));
+        assert!(matches!(
+            code(crate::Error::new_body(crate::error::TimedOut)),
+            hyper_code::HYPERE_TIMEOUT
+        ));
+    }
+
+    #[cfg(feature = "http2")]
+    #[test]
+    fn test_error_http2_reason() {
+        let err = hyper_error(crate::Error::new_h2(h2::Reason::REFUSED_STREAM.into()));
+        assert!(matches!(err.code(), hyper_code::HYPERE_HTTP2));
+
+        let mut reason = 0;
+        assert!(matches!(
+            hyper_error_http2_reason(&err, &mut reason),
+            hyper_code::HYPERE_OK
+        ));
+        assert_eq!(reason, u32::from(h2::Reason::REFUSED_STREAM));
+
+        let err = hyper_error(crate::Error::new_canceled());
+        assert!(matches!(
+            hyper_error_http2_reason(&err, &mut reason),
+            hyper_code::HYPERE_ERROR
+        ));
+    }
+
+    #[test]
+    fn test_error_foreach_source() {
+        extern "C" fn collect(userdata: *mut c_void, msg: *const u8, len: size_t) -> c_int {
+            let msgs = unsafe { &mut *(userdata as *mut Vec<String>) };
+            let msg = unsafe { std::slice::from_raw_parts(msg, len) };
+            msgs.push(String::from_utf8(msg.to_vec()).unwrap());
+            HYPER_ITER_CONTINUE
+        }
+
+        let io = std::io::Error::new(std::io::ErrorKind::Other, "oh no");
+        let err = hyper_error(crate::Error::new_body_write(io));
+
+        let mut msgs = Vec::<String>::new();
+        hyper_error_foreach_source(&err, collect, &mut msgs as *mut Vec<String> as *mut c_void);
+        assert_eq!(msgs, ["oh no"]);
+    }
+}
@@ -1,5 +1,6 @@
 use bytes::Bytes;
 use libc::{c_int, size_t};
+use std::convert::TryFrom;
 use std::ffi::c_void;
 
 use super::body::hyper_body;
@@ -9,7 +10,7 @@ use super::{UserDataPointer, HYPER_ITER_CONTINUE};
 use crate::body::Incoming as IncomingBody;
 use crate::ext::{HeaderCaseMap, OriginalHeaderOrder, ReasonPhrase};
 use crate::header::{HeaderName, HeaderValue};
-use crate::{HeaderMap, Method, Request, Response, Uri};
+use crate::{HeaderMap, Method, Request, Response, StatusCode, Uri};
 
 /// An HTTP request.
 pub struct hyper_request(pub(super) Request<IncomingBody>);
@@ -26,6 +27,7 @@ pub struct hyper_headers {
     orig_order: OriginalHeaderOrder,
 }
 
+#[cfg_attr(not(feature = "client"), allow(dead_code))]
 pub(crate) struct OnInformational {
     func: hyper_request_on_informational_callback,
     data: UserDataPointer,
@@ -43,7 +45,8 @@ ffi_fn! {
 }
 
 ffi_fn! {
-    /// Free an HTTP request if not going to send it on a client.
+    /// Free an HTTP request if not going to send it on a client, or once done
+    /// with a request received by a `hyper_service`.
     fn hyper_request_free(req: *mut hyper_request) {
         drop(non_null!(Box::from_raw(req) ?= ()));
     }
@@ -173,6 +176,67 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Get the method of this request.
+    ///
+    /// The `method` and `method_len` arguments are set to the method's
+    /// buffer, which isn't null-terminated. It is owned by the request, and
+    /// should not be used after the request has been freed.
+    fn hyper_request_method(req: *const hyper_request, method: *mut *const u8, method_len: *mut size_t) -> hyper_code {
+        let req = non_null!(&*req ?= hyper_code::HYPERE_INVALID_ARG);
+        if method.is_null() || method_len.is_null() {
+            return hyper_code::HYPERE_INVALID_ARG;
+        }
+        unsafe { set_str_out(method, method_len, Some(req.0.method().as_str())) };
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Get the URI of this request as separate scheme, authority, and
+    /// path/query strings.
+    ///
+    /// Each of `scheme`, `authority`, and `path_and_query` may be null, to
+    /// skip that component. Otherwise, it and its corresponding `len` are set
+    /// to the component's buffer, which isn't null-terminated, or to null and
+    /// `0` if the URI has no such component. Requests received by a server
+    /// typically only have a path and query.
+    ///
+    /// The buffers are owned by the request, and should not be used after the
+    /// request has been freed.
+    fn hyper_request_uri_parts(
+        req: *const hyper_request,
+        scheme: *mut *const u8,
+        scheme_len: *mut size_t,
+        authority: *mut *const u8,
+        authority_len: *mut size_t,
+        path_and_query: *mut *const u8,
+        path_and_query_len: *mut size_t
+    ) -> hyper_code {
+        let uri = non_null!(&*req ?= hyper_code::HYPERE_INVALID_ARG).0.uri();
+        unsafe {
+            set_str_out(scheme, scheme_len, uri.scheme_str());
+            set_str_out(authority, authority_len, uri.authority().map(|a| a.as_str()));
+            set_str_out(path_and_query, path_and_query_len, uri.path_and_query().map(|p| p.as_str()));
+        }
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Get the HTTP version of this request.
+    ///
+    /// The returned value could be:
+    ///
+    /// - `HYPER_HTTP_VERSION_1_0`
+    /// - `HYPER_HTTP_VERSION_1_1`
+    /// - `HYPER_HTTP_VERSION_2`
+    /// - `HYPER_HTTP_VERSION_NONE` if newer (or older).
+    fn hyper_request_version(req: *const hyper_request) -> c_int {
+        version_to_c(non_null!(&*req ?= 0).0.version())
+    }
+}
+
 ffi_fn! {
     /// Gets a reference to the HTTP headers of this request
     ///
@@ -193,7 +257,7 @@ ffi_fn! {
     fn hyper_request_set_body(req: *mut hyper_request, body: *mut hyper_body) -> hyper_code {
         let body = non_null!(Box::from_raw(body) ?= hyper_code::HYPERE_INVALID_ARG);
         let req = non_null!(&mut *req ?= hyper_code::HYPERE_INVALID_ARG);
-        *req.0.body_mut() = body.0;
+        *req.0.body_mut() = body.body;
         hyper_code::HYPERE_OK
     }
 }
@@ -226,6 +290,16 @@ ffi_fn! {
 }
 
 impl hyper_request {
+    #[cfg(feature = "server")]
+    pub(super) fn wrap(mut req: Request<IncomingBody>) -> hyper_request {
+        let headers = std::mem::take(req.headers_mut());
+        let headers = hyper_headers::with_original(req.extensions_mut(), headers);
+        req.extensions_mut().insert(headers);
+
+        hyper_request(req)
+    }
+
+    #[cfg(feature = "client")]
     pub(super) fn finalize_request(&mut self) {
         if let Some(headers) = self.0.extensions_mut().remove::<hyper_headers>() {
             *self.0.headers_mut() = headers.headers;
@@ -237,6 +311,15 @@ impl hyper_request {
 
 // ===== impl hyper_response =====
 
+ffi_fn! {
+    /// Construct a new HTTP response.
+    ///
+    /// The response has a `200 OK` status and an empty body, until changed.
+    fn hyper_response_new() -> *mut hyper_response {
+        Box::into_raw(Box::new(hyper_response(Response::new(IncomingBody::empty()))))
+    } ?= std::ptr::null_mut()
+}
+
 ffi_fn! {
     /// Free an HTTP response after using it.
     fn hyper_response_free(resp: *mut hyper_response) {
@@ -244,6 +327,46 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Set the HTTP-Status code of this response.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if the code isn't within the range of
+    /// 100-999.
+    fn hyper_response_set_status(resp: *mut hyper_response, status: u16) -> hyper_code {
+        let resp = non_null!(&mut *resp ?= hyper_code::HYPERE_INVALID_ARG);
+        match StatusCode::from_u16(status) {
+            Ok(status) => {
+                *resp.0.status_mut() = status;
+                hyper_code::HYPERE_OK
+            }
+            Err(_) => hyper_code::HYPERE_INVALID_ARG,
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set the reason-phrase of this response.
+    ///
+    /// By default, the canonical reason of the status code is used. The
+    /// reason-phrase is only sent on HTTP/1 connections.
+    ///
+    /// Returns `HYPERE_INVALID_ARG` if the reason contains a byte that isn't
+    /// allowed, such as `\r` or `\n`.
+    fn hyper_response_set_reason_phrase(resp: *mut hyper_response, reason: *const u8, reason_len: size_t) -> hyper_code {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(reason, reason_len as usize)
+        };
+        let resp = non_null!(&mut *resp ?= hyper_code::HYPERE_INVALID_ARG);
+        match ReasonPhrase::try_from(bytes) {
+            Ok(reason) => {
+                resp.0.extensions_mut().insert(reason);
+                hyper_code::HYPERE_OK
+            }
+            Err(_) => hyper_code::HYPERE_INVALID_ARG,
+        }
+    }
+}
+
 ffi_fn! {
     /// Get the HTTP-Status code of this response.
     ///
@@ -287,14 +410,7 @@ ffi_fn! {
     /// - `HYPER_HTTP_VERSION_2`
     /// - `HYPER_HTTP_VERSION_NONE` if newer (or older).
     fn hyper_response_version(resp: *const hyper_response) -> c_int {
-        use http::Version;
-
-        match non_null!(&*resp ?= 0).0.version() {
-            Version::HTTP_10 => super::HYPER_HTTP_VERSION_1_0,
-            Version::HTTP_11 => super::HYPER_HTTP_VERSION_1_1,
-            Version::HTTP_2 => super::HYPER_HTTP_VERSION_2,
-            _ => super::HYPER_HTTP_VERSION_NONE,
-        }
+        version_to_c(non_null!(&*resp ?= 0).0.version())
     }
 }
 
@@ -314,30 +430,44 @@ ffi_fn! {
     /// It is safe to free the response even after taking ownership of its body.
     fn hyper_response_body(resp: *mut hyper_response) -> *mut hyper_body {
         let body = std::mem::replace(non_null!(&mut *resp ?= std::ptr::null_mut()).0.body_mut(), IncomingBody::empty());
-        Box::into_raw(Box::new(hyper_body(body)))
+        Box::into_raw(Box::new(hyper_body::wrap(body)))
     } ?= std::ptr::null_mut()
 }
 
+ffi_fn! {
+    /// Set the body of the response.
+    ///
+    /// The default is an empty body.
+    ///
+    /// This takes ownership of the `hyper_body *`, you must not use it or
+    /// free it after setting it on the response.
+    fn hyper_response_set_body(resp: *mut hyper_response, body: *mut hyper_body) -> hyper_code {
+        let body = non_null!(Box::from_raw(body) ?= hyper_code::HYPERE_INVALID_ARG);
+        let resp = non_null!(&mut *resp ?= hyper_code::HYPERE_INVALID_ARG);
+        *resp.0.body_mut() = body.body;
+        hyper_code::HYPERE_OK
+    }
+}
+
 impl hyper_response {
+    #[cfg(feature = "client")]
     pub(super) fn wrap(mut resp: Response<IncomingBody>) -> hyper_response {
         let headers = std::mem::take(resp.headers_mut());
-        let orig_casing = resp
-            .extensions_mut()
-            .remove::<HeaderCaseMap>()
-            .unwrap_or_else(HeaderCaseMap::default);
-        let orig_order = resp
-            .extensions_mut()
-            .remove::<OriginalHeaderOrder>()
-            .unwrap_or_else(OriginalHeaderOrder::default);
-        resp.extensions_mut().insert(hyper_headers {
-            headers,
-            orig_casing,
-            orig_order,
-        });
+        let headers = hyper_headers::with_original(resp.extensions_mut(), headers);
+        resp.extensions_mut().insert(headers);
 
         hyper_response(resp)
     }
 
+    #[cfg(feature = "server")]
+    pub(super) fn finalize_response(&mut self) {
+        if let Some(headers) = self.0.extensions_mut().remove::<hyper_headers>() {
+            *self.0.headers_mut() = headers.headers;
+            self.0.extensions_mut().insert(headers.orig_casing);
+            self.0.extensions_mut().insert(headers.orig_order);
+        }
+    }
+
     fn reason_phrase(&self) -> &[u8] {
         if let Some(reason) = self.0.extensions().get::<ReasonPhrase>() {
             return reason.as_bytes();
@@ -363,6 +493,27 @@ type hyper_headers_foreach_callback =
     extern "C" fn(*mut c_void, *const u8, size_t, *const u8, size_t) -> c_int;
 
 impl hyper_headers {
+    /// Moves the original casing and order of the headers of a received
+    /// message out of its extensions, along with the headers.
+    fn with_original(ext: &mut http::Extensions, headers: HeaderMap) -> hyper_headers {
+        hyper_headers {
+            headers,
+            orig_casing: ext
+                .remove::<HeaderCaseMap>()
+                .unwrap_or_else(HeaderCaseMap::default),
+            orig_order: ext
+                .remove::<OriginalHeaderOrder>()
+                .unwrap_or_else(OriginalHeaderOrder::default),
+        }
+    }
+
+    pub(super) fn new(headers: HeaderMap) -> hyper_headers {
+        hyper_headers {
+            headers,
+            ..Default::default()
+        }
+    }
+
     pub(super) fn get_or_default(ext: &mut http::Extensions) -> &mut hyper_headers {
         if let None = ext.get_mut::<hyper_headers>() {
             ext.insert(hyper_headers::default());
@@ -507,9 +658,34 @@ unsafe fn raw_name_value(
     Ok((name, value, orig_name))
 }
 
+fn version_to_c(version: http::Version) -> c_int {
+    use http::Version;
+
+    match version {
+        Version::HTTP_10 => super::HYPER_HTTP_VERSION_1_0,
+        Version::HTTP_11 => super::HYPER_HTTP_VERSION_1_1,
+        Version::HTTP_2 => super::HYPER_HTTP_VERSION_2,
+        _ => super::HYPER_HTTP_VERSION_NONE,
+    }
+}
+
+/// Sets the `ptr` and `len` out arguments to a borrowed string, or to null
+/// and `0` if missing. Does nothing if `ptr` is null.
+unsafe fn set_str_out(ptr: *mut *const u8, len: *mut size_t, s: Option<&str>) {
+    if ptr.is_null() {
+        return;
+    }
+    let (p, l) = s.map_or((std::ptr::null(), 0), |s| (s.as_ptr(), s.len()));
+    *ptr = p;
+    if !len.is_null() {
+        *len = l;
+    }
+}
+
 // ===== impl OnInformational =====
 
 impl OnInformational {
+    #[cfg(feature = "client")]
     pub(crate) fn call(&mut self, resp: Response<IncomingBody>) {
         let mut resp = hyper_response::wrap(resp);
         (self.func)(self.data.0, &mut resp);
@@ -632,4 +808,104 @@ mod tests {
             HYPER_ITER_CONTINUE
         }
     }
+
+    #[test]
+    fn test_request_method_uri_parts_and_version() {
+        let req = hyper_request_new();
+        let method = b"PATCH";
+        hyper_request_set_method(req, method.as_ptr(), method.len());
+        let uri = b"http://hyper.rs:8080/ffi?q=1";
+        hyper_request_set_uri(req, uri.as_ptr(), uri.len());
+
+        unsafe fn slice<'a>(ptr: *const u8, len: size_t) -> &'a [u8] {
+            std::slice::from_raw_parts(ptr, len)
+        }
+
+        let mut ptr = std::ptr::null();
+        let mut len = 0;
+        assert!(matches!(
+            hyper_request_method(req, &mut ptr, &mut len),
+            hyper_code::HYPERE_OK
+        ));
+        assert_eq!(unsafe { slice(ptr, len) }, b"PATCH");
+
+        let (mut scheme, mut scheme_len) = (std::ptr::null(), 0);
+        let (mut path, mut path_len) = (std::ptr::null(), 0);
+        assert!(matches!(
+            hyper_request_uri_parts(
+                req,
+                &mut scheme,
+                &mut scheme_len,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut path,
+                &mut path_len,
+            ),
+            hyper_code::HYPERE_OK
+        ));
+        assert_eq!(unsafe { slice(scheme, scheme_len) }, b"http");
+        assert_eq!(unsafe { slice(path, path_len) }, b"/ffi?q=1");
+
+        assert_eq!(
+            hyper_request_version(req),
+            crate::ffi::HYPER_HTTP_VERSION_1_1
+        );
+
+        hyper_request_free(req);
+    }
+
+    #[test]
+    fn test_request_uri_parts_missing() {
+        let req = hyper_request_new();
+        let uri = b"/only-path";
+        hyper_request_set_uri(req, uri.as_ptr(), uri.len());
+
+        let (mut authority, mut authority_len) = (b"x".as_ptr(), 1);
+        hyper_request_uri_parts(
+            req,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut authority,
+            &mut authority_len,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+        assert!(authority.is_null());
+        assert_eq!(authority_len, 0);
+
+        hyper_request_free(req);
+    }
+
+    #[test]
+    fn test_response_set_status_and_reason_phrase() {
+        let resp = hyper_response_new();
+        assert_eq!(hyper_response_status(resp), 200);
+
+        assert!(matches!(
+            hyper_response_set_status(resp, 42),
+            hyper_code::HYPERE_INVALID_ARG
+        ));
+        assert!(matches!(
+            hyper_response_set_status(resp, 404),
+            hyper_code::HYPERE_OK
+        ));
+        assert_eq!(hyper_response_status(resp), 404);
+
+        let reason = b"Not Here";
+        assert!(matches!(
+            hyper_response_set_reason_phrase(resp, reason.as_ptr(), reason.len()),
+            hyper_code::HYPERE_OK
+        ));
+        let bad = b"Not\r\nHere";
+        assert!(matches!(
+            hyper_response_set_reason_phrase(resp, bad.as_ptr(), bad.len()),
+            hyper_code::HYPERE_INVALID_ARG
+        ));
+
+        let ptr = hyper_response_reason_phrase(resp);
+        let len = hyper_response_reason_phrase_len(resp);
+        assert_eq!(unsafe { std::slice::from_raw_parts(ptr, len) }, reason);
+
+        hyper_response_free(resp);
+    }
 }
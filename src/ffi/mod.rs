@@ -27,14 +27,18 @@
 //! ```notrust
 //! RUSTFLAGS="--cfg hyper_unstable_ffi" cargo rustc --features client,http1,http2,ffi --crate-type cdylib
 //! ```
+//!
+//! The server API is compiled in with the `server` feature, in addition to
+//! or instead of `client`.
 
-// We may eventually allow the FFI to be enabled without `client` or `http1`,
-// that is why we don't auto enable them as `ffi = ["client", "http1"]` in
-// the `Cargo.toml`.
+// We may eventually allow the FFI to be enabled without `http1`, that is
+// why we don't auto enable it as `ffi = ["http1"]` in the `Cargo.toml`.
 //
 // But for now, give a clear message that this compile error is expected.
-#[cfg(not(all(feature = "client", feature = "http1")))]
-compile_error!("The `ffi` feature currently requires the `client` and `http1` features.");
+#[cfg(not(all(any(feature = "client", feature = "server"), feature = "http1")))]
+compile_error!(
+    "The `ffi` feature currently requires the `http1` feature, and either `client` or `server`."
+);
 
 #[cfg(not(hyper_unstable_ffi))]
 compile_error!(
@@ -48,18 +52,30 @@ compile_error!(
 mod macros;
 
 mod body;
+#[cfg(feature = "client")]
 mod client;
 mod error;
 mod http_types;
 mod io;
+#[cfg(feature = "server")]
+mod server;
 mod task;
+mod time;
+#[cfg(feature = "client")]
+mod upgrade;
 
 pub use self::body::*;
+#[cfg(feature = "client")]
 pub use self::client::*;
 pub use self::error::*;
 pub use self::http_types::*;
 pub use self::io::*;
+#[cfg(feature = "server")]
+pub use self::server::*;
 pub use self::task::*;
+pub use self::time::*;
+#[cfg(feature = "client")]
+pub use self::upgrade::*;
 
 /// Return in iter functions to continue iterating.
 pub const HYPER_ITER_CONTINUE: libc::c_int = 0;
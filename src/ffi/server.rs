@@ -0,0 +1,346 @@
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::Arc;
+
+use libc::{c_int, size_t};
+use tokio::sync::oneshot;
+
+use crate::body::Incoming as IncomingBody;
+use crate::server::conn;
+use crate::service::Service;
+use crate::{Request, Response};
+
+use super::error::hyper_code;
+use super::http_types::{hyper_request, hyper_response};
+use super::io::hyper_io;
+use super::task::{hyper_executor, hyper_task, BoxFuture, WeakExec};
+use super::UserDataPointer;
+
+/// An options builder to configure an HTTP server connection.
+pub struct hyper_serverconn_options {
+    http1: conn::http1::Builder,
+    #[cfg(feature = "http2")]
+    http2: bool,
+    /// Use a `Weak` to prevent cycles.
+    exec: WeakExec,
+}
+
+/// A service that handles the requests received on a server connection.
+///
+/// The service callback is called with each request, and a
+/// `hyper_response_channel *` to send the response on.
+pub struct hyper_service {
+    func: hyper_service_callback,
+    userdata: UserDataPointer,
+}
+
+/// A channel to send the response to a request with.
+///
+/// It is given to the `hyper_service` callback with each request, and is
+/// consumed by `hyper_response_channel_send`.
+pub struct hyper_response_channel(oneshot::Sender<Box<hyper_response>>);
+
+type hyper_service_callback =
+    extern "C" fn(*mut c_void, *mut hyper_request, *mut hyper_response_channel);
+
+// ===== impl hyper_serverconn =====
+
+ffi_fn! {
+    /// Serves an HTTP connection on the provided IO transport, calling the
+    /// service for each request.
+    ///
+    /// The `io` and the `service` are consumed in this function call, but the
+    /// `options` are not, so they can be used for more connections.
+    ///
+    /// The returned `hyper_task *` must be polled with an executor until the
+    /// connection is closed. Its value is empty, or a `hyper_error *` if the
+    /// connection failed.
+    fn hyper_serverconn_serve(io: *mut hyper_io, options: *const hyper_serverconn_options, service: *mut hyper_service) -> *mut hyper_task {
+        let options = non_null! { &*options ?= ptr::null_mut() };
+        let io = non_null! { Box::from_raw(io) ?= ptr::null_mut() };
+        let service = non_null! { Box::from_raw(service) ?= ptr::null_mut() };
+
+        #[cfg(feature = "http2")]
+        {
+            if options.http2 {
+                let mut builder = conn::auto::Builder::new(options.exec.clone());
+                *builder.http1() = options.http1.clone();
+                let conn = builder.serve_connection(io, *service);
+                return Box::into_raw(hyper_task::boxed(conn));
+            }
+        }
+
+        let conn = options.http1.serve_connection(io, *service);
+        Box::into_raw(hyper_task::boxed(conn))
+    } ?= ptr::null_mut()
+}
+
+// ===== impl hyper_serverconn_options =====
+
+ffi_fn! {
+    /// Creates a new set of HTTP serverconn options to be used when serving
+    /// connections.
+    fn hyper_serverconn_options_new() -> *mut hyper_serverconn_options {
+        Box::into_raw(Box::new(hyper_serverconn_options {
+            http1: conn::http1::Builder::new(),
+            #[cfg(feature = "http2")]
+            http2: false,
+            exec: WeakExec::new(),
+        }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Free a `hyper_serverconn_options *`.
+    fn hyper_serverconn_options_free(opts: *mut hyper_serverconn_options) {
+        drop(non_null! { Box::from_raw(opts) ?= () });
+    }
+}
+
+ffi_fn! {
+    /// Set the server background task executor.
+    ///
+    /// It is used to drive the streams of HTTP/2 connections.
+    ///
+    /// This does not consume the `options` or the `exec`.
+    fn hyper_serverconn_options_exec(opts: *mut hyper_serverconn_options, exec: *const hyper_executor) {
+        let opts = non_null! { &mut *opts ?= () };
+
+        let exec = non_null! { Arc::from_raw(exec) ?= () };
+        let weak_exec = hyper_executor::downgrade(&exec);
+        std::mem::forget(exec);
+
+        opts.exec = weak_exec;
+    }
+}
+
+ffi_fn! {
+    /// Set whether to accept HTTP/2 connections.
+    ///
+    /// When enabled, the version is detected from the first bytes the client
+    /// sends. Connections starting with the HTTP/2 preface are served as
+    /// HTTP/2, and others as HTTP/1. An executor must be set with
+    /// `hyper_serverconn_options_exec` for HTTP/2 to make progress.
+    ///
+    /// Pass `0` to disable, `1` to enable.
+    fn hyper_serverconn_options_http2(opts: *mut hyper_serverconn_options, enabled: c_int) -> hyper_code {
+        #[cfg(feature = "http2")]
+        {
+            let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+            opts.http2 = enabled != 0;
+            hyper_code::HYPERE_OK
+        }
+
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = (opts, enabled);
+            hyper_code::HYPERE_FEATURE_NOT_ENABLED
+        }
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/1 connections should support half-closures.
+    ///
+    /// Pass `0` to disable (default), `1` to enable.
+    fn hyper_serverconn_options_http1_half_close(opts: *mut hyper_serverconn_options, enabled: c_int) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1.half_close(enabled != 0);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/1 connections are kept alive after a response.
+    ///
+    /// Pass `0` to disable, `1` to enable (default).
+    fn hyper_serverconn_options_http1_keep_alive(opts: *mut hyper_serverconn_options, enabled: c_int) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1.keep_alive(enabled != 0);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/1 connections will write header names as title case.
+    ///
+    /// Pass `0` to disable (default), `1` to enable.
+    fn hyper_serverconn_options_http1_title_case_headers(opts: *mut hyper_serverconn_options, enabled: c_int) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1.title_case_headers(enabled != 0);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set whether HTTP/1 connections will preserve the original case of
+    /// header names.
+    ///
+    /// Pass `0` to allow lowercase normalization (default), `1` to retain
+    /// original case.
+    fn hyper_serverconn_options_http1_preserve_header_case(opts: *mut hyper_serverconn_options, enabled: c_int) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        opts.http1.preserve_header_case(enabled != 0);
+        hyper_code::HYPERE_OK
+    }
+}
+
+ffi_fn! {
+    /// Set the maximum buffer size for HTTP/1 connections.
+    ///
+    /// The default is ~400kb. Returns `HYPERE_INVALID_ARG` if `max_buf_size`
+    /// is less than 8192.
+    fn hyper_serverconn_options_http1_max_buf_size(opts: *mut hyper_serverconn_options, max_buf_size: size_t) -> hyper_code {
+        let opts = non_null! { &mut *opts ?= hyper_code::HYPERE_INVALID_ARG };
+        if max_buf_size < crate::proto::h1::MINIMUM_MAX_BUFFER_SIZE {
+            return hyper_code::HYPERE_INVALID_ARG;
+        }
+        opts.http1.max_buf_size(max_buf_size);
+        hyper_code::HYPERE_OK
+    }
+}
+
+// ===== impl hyper_service =====
+
+ffi_fn! {
+    /// Create a new service from a callback.
+    ///
+    /// The callback is called with the userdata set by
+    /// `hyper_service_set_userdata`, the received `hyper_request *`, and a
+    /// `hyper_response_channel *`.
+    ///
+    /// The callback owns the request, and must free it once done with it. The
+    /// response, which may be sent later on, must be sent with
+    /// `hyper_response_channel_send`. If the connection is closed before
+    /// then, the response is dropped.
+    fn hyper_service_new(func: hyper_service_callback) -> *mut hyper_service {
+        Box::into_raw(Box::new(hyper_service {
+            func,
+            userdata: UserDataPointer(ptr::null_mut()),
+        }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Set the user data pointer passed to the service callback.
+    fn hyper_service_set_userdata(service: *mut hyper_service, userdata: *mut c_void) {
+        non_null! { &mut *service ?= () }.userdata = UserDataPointer(userdata);
+    }
+}
+
+ffi_fn! {
+    /// Free a `hyper_service *` that wasn't used to serve a connection.
+    fn hyper_service_free(service: *mut hyper_service) {
+        drop(non_null! { Box::from_raw(service) ?= () });
+    }
+}
+
+impl Service<Request<IncomingBody>> for hyper_service {
+    type Response = Response<IncomingBody>;
+    type Error = crate::Error;
+    type Future = BoxFuture<crate::Result<Response<IncomingBody>>>;
+
+    fn call(&mut self, req: Request<IncomingBody>) -> Self::Future {
+        let req = Box::new(hyper_request::wrap(req));
+        let (tx, rx) = oneshot::channel();
+        let channel = Box::new(hyper_response_channel(tx));
+
+        (self.func)(self.userdata.0, Box::into_raw(req), Box::into_raw(channel));
+
+        Box::pin(async move {
+            match rx.await {
+                Ok(mut res) => {
+                    res.finalize_response();
+                    Ok(res.0)
+                }
+                Err(_) => Err(crate::Error::new_canceled()),
+            }
+        })
+    }
+}
+
+// ===== impl hyper_response_channel =====
+
+ffi_fn! {
+    /// Send a response on the channel.
+    ///
+    /// Both the `channel` and the `response` are consumed.
+    fn hyper_response_channel_send(channel: *mut hyper_response_channel, response: *mut hyper_response) -> hyper_code {
+        let channel = non_null! { Box::from_raw(channel) ?= hyper_code::HYPERE_INVALID_ARG };
+        let response = non_null! { Box::from_raw(response) ?= hyper_code::HYPERE_INVALID_ARG };
+        // The connection may have closed already, then there is nobody to
+        // respond to.
+        let _ = channel.0.send(response);
+        hyper_code::HYPERE_OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::http_types::{hyper_response_new, hyper_response_set_status};
+    use futures_util::FutureExt;
+
+    #[test]
+    fn test_service_callback_responds() {
+        extern "C" fn respond(
+            userdata: *mut c_void,
+            req: *mut hyper_request,
+            channel: *mut hyper_response_channel,
+        ) {
+            let calls = unsafe { &mut *(userdata as *mut usize) };
+            *calls += 1;
+
+            let req = unsafe { Box::from_raw(req) };
+            assert_eq!(req.0.uri(), "/ffi");
+
+            let res = hyper_response_new();
+            assert!(matches!(
+                hyper_response_set_status(res, 201),
+                hyper_code::HYPERE_OK
+            ));
+            assert!(matches!(
+                hyper_response_channel_send(channel, res),
+                hyper_code::HYPERE_OK
+            ));
+        }
+
+        let mut calls = 0usize;
+        let service = hyper_service_new(respond);
+        hyper_service_set_userdata(service, &mut calls as *mut usize as *mut c_void);
+        let mut service = unsafe { Box::from_raw(service) };
+
+        let req = Request::builder()
+            .uri("/ffi")
+            .body(IncomingBody::empty())
+            .unwrap();
+        let res = service
+            .call(req)
+            .now_or_never()
+            .expect("response sent from callback")
+            .expect("response");
+
+        assert_eq!(calls, 1);
+        assert_eq!(res.status(), 201);
+    }
+
+    #[test]
+    fn test_service_channel_dropped() {
+        extern "C" fn drop_channel(
+            _userdata: *mut c_void,
+            req: *mut hyper_request,
+            channel: *mut hyper_response_channel,
+        ) {
+            drop(unsafe { Box::from_raw(req) });
+            drop(unsafe { Box::from_raw(channel) });
+        }
+
+        let mut service = unsafe { Box::from_raw(hyper_service_new(drop_channel)) };
+        let err = service
+            .call(Request::new(IncomingBody::empty()))
+            .now_or_never()
+            .expect("channel dropped")
+            .unwrap_err();
+        assert!(err.is_canceled());
+    }
+}
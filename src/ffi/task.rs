@@ -1,9 +1,10 @@
+use std::collections::{HashMap, VecDeque};
 use std::ffi::c_void;
 use std::future::Future;
 use std::pin::Pin;
 use std::ptr;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicUsize, Ordering},
     Arc, Mutex, Weak,
 };
 use std::task::{Context, Poll};
@@ -14,7 +15,7 @@ use libc::c_int;
 use super::error::hyper_code;
 use super::UserDataPointer;
 
-type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+pub(crate) type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 type BoxAny = Box<dyn AsTaskType + Send + Sync>;
 
 /// Return in a poll function to indicate it was ready.
@@ -47,12 +48,47 @@ pub struct hyper_executor {
     /// This is used to track when a future calls `wake` while we are within
     /// `hyper_executor::poll_next`.
     is_woken: Arc<ExecWaker>,
+
+    /// The tasks of a multi-threaded executor.
+    ///
+    /// If set, tasks are scheduled here instead of in the `driver`, so that
+    /// several threads can poll different tasks at the same time.
+    run_queue: Option<Arc<RunQueue>>,
+
+    /// Tells the application that there are tasks to poll.
+    notify: Arc<ExecNotify>,
 }
 
 #[derive(Clone)]
 pub(crate) struct WeakExec(Weak<hyper_executor>);
 
-struct ExecWaker(AtomicBool);
+struct ExecWaker {
+    woken: AtomicBool,
+    notify: Arc<ExecNotify>,
+}
+
+struct ExecNotify(Mutex<Option<(hyper_executor_notify_callback, UserDataPointer)>>);
+
+type hyper_executor_notify_callback = extern "C" fn(*mut c_void);
+
+struct RunQueue {
+    /// The tasks that have been woken, in the order they should be polled.
+    ready: Mutex<VecDeque<Arc<RunTask>>>,
+    /// All incomplete tasks, so they can be dropped with the executor.
+    tasks: Mutex<HashMap<usize, Arc<RunTask>>>,
+    next_id: AtomicUsize,
+    notify: Arc<ExecNotify>,
+}
+
+struct RunTask {
+    id: usize,
+    /// The future, or `None` once it has completed.
+    future: Mutex<Option<TaskFuture>>,
+    /// Whether the task is in the `ready` queue already.
+    queued: AtomicBool,
+    /// Use a `Weak` to prevent cycles.
+    queue: Weak<RunQueue>,
+}
 
 /// An async task.
 pub struct hyper_task {
@@ -86,6 +122,8 @@ pub enum hyper_task_return_type {
     HYPER_TASK_RESPONSE,
     /// The value of this task is `hyper_buf *`.
     HYPER_TASK_BUF,
+    /// The value of this task is `hyper_upgraded *`.
+    HYPER_TASK_UPGRADED,
 }
 
 pub(crate) unsafe trait AsTaskType {
@@ -99,11 +137,28 @@ pub(crate) trait IntoDynTaskType {
 // ===== impl hyper_executor =====
 
 impl hyper_executor {
-    fn new() -> Arc<hyper_executor> {
+    fn new(multi_thread: bool) -> Arc<hyper_executor> {
+        let notify = Arc::new(ExecNotify(Mutex::new(None)));
+        let run_queue = if multi_thread {
+            Some(Arc::new(RunQueue {
+                ready: Mutex::new(VecDeque::new()),
+                tasks: Mutex::new(HashMap::new()),
+                next_id: AtomicUsize::new(0),
+                notify: notify.clone(),
+            }))
+        } else {
+            None
+        };
+
         Arc::new(hyper_executor {
             driver: Mutex::new(FuturesUnordered::new()),
             spawn_queue: Mutex::new(Vec::new()),
-            is_woken: Arc::new(ExecWaker(AtomicBool::new(false))),
+            is_woken: Arc::new(ExecWaker {
+                woken: AtomicBool::new(false),
+                notify: notify.clone(),
+            }),
+            run_queue,
+            notify,
         })
     }
 
@@ -112,13 +167,26 @@ impl hyper_executor {
     }
 
     fn spawn(&self, task: Box<hyper_task>) {
+        if let Some(ref run_queue) = self.run_queue {
+            run_queue.spawn(TaskFuture { task: Some(task) });
+            return;
+        }
+
         self.spawn_queue
             .lock()
             .unwrap()
             .push(TaskFuture { task: Some(task) });
+        futures_util::task::ArcWake::wake_by_ref(&self.is_woken);
     }
 
     fn poll_next(&self) -> Option<Box<hyper_task>> {
+        if let Some(ref run_queue) = self.run_queue {
+            return run_queue.poll_next();
+        }
+
+        // Wakes from now on need to notify again.
+        self.is_woken.woken.store(false, Ordering::SeqCst);
+
         // Drain the queue first.
         self.drain_queue();
 
@@ -137,7 +205,7 @@ impl hyper_executor {
 
                     // If the driver called `wake` while we were polling,
                     // we should poll again immediately!
-                    if self.is_woken.0.swap(false, Ordering::SeqCst) {
+                    if self.is_woken.woken.swap(false, Ordering::SeqCst) {
                         continue;
                     }
 
@@ -163,9 +231,102 @@ impl hyper_executor {
     }
 }
 
+impl Drop for hyper_executor {
+    fn drop(&mut self) {
+        if let Some(ref run_queue) = self.run_queue {
+            run_queue.clear();
+        }
+    }
+}
+
 impl futures_util::task::ArcWake for ExecWaker {
     fn wake_by_ref(me: &Arc<ExecWaker>) {
-        me.0.store(true, Ordering::SeqCst);
+        // Only notify once until the executor has been polled again.
+        if !me.woken.swap(true, Ordering::SeqCst) {
+            me.notify.notify();
+        }
+    }
+}
+
+// ===== impl ExecNotify =====
+
+impl ExecNotify {
+    fn notify(&self) {
+        // Don't hold the lock while calling out, the callback could set a
+        // new one.
+        let func = self
+            .0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|&(func, ref userdata)| (func, userdata.0));
+        if let Some((func, userdata)) = func {
+            func(userdata);
+        }
+    }
+}
+
+// ===== impl RunQueue =====
+
+impl RunQueue {
+    fn spawn(self: &Arc<Self>, future: TaskFuture) {
+        let task = Arc::new(RunTask {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            future: Mutex::new(Some(future)),
+            queued: AtomicBool::new(false),
+            queue: Arc::downgrade(self),
+        });
+        self.tasks.lock().unwrap().insert(task.id, task.clone());
+        futures_util::task::ArcWake::wake(task);
+    }
+
+    fn poll_next(&self) -> Option<Box<hyper_task>> {
+        loop {
+            let task = self.ready.lock().unwrap().pop_front()?;
+            // Clear the flag first, so waking the task while it is being
+            // polled queues it again.
+            task.queued.store(false, Ordering::SeqCst);
+
+            // Another thread may still be polling this task from an earlier
+            // wake, in which case this waits for it to finish.
+            let mut slot = task.future.lock().unwrap();
+            let future = match *slot {
+                Some(ref mut future) => future,
+                None => continue,
+            };
+
+            let waker = futures_util::task::waker_ref(&task);
+            let mut cx = Context::from_waker(&waker);
+            if let Poll::Ready(done) = Pin::new(future).poll(&mut cx) {
+                *slot = None;
+                drop(slot);
+                self.tasks.lock().unwrap().remove(&task.id);
+                return Some(done);
+            }
+        }
+    }
+
+    fn clear(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        for task in tasks.values() {
+            // Take the future out before dropping it, as that may wake
+            // other tasks.
+            let future = task.future.lock().unwrap().take();
+            drop(future);
+        }
+        self.ready.lock().unwrap().clear();
+    }
+}
+
+impl futures_util::task::ArcWake for RunTask {
+    fn wake_by_ref(me: &Arc<RunTask>) {
+        if me.queued.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Some(queue) = me.queue.upgrade() {
+            queue.ready.lock().unwrap().push_back(me.clone());
+            queue.notify.notify();
+        }
     }
 }
 
@@ -177,8 +338,11 @@ impl WeakExec {
     }
 }
 
-impl crate::rt::Executor<BoxFuture<()>> for WeakExec {
-    fn execute(&self, fut: BoxFuture<()>) {
+impl<F> crate::rt::Executor<F> for WeakExec
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    fn execute(&self, fut: F) {
         if let Some(exec) = self.0.upgrade() {
             exec.spawn(hyper_task::boxed(fut));
         }
@@ -188,7 +352,18 @@ impl crate::rt::Executor<BoxFuture<()>> for WeakExec {
 ffi_fn! {
     /// Creates a new task executor.
     fn hyper_executor_new() -> *const hyper_executor {
-        Arc::into_raw(hyper_executor::new())
+        Arc::into_raw(hyper_executor::new(false))
+    } ?= ptr::null()
+}
+
+ffi_fn! {
+    /// Creates a new task executor that can be polled from several threads.
+    ///
+    /// Each thread calling `hyper_executor_poll` polls a different task, so
+    /// tasks can make progress at the same time. The IO, body and other
+    /// callbacks of the tasks may then be called from any of those threads.
+    fn hyper_executor_new_multi_thread() -> *const hyper_executor {
+        Arc::into_raw(hyper_executor::new(true))
     } ?= ptr::null()
 }
 
@@ -212,6 +387,22 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Set a callback to be called when tasks of the executor are ready to
+    /// make progress, so `hyper_executor_poll` should be called.
+    ///
+    /// The callback is passed the `userdata`. It may be called from any
+    /// thread, including from within `hyper_executor_poll`, and only needs to
+    /// signal the thread that polls the executor, such as by writing to an
+    /// eventfd.
+    ///
+    /// This does not consume the `exec`.
+    fn hyper_executor_set_notify(exec: *const hyper_executor, func: hyper_executor_notify_callback, userdata: *mut c_void) {
+        let exec = non_null!(&*exec ?= ());
+        *exec.notify.0.lock().unwrap() = Some((func, UserDataPointer(userdata)));
+    }
+}
+
 ffi_fn! {
     /// Polls the executor, trying to make progress on any tasks that have notified
     /// that they are ready again.
@@ -219,6 +410,9 @@ ffi_fn! {
     /// If ready, returns a task from the executor that has completed.
     ///
     /// If there are no ready tasks, this returns `NULL`.
+    ///
+    /// Executors created with `hyper_executor_new_multi_thread` can be polled
+    /// from several threads at the same time.
     fn hyper_executor_poll(exec: *const hyper_executor) -> *mut hyper_task {
         let exec = non_null!(&*exec ?= ptr::null_mut());
         match exec.poll_next() {
@@ -409,3 +603,87 @@ ffi_fn! {
         waker.waker.wake();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn count_notify(userdata: *mut c_void) {
+        let count = unsafe { &*(userdata as *const AtomicUsize) };
+        count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_multi_thread_executor_polls_from_threads() {
+        let exec = hyper_executor::new(true);
+        let notified = AtomicUsize::new(0);
+        hyper_executor_set_notify(
+            &*exec,
+            count_notify,
+            &notified as *const AtomicUsize as *mut c_void,
+        );
+
+        let mut senders = Vec::new();
+        for _ in 0..4 {
+            let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+            senders.push(tx);
+            exec.spawn(hyper_task::boxed(async move {
+                let _ = rx.await;
+            }));
+        }
+        assert_eq!(notified.load(Ordering::SeqCst), 4);
+        assert!(exec.poll_next().is_none());
+
+        for tx in senders {
+            tx.send(()).unwrap();
+        }
+        assert_eq!(notified.load(Ordering::SeqCst), 8);
+
+        let completed = Arc::new(AtomicUsize::new(0));
+        let threads = (0..2)
+            .map(|_| {
+                let exec = exec.clone();
+                let completed = completed.clone();
+                std::thread::spawn(move || {
+                    while let Some(task) = exec.poll_next() {
+                        assert!(matches!(
+                            task.output_type(),
+                            hyper_task_return_type::HYPER_TASK_EMPTY
+                        ));
+                        completed.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(completed.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_multi_thread_executor_free_drops_tasks() {
+        struct SetOnDrop(Arc<AtomicBool>);
+
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = SetOnDrop(dropped.clone());
+
+        let exec = hyper_executor_new_multi_thread();
+        let task = hyper_task::boxed(async move {
+            let _guard = guard;
+            futures_util::future::pending::<()>().await;
+        });
+        hyper_executor_push(exec, Box::into_raw(task));
+        assert!(hyper_executor_poll(exec).is_null());
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        hyper_executor_free(exec);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
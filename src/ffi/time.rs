@@ -0,0 +1,170 @@
+use std::ffi::c_void;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::rt::{Sleep, Timer};
+
+use super::task::hyper_context;
+use super::UserDataPointer;
+
+type hyper_timer_sleep_callback = extern "C" fn(*mut c_void, *mut hyper_context<'_>, u64);
+
+/// A timer used to time background tasks, such as HTTP/2 keep-alive pings.
+///
+/// hyper keeps track of the deadlines, the timer only has to wake the tasks
+/// waiting on them in time.
+pub struct hyper_timer {
+    sleep: hyper_timer_sleep_callback,
+    userdata: UserDataPointer,
+}
+
+struct TimerSleep {
+    func: hyper_timer_sleep_callback,
+    userdata: UserDataPointer,
+    deadline: Instant,
+    /// The waker the timer was last asked to wake, and when.
+    registered: Option<(Waker, Instant)>,
+}
+
+// ===== impl hyper_timer =====
+
+ffi_fn! {
+    /// Create a new timer.
+    ///
+    /// The sleep function of this timer should be set with
+    /// `hyper_timer_set_sleep`.
+    fn hyper_timer_new() -> *mut hyper_timer {
+        Box::into_raw(Box::new(hyper_timer {
+            sleep: sleep_noop,
+            userdata: UserDataPointer(std::ptr::null_mut()),
+        }))
+    } ?= std::ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Free a `hyper_timer *`.
+    ///
+    /// Options the timer was set on keep their own reference to the sleep
+    /// function and user data, so the timer can be freed right after.
+    fn hyper_timer_free(timer: *mut hyper_timer) {
+        drop(non_null!(Box::from_raw(timer) ?= ()));
+    }
+}
+
+ffi_fn! {
+    /// Set the user data pointer for this timer to some value.
+    ///
+    /// This value is passed as an argument to the sleep callback.
+    fn hyper_timer_set_userdata(timer: *mut hyper_timer, data: *mut c_void) {
+        non_null!(&mut *timer ?= ()).userdata = UserDataPointer(data);
+    }
+}
+
+ffi_fn! {
+    /// Set the sleep function for this timer.
+    ///
+    /// The callback is called when a task has to wait until some deadline,
+    /// with a `hyper_context *` and the number of milliseconds until that
+    /// deadline. A waker should be claimed from the `ctx` with
+    /// `hyper_context_waker`, and woken once at least that many milliseconds
+    /// have passed.
+    ///
+    /// Waking it earlier is allowed, the callback is then called again with
+    /// the time that is left.
+    fn hyper_timer_set_sleep(timer: *mut hyper_timer, func: hyper_timer_sleep_callback) {
+        non_null!(&mut *timer ?= ()).sleep = func;
+    }
+}
+
+impl Clone for hyper_timer {
+    fn clone(&self) -> Self {
+        hyper_timer {
+            sleep: self.sleep,
+            userdata: UserDataPointer(self.userdata.0),
+        }
+    }
+}
+
+impl Timer for hyper_timer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Sleep>> {
+        self.sleep_until(Instant::now() + duration)
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Sleep>> {
+        Box::pin(TimerSleep {
+            func: self.sleep,
+            userdata: UserDataPointer(self.userdata.0),
+            deadline,
+            registered: None,
+        })
+    }
+}
+
+extern "C" fn sleep_noop(_userdata: *mut c_void, _: *mut hyper_context<'_>, _timeout_ms: u64) {}
+
+// ===== impl TimerSleep =====
+
+impl Future for TimerSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        // Only ask for a wake up again if the last one was for another task,
+        // or has already happened.
+        if let Some((ref waker, wake_at)) = self.registered {
+            if waker.will_wake(cx.waker()) && now < wake_at {
+                return Poll::Pending;
+            }
+        }
+
+        // Round up, so the waker isn't woken right before the deadline.
+        let left = self.deadline - now;
+        let timeout_ms = left.as_millis() as u64 + u64::from(left.subsec_nanos() % 1_000_000 != 0);
+
+        (self.func)(self.userdata.0, hyper_context::wrap(cx), timeout_ms);
+        self.registered = Some((cx.waker().clone(), now + Duration::from_millis(timeout_ms)));
+        Poll::Pending
+    }
+}
+
+impl Sleep for TimerSleep {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::task::{hyper_context_waker, hyper_waker_free};
+
+    #[test]
+    fn test_timer_sleep_registers_once() {
+        extern "C" fn sleep(userdata: *mut c_void, cx: *mut hyper_context<'_>, timeout_ms: u64) {
+            let calls = unsafe { &mut *(userdata as *mut Vec<u64>) };
+            calls.push(timeout_ms);
+            hyper_waker_free(hyper_context_waker(cx));
+        }
+
+        let mut calls = Vec::<u64>::new();
+        let timer = hyper_timer_new();
+        hyper_timer_set_sleep(timer, sleep);
+        hyper_timer_set_userdata(timer, &mut calls as *mut Vec<u64> as *mut c_void);
+        let timer = unsafe { Box::from_raw(timer) };
+
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut sleep = timer.sleep(Duration::from_millis(20));
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0] > 0 && calls[0] <= 20, "timeout_ms = {}", calls[0]);
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(sleep.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(calls.len(), 1);
+    }
+}
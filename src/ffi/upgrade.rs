@@ -0,0 +1,231 @@
+use std::io;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+
+use bytes::Bytes;
+use futures_util::future::poll_fn;
+use libc::size_t;
+
+use crate::rt::{Read, ReadBuf, Write};
+use crate::upgrade::Upgraded;
+
+use super::body::hyper_buf;
+use super::http_types::hyper_response;
+use super::task::{hyper_task, hyper_task_return_type, AsTaskType};
+
+/// The size of the buffers returned by `hyper_upgraded_read`.
+const READ_BUF_SIZE: usize = 8192;
+
+/// A connection that was upgraded to some other protocol.
+///
+/// Any bytes the peer sent right after agreeing to the upgrade, which were
+/// already read by hyper, are returned by the first reads.
+pub struct hyper_upgraded(Arc<Mutex<Upgraded>>);
+
+// ===== impl hyper_response =====
+
+ffi_fn! {
+    /// Return a task that waits for the connection of this response to be
+    /// upgraded.
+    ///
+    /// This is used with the response to a `CONNECT` request, or to a request
+    /// with an `Upgrade` header, once its status shows that the server agreed
+    /// to it.
+    ///
+    /// The task value may have different types depending on the outcome:
+    ///
+    /// - `HYPER_TASK_UPGRADED`: Success, the value is a `hyper_upgraded *`.
+    /// - `HYPER_TASK_ERROR`: The connection couldn't be upgraded.
+    ///
+    /// This does not consume the `hyper_response *`.
+    fn hyper_response_upgrade(resp: *mut hyper_response) -> *mut hyper_task {
+        let resp = non_null!(&mut *resp ?= ptr::null_mut());
+        let on_upgrade = crate::upgrade::on(&mut resp.0);
+
+        Box::into_raw(hyper_task::boxed(async move {
+            on_upgrade
+                .await
+                .map(|upgraded| hyper_upgraded(Arc::new(Mutex::new(upgraded))))
+        }))
+    } ?= ptr::null_mut()
+}
+
+// ===== impl hyper_upgraded =====
+
+ffi_fn! {
+    /// Return a task that reads the next bytes from the upgraded connection.
+    ///
+    /// The task value may have different types depending on the outcome:
+    ///
+    /// - `HYPER_TASK_BUF`: Success, and some bytes were read.
+    /// - `HYPER_TASK_ERROR`: An error reading from the connection.
+    /// - `HYPER_TASK_EMPTY`: The connection was closed by the peer.
+    ///
+    /// This does not consume the `hyper_upgraded *`. A read and a write may
+    /// be in progress at the same time.
+    fn hyper_upgraded_read(upgraded: *const hyper_upgraded) -> *mut hyper_task {
+        let upgraded = non_null!(&*upgraded ?= ptr::null_mut()).0.clone();
+
+        Box::into_raw(hyper_task::boxed(async move {
+            let mut buf = vec![0; READ_BUF_SIZE];
+            let n = poll_fn(|cx| {
+                let mut read_buf = ReadBuf::new(&mut buf);
+                let mut upgraded = upgraded.lock().unwrap();
+                futures_util::ready!(Pin::new(&mut *upgraded).poll_read(cx, read_buf.unfilled()))?;
+                Poll::Ready(Ok::<_, io::Error>(read_buf.filled().len()))
+            })
+            .await
+            .map_err(crate::Error::new_io)?;
+
+            if n == 0 {
+                return Ok(None);
+            }
+            buf.truncate(n);
+            Ok(Some(hyper_buf(Bytes::from(buf))))
+        }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Return a task that writes all of the bytes to the upgraded connection,
+    /// and flushes it.
+    ///
+    /// The bytes are copied, so the `buf` can be freed right away. The task
+    /// value is empty on success, or a `hyper_error *` if writing failed.
+    ///
+    /// This does not consume the `hyper_upgraded *`. A read and a write may
+    /// be in progress at the same time.
+    fn hyper_upgraded_write(upgraded: *const hyper_upgraded, buf: *const u8, buf_len: size_t) -> *mut hyper_task {
+        let upgraded = non_null!(&*upgraded ?= ptr::null_mut()).0.clone();
+        let data = if buf_len == 0 {
+            Vec::new()
+        } else {
+            non_null!(buf, std::slice::from_raw_parts(buf, buf_len), ptr::null_mut()).to_vec()
+        };
+
+        Box::into_raw(hyper_task::boxed(async move {
+            let mut written = 0;
+            poll_fn(|cx| {
+                let mut upgraded = upgraded.lock().unwrap();
+                while written < data.len() {
+                    let n = futures_util::ready!(Pin::new(&mut *upgraded).poll_write(cx, &data[written..]))?;
+                    if n == 0 {
+                        return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                    }
+                    written += n;
+                }
+                Pin::new(&mut *upgraded).poll_flush(cx)
+            })
+            .await
+            .map_err(crate::Error::new_io)
+        }))
+    } ?= ptr::null_mut()
+}
+
+ffi_fn! {
+    /// Free a `hyper_upgraded *`.
+    ///
+    /// The connection is closed once the tasks still reading or writing it
+    /// have completed.
+    fn hyper_upgraded_free(upgraded: *mut hyper_upgraded) {
+        drop(non_null!(Box::from_raw(upgraded) ?= ()));
+    }
+}
+
+unsafe impl AsTaskType for hyper_upgraded {
+    fn as_task_type(&self) -> hyper_task_return_type {
+        hyper_task_return_type::HYPER_TASK_UPGRADED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::task::{
+        hyper_executor, hyper_executor_free, hyper_executor_new, hyper_executor_poll,
+        hyper_executor_push, hyper_task_free, hyper_task_type, hyper_task_value,
+    };
+    use crate::rt::TokioIo;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn run(exec: *const hyper_executor, task: *mut hyper_task) -> *mut hyper_task {
+        hyper_executor_push(exec, task);
+        loop {
+            let task = hyper_executor_poll(exec);
+            if !task.is_null() {
+                return task;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_upgraded_read_buffered_then_io() {
+        let (io, mut peer) = tokio::io::duplex(64);
+        let upgraded = hyper_upgraded(Arc::new(Mutex::new(Upgraded::new(
+            TokioIo::new(io),
+            Bytes::from_static(b"buffered"),
+        ))));
+        futures_util::FutureExt::now_or_never(peer.write_all(b"from peer"))
+            .unwrap()
+            .unwrap();
+
+        let exec = hyper_executor_new();
+
+        let task = run(exec, hyper_upgraded_read(&upgraded));
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_BUF
+        ));
+        let buf = unsafe { Box::from_raw(hyper_task_value(task) as *mut hyper_buf) };
+        assert_eq!(buf.0, "buffered");
+        hyper_task_free(task);
+
+        let task = run(exec, hyper_upgraded_read(&upgraded));
+        let buf = unsafe { Box::from_raw(hyper_task_value(task) as *mut hyper_buf) };
+        assert_eq!(buf.0, "from peer");
+        hyper_task_free(task);
+
+        drop(peer);
+        let task = run(exec, hyper_upgraded_read(&upgraded));
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_EMPTY
+        ));
+        hyper_task_free(task);
+
+        hyper_executor_free(exec);
+    }
+
+    #[test]
+    fn test_upgraded_write() {
+        let (io, mut peer) = tokio::io::duplex(64);
+        let upgraded = hyper_upgraded(Arc::new(Mutex::new(Upgraded::new(
+            TokioIo::new(io),
+            Bytes::new(),
+        ))));
+
+        let exec = hyper_executor_new();
+
+        let data = b"to peer";
+        let task = run(
+            exec,
+            hyper_upgraded_write(&upgraded, data.as_ptr(), data.len()),
+        );
+        assert!(matches!(
+            hyper_task_type(task),
+            hyper_task_return_type::HYPER_TASK_EMPTY
+        ));
+        hyper_task_free(task);
+
+        let mut received = [0; 7];
+        futures_util::FutureExt::now_or_never(peer.read_exact(&mut received))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&received, data);
+
+        hyper_executor_free(exec);
+    }
+}
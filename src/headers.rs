@@ -1,6 +1,8 @@
 #[cfg(feature = "http1")]
 use bytes::BytesMut;
 use http::header::CONTENT_LENGTH;
+#[cfg(all(feature = "http1", feature = "client"))]
+use http::header::EXPECT;
 use http::header::{HeaderValue, ValueIter};
 use http::HeaderMap;
 #[cfg(all(feature = "http2", feature = "client"))]
@@ -28,6 +30,18 @@ fn connection_has(value: &HeaderValue, needle: &str) -> bool {
     false
 }
 
+#[cfg(all(feature = "http1", feature = "server"))]
+pub(super) fn te_trailers(value: &HeaderValue) -> bool {
+    connection_has(value, "trailers")
+}
+
+#[cfg(all(feature = "http1", feature = "client"))]
+pub(super) fn expect_continue(headers: &HeaderMap) -> bool {
+    headers.get(EXPECT).map_or(false, |value| {
+        value.as_bytes().eq_ignore_ascii_case(b"100-continue")
+    })
+}
+
 #[cfg(all(feature = "http1", feature = "server"))]
 pub(super) fn content_length_parse(value: &HeaderValue) -> Option<u64> {
     from_digits(value.as_bytes())
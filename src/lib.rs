@@ -49,6 +49,10 @@
 //! - `http2`: Enables HTTP/2 support.
 //! - `client`: Enables the HTTP `client`.
 //! - `server`: Enables the HTTP `server`.
+//! - `runtime`: Enables the tokio [`Executor`](rt::Executor) and
+//!   [`Timer`](rt::Timer) implementations in [`rt`].
+//! - `test-util`: Enables the in-memory IO and manually advanced timer in
+//!   [`mock`](mod@mock).
 //!
 //! [feature flags]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
 
@@ -72,8 +76,9 @@ mod common;
 pub mod body;
 mod error;
 pub mod ext;
-#[cfg(test)]
-mod mock;
+#[cfg(any(test, feature = "test-util"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod mock;
 pub mod rt;
 pub mod service;
 pub mod upgrade;
@@ -83,6 +88,7 @@ pub mod ffi;
 
 cfg_proto! {
     mod headers;
+    pub mod observe;
     mod proto;
 }
 
@@ -1,235 +1,702 @@
-// FIXME: re-implement tests with `async/await`
-/*
-#[cfg(feature = "runtime")]
+//! Utilities for testing code built on hyper.
+//!
+//! This module provides an in-memory transport, [`duplex`], and a timer that
+//! only moves when told to, [`MockTimer`]. Together they make it possible to
+//! exercise timeouts, keep-alive and pipelining deterministically, without
+//! sockets or wall-clock sleeps.
+//!
+//! Requires the `test-util` feature.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(all(feature = "server", feature = "http1"))]
+//! # async fn run() {
+//! use std::time::Duration;
+//!
+//! use hyper::mock::{duplex, MockTimer};
+//! # use hyper::{body::Incoming, Request, Response};
+//! # async fn handle(_: Request<Incoming>) -> Result<Response<String>, hyper::Error> {
+//! #     Ok(Response::new(String::new()))
+//! # }
+//!
+//! let (client, server) = duplex(1024);
+//! let timer = MockTimer::new();
+//!
+//! let conn = hyper::server::conn::http1::Builder::new()
+//!     .timer(timer.clone())
+//!     .header_read_timeout(Duration::from_secs(5))
+//!     .serve_connection(server, hyper::service::service_fn(handle));
+//!
+//! // Drive `conn`, write a partial request head with `client`, then:
+//! timer.advance(Duration::from_secs(5));
+//! # drop((client, conn));
+//! # }
+//! # fn main() {}
+//! ```
+
 use std::collections::HashMap;
-use std::cmp;
-use std::io::{self, Read, Write};
-#[cfg(feature = "runtime")]
+use std::convert::TryFrom;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use bytes::{Buf, BytesMut};
+
+use crate::rt::{Read, ReadBufCursor, Sleep, Timer, Write};
+
+/// Create a connected pair of in-memory IO objects.
+///
+/// Bytes written to one end can be read from the other. Each direction
+/// buffers at most `capacity` bytes; once full, writes return
+/// `Poll::Pending` until the other end reads.
+///
+/// Shutting down or dropping one end makes the other end read EOF once the
+/// buffered bytes are consumed.
+pub fn duplex(capacity: usize) -> (Duplex, Duplex) {
+    let a = Arc::new(Mutex::new(Pipe::new(capacity)));
+    let b = Arc::new(Mutex::new(Pipe::new(capacity)));
+
+    (Duplex::new(a.clone(), b.clone()), Duplex::new(b, a))
+}
 
-use bytes::Buf;
-use futures::{Async, Poll};
-#[cfg(feature = "runtime")]
-use futures::Future;
-use futures::task::{self, Task};
-use tokio_io::{AsyncRead, AsyncWrite};
-
-#[cfg(feature = "runtime")]
-use crate::client::connect::{Connect, Connected, Destination};
-
-
-
-#[cfg(feature = "runtime")]
+/// One end of an in-memory connection, created with [`duplex`].
+///
+/// Faults can be scheduled at byte offsets before the IO is handed to the
+/// code under test. Offsets count the bytes read from, or written to, this
+/// end since it was created.
+///
+/// `Duplex` implements hyper's [`Read`] and [`Write`] traits. Wrap it in
+/// [`TokioIo`](crate::rt::TokioIo) to use the Tokio IO helpers.
 pub struct Duplex {
-    inner: Arc<Mutex<DuplexInner>>,
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>,
+    max_read: usize,
+    max_write: usize,
+    read_error_at: Option<(u64, io::ErrorKind)>,
+    write_error_at: Option<(u64, io::ErrorKind)>,
+    read_eof_at: Option<u64>,
+    bytes_read: u64,
+    bytes_written: u64,
 }
 
-#[cfg(feature = "runtime")]
-struct DuplexInner {
-    handle_read_task: Option<Task>,
-    read: AsyncIo<MockCursor>,
-    write: AsyncIo<MockCursor>,
+struct Pipe {
+    buf: BytesMut,
+    capacity: usize,
+    writer_closed: bool,
+    reader_closed: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
 }
 
-#[cfg(feature = "runtime")]
 impl Duplex {
-    pub(crate) fn channel() -> (Duplex, DuplexHandle) {
-        let mut inner = DuplexInner {
-            handle_read_task: None,
-            read: AsyncIo::new_buf(Vec::new(), 0),
-            write: AsyncIo::new_buf(Vec::new(), std::usize::MAX),
-        };
+    fn new(read: Arc<Mutex<Pipe>>, write: Arc<Mutex<Pipe>>) -> Self {
+        Self {
+            read,
+            write,
+            max_read: usize::MAX,
+            max_write: usize::MAX,
+            read_error_at: None,
+            write_error_at: None,
+            read_eof_at: None,
+            bytes_read: 0,
+            bytes_written: 0,
+        }
+    }
 
-        inner.read.park_tasks(true);
-        inner.write.park_tasks(true);
+    /// Limit each read to at most `max` bytes.
+    ///
+    /// Useful to make sure a parser copes with a message arriving in pieces.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    pub fn max_read(&mut self, max: usize) -> &mut Self {
+        assert!(max > 0, "max_read must be larger than 0");
+        self.max_read = max;
+        self
+    }
 
-        let inner = Arc::new(Mutex::new(inner));
+    /// Limit each write to at most `max` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    pub fn max_write(&mut self, max: usize) -> &mut Self {
+        assert!(max > 0, "max_write must be larger than 0");
+        self.max_write = max;
+        self
+    }
 
-        let duplex = Duplex {
-            inner: inner.clone(),
-        };
-        let handle = DuplexHandle {
-            inner: inner,
-        };
+    /// Fail reads with an error of `kind` once `offset` bytes have been read.
+    pub fn read_error_at(&mut self, offset: u64, kind: io::ErrorKind) -> &mut Self {
+        self.read_error_at = Some((offset, kind));
+        self
+    }
 
-        (duplex, handle)
+    /// Fail writes with an error of `kind` once `offset` bytes have been
+    /// written.
+    pub fn write_error_at(&mut self, offset: u64, kind: io::ErrorKind) -> &mut Self {
+        self.write_error_at = Some((offset, kind));
+        self
     }
-}
 
-#[cfg(feature = "runtime")]
-impl Read for Duplex {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.lock().unwrap().read.read(buf)
+    /// Report EOF once `offset` bytes have been read, even if the other end
+    /// has written more.
+    pub fn read_eof_at(&mut self, offset: u64) -> &mut Self {
+        self.read_eof_at = Some(offset);
+        self
     }
-}
 
-#[cfg(feature = "runtime")]
-impl Write for Duplex {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let mut inner = self.inner.lock().unwrap();
-        let ret = inner.write.write(buf);
-        if let Some(task) = inner.handle_read_task.take() {
-            trace!("waking DuplexHandle read");
-            task.notify();
-        }
-        ret
+    /// The number of bytes read from this end so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        self.inner.lock().unwrap().write.flush()
+    /// The number of bytes written to this end so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
     }
 }
 
-#[cfg(feature = "runtime")]
-impl AsyncRead for Duplex {
+/// How many bytes may be transferred before reaching `offset`.
+fn until(offset: Option<u64>, pos: u64) -> usize {
+    match offset {
+        Some(offset) => usize::try_from(offset - pos).unwrap_or(usize::MAX),
+        None => usize::MAX,
+    }
 }
 
-#[cfg(feature = "runtime")]
-impl AsyncWrite for Duplex {
-    fn shutdown(&mut self) -> Poll<(), io::Error> {
-        Ok(().into())
-    }
+impl Read for Duplex {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if let Some((offset, kind)) = this.read_error_at {
+            if this.bytes_read >= offset {
+                return Poll::Ready(Err(kind.into()));
+            }
+        }
+        if let Some(offset) = this.read_eof_at {
+            if this.bytes_read >= offset {
+                return Poll::Ready(Ok(()));
+            }
+        }
+
+        let mut pipe = this.read.lock().unwrap();
+        if pipe.buf.is_empty() {
+            if pipe.writer_closed {
+                return Poll::Ready(Ok(()));
+            }
+            pipe.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
 
-    fn write_buf<B: Buf>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
-        let mut inner = self.inner.lock().unwrap();
-        if let Some(task) = inner.handle_read_task.take() {
-            task.notify();
+        let n = buf
+            .remaining()
+            .min(pipe.buf.len())
+            .min(this.max_read)
+            .min(until(this.read_error_at.map(|(o, _)| o), this.bytes_read))
+            .min(until(this.read_eof_at, this.bytes_read));
+        buf.put_slice(&pipe.buf[..n]);
+        pipe.buf.advance(n);
+        this.bytes_read += n as u64;
+
+        if let Some(waker) = pipe.write_waker.take() {
+            waker.wake();
         }
-        inner.write.write_buf(buf)
+        Poll::Ready(Ok(()))
     }
 }
 
-#[cfg(feature = "runtime")]
-pub struct DuplexHandle {
-    inner: Arc<Mutex<DuplexInner>>,
-}
+impl Write for Duplex {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        if let Some((offset, kind)) = this.write_error_at {
+            if this.bytes_written >= offset {
+                return Poll::Ready(Err(kind.into()));
+            }
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let mut pipe = this.write.lock().unwrap();
+        if pipe.writer_closed || pipe.reader_closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        let space = pipe.capacity - pipe.buf.len();
+        if space == 0 {
+            pipe.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let n = buf.len().min(space).min(this.max_write).min(until(
+            this.write_error_at.map(|(o, _)| o),
+            this.bytes_written,
+        ));
+        pipe.buf.extend_from_slice(&buf[..n]);
+        this.bytes_written += n as u64;
 
-#[cfg(feature = "runtime")]
-impl DuplexHandle {
-    pub fn read(&self, buf: &mut [u8]) -> Poll<usize, io::Error> {
-        let mut inner = self.inner.lock().unwrap();
-        assert!(buf.len() >= inner.write.inner.len());
-        if inner.write.inner.is_empty() {
-            trace!("DuplexHandle read parking");
-            inner.handle_read_task = Some(task::current());
-            return Ok(Async::NotReady);
+        if let Some(waker) = pipe.read_waker.take() {
+            waker.wake();
         }
-        inner.write.read(buf).map(Async::Ready)
+        Poll::Ready(Ok(n))
     }
 
-    pub fn write(&self, bytes: &[u8]) -> Poll<usize, io::Error> {
-        let mut inner = self.inner.lock().unwrap();
-        assert_eq!(inner.read.inner.pos, 0);
-        assert_eq!(inner.read.inner.vec.len(), 0, "write but read isn't empty");
-        inner
-            .read
-            .inner
-            .vec
-            .extend(bytes);
-        inner.read.block_in(bytes.len());
-        Ok(Async::Ready(bytes.len()))
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.write.lock().unwrap().close_writer();
+        Poll::Ready(Ok(()))
     }
 }
 
-#[cfg(feature = "runtime")]
-impl Drop for DuplexHandle {
+impl Drop for Duplex {
     fn drop(&mut self) {
-        trace!("mock duplex handle drop");
-        if !::std::thread::panicking() {
-            let mut inner = self.inner.lock().unwrap();
-            inner.read.close();
-            inner.write.close();
+        if let Ok(mut pipe) = self.write.lock() {
+            pipe.close_writer();
+        }
+        if let Ok(mut pipe) = self.read.lock() {
+            pipe.reader_closed = true;
+            if let Some(waker) = pipe.write_waker.take() {
+                waker.wake();
+            }
         }
     }
 }
 
-#[cfg(feature = "runtime")]
-type BoxedConnectFut = Box<dyn Future<Item=(Duplex, Connected), Error=io::Error> + Send>;
+impl fmt::Debug for Duplex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Duplex")
+            .field("bytes_read", &self.bytes_read)
+            .field("bytes_written", &self.bytes_written)
+            .finish()
+    }
+}
+
+impl Pipe {
+    fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "duplex capacity must be larger than 0");
+        Pipe {
+            buf: BytesMut::new(),
+            capacity,
+            writer_closed: false,
+            reader_closed: false,
+            read_waker: None,
+            write_waker: None,
+        }
+    }
+
+    fn close_writer(&mut self) {
+        self.writer_closed = true;
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+    }
+}
 
-#[cfg(feature = "runtime")]
+/// A [`Timer`] whose clock only moves when [`advance`](MockTimer::advance)
+/// is called.
+///
+/// Clones share the same clock, so one can be given to hyper while the test
+/// keeps another to move time forward.
+///
+/// [`Timer::now`] returns the instant of this clock, so the deadlines and
+/// time checks hyper computes from it, such as timeouts, HTTP/2 keep-alive
+/// and minimum data rates, only move when the clock is advanced.
 #[derive(Clone)]
-pub struct MockConnector {
-    mocks: Arc<Mutex<MockedConnections>>,
+pub struct MockTimer {
+    clock: Arc<Mutex<Clock>>,
+}
+
+struct Clock {
+    now: Instant,
+    next_id: u64,
+    sleeps: HashMap<u64, Entry>,
+}
+
+struct Entry {
+    deadline: Instant,
+    waker: Option<Waker>,
 }
 
-#[cfg(feature = "runtime")]
-struct MockedConnections(HashMap<String, Vec<BoxedConnectFut>>);
+struct MockSleep {
+    id: u64,
+    clock: Arc<Mutex<Clock>>,
+}
+
+impl MockTimer {
+    /// Create a new timer, with its clock starting at the current instant.
+    pub fn new() -> Self {
+        MockTimer {
+            clock: Arc::new(Mutex::new(Clock {
+                now: Instant::now(),
+                next_id: 0,
+                sleeps: HashMap::new(),
+            })),
+        }
+    }
+
+    /// The current instant of this timer's clock.
+    pub fn now(&self) -> Instant {
+        self.clock.lock().unwrap().now
+    }
+
+    /// Move the clock forward by `duration`, waking every sleep whose
+    /// deadline has been reached.
+    pub fn advance(&self, duration: Duration) {
+        let wakers = {
+            let mut clock = self.clock.lock().unwrap();
+            clock.now += duration;
+            let now = clock.now;
+            clock
+                .sleeps
+                .values_mut()
+                .filter(|entry| entry.deadline <= now)
+                .filter_map(|entry| entry.waker.take())
+                .collect::<Vec<_>>()
+        };
 
-#[cfg(feature = "runtime")]
-impl MockConnector {
-    pub fn new() -> MockConnector {
-        MockConnector {
-            mocks: Arc::new(Mutex::new(MockedConnections(HashMap::new()))),
+        for waker in wakers {
+            waker.wake();
         }
     }
 
-    pub fn mock(&mut self, key: &str) -> DuplexHandle {
-        use futures::future;
-        self.mock_fut(key, future::ok::<_, ()>(()))
+    /// The number of sleeps that exist and have not reached their deadline.
+    pub fn pending_sleeps(&self) -> usize {
+        let clock = self.clock.lock().unwrap();
+        clock
+            .sleeps
+            .values()
+            .filter(|entry| entry.deadline > clock.now)
+            .count()
+    }
+
+    fn sleep_at(&self, deadline: Instant) -> Pin<Box<dyn Sleep>> {
+        let mut clock = self.clock.lock().unwrap();
+        let id = clock.next_id;
+        clock.next_id += 1;
+        clock.sleeps.insert(
+            id,
+            Entry {
+                deadline,
+                waker: None,
+            },
+        );
+
+        Box::pin(MockSleep {
+            id,
+            clock: self.clock.clone(),
+        })
     }
+}
+
+impl Default for MockTimer {
+    fn default() -> Self {
+        MockTimer::new()
+    }
+}
 
-    pub fn mock_fut<F>(&mut self, key: &str, fut: F) -> DuplexHandle
-    where
-        F: Future + Send + 'static,
-    {
-        self.mock_opts(key, Connected::new(), fut)
+impl Timer for MockTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Sleep>> {
+        let deadline = self.now() + duration;
+        self.sleep_at(deadline)
     }
 
-    pub fn mock_opts<F>(&mut self, key: &str, connected: Connected, fut: F) -> DuplexHandle
-    where
-        F: Future + Send + 'static,
-    {
-        let key = key.to_owned();
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Sleep>> {
+        self.sleep_at(deadline)
+    }
 
-        let (duplex, handle) = Duplex::channel();
+    fn reset(&self, sleep: &mut Pin<Box<dyn Sleep>>, deadline: Instant) {
+        match sleep.as_mut().downcast_mut_pin::<MockSleep>() {
+            Some(sleep) if Arc::ptr_eq(&sleep.clock, &self.clock) => {
+                let mut clock = self.clock.lock().unwrap();
+                let now = clock.now;
+                if let Some(entry) = clock.sleeps.get_mut(&sleep.id) {
+                    entry.deadline = deadline;
+                    if deadline <= now {
+                        if let Some(waker) = entry.waker.take() {
+                            waker.wake();
+                        }
+                    }
+                }
+            }
+            _ => *sleep = self.sleep_at(deadline),
+        }
+    }
 
-        let fut = Box::new(fut.then(move |_| {
-            trace!("MockConnector mocked fut ready");
-            Ok((duplex, connected))
-        }));
-        self.mocks.lock().unwrap().0.entry(key)
-            .or_insert(Vec::new())
-            .push(fut);
+    fn now(&self) -> Instant {
+        MockTimer::now(self)
+    }
+}
 
-        handle
+impl fmt::Debug for MockTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockTimer")
+            .field("pending_sleeps", &self.pending_sleeps())
+            .finish()
     }
 }
 
-#[cfg(feature = "runtime")]
-impl Connect for MockConnector {
-    type Transport = Duplex;
-    type Error = io::Error;
-    type Future = BoxedConnectFut;
+impl Future for MockSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut clock = self.clock.lock().unwrap();
+        let now = clock.now;
+        let entry = clock
+            .sleeps
+            .get_mut(&self.id)
+            .expect("sleep entry removed while alive");
 
-    fn connect(&self, dst: Destination) -> Self::Future {
-        trace!("mock connect: {:?}", dst);
-        let key = format!("{}://{}{}", dst.scheme(), dst.host(), if let Some(port) = dst.port() {
-            format!(":{}", port)
+        if entry.deadline <= now {
+            Poll::Ready(())
         } else {
-            "".to_owned()
-        });
-        let mut mocks = self.mocks.lock().unwrap();
-        let mocks = mocks.0.get_mut(&key)
-            .expect(&format!("unknown mocks uri: {}", key));
-        assert!(!mocks.is_empty(), "no additional mocks for {}", key);
-        mocks.remove(0)
+            entry.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
     }
 }
 
+impl Sleep for MockSleep {}
 
-#[cfg(feature = "runtime")]
-impl Drop for MockedConnections {
+impl Drop for MockSleep {
     fn drop(&mut self) {
-        if !::std::thread::panicking() {
-            for (key, mocks) in self.0.iter() {
-                assert_eq!(
-                    mocks.len(),
-                    0,
-                    "not all mocked connects for {:?} were used",
-                    key,
-                );
-            }
+        if let Ok(mut clock) = self.clock.lock() {
+            clock.sleeps.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_test::{assert_pending, assert_ready};
+
+    use super::{duplex, MockTimer};
+    use crate::rt::{Timer, TokioIo};
+
+    #[tokio::test]
+    async fn duplex_partial_reads_and_eof_at() {
+        let (a, mut b) = duplex(64);
+        b.max_read(3).read_eof_at(5);
+        let mut a = TokioIo::new(a);
+        let mut b = TokioIo::new(b);
+
+        a.write_all(b"hello world").await.unwrap();
+
+        let mut buf = [0; 16];
+        assert_eq!(b.read(&mut buf).await.unwrap(), 3);
+        assert_eq!(&buf[..3], b"hel");
+        assert_eq!(b.read(&mut buf).await.unwrap(), 2);
+        assert_eq!(&buf[..2], b"lo");
+        assert_eq!(b.read(&mut buf).await.unwrap(), 0);
+        assert_eq!(b.inner().bytes_read(), 5);
+    }
+
+    #[tokio::test]
+    async fn duplex_errors_at_offset() {
+        let (mut a, mut b) = duplex(64);
+        a.write_error_at(4, io::ErrorKind::ConnectionReset);
+        b.read_error_at(2, io::ErrorKind::UnexpectedEof);
+        let mut a = TokioIo::new(a);
+        let mut b = TokioIo::new(b);
+
+        assert_eq!(a.write(b"abcdef").await.unwrap(), 4);
+        let err = a.write(b"ef").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+
+        let mut buf = [0; 16];
+        assert_eq!(b.read(&mut buf).await.unwrap(), 2);
+        let err = b.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn duplex_write_backpressure() {
+        let (a, b) = duplex(4);
+        let mut a = TokioIo::new(a);
+        let mut b = TokioIo::new(b);
+
+        assert_eq!(a.write(b"abcdef").await.unwrap(), 4);
+        {
+            let mut write = tokio_test::task::spawn(a.write(b"ef"));
+            assert_pending!(write.poll());
+
+            let mut buf = [0; 2];
+            b.read_exact(&mut buf).await.unwrap();
+            assert!(write.is_woken());
+            assert_eq!(assert_ready!(write.poll()).unwrap(), 2);
+        }
+
+        a.shutdown().await.unwrap();
+        let mut rest = Vec::new();
+        b.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"cdef");
+    }
+
+    #[test]
+    fn timer_advance_and_reset() {
+        let timer = MockTimer::new();
+        let mut sleep = tokio_test::task::spawn(timer.sleep(Duration::from_secs(10)));
+        assert_eq!(timer.pending_sleeps(), 1);
+        assert_pending!(sleep.poll());
+
+        timer.advance(Duration::from_secs(9));
+        assert!(!sleep.is_woken());
+        assert_pending!(sleep.poll());
+
+        timer.reset(&mut *sleep, timer.now() + Duration::from_secs(5));
+        timer.advance(Duration::from_secs(4));
+        assert_pending!(sleep.poll());
+        timer.advance(Duration::from_secs(1));
+        assert!(sleep.is_woken());
+        assert_ready!(sleep.poll());
+        assert_eq!(timer.pending_sleeps(), 0);
+    }
+
+    #[cfg(all(feature = "server", feature = "http1"))]
+    #[tokio::test]
+    async fn header_read_timeout_with_mock_timer() {
+        use crate::body::Incoming;
+        use crate::service::service_fn;
+        use crate::{Request, Response};
+
+        let (client, server) = duplex(1024);
+        let timer = MockTimer::new();
+
+        let conn = crate::server::conn::http1::Builder::new()
+            .timer(timer.clone())
+            .header_read_timeout(Duration::from_secs(5))
+            .serve_connection(
+                server,
+                service_fn(|_: Request<Incoming>| async {
+                    Ok::<_, crate::Error>(Response::new(String::new()))
+                }),
+            );
+        let conn = tokio::spawn(conn);
+
+        let mut client = TokioIo::new(client);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        while timer.pending_sleeps() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        timer.advance(Duration::from_secs(4));
+        tokio::task::yield_now().await;
+        assert!(!conn.is_finished());
+
+        timer.advance(Duration::from_secs(1));
+        let err = conn.await.unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "read header from client timeout");
+    }
+
+    #[cfg(all(feature = "server", feature = "http2", feature = "runtime"))]
+    #[tokio::test]
+    async fn http2_keep_alive_with_mock_timer() {
+        use futures_util::FutureExt;
+
+        use crate::body::Incoming;
+        use crate::rt::TokioExecutor;
+        use crate::service::service_fn;
+        use crate::{Request, Response};
+
+        const PING: u8 = 0x6;
+
+        async fn read_frame<T: tokio::io::AsyncRead + Unpin>(io: &mut T) -> (u8, u8) {
+            let mut head = [0; 9];
+            io.read_exact(&mut head).await.unwrap();
+            let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+            io.read_exact(&mut vec![0; len]).await.unwrap();
+            (head[3], head[4])
+        }
+
+        let (client, server) = duplex(1024);
+        let timer = MockTimer::new();
+
+        let conn = crate::server::conn::http2::Builder::new(TokioExecutor)
+            .timer(timer.clone())
+            .keep_alive_interval(Duration::from_secs(10))
+            .keep_alive_timeout(Duration::from_secs(5))
+            .serve_connection(
+                server,
+                service_fn(|_: Request<Incoming>| async {
+                    Ok::<_, crate::Error>(Response::new(String::new()))
+                }),
+            );
+        let conn = tokio::spawn(conn);
+
+        let mut client = TokioIo::new(client);
+        client
+            .write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+            .await
+            .unwrap();
+        // an empty SETTINGS frame
+        client
+            .write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+
+        // the server's SETTINGS and the ACK of ours
+        let mut acked = false;
+        while !acked {
+            let (kind, flags) = read_frame(&mut client).await;
+            acked = kind == 0x4 && flags & 0x1 != 0;
+        }
+
+        // a request halfway through the interval pushes the ping back
+        timer.advance(Duration::from_secs(5));
+        // HEADERS for `GET /`, with END_STREAM and END_HEADERS
+        client
+            .write_all(&[0, 0, 3, 1, 0x5, 0, 0, 0, 1, 0x82, 0x86, 0x84])
+            .await
+            .unwrap();
+        let mut responded = false;
+        while !responded {
+            let (kind, flags) = read_frame(&mut client).await;
+            responded = kind == 0x1 && flags & 0x1 != 0;
         }
+
+        timer.advance(Duration::from_secs(5));
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        while let Some((kind, _)) = read_frame(&mut client).now_or_never() {
+            assert_ne!(kind, PING, "ping sent before the keep-alive interval");
+        }
+
+        timer.advance(Duration::from_secs(5));
+        let (kind, flags) = read_frame(&mut client).await;
+        assert_eq!((kind, flags), (PING, 0));
+
+        // the ping is never acknowledged
+        timer.advance(Duration::from_secs(4));
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert!(!conn.is_finished());
+
+        timer.advance(Duration::from_secs(1));
+        let err = conn.await.unwrap().unwrap_err();
+        assert!(err.is_timeout(), "{:?}", err);
     }
 }
-*/
@@ -0,0 +1,350 @@
+//! Observing connections.
+//!
+//! An [`Observer`] set on a connection builder is called with an [`Event`]
+//! at each step of the connections made with it: opening and closing,
+//! request and response heads, the first byte and the end of message
+//! bodies, keep-alive reuse, upgrades, HTTP/2 `GOAWAY` frames, and errors.
+//! Every event records when it happened, and the end of a body records how
+//! many bytes it had, so metrics can be collected without parsing logs.
+//!
+//! ## Example
+//!
+//! ```
+//! # #[cfg(all(feature = "server", feature = "http1"))]
+//! # fn doc() {
+//! use hyper::observe::{Event, EventKind};
+//! use hyper::server::conn::http1;
+//!
+//! let mut http = http1::Builder::new();
+//! http.observer(|event: &Event<'_>| {
+//!     if event.kind() == EventKind::RequestBodyEnd {
+//!         println!(
+//!             "connection {} received a body of {} bytes",
+//!             event.connection_id(),
+//!             event.bytes().unwrap_or(0),
+//!         );
+//!     }
+//! });
+//! # }
+//! # fn main() {}
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use http::{Method, StatusCode, Uri, Version};
+
+/// An observer of connection events.
+///
+/// Observers are called from the task driving the connection, so they
+/// should return quickly, handing any slow work off elsewhere.
+///
+/// This trait is implemented for closures taking an `&Event`.
+pub trait Observer: Send + Sync {
+    /// Called with each event of an observed connection.
+    fn on_event(&self, event: &Event<'_>);
+}
+
+impl<F> Observer for F
+where
+    F: Fn(&Event<'_>) + Send + Sync,
+{
+    fn on_event(&self, event: &Event<'_>) {
+        self(event)
+    }
+}
+
+/// What happened in an [`Event`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EventKind {
+    /// The connection was opened.
+    ConnectionOpen,
+    /// The connection was closed.
+    ///
+    /// This is the last event of a connection.
+    ConnectionClose,
+    /// A request head was received by a server, or sent by a client.
+    RequestHead,
+    /// The first bytes of a request body were received or sent.
+    RequestBodyFirstByte,
+    /// A request body was completely received or sent.
+    RequestBodyEnd,
+    /// A response head was sent by a server, or received by a client.
+    ResponseHead,
+    /// The first bytes of a response body were received or sent.
+    ResponseBodyFirstByte,
+    /// A response body was completely received or sent.
+    ResponseBodyEnd,
+    /// An HTTP/1 connection finished an exchange, and is kept alive to be
+    /// reused for the next one.
+    KeepAlive,
+    /// The connection, or an HTTP/2 stream, was upgraded to another
+    /// protocol.
+    Upgrade,
+    /// An HTTP/2 `GOAWAY` frame was sent, or received by a client.
+    GoAway,
+    /// The connection failed with an error.
+    Error,
+}
+
+/// An event of an observed connection.
+#[derive(Debug)]
+pub struct Event<'a> {
+    kind: EventKind,
+    connection_id: usize,
+    version: Version,
+    at: Instant,
+    stream_id: Option<u32>,
+    method: Option<&'a Method>,
+    uri: Option<&'a Uri>,
+    status: Option<StatusCode>,
+    bytes: Option<u64>,
+    error: Option<&'a crate::Error>,
+}
+
+impl<'a> Event<'a> {
+    /// Returns what happened.
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// Returns the id of the connection this event happened on.
+    ///
+    /// Ids are unique among the connections of a process.
+    pub fn connection_id(&self) -> usize {
+        self.connection_id
+    }
+
+    /// Returns the protocol of the connection, `HTTP/1.1` or `HTTP/2`.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns when this event happened.
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+
+    /// Returns the HTTP/2 stream this event happened on.
+    pub fn stream_id(&self) -> Option<u32> {
+        self.stream_id
+    }
+
+    /// Returns the method of a [`RequestHead`](EventKind::RequestHead).
+    pub fn method(&self) -> Option<&'a Method> {
+        self.method
+    }
+
+    /// Returns the URI of a [`RequestHead`](EventKind::RequestHead).
+    pub fn uri(&self) -> Option<&'a Uri> {
+        self.uri
+    }
+
+    /// Returns the status of a [`ResponseHead`](EventKind::ResponseHead).
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status
+    }
+
+    /// Returns the length in bytes of a body that ended.
+    pub fn bytes(&self) -> Option<u64> {
+        self.bytes
+    }
+
+    /// Returns the error of an [`Error`](EventKind::Error) event.
+    pub fn error(&self) -> Option<&'a crate::Error> {
+        self.error
+    }
+}
+
+// ===== internal =====
+
+static NEXT_CONNECTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A user-provided observer, as configured on a builder.
+#[derive(Clone, Default)]
+pub(crate) struct Observe(Option<Arc<dyn Observer>>);
+
+/// The observer of one connection.
+///
+/// Clones report on the same connection. Without an observer, reporting
+/// does nothing.
+#[derive(Clone, Default)]
+pub(crate) struct ConnObserver {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    observer: Arc<dyn Observer>,
+    id: usize,
+    version: Version,
+    closed: AtomicBool,
+}
+
+/// Reports the progress of one message body.
+#[derive(Default)]
+pub(crate) struct BodyObserver {
+    conn: ConnObserver,
+    stream_id: Option<u32>,
+    request: bool,
+    bytes: u64,
+    ended: bool,
+}
+
+impl Observe {
+    pub(crate) fn new<O: Observer + 'static>(observer: O) -> Observe {
+        Observe(Some(Arc::new(observer)))
+    }
+
+    /// Reports a new connection, returning its observer.
+    pub(crate) fn open(&self, version: Version) -> ConnObserver {
+        let conn = ConnObserver {
+            inner: self.0.as_ref().map(|observer| {
+                Arc::new(Inner {
+                    observer: observer.clone(),
+                    id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+                    version,
+                    closed: AtomicBool::new(false),
+                })
+            }),
+        };
+        conn.emit(EventKind::ConnectionOpen, None, |_| ());
+        conn
+    }
+}
+
+impl fmt::Debug for Observe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observe").finish()
+    }
+}
+
+impl ConnObserver {
+    fn emit<'a>(&self, kind: EventKind, stream_id: Option<u32>, f: impl FnOnce(&mut Event<'a>)) {
+        if let Some(ref inner) = self.inner {
+            inner.emit(kind, stream_id, f);
+        }
+    }
+
+    #[cfg(all(feature = "client", feature = "http2"))]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    pub(crate) fn request_head(&self, stream_id: Option<u32>, method: &Method, uri: &Uri) {
+        self.emit(EventKind::RequestHead, stream_id, |event| {
+            event.method = Some(method);
+            event.uri = Some(uri);
+        });
+    }
+
+    pub(crate) fn response_head(&self, stream_id: Option<u32>, status: StatusCode) {
+        self.emit(EventKind::ResponseHead, stream_id, |event| {
+            event.status = Some(status);
+        });
+    }
+
+    /// Starts reporting a message body.
+    pub(crate) fn body(&self, stream_id: Option<u32>, request: bool) -> BodyObserver {
+        BodyObserver {
+            conn: self.clone(),
+            stream_id,
+            request,
+            bytes: 0,
+            ended: self.inner.is_none(),
+        }
+    }
+
+    #[cfg(feature = "http1")]
+    pub(crate) fn keep_alive(&self) {
+        self.emit(EventKind::KeepAlive, None, |_| ());
+    }
+
+    pub(crate) fn upgrade(&self, stream_id: Option<u32>) {
+        self.emit(EventKind::Upgrade, stream_id, |_| ());
+    }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn go_away(&self) {
+        self.emit(EventKind::GoAway, None, |_| ());
+    }
+
+    pub(crate) fn error(&self, err: &crate::Error) {
+        self.emit(EventKind::Error, None, |event| event.error = Some(err));
+    }
+
+    /// Reports the connection closed, if it wasn't already.
+    pub(crate) fn close(&self) {
+        if let Some(ref inner) = self.inner {
+            inner.close();
+        }
+    }
+}
+
+impl Inner {
+    fn emit<'a>(&self, kind: EventKind, stream_id: Option<u32>, f: impl FnOnce(&mut Event<'a>)) {
+        let mut event = Event {
+            kind,
+            connection_id: self.id,
+            version: self.version,
+            at: Instant::now(),
+            stream_id,
+            method: None,
+            uri: None,
+            status: None,
+            bytes: None,
+            error: None,
+        };
+        f(&mut event);
+        self.observer.on_event(&event);
+    }
+
+    fn close(&self) {
+        if !self.closed.swap(true, Ordering::AcqRel) {
+            self.emit(EventKind::ConnectionClose, None, |_| ());
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // The connection was dropped before it finished.
+        self.close();
+    }
+}
+
+impl BodyObserver {
+    /// Records data of the body, reporting its first byte.
+    pub(crate) fn data(&mut self, len: usize) {
+        if self.ended || len == 0 {
+            return;
+        }
+        if self.bytes == 0 {
+            let kind = if self.request {
+                EventKind::RequestBodyFirstByte
+            } else {
+                EventKind::ResponseBodyFirstByte
+            };
+            self.conn.emit(kind, self.stream_id, |_| ());
+        }
+        self.bytes += len as u64;
+    }
+
+    /// Reports the end of the body, if it wasn't already.
+    pub(crate) fn end(&mut self) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        let kind = if self.request {
+            EventKind::RequestBodyEnd
+        } else {
+            EventKind::ResponseBodyEnd
+        };
+        let bytes = self.bytes;
+        self.conn
+            .emit(kind, self.stream_id, |event| event.bytes = Some(bytes));
+    }
+}
@@ -1,31 +1,38 @@
 use std::fmt;
 use std::io;
 use std::marker::PhantomData;
-#[cfg(feature = "server")]
 use std::time::Duration;
 
 use bytes::{Buf, Bytes};
+#[cfg(feature = "server")]
+use http::header::TE;
 use http::header::{HeaderValue, CONNECTION};
 use http::{HeaderMap, Method, Version};
+use http_body::Frame;
 use httparse::ParserConfig;
-use tokio::io::{AsyncRead, AsyncWrite};
 use tracing::{debug, error, trace};
 
 use super::io::Buffered;
-use super::{Decoder, Encode, EncodedBuf, Encoder, Http1Transaction, ParseContext, Wants};
+#[cfg(feature = "server")]
+use super::ExpectContinue;
+use super::{
+    Decoder, Encode, EncodedBuf, Encoder, Http1Transaction, ParseContext, Subject, Wants,
+};
 use crate::body::DecodedLength;
 #[cfg(feature = "server")]
+use crate::common::rate::{DataRate, MinDataRate};
 use crate::common::time::Time;
 use crate::common::{task, Pin, Poll, Unpin};
+use crate::headers;
 use crate::headers::connection_keep_alive;
+use crate::observe::{BodyObserver, ConnObserver};
 use crate::proto::{BodyLength, MessageHead};
-#[cfg(feature = "server")]
-use crate::rt::Sleep;
+use crate::rt::{Read, Sleep, Write};
 
 const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
 
-/// This handles a connection, which will have been established over an
-/// `AsyncRead + AsyncWrite` (like a socket), and will likely include multiple
+/// This handles a connection, which will have been established over a
+/// `Read + Write` (like a socket), and will likely include multiple
 /// `Transaction`s over HTTP.
 ///
 /// The connection will determine when a message begins and ends as well as
@@ -39,7 +46,7 @@ pub(crate) struct Conn<I, B, T> {
 
 impl<I, B, T> Conn<I, B, T>
 where
-    I: AsyncRead + AsyncWrite + Unpin,
+    I: Read + Write + Unpin,
     B: Buf,
     T: Http1Transaction,
 {
@@ -53,6 +60,11 @@ where
                 keep_alive: KA::Busy,
                 method: None,
                 h1_parser_config: ParserConfig::default(),
+                h1_max_headers: None,
+                h1_max_body_size: None,
+                body_read: 0,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
                 #[cfg(feature = "server")]
                 h1_header_read_timeout: None,
                 #[cfg(feature = "server")]
@@ -60,7 +72,29 @@ where
                 #[cfg(feature = "server")]
                 h1_header_read_timeout_running: false,
                 #[cfg(feature = "server")]
+                h1_idle_timeout: None,
+                #[cfg(feature = "server")]
+                h1_idle_timeout_fut: None,
+                #[cfg(feature = "server")]
+                h1_idle_timeout_running: false,
+                #[cfg(feature = "server")]
+                h1_min_receive_rate: None,
+                #[cfg(feature = "server")]
+                h1_min_send_rate: None,
+                #[cfg(feature = "server")]
+                receive_rate: None,
+                #[cfg(feature = "server")]
+                send_rate: None,
+                #[cfg(feature = "server")]
+                h1_expect_continue: None,
+                #[cfg(feature = "client")]
+                h1_expect_continue_timeout: None,
+                #[cfg(feature = "client")]
+                h1_expect_continue_fut: None,
                 timer: Time::Empty,
+                observer: ConnObserver::default(),
+                read_body: BodyObserver::default(),
+                write_body: BodyObserver::default(),
                 preserve_header_case: false,
                 #[cfg(feature = "ffi")]
                 preserve_header_order: false,
@@ -80,11 +114,18 @@ where
         }
     }
 
-    #[cfg(feature = "server")]
     pub(crate) fn set_timer(&mut self, timer: Time) {
         self.state.timer = timer;
     }
 
+    pub(crate) fn set_observer(&mut self, observer: ConnObserver) {
+        self.state.observer = observer;
+    }
+
+    pub(crate) fn observer(&self) -> &ConnObserver {
+        &self.state.observer
+    }
+
     #[cfg(feature = "server")]
     pub(crate) fn set_flush_pipeline(&mut self, enabled: bool) {
         self.io.set_flush_pipeline(enabled);
@@ -112,6 +153,14 @@ where
         self.state.h1_parser_config = parser_config;
     }
 
+    pub(crate) fn set_http1_max_headers(&mut self, val: usize) {
+        self.state.h1_max_headers = Some(val);
+    }
+
+    pub(crate) fn set_http1_max_body_size(&mut self, max: u64) {
+        self.state.h1_max_body_size = Some(max);
+    }
+
     pub(crate) fn set_title_case_headers(&mut self) {
         self.state.title_case_headers = true;
     }
@@ -120,7 +169,7 @@ where
         self.state.preserve_header_case = true;
     }
 
-    #[cfg(feature = "ffi")]
+    #[cfg(all(feature = "client", feature = "ffi"))]
     pub(crate) fn set_preserve_header_order(&mut self) {
         self.state.preserve_header_order = true;
     }
@@ -135,6 +184,31 @@ where
         self.state.h1_header_read_timeout = Some(val);
     }
 
+    #[cfg(feature = "server")]
+    pub(crate) fn set_http1_idle_timeout(&mut self, val: Duration) {
+        self.state.h1_idle_timeout = Some(val);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_http1_min_receive_rate(&mut self, rate: MinDataRate) {
+        self.state.h1_min_receive_rate = Some(rate);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_http1_min_send_rate(&mut self, rate: MinDataRate) {
+        self.state.h1_min_send_rate = Some(rate);
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set_http1_expect_continue(&mut self, policy: ExpectContinue) {
+        self.state.h1_expect_continue = Some(policy);
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn set_http1_expect_continue_timeout(&mut self, val: Duration) {
+        self.state.h1_expect_continue_timeout = Some(val);
+    }
+
     #[cfg(feature = "server")]
     pub(crate) fn set_allow_half_close(&mut self) {
         self.state.allow_half_close = true;
@@ -192,12 +266,20 @@ where
         debug_assert!(self.can_read_head());
         trace!("Conn::read_head");
 
-        let msg = match ready!(self.io.parse::<T>(
+        #[cfg(feature = "server")]
+        if let Poll::Ready(err) = self.poll_idle_timeout(cx) {
+            return Poll::Ready(Some(Err(err)));
+        }
+
+        // Only servers rewrite the head, when rejecting an expectation.
+        #[cfg_attr(not(feature = "server"), allow(unused_mut))]
+        let mut msg = match ready!(self.io.parse::<T>(
             cx,
             ParseContext {
                 cached_headers: &mut self.state.cached_headers,
                 req_method: &mut self.state.method,
                 h1_parser_config: self.state.h1_parser_config.clone(),
+                h1_max_headers: self.state.h1_max_headers,
                 #[cfg(feature = "server")]
                 h1_header_read_timeout: self.state.h1_header_read_timeout,
                 #[cfg(feature = "server")]
@@ -212,6 +294,8 @@ where
                 h09_responses: self.state.h09_responses,
                 #[cfg(feature = "ffi")]
                 on_informational: &mut self.state.on_informational,
+                #[cfg(feature = "client")]
+                h1_expect_continue_fut: &mut self.state.h1_expect_continue_fut,
             }
         )) {
             Ok(msg) => msg,
@@ -223,6 +307,20 @@ where
 
         debug!("incoming body is {}", msg.decode);
 
+        if let Some(max) = self.state.h1_max_body_size {
+            if msg.decode.into_opt().map_or(false, |len| len > max) {
+                debug!("incoming body is larger than the maximum of {} bytes", max);
+                self.close_read();
+                return match self.on_parse_error(crate::Error::new_body_too_large()) {
+                    Ok(()) => Poll::Pending,
+                    Err(e) => Poll::Ready(Some(Err(e))),
+                };
+            }
+            self.state.body_read = 0;
+        }
+
+        msg.head.subject.observe(&self.state.observer);
+
         // Prevent accepting HTTP/0.9 responses after the initial one, if any.
         self.state.h09_responses = false;
 
@@ -232,10 +330,30 @@ where
             self.state.on_informational = None;
         }
 
+        // A final response came before `100 Continue`, so the server doesn't
+        // want the body.
+        #[cfg(feature = "client")]
+        if self.state.h1_expect_continue_fut.take().is_some() {
+            debug!("response received before 100-continue, not sending body");
+            self.state.close_write();
+        }
+
         self.state.busy();
         self.state.keep_alive &= msg.keep_alive;
         self.state.version = msg.head.version;
 
+        // Trailers in the response may only be sent if the client said it
+        // will accept them, with a `TE: trailers` header.
+        #[cfg(feature = "server")]
+        if T::is_server() {
+            self.state.allow_trailer_fields = msg
+                .head
+                .headers
+                .get_all(TE)
+                .iter()
+                .any(headers::te_trailers);
+        }
+
         let mut wants = if msg.wants_upgrade {
             Wants::UPGRADE
         } else {
@@ -251,15 +369,63 @@ where
                 self.try_keep_alive(cx);
             }
         } else if msg.expect_continue {
-            self.state.reading = Reading::Continue(Decoder::new(msg.decode));
+            #[cfg(feature = "server")]
+            if let Some(ref policy) = self.state.h1_expect_continue {
+                if let Some(res) = T::reject_expectation(&mut msg.head, policy) {
+                    // The body is never read, so the connection can't be
+                    // used again.
+                    self.state.close_read();
+                    self.write_head(res, Some(BodyLength::Known(0)));
+                    return Poll::Ready(None);
+                }
+            }
+            self.state.reading =
+                Reading::Continue(Decoder::new(msg.decode, self.state.h1_max_headers));
+            self.state.read_body = self.state.observer.body(None, T::is_server());
             wants = wants.add(Wants::EXPECT);
         } else {
-            self.state.reading = Reading::Body(Decoder::new(msg.decode));
+            self.state.reading = Reading::Body(Decoder::new(msg.decode, self.state.h1_max_headers));
+            self.state.read_body = self.state.observer.body(None, T::is_server());
         }
 
         Poll::Ready(Some(Ok((msg.head, msg.decode, wants))))
     }
 
+    // Waits for the idle timeout while a kept-alive connection has no bytes
+    // of a next request. The header read timeout takes over once they start
+    // arriving.
+    #[cfg(feature = "server")]
+    fn poll_idle_timeout(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Error> {
+        let timeout = match self.state.h1_idle_timeout {
+            Some(timeout) if self.state.is_idle() && self.io.read_buf().is_empty() => timeout,
+            _ => return Poll::Pending,
+        };
+
+        if !self.state.h1_idle_timeout_running {
+            let deadline = self.state.timer.now() + timeout;
+            self.state.h1_idle_timeout_running = true;
+            match self.state.h1_idle_timeout_fut {
+                Some(ref mut fut) => {
+                    trace!("resetting h1 idle timeout timer");
+                    self.state.timer.reset(fut, deadline);
+                }
+                None => {
+                    trace!("setting h1 idle timeout timer");
+                    self.state.h1_idle_timeout_fut = Some(self.state.timer.sleep_until(deadline));
+                }
+            }
+        }
+
+        if let Some(ref mut fut) = self.state.h1_idle_timeout_fut {
+            ready!(fut.as_mut().poll(cx));
+            debug!("idle connection timed out");
+            self.state.h1_idle_timeout_running = false;
+            self.state.close();
+            return Poll::Ready(crate::Error::new_idle_timeout());
+        }
+        Poll::Pending
+    }
+
     fn on_read_head_error<Z>(&mut self, e: crate::Error) -> Poll<Option<crate::Result<Z>>> {
         // If we are currently waiting on a message, then an empty
         // message should be reported as an error. If not, it is just
@@ -289,33 +455,113 @@ where
     pub(crate) fn poll_read_body(
         &mut self,
         cx: &mut task::Context<'_>,
-    ) -> Poll<Option<io::Result<Bytes>>> {
+    ) -> Poll<Option<io::Result<Frame<Bytes>>>> {
+        #[cfg(feature = "server")]
+        if let Some(min_rate) = self.state.h1_min_receive_rate {
+            return self.poll_read_body_min_rate(cx, min_rate);
+        }
+
+        self.poll_read_body_frame(cx)
+    }
+
+    // Reads the body while enforcing the minimum receive rate. Time only
+    // counts while the body is waiting on the client.
+    #[cfg(feature = "server")]
+    fn poll_read_body_min_rate(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        min_rate: MinDataRate,
+    ) -> Poll<Option<io::Result<Frame<Bytes>>>> {
+        let timer = &self.state.timer;
+        let rate = self
+            .state
+            .receive_rate
+            .get_or_insert_with(|| DataRate::new(min_rate, timer.clone()))
+            .clone();
+        rate.resume();
+
+        match self.poll_read_body_frame(cx) {
+            Poll::Ready(ret) => {
+                if let Some(Ok(ref frame)) = ret {
+                    if let Some(data) = frame.data_ref() {
+                        rate.record(data.len());
+                    }
+                }
+                rate.pause();
+                if !self.can_read_body() {
+                    self.state.receive_rate = None;
+                }
+                Poll::Ready(ret)
+            }
+            Poll::Pending => {
+                ready!(rate.poll_expired(cx));
+                debug!("request body data rate too low, closing");
+                self.state.close();
+                self.state.error = Some(crate::Error::new_min_data_rate());
+                Poll::Ready(Some(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "request body data rate too low",
+                ))))
+            }
+        }
+    }
+
+    fn poll_read_body_frame(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<io::Result<Frame<Bytes>>>> {
         debug_assert!(self.can_read_body());
 
         let (reading, ret) = match self.state.reading {
             Reading::Body(ref mut decoder) => {
                 match ready!(decoder.decode(cx, &mut self.io)) {
-                    Ok(slice) => {
-                        let (reading, chunk) = if decoder.is_eof() {
-                            debug!("incoming body completed");
-                            (
-                                Reading::KeepAlive,
-                                if !slice.is_empty() {
-                                    Some(Ok(slice))
-                                } else {
-                                    None
-                                },
-                            )
-                        } else if slice.is_empty() {
-                            error!("incoming body unexpectedly ended");
-                            // This should be unreachable, since all 3 decoders
-                            // either set eof=true or return an Err when reading
-                            // an empty slice...
-                            (Reading::Closed, None)
+                    Ok(frame) => {
+                        if let Some(data) = frame.data_ref() {
+                            self.state.read_body.data(data.len());
+                        }
+                        if let (Some(max), Some(data)) =
+                            (self.state.h1_max_body_size, frame.data_ref())
+                        {
+                            self.state.body_read += data.len() as u64;
+                            if self.state.body_read > max {
+                                debug!("incoming body is larger than the maximum of {} bytes", max);
+                                self.state.close_read();
+                                self.state.error = Some(crate::Error::new_body_too_large());
+                                return Poll::Ready(Some(Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    crate::Error::new_body_too_large(),
+                                ))));
+                            }
+                        }
+                        if frame.is_trailers() {
+                            debug!("incoming body completed with trailers");
+                            debug_assert!(decoder.is_eof());
+                            (Reading::KeepAlive, Poll::Ready(Some(Ok(frame))))
                         } else {
-                            return Poll::Ready(Some(Ok(slice)));
-                        };
-                        (reading, Poll::Ready(chunk))
+                            let slice = frame.into_data().unwrap_or_else(|_| {
+                                unreachable!("decoder only yields data or trailers")
+                            });
+                            let (reading, chunk) = if decoder.is_eof() {
+                                debug!("incoming body completed");
+                                (
+                                    Reading::KeepAlive,
+                                    if !slice.is_empty() {
+                                        Some(Ok(Frame::data(slice)))
+                                    } else {
+                                        None
+                                    },
+                                )
+                            } else if slice.is_empty() {
+                                error!("incoming body unexpectedly ended");
+                                // This should be unreachable, since all 3 decoders
+                                // either set eof=true or return an Err when reading
+                                // an empty slice...
+                                (Reading::Closed, None)
+                            } else {
+                                return Poll::Ready(Some(Ok(Frame::data(slice))));
+                            };
+                            (reading, Poll::Ready(chunk))
+                        }
                     }
                     Err(e) => {
                         debug!("incoming body decode error: {}", e);
@@ -333,11 +579,14 @@ where
 
                 // And now recurse once in the Reading::Body state...
                 self.state.reading = Reading::Body(decoder.clone());
-                return self.poll_read_body(cx);
+                return self.poll_read_body_frame(cx);
             }
             _ => unreachable!("poll_read_body invalid state: {:?}", self.state.reading),
         };
 
+        if let Reading::KeepAlive = reading {
+            self.state.read_body.end();
+        }
         self.state.reading = reading;
         self.try_keep_alive(cx);
         ret
@@ -513,8 +762,22 @@ where
     }
 
     pub(crate) fn write_head(&mut self, head: MessageHead<T::Outgoing>, body: Option<BodyLength>) {
+        #[cfg(feature = "client")]
+        let expect_continue = match self.state.h1_expect_continue_timeout {
+            Some(timeout) if T::is_client() && headers::expect_continue(&head.headers) => {
+                Some(timeout)
+            }
+            _ => None,
+        };
+
         if let Some(encoder) = self.encode_head(head, body) {
             self.state.writing = if !encoder.is_eof() {
+                self.state.write_body = self.state.observer.body(None, T::is_client());
+                #[cfg(feature = "client")]
+                if let Some(timeout) = expect_continue {
+                    trace!("withholding body until 100-continue or {:?}", timeout);
+                    self.state.h1_expect_continue_fut = Some(self.state.timer.sleep(timeout));
+                }
                 Writing::Body(encoder)
             } else if encoder.is_last() {
                 Writing::Closed
@@ -524,6 +787,36 @@ where
         }
     }
 
+    /// Waits until the body of a request expecting `100 Continue` may be
+    /// written.
+    #[cfg(feature = "client")]
+    pub(crate) fn poll_expect_continue(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        if let Some(ref mut sleep) = self.state.h1_expect_continue_fut {
+            ready!(sleep.as_mut().poll(cx));
+            debug!("no 100-continue received before timeout, sending body");
+            self.state.h1_expect_continue_fut = None;
+        }
+        Poll::Ready(())
+    }
+
+    pub(crate) fn write_informational(&mut self, head: MessageHead<T::Outgoing>) {
+        debug_assert!(self.can_write_head());
+
+        // HTTP/1.0 clients don't expect any 1xx responses
+        if let Version::HTTP_10 = self.state.version {
+            debug!("dropping informational response to HTTP/1.0 peer");
+            return;
+        }
+
+        let buf = self.io.headers_buf();
+        if T::encode_informational(head, buf, self.state.title_case_headers) {
+            // The service already told the client to continue.
+            if let Reading::Continue(ref decoder) = self.state.reading {
+                self.state.reading = Reading::Body(decoder.clone());
+            }
+        }
+    }
+
     fn encode_head(
         &mut self,
         mut head: MessageHead<T::Outgoing>,
@@ -544,12 +837,16 @@ where
                 body,
                 #[cfg(feature = "server")]
                 keep_alive: self.state.wants_keep_alive(),
+                #[cfg(feature = "server")]
+                allow_trailer_fields: self.state.allow_trailer_fields,
                 req_method: &mut self.state.method,
                 title_case_headers: self.state.title_case_headers,
             },
             buf,
         ) {
             Ok(encoder) => {
+                head.subject.observe(&self.state.observer);
+
                 debug_assert!(self.state.cached_headers.is_none());
                 debug_assert!(head.headers.is_empty());
                 self.state.cached_headers = Some(head.headers);
@@ -616,6 +913,7 @@ where
         // empty chunks should be discarded at Dispatcher level
         debug_assert!(chunk.remaining() != 0);
 
+        self.state.write_body.data(chunk.remaining());
         let state = match self.state.writing {
             Writing::Body(ref mut encoder) => {
                 self.io.buffer(encoder.encode(chunk));
@@ -633,6 +931,7 @@ where
             _ => unreachable!("write_body invalid state: {:?}", self.state.writing),
         };
 
+        self.state.write_body.end();
         self.state.writing = state;
     }
 
@@ -641,6 +940,7 @@ where
         // empty chunks should be discarded at Dispatcher level
         debug_assert!(chunk.remaining() != 0);
 
+        self.state.write_body.data(chunk.remaining());
         let state = match self.state.writing {
             Writing::Body(ref encoder) => {
                 let can_keep_alive = encoder.encode_and_end(chunk, self.io.write_buf());
@@ -653,9 +953,36 @@ where
             _ => unreachable!("write_body invalid state: {:?}", self.state.writing),
         };
 
+        self.state.write_body.end();
         self.state.writing = state;
     }
 
+    pub(crate) fn write_trailers(&mut self, trailers: HeaderMap) -> crate::Result<()> {
+        debug_assert!(self.can_write_body() && self.can_buffer_body());
+
+        let encoder = match self.state.writing {
+            Writing::Body(ref encoder) => encoder,
+            _ => unreachable!("write_trailers invalid state: {:?}", self.state.writing),
+        };
+
+        match encoder.encode_trailers(trailers, self.state.title_case_headers) {
+            Some(end) => {
+                self.io.buffer(end);
+                self.state.write_body.end();
+
+                self.state.writing = if encoder.is_last() || encoder.is_close_delimited() {
+                    Writing::Closed
+                } else {
+                    Writing::KeepAlive
+                };
+
+                Ok(())
+            }
+            // trailers weren't allowed, so just end the body normally
+            None => self.end_body(),
+        }
+    }
+
     pub(crate) fn end_body(&mut self) -> crate::Result<()> {
         debug_assert!(self.can_write_body());
 
@@ -670,6 +997,7 @@ where
                 if let Some(end) = end {
                     self.io.buffer(end);
                 }
+                self.state.write_body.end();
 
                 self.state.writing = if encoder.is_last() || encoder.is_close_delimited() {
                     Writing::Closed
@@ -711,12 +1039,65 @@ where
     }
 
     pub(crate) fn poll_flush(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        #[cfg(feature = "server")]
+        if let Some(min_rate) = self.state.h1_min_send_rate {
+            return self.poll_flush_min_rate(cx, min_rate);
+        }
+
         ready!(Pin::new(&mut self.io).poll_flush(cx))?;
         self.try_keep_alive(cx);
         trace!("flushed({}): {:?}", T::LOG, self.state);
         Poll::Ready(Ok(()))
     }
 
+    // Flushes while enforcing the minimum send rate. Time only counts while
+    // buffered bytes are waiting on the client to read them.
+    #[cfg(feature = "server")]
+    fn poll_flush_min_rate(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        min_rate: MinDataRate,
+    ) -> Poll<io::Result<()>> {
+        let flushed = self.io.bytes_flushed();
+        let res = Pin::new(&mut self.io).poll_flush(cx);
+        let pending_bytes = self.io.write_buf().remaining() > 0;
+
+        if let Some(ref rate) = self.state.send_rate {
+            rate.record((self.io.bytes_flushed() - flushed) as usize);
+        }
+
+        if res.is_pending() && pending_bytes {
+            let timer = &self.state.timer;
+            let rate = self
+                .state
+                .send_rate
+                .get_or_insert_with(|| DataRate::new(min_rate, timer.clone()));
+            rate.resume();
+            if rate.poll_expired(cx).is_ready() {
+                debug!("response data rate too low, closing");
+                self.state.close();
+                self.state.error = Some(crate::Error::new_min_data_rate());
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "response data rate too low",
+                )));
+            }
+            return Poll::Pending;
+        }
+
+        match self.state.send_rate {
+            // Waiting on the service for more of the body.
+            Some(ref rate) if matches!(self.state.writing, Writing::Body(..)) => rate.pause(),
+            Some(_) => self.state.send_rate = None,
+            None => (),
+        }
+
+        ready!(res)?;
+        self.try_keep_alive(cx);
+        trace!("flushed({}): {:?}", T::LOG, self.state);
+        Poll::Ready(Ok(()))
+    }
+
     pub(crate) fn poll_shutdown(&mut self, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
         match ready!(Pin::new(self.io.io_mut()).poll_shutdown(cx)) {
             Ok(()) => {
@@ -807,14 +1188,53 @@ struct State {
     /// a body or not.
     method: Option<Method>,
     h1_parser_config: ParserConfig,
+    h1_max_headers: Option<usize>,
+    /// The largest incoming body allowed, in bytes.
+    h1_max_body_size: Option<u64>,
+    /// How much of the current incoming body has been read.
+    body_read: u64,
+    /// If the current request allows the response to include trailer fields.
+    #[cfg(feature = "server")]
+    allow_trailer_fields: bool,
     #[cfg(feature = "server")]
     h1_header_read_timeout: Option<Duration>,
     #[cfg(feature = "server")]
     h1_header_read_timeout_fut: Option<Pin<Box<dyn Sleep>>>,
     #[cfg(feature = "server")]
     h1_header_read_timeout_running: bool,
+    /// How long a kept-alive connection may wait for the next request.
+    #[cfg(feature = "server")]
+    h1_idle_timeout: Option<Duration>,
+    #[cfg(feature = "server")]
+    h1_idle_timeout_fut: Option<Pin<Box<dyn Sleep>>>,
+    #[cfg(feature = "server")]
+    h1_idle_timeout_running: bool,
+    /// The minimum rate request bodies must be received at.
+    #[cfg(feature = "server")]
+    h1_min_receive_rate: Option<MinDataRate>,
+    /// The minimum rate responses must be sent at.
+    #[cfg(feature = "server")]
+    h1_min_send_rate: Option<MinDataRate>,
     #[cfg(feature = "server")]
+    receive_rate: Option<DataRate>,
+    #[cfg(feature = "server")]
+    send_rate: Option<DataRate>,
+    /// Decides whether to accept requests expecting `100 Continue`.
+    #[cfg(feature = "server")]
+    h1_expect_continue: Option<ExpectContinue>,
+    /// How long to withhold the body of a request expecting `100 Continue`.
+    #[cfg(feature = "client")]
+    h1_expect_continue_timeout: Option<Duration>,
+    /// Set while the body is withheld, until a `100 Continue` is received
+    /// or the timeout elapses.
+    #[cfg(feature = "client")]
+    h1_expect_continue_fut: Option<Pin<Box<dyn Sleep>>>,
     timer: Time,
+    observer: ConnObserver,
+    /// Reports the progress of the current incoming body.
+    read_body: BodyObserver,
+    /// Reports the progress of the current outgoing body.
+    write_body: BodyObserver,
     preserve_header_case: bool,
     #[cfg(feature = "ffi")]
     preserve_header_order: bool,
@@ -982,6 +1402,10 @@ impl State {
     }
 
     fn busy(&mut self) {
+        #[cfg(feature = "server")]
+        {
+            self.h1_idle_timeout_running = false;
+        }
         if let KA::Disabled = self.keep_alive.status() {
             return;
         }
@@ -1001,6 +1425,7 @@ impl State {
 
         self.reading = Reading::Init;
         self.writing = Writing::Init;
+        self.observer.keep_alive();
 
         // !T::should_read_first() means Client.
         //
@@ -1010,6 +1435,13 @@ impl State {
         if !T::should_read_first() {
             self.notify_read = true;
         }
+
+        // A server with an idle timeout should poll reading again too, so
+        // the timer starts right away.
+        #[cfg(feature = "server")]
+        if self.h1_idle_timeout.is_some() {
+            self.notify_read = true;
+        }
     }
 
     fn is_idle(&self) -> bool {
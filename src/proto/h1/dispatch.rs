@@ -2,13 +2,13 @@ use std::error::Error as StdError;
 
 use bytes::{Buf, Bytes};
 use http::Request;
-use tokio::io::{AsyncRead, AsyncWrite};
-use tracing::{debug, trace};
+use tracing::{debug, error, trace};
 
 use super::{Http1Transaction, Wants};
 use crate::body::{Body, DecodedLength, Incoming as IncomingBody};
 use crate::common::{task, Future, Pin, Poll, Unpin};
 use crate::proto::{BodyLength, Conn, Dispatched, MessageHead, RequestHead};
+use crate::rt::{Read, Write};
 use crate::upgrade::OnUpgrade;
 
 pub(crate) struct Dispatcher<D, Bs: Body, I, T> {
@@ -31,13 +31,22 @@ pub(crate) trait Dispatch {
     fn recv_msg(&mut self, msg: crate::Result<(Self::RecvItem, IncomingBody)>) -> crate::Result<()>;
     fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), ()>>;
     fn should_poll(&self) -> bool;
+    fn poll_informational(&mut self, _cx: &mut task::Context<'_>) -> Poll<Option<Self::PollItem>> {
+        Poll::Ready(None)
+    }
 }
 
 cfg_server! {
+    use std::sync::Arc;
+
+    use crate::ext::{InformationalQueue, InformationalSender};
     use crate::service::HttpService;
 
     pub(crate) struct Server<S: HttpService<B>, B> {
         in_flight: Pin<Box<Option<S::Future>>>,
+        // Created with the first request that can send informational
+        // responses.
+        informational: Option<Arc<InformationalQueue>>,
         pub(crate) service: S,
     }
 }
@@ -63,7 +72,7 @@ where
             RecvItem = MessageHead<T::Incoming>,
         > + Unpin,
     D::PollError: Into<Box<dyn StdError + Send + Sync>>,
-    I: AsyncRead + AsyncWrite + Unpin,
+    I: Read + Write + Unpin,
     T: Http1Transaction + Unpin,
     Bs: Body + 'static,
     Bs::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -92,7 +101,7 @@ where
     }
 
     /// Run this dispatcher until HTTP says this connection is done,
-    /// but don't call `AsyncWrite::shutdown` on the underlying IO.
+    /// but don't call `Write::poll_shutdown` on the underlying IO.
     ///
     /// This is useful for old-style HTTP upgrades, but ignores
     /// newer-style upgrade API.
@@ -115,14 +124,20 @@ where
         cx: &mut task::Context<'_>,
         should_shutdown: bool,
     ) -> Poll<crate::Result<Dispatched>> {
-        Poll::Ready(ready!(self.poll_inner(cx, should_shutdown)).or_else(|e| {
+        let ret = ready!(self.poll_inner(cx, should_shutdown)).or_else(|e| {
+            self.conn.observer().error(&e);
             // An error means we're shutting down either way.
             // We just try to give the error to the user,
             // and close the connection with an Ok. If we
             // cannot give it to the user, then return the Err.
             self.dispatch.recv_msg(Err(e))?;
             Ok(Dispatched::Shutdown)
-        }))
+        });
+        if let Ok(Dispatched::Upgrade(_)) = ret {
+            self.conn.observer().upgrade(None);
+        }
+        self.conn.observer().close();
+        Poll::Ready(ret)
     }
 
     fn poll_inner(
@@ -201,17 +216,39 @@ where
                         }
                     }
                     match self.conn.poll_read_body(cx) {
-                        Poll::Ready(Some(Ok(chunk))) => match body.try_send_data(chunk) {
-                            Ok(()) => {
-                                self.body_tx = Some(body);
-                            }
-                            Err(_canceled) => {
-                                if self.conn.can_read_body() {
-                                    trace!("body receiver dropped before eof, closing");
-                                    self.conn.close_read();
+                        Poll::Ready(Some(Ok(frame))) => {
+                            if frame.is_data() {
+                                let chunk = frame.into_data().unwrap_or_else(|_| unreachable!());
+                                match body.try_send_data(chunk) {
+                                    Ok(()) => {
+                                        self.body_tx = Some(body);
+                                    }
+                                    Err(_canceled) => {
+                                        if self.conn.can_read_body() {
+                                            trace!("body receiver dropped before eof, closing");
+                                            self.conn.close_read();
+                                        }
+                                    }
+                                }
+                            } else if frame.is_trailers() {
+                                let trailers =
+                                    frame.into_trailers().unwrap_or_else(|_| unreachable!());
+                                match body.try_send_trailers(trailers) {
+                                    Ok(()) => {
+                                        self.body_tx = Some(body);
+                                    }
+                                    Err(_canceled) => {
+                                        if self.conn.can_read_body() {
+                                            trace!("body receiver dropped before eof, closing");
+                                            self.conn.close_read();
+                                        }
+                                    }
                                 }
+                            } else {
+                                // we should have dropped all unknown frames in poll_read_body
+                                error!("unexpected frame");
                             }
-                        },
+                        }
                         Poll::Ready(None) => {
                             // just drop, the body will close automatically
                         }
@@ -296,7 +333,12 @@ where
                 && self.conn.can_write_head()
                 && self.dispatch.should_poll()
             {
-                if let Some(msg) = ready!(Pin::new(&mut self.dispatch).poll_msg(cx)) {
+                self.write_informational(cx);
+                let msg = Pin::new(&mut self.dispatch).poll_msg(cx);
+                // Informational responses sent while polling the service
+                // go out before anything else.
+                self.write_informational(cx);
+                if let Some(msg) = ready!(msg) {
                     let (head, body) = msg.map_err(crate::Error::new_user_service)?;
 
                     let body_type = if body.is_end_stream() {
@@ -324,6 +366,10 @@ where
                     OptGuard::new(self.body_rx.as_mut()).guard_mut()
                 {
                     debug_assert!(!*clear_body, "opt guard defaults to keeping body");
+                    #[cfg(feature = "client")]
+                    if self.conn.poll_expect_continue(cx).is_pending() {
+                        return Poll::Pending;
+                    }
                     if !self.conn.can_write_body() {
                         trace!(
                             "no more write body allowed, user body is_end_stream = {}",
@@ -339,6 +385,12 @@ where
                             *clear_body = true;
                             crate::Error::new_user_body(e)
                         })?;
+                        if frame.is_trailers() {
+                            *clear_body = true;
+                            let trailers = frame.into_trailers().unwrap_or_else(|_| unreachable!());
+                            self.conn.write_trailers(trailers)?;
+                            continue;
+                        }
                         let chunk = if let Ok(data) = frame.into_data() {
                             data
                         } else {
@@ -372,10 +424,21 @@ where
         }
     }
 
+    fn write_informational(&mut self, cx: &mut task::Context<'_>) {
+        while let Poll::Ready(Some(head)) = self.dispatch.poll_informational(cx) {
+            self.conn.write_informational(head);
+        }
+    }
+
     fn poll_flush(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
         self.conn.poll_flush(cx).map_err(|err| {
             debug!("error writing: {}", err);
-            crate::Error::new_body_write(err)
+            // Prefer the error the connection gave up with, such as the
+            // response being sent too slowly.
+            match self.conn.take_error() {
+                Err(e) => e,
+                Ok(()) => crate::Error::new_body_write(err),
+            }
         })
     }
 
@@ -411,7 +474,7 @@ where
             RecvItem = MessageHead<T::Incoming>,
         > + Unpin,
     D::PollError: Into<Box<dyn StdError + Send + Sync>>,
-    I: AsyncRead + AsyncWrite + Unpin,
+    I: Read + Write + Unpin,
     T: Http1Transaction + Unpin,
     Bs: Body + 'static,
     Bs::Error: Into<Box<dyn StdError + Send + Sync>>,
@@ -458,6 +521,7 @@ cfg_server! {
         pub(crate) fn new(service: S) -> Server<S, B> {
             Server {
                 in_flight: Box::pin(None),
+                informational: None,
                 service,
             }
         }
@@ -502,6 +566,9 @@ cfg_server! {
 
             // Since in_flight finished, remove it
             this.in_flight.set(None);
+            if let Some(ref queue) = this.informational {
+                queue.finish();
+            }
             ret
         }
 
@@ -513,6 +580,12 @@ cfg_server! {
             *req.headers_mut() = msg.headers;
             *req.version_mut() = msg.version;
             *req.extensions_mut() = msg.extensions;
+            let info = if msg.version == http::Version::HTTP_10 {
+                InformationalSender::unsupported()
+            } else {
+                self.informational.get_or_insert_with(Default::default).sender()
+            };
+            req.extensions_mut().insert(info);
             let fut = self.service.call(req);
             self.in_flight.set(Some(fut));
             Ok(())
@@ -529,6 +602,30 @@ cfg_server! {
         fn should_poll(&self) -> bool {
             self.in_flight.is_some()
         }
+
+        fn poll_informational(
+            &mut self,
+            cx: &mut task::Context<'_>,
+        ) -> Poll<Option<Self::PollItem>> {
+            let queue = match self.informational {
+                Some(ref queue) => queue,
+                None => return Poll::Ready(None),
+            };
+            match queue.poll_recv(cx) {
+                Poll::Ready(res) => {
+                    let (parts, ()) = res.into_parts();
+                    Poll::Ready(Some(MessageHead {
+                        version: parts.version,
+                        subject: parts.status,
+                        headers: parts.headers,
+                        extensions: parts.extensions,
+                    }))
+                }
+                // Once the final response is sent, no more are allowed.
+                Poll::Pending if self.in_flight.is_some() => Poll::Pending,
+                Poll::Pending => Poll::Ready(None),
+            }
+        }
     }
 }
 
@@ -650,6 +747,7 @@ cfg_client! {
 mod tests {
     use super::*;
     use crate::proto::h1::ClientTransaction;
+    use crate::rt::TokioIo;
     use std::time::Duration;
 
     #[test]
@@ -662,7 +760,7 @@ mod tests {
             // Block at 0 for now, but we will release this response before
             // the request is ready to write later...
             let (mut tx, rx) = crate::client::dispatch::channel();
-            let conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(io);
+            let conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(TokioIo::new(io));
             let mut dispatcher = Dispatcher::new(Client::new(rx), conn);
 
             // First poll is needed to allow tx to send...
@@ -699,7 +797,7 @@ mod tests {
             .build_with_handle();
 
         let (mut tx, rx) = crate::client::dispatch::channel();
-        let mut conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(io);
+        let mut conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(TokioIo::new(io));
         conn.set_write_strategy_queue();
 
         let dispatcher = Dispatcher::new(Client::new(rx), conn);
@@ -730,7 +828,7 @@ mod tests {
             .build();
 
         let (mut tx, rx) = crate::client::dispatch::channel();
-        let conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(io);
+        let conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(TokioIo::new(io));
         let mut dispatcher = tokio_test::task::spawn(Dispatcher::new(Client::new(rx), conn));
 
         // First poll is needed to allow tx to send...
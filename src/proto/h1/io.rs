@@ -7,12 +7,12 @@ use std::marker::Unpin;
 use std::mem::MaybeUninit;
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tracing::{debug, trace};
 
 use super::{Http1Transaction, ParseContext, ParsedMessage};
 use crate::common::buf::BufList;
 use crate::common::{task, Pin, Poll};
+use crate::rt::{Read, ReadBuf, Write};
 
 /// The initial buffer size allocated before trying to read from IO.
 pub(crate) const INIT_BUFFER_SIZE: usize = 8192;
@@ -39,6 +39,8 @@ pub(crate) struct Buffered<T, B> {
     read_buf: BytesMut,
     read_buf_strategy: ReadStrategy,
     write_buf: WriteBuf<B>,
+    #[cfg(feature = "server")]
+    bytes_flushed: u64,
 }
 
 impl<T, B> fmt::Debug for Buffered<T, B>
@@ -55,7 +57,7 @@ where
 
 impl<T, B> Buffered<T, B>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: Read + Write + Unpin,
     B: Buf,
 {
     pub(crate) fn new(io: T) -> Buffered<T, B> {
@@ -72,6 +74,8 @@ where
             read_buf: BytesMut::with_capacity(0),
             read_buf_strategy: ReadStrategy::default(),
             write_buf,
+            #[cfg(feature = "server")]
+            bytes_flushed: 0,
         }
     }
 
@@ -183,6 +187,7 @@ where
                     cached_headers: parse_ctx.cached_headers,
                     req_method: parse_ctx.req_method,
                     h1_parser_config: parse_ctx.h1_parser_config.clone(),
+                    h1_max_headers: parse_ctx.h1_max_headers,
                     #[cfg(feature = "server")]
                     h1_header_read_timeout: parse_ctx.h1_header_read_timeout,
                     #[cfg(feature = "server")]
@@ -197,6 +202,8 @@ where
                     h09_responses: parse_ctx.h09_responses,
                     #[cfg(feature = "ffi")]
                     on_informational: parse_ctx.on_informational,
+                    #[cfg(feature = "client")]
+                    h1_expect_continue_fut: parse_ctx.h1_expect_continue_fut,
                 },
             )? {
                 Some(msg) => {
@@ -251,7 +258,7 @@ where
         let dst = self.read_buf.chunk_mut();
         let dst = unsafe { &mut *(dst as *mut _ as *mut [MaybeUninit<u8>]) };
         let mut buf = ReadBuf::uninit(dst);
-        match Pin::new(&mut self.io).poll_read(cx, &mut buf) {
+        match Pin::new(&mut self.io).poll_read(cx, buf.unfilled()) {
             Poll::Ready(Ok(_)) => {
                 let n = buf.filled().len();
                 trace!("received {} bytes", n);
@@ -280,6 +287,12 @@ where
         &mut self.io
     }
 
+    /// The total number of bytes written to the IO so far.
+    #[cfg(feature = "server")]
+    pub(crate) fn bytes_flushed(&self) -> u64 {
+        self.bytes_flushed
+    }
+
     pub(crate) fn is_read_blocked(&self) -> bool {
         self.read_blocked
     }
@@ -306,6 +319,10 @@ where
                 // `poll_write_buf` comes back, the manual advance will need to leave!
                 self.write_buf.advance(n);
                 debug!("flushed {} bytes", n);
+                #[cfg(feature = "server")]
+                {
+                    self.bytes_flushed += n as u64;
+                }
                 if self.write_buf.remaining() == 0 {
                     break;
                 } else if n == 0 {
@@ -329,6 +346,10 @@ where
             let n = ready!(Pin::new(&mut self.io).poll_write(cx, self.write_buf.headers.chunk()))?;
             debug!("flushed {} bytes", n);
             self.write_buf.headers.advance(n);
+            #[cfg(feature = "server")]
+            {
+                self.bytes_flushed += n as u64;
+            }
             if self.write_buf.headers.remaining() == 0 {
                 self.write_buf.headers.reset();
                 break;
@@ -359,7 +380,7 @@ pub(crate) trait MemRead {
 
 impl<T, B> MemRead for Buffered<T, B>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: Read + Write + Unpin,
     B: Buf,
 {
     fn read_mem(&mut self, cx: &mut task::Context<'_>, len: usize) -> Poll<io::Result<Bytes>> {
@@ -667,6 +688,7 @@ mod tests {
     use super::*;
     use std::time::Duration;
 
+    use crate::rt::TokioIo;
     use tokio_test::io::Builder as Mock;
 
     // #[cfg(feature = "nightly")]
@@ -699,7 +721,7 @@ mod tests {
         // // so we are testing that the io_buf does not trigger a write
         // // when there is nothing to flush
         // let mock = Mock::new().build();
-        // let mut io_buf = Buffered::<_, Cursor<Vec<u8>>>::new(mock);
+        // let mut io_buf = Buffered::<_, Cursor<Vec<u8>>>::new(TokioIo::new(mock));
         // io_buf.flush().await.expect("should short-circuit flush");
     }
 
@@ -717,7 +739,7 @@ mod tests {
             .wait(Duration::from_secs(1))
             .build();
 
-        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(mock);
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(TokioIo::new(mock));
 
         // We expect a `parse` to be not ready, and so can't await it directly.
         // Rather, this `poll_fn` will wrap the `Poll` result.
@@ -726,6 +748,7 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut None,
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
                 h1_header_read_timeout: None,
                 h1_header_read_timeout_fut: &mut None,
                 h1_header_read_timeout_running: &mut false,
@@ -736,6 +759,7 @@ mod tests {
                 h09_responses: false,
                 #[cfg(feature = "ffi")]
                 on_informational: &mut None,
+                h1_expect_continue_fut: &mut None,
             };
             assert!(buffered
                 .parse::<ClientTransaction>(cx, parse_ctx)
@@ -862,7 +886,7 @@ mod tests {
     #[cfg(debug_assertions)] // needs to trigger a debug_assert
     fn write_buf_requires_non_empty_bufs() {
         let mock = Mock::new().build();
-        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(mock);
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(TokioIo::new(mock));
 
         buffered.buffer(Cursor::new(Vec::new()));
     }
@@ -874,7 +898,7 @@ mod tests {
         let _ = pretty_env_logger::try_init();
 
         let mock = AsyncIo::new_buf(vec![], 1024);
-        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(mock);
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(TokioIo::new(mock));
 
 
         buffered.headers_buf().extend(b"hello ");
@@ -897,7 +921,7 @@ mod tests {
 
         let mock = Mock::new().write(b"hello world, it's hyper!").build();
 
-        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(mock);
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(TokioIo::new(mock));
         buffered.write_buf.set_strategy(WriteStrategy::Flatten);
 
         buffered.headers_buf().extend(b"hello ");
@@ -956,7 +980,7 @@ mod tests {
             .write(b"hyper!")
             .build();
 
-        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(mock);
+        let mut buffered = Buffered::<_, Cursor<Vec<u8>>>::new(TokioIo::new(mock));
         buffered.write_buf.set_strategy(WriteStrategy::Queue);
 
         // we have 4 buffers, and vec IO disabled, but explicitly said
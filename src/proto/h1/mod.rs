@@ -1,15 +1,20 @@
+#[cfg(any(feature = "client", feature = "server"))]
+use std::pin::Pin;
 #[cfg(feature = "server")]
-use std::{pin::Pin, time::Duration};
+use std::{fmt, sync::Arc, time::Duration};
 
 use bytes::BytesMut;
-use http::{HeaderMap, Method};
+use http::{HeaderMap, Method, StatusCode};
+#[cfg(feature = "server")]
+use http::Request;
 use httparse::ParserConfig;
 
 use crate::body::DecodedLength;
 #[cfg(feature = "server")]
 use crate::common::time::Time;
-use crate::proto::{BodyLength, MessageHead};
-#[cfg(feature = "server")]
+use crate::observe::ConnObserver;
+use crate::proto::{BodyLength, MessageHead, RequestLine};
+#[cfg(any(feature = "client", feature = "server"))]
 use crate::rt::Sleep;
 
 pub(crate) use self::conn::Conn;
@@ -35,14 +40,34 @@ cfg_server! {
 }
 
 pub(crate) trait Http1Transaction {
-    type Incoming;
-    type Outgoing: Default;
+    type Incoming: Subject;
+    type Outgoing: Default + Subject;
     const LOG: &'static str;
     fn parse(bytes: &mut BytesMut, ctx: ParseContext<'_>) -> ParseResult<Self::Incoming>;
     fn encode(enc: Encode<'_, Self::Outgoing>, dst: &mut Vec<u8>) -> crate::Result<Encoder>;
 
     fn on_error(err: &crate::Error) -> Option<MessageHead<Self::Outgoing>>;
 
+    /// Encodes an informational (1xx) response, returning whether it was a
+    /// `100 Continue`.
+    fn encode_informational(
+        _head: MessageHead<Self::Outgoing>,
+        _dst: &mut Vec<u8>,
+        _title_case_headers: bool,
+    ) -> bool {
+        unreachable!("only servers send informational responses")
+    }
+
+    /// Asks the policy whether to accept the `Expect: 100-continue` of an
+    /// incoming message, returning the response to reject it with.
+    #[cfg(feature = "server")]
+    fn reject_expectation(
+        _head: &mut MessageHead<Self::Incoming>,
+        _policy: &ExpectContinue,
+    ) -> Option<MessageHead<Self::Outgoing>> {
+        None
+    }
+
     fn is_client() -> bool {
         !Self::is_server()
     }
@@ -62,6 +87,23 @@ pub(crate) trait Http1Transaction {
     fn update_date() {}
 }
 
+/// The subject of a message head, reported to observers.
+pub(crate) trait Subject {
+    fn observe(&self, observer: &ConnObserver);
+}
+
+impl Subject for RequestLine {
+    fn observe(&self, observer: &ConnObserver) {
+        observer.request_head(None, &self.0, &self.1);
+    }
+}
+
+impl Subject for StatusCode {
+    fn observe(&self, observer: &ConnObserver) {
+        observer.response_head(None, *self);
+    }
+}
+
 /// Result newtype for Http1Transaction::parse.
 pub(crate) type ParseResult<T> = Result<Option<ParsedMessage<T>>, crate::error::Parse>;
 
@@ -78,6 +120,7 @@ pub(crate) struct ParseContext<'a> {
     cached_headers: &'a mut Option<HeaderMap>,
     req_method: &'a mut Option<Method>,
     h1_parser_config: ParserConfig,
+    h1_max_headers: Option<usize>,
     #[cfg(feature = "server")]
     h1_header_read_timeout: Option<Duration>,
     #[cfg(feature = "server")]
@@ -92,6 +135,8 @@ pub(crate) struct ParseContext<'a> {
     h09_responses: bool,
     #[cfg(feature = "ffi")]
     on_informational: &'a mut Option<crate::ffi::OnInformational>,
+    #[cfg(feature = "client")]
+    h1_expect_continue_fut: &'a mut Option<Pin<Box<dyn Sleep>>>,
 }
 
 /// Passed to Http1Transaction::encode
@@ -100,10 +145,56 @@ pub(crate) struct Encode<'a, T> {
     body: Option<BodyLength>,
     #[cfg(feature = "server")]
     keep_alive: bool,
+    #[cfg(feature = "server")]
+    allow_trailer_fields: bool,
     req_method: &'a mut Option<Method>,
     title_case_headers: bool,
 }
 
+#[cfg(feature = "server")]
+type ExpectContinueFn = dyn Fn(&Request<()>) -> Result<(), StatusCode> + Send + Sync;
+
+/// A server policy deciding whether to accept `Expect: 100-continue`.
+#[cfg(feature = "server")]
+#[derive(Clone)]
+pub(crate) struct ExpectContinue(Arc<ExpectContinueFn>);
+
+#[cfg(feature = "server")]
+impl ExpectContinue {
+    pub(crate) fn new<F>(policy: F) -> ExpectContinue
+    where
+        F: Fn(&Request<()>) -> Result<(), StatusCode> + Send + Sync + 'static,
+    {
+        ExpectContinue(Arc::new(policy))
+    }
+
+    /// Calls the policy with the request head, which is moved into a
+    /// `Request` and back to avoid copying it.
+    fn check(&self, head: &mut MessageHead<RequestLine>) -> Result<(), StatusCode> {
+        let mut req = Request::new(());
+        *req.method_mut() = std::mem::take(&mut head.subject.0);
+        *req.uri_mut() = std::mem::take(&mut head.subject.1);
+        *req.version_mut() = head.version;
+        *req.headers_mut() = std::mem::take(&mut head.headers);
+        *req.extensions_mut() = std::mem::take(&mut head.extensions);
+
+        let result = (self.0)(&req);
+
+        let (parts, ()) = req.into_parts();
+        head.subject = RequestLine(parts.method, parts.uri);
+        head.headers = parts.headers;
+        head.extensions = parts.extensions;
+        result
+    }
+}
+
+#[cfg(feature = "server")]
+impl fmt::Debug for ExpectContinue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpectContinue").finish()
+    }
+}
+
 /// Extra flags that a request "wants", like expect-continue or upgrades.
 #[derive(Clone, Copy, Debug)]
 struct Wants(u8);
@@ -1,7 +1,5 @@
 use std::fmt::{self, Write};
 use std::mem::MaybeUninit;
-#[cfg(feature = "server")]
-use std::time::Instant;
 
 use bytes::Bytes;
 use bytes::BytesMut;
@@ -19,12 +17,14 @@ use crate::ext::HeaderCaseMap;
 #[cfg(feature = "ffi")]
 use crate::ext::OriginalHeaderOrder;
 use crate::headers;
+#[cfg(feature = "server")]
+use crate::proto::h1::ExpectContinue;
 use crate::proto::h1::{
     Encode, Encoder, Http1Transaction, ParseContext, ParseResult, ParsedMessage,
 };
 use crate::proto::{BodyLength, MessageHead, RequestHead, RequestLine};
 
-const MAX_HEADERS: usize = 100;
+pub(crate) const DEFAULT_MAX_HEADERS: usize = 100;
 const AVERAGE_HEADER_SIZE: usize = 30; // totally scientific
 #[cfg(feature = "server")]
 const MAX_URI_LEN: usize = (u16::MAX - 1) as usize;
@@ -78,7 +78,7 @@ where
     #[cfg(feature = "server")]
     if !*ctx.h1_header_read_timeout_running {
         if let Some(h1_header_read_timeout) = ctx.h1_header_read_timeout {
-            let deadline = Instant::now() + h1_header_read_timeout;
+            let deadline = ctx.timer.now() + h1_header_read_timeout;
             *ctx.h1_header_read_timeout_running = true;
             match ctx.h1_header_read_timeout_fut {
                 Some(h1_header_read_timeout_fut) => {
@@ -132,22 +132,32 @@ impl Http1Transaction for Server {
         let len;
         let headers_len;
 
+        let max_headers = ctx.h1_max_headers.unwrap_or(DEFAULT_MAX_HEADERS);
+
         // Unsafe: both headers_indices and headers are using uninitialized memory,
         // but we *never* read any of it until after httparse has assigned
         // values into it. By not zeroing out the stack memory, this saves
         // a good ~5% on pipeline benchmarks.
-        let mut headers_indices: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
+        let mut stack_headers_indices: [MaybeUninit<HeaderIndices>; DEFAULT_MAX_HEADERS] = unsafe {
             // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
             MaybeUninit::uninit().assume_init()
         };
+        let mut heap_headers_indices = Vec::new();
+        let headers_indices = uninit_slots(
+            &mut stack_headers_indices,
+            &mut heap_headers_indices,
+            max_headers,
+        );
         {
             /* SAFETY: it is safe to go from MaybeUninit array to array of MaybeUninit */
-            let mut headers: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
+            let mut stack_headers: [MaybeUninit<httparse::Header<'_>>; DEFAULT_MAX_HEADERS] =
                 unsafe { MaybeUninit::uninit().assume_init() };
+            let mut heap_headers = Vec::new();
+            let headers = uninit_slots(&mut stack_headers, &mut heap_headers, max_headers);
             trace!(bytes = buf.len(), "Request.parse");
             let mut req = httparse::Request::new(&mut []);
             let bytes = buf.as_ref();
-            match req.parse_with_uninit_headers(bytes, &mut headers) {
+            match req.parse_with_uninit_headers(bytes, headers) {
                 Ok(httparse::Status::Complete(parsed_len)) => {
                     trace!("Request.parse Complete({})", parsed_len);
                     len = parsed_len;
@@ -169,7 +179,7 @@ impl Http1Transaction for Server {
                         Version::HTTP_10
                     };
 
-                    record_header_indices(bytes, &req.headers, &mut headers_indices)?;
+                    record_header_indices(bytes, req.headers, headers_indices)?;
                     headers_len = req.headers.len();
                 }
                 Ok(httparse::Status::Partial) => return Ok(None),
@@ -350,7 +360,8 @@ impl Http1Transaction for Server {
         // hyper currently doesn't support returning 1xx status codes as a Response
         // This is because Service only allows returning a single Response, and
         // so if you try to reply with a e.g. 100 Continue, you have no way of
-        // replying with the latter status code response.
+        // replying with the latter status code response. Those are sent
+        // with `ext::InformationalSender` instead.
         let (ret, is_last) = if msg.head.subject == StatusCode::SWITCHING_PROTOCOLS {
             (Ok(()), true)
         } else if msg.req_method == &Some(Method::CONNECT) && msg.head.subject.is_success() {
@@ -448,6 +459,7 @@ impl Http1Transaction for Server {
             | Kind::Parse(Parse::Version) => StatusCode::BAD_REQUEST,
             Kind::Parse(Parse::TooLarge) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
             Kind::Parse(Parse::UriTooLong) => StatusCode::URI_TOO_LONG,
+            Kind::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
             _ => return None,
         };
 
@@ -457,6 +469,62 @@ impl Http1Transaction for Server {
         Some(msg)
     }
 
+    fn reject_expectation(
+        head: &mut MessageHead<Self::Incoming>,
+        policy: &ExpectContinue,
+    ) -> Option<MessageHead<Self::Outgoing>> {
+        let status = match policy.check(head) {
+            Ok(()) => return None,
+            Err(status) if status.is_informational() => {
+                warn!("expect-continue policy rejected with {}, using 417", status);
+                StatusCode::EXPECTATION_FAILED
+            }
+            Err(status) => status,
+        };
+
+        debug!("rejecting expect-continue with {}", status);
+        let mut msg = MessageHead {
+            subject: status,
+            ..MessageHead::default()
+        };
+        // The body won't be read, so the connection is closed after this.
+        msg.headers
+            .insert(header::CONNECTION, HeaderValue::from_static("close"));
+        Some(msg)
+    }
+
+    fn encode_informational(
+        head: MessageHead<Self::Outgoing>,
+        dst: &mut Vec<u8>,
+        title_case_headers: bool,
+    ) -> bool {
+        debug_assert!(head.subject.is_informational());
+
+        extend(dst, b"HTTP/1.1 ");
+        extend(dst, head.subject.as_str().as_bytes());
+        extend(dst, b" ");
+        if let Some(reason) = head.extensions.get::<crate::ext::ReasonPhrase>() {
+            extend(dst, reason.as_bytes());
+        } else {
+            // `http` doesn't know the reason of 103 Early Hints.
+            let reason = match head.subject.as_u16() {
+                103 => Some("Early Hints"),
+                _ => head.subject.canonical_reason(),
+            };
+            extend(dst, reason.unwrap_or("<none>").as_bytes());
+        }
+        extend(dst, b"\r\n");
+
+        if title_case_headers {
+            write_headers_title_case(&head.headers, dst);
+        } else {
+            write_headers(&head.headers, dst);
+        }
+        extend(dst, b"\r\n");
+
+        head.subject == StatusCode::CONTINUE
+    }
+
     fn is_server() -> bool {
         true
     }
@@ -636,6 +704,7 @@ impl Server {
         let mut is_name_written = false;
         let mut must_write_chunked = false;
         let mut prev_con_len = None;
+        let mut allowed_trailer_fields: Option<Vec<HeaderValue>> = None;
 
         macro_rules! handle_is_name_written {
             () => {{
@@ -817,6 +886,12 @@ impl Server {
                 header::DATE => {
                     wrote_date = true;
                 }
+                // only send trailer fields if the client said it accepts them
+                header::TRAILER if msg.allow_trailer_fields => {
+                    allowed_trailer_fields
+                        .get_or_insert_with(Vec::new)
+                        .push(value.clone());
+                }
                 _ => (),
             }
             //TODO: this should perhaps instead combine them into
@@ -901,6 +976,10 @@ impl Server {
             extend(dst, b"\r\n");
         }
 
+        if let Some(allowed_trailer_fields) = allowed_trailer_fields {
+            encoder = encoder.into_chunked_with_trailing_fields(allowed_trailer_fields);
+        }
+
         Ok(encoder.set_last(is_last))
     }
 }
@@ -931,25 +1010,34 @@ impl Http1Transaction for Client {
     fn parse(buf: &mut BytesMut, ctx: ParseContext<'_>) -> ParseResult<StatusCode> {
         debug_assert!(!buf.is_empty(), "parse called with empty buf");
 
+        let max_headers = ctx.h1_max_headers.unwrap_or(DEFAULT_MAX_HEADERS);
+
         // Loop to skip information status code headers (100 Continue, etc).
         loop {
             // Unsafe: see comment in Server Http1Transaction, above.
-            let mut headers_indices: [MaybeUninit<HeaderIndices>; MAX_HEADERS] = unsafe {
+            let mut stack_headers_indices: [MaybeUninit<HeaderIndices>; DEFAULT_MAX_HEADERS] = unsafe {
                 // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
                 MaybeUninit::uninit().assume_init()
             };
+            let mut heap_headers_indices = Vec::new();
+            let headers_indices = uninit_slots(
+                &mut stack_headers_indices,
+                &mut heap_headers_indices,
+                max_headers,
+            );
             let (len, status, reason, version, headers_len) = {
                 // SAFETY: We can go safely from MaybeUninit array to array of MaybeUninit
-                let mut headers: [MaybeUninit<httparse::Header<'_>>; MAX_HEADERS] =
+                let mut stack_headers: [MaybeUninit<httparse::Header<'_>>; DEFAULT_MAX_HEADERS] =
                     unsafe { MaybeUninit::uninit().assume_init() };
+                let mut heap_headers = Vec::new();
+                let headers = uninit_slots(&mut stack_headers, &mut heap_headers, max_headers);
                 trace!(bytes = buf.len(), "Response.parse");
                 let mut res = httparse::Response::new(&mut []);
                 let bytes = buf.as_ref();
-                match ctx.h1_parser_config.parse_response_with_uninit_headers(
-                    &mut res,
-                    bytes,
-                    &mut headers,
-                ) {
+                match ctx
+                    .h1_parser_config
+                    .parse_response_with_uninit_headers(&mut res, bytes, headers)
+                {
                     Ok(httparse::Status::Complete(len)) => {
                         trace!("Response.parse Complete({})", len);
                         let status = StatusCode::from_u16(res.code.unwrap())?;
@@ -969,7 +1057,7 @@ impl Http1Transaction for Client {
                         } else {
                             Version::HTTP_10
                         };
-                        record_header_indices(bytes, &res.headers, &mut headers_indices)?;
+                        record_header_indices(bytes, res.headers, headers_indices)?;
                         let headers_len = res.headers.len();
                         (len, status, reason, version, headers_len)
                     }
@@ -1081,6 +1169,11 @@ impl Http1Transaction for Client {
                 }));
             }
 
+            if head.subject == StatusCode::CONTINUE {
+                // The server wants the body, stop withholding it.
+                *ctx.h1_expect_continue_fut = None;
+            }
+
             #[cfg(feature = "ffi")]
             if head.subject.is_informational() {
                 if let Some(callback) = ctx.on_informational {
@@ -1106,6 +1199,18 @@ impl Http1Transaction for Client {
         *msg.req_method = Some(msg.head.subject.0.clone());
 
         let body = Client::set_length(msg.head, msg.body);
+        let trailers: Vec<HeaderValue> = msg
+            .head
+            .headers
+            .get_all(header::TRAILER)
+            .iter()
+            .cloned()
+            .collect();
+        let body = if trailers.is_empty() {
+            body
+        } else {
+            body.into_chunked_with_trailing_fields(trailers)
+        };
 
         let init_cap = 30 + msg.head.headers.len() * AVERAGE_HEADER_SIZE;
         dst.reserve(init_cap);
@@ -1428,6 +1533,23 @@ fn set_content_length(headers: &mut HeaderMap, len: u64) -> Encoder {
     }
 }
 
+/// Picks the storage for `max` uninitialized header slots.
+///
+/// Limits that fit in the `stack` array borrow a prefix of it, so the default
+/// configuration never allocates. Larger limits fall back to filling `heap`.
+fn uninit_slots<'a, T: Copy>(
+    stack: &'a mut [MaybeUninit<T>; DEFAULT_MAX_HEADERS],
+    heap: &'a mut Vec<MaybeUninit<T>>,
+    max: usize,
+) -> &'a mut [MaybeUninit<T>] {
+    if max <= DEFAULT_MAX_HEADERS {
+        &mut stack[..max]
+    } else {
+        *heap = vec![MaybeUninit::uninit(); max];
+        &mut heap[..]
+    }
+}
+
 #[derive(Clone, Copy)]
 struct HeaderIndices {
     name: (usize, usize),
@@ -1481,7 +1603,7 @@ fn title_case(dst: &mut Vec<u8>, name: &[u8]) {
     }
 }
 
-fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
+pub(super) fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
     for (name, value) in headers {
         title_case(dst, name.as_str().as_bytes());
         extend(dst, b": ");
@@ -1490,7 +1612,7 @@ fn write_headers_title_case(headers: &HeaderMap, dst: &mut Vec<u8>) {
     }
 }
 
-fn write_headers(headers: &HeaderMap, dst: &mut Vec<u8>) {
+pub(super) fn write_headers(headers: &HeaderMap, dst: &mut Vec<u8>) {
     for (name, value) in headers {
         extend(dst, name.as_str().as_bytes());
         extend(dst, b": ");
@@ -1574,6 +1696,7 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut method,
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
                 h1_header_read_timeout: None,
                 h1_header_read_timeout_fut: &mut None,
                 h1_header_read_timeout_running: &mut false,
@@ -1584,6 +1707,7 @@ mod tests {
                 h09_responses: false,
                 #[cfg(feature = "ffi")]
                 on_informational: &mut None,
+                h1_expect_continue_fut: &mut None,
             },
         )
         .unwrap()
@@ -1605,6 +1729,7 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
@@ -1615,6 +1740,7 @@ mod tests {
             h09_responses: false,
             #[cfg(feature = "ffi")]
             on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw.len(), 0);
@@ -1631,6 +1757,7 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut None,
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
@@ -1641,10 +1768,90 @@ mod tests {
             h09_responses: false,
             #[cfg(feature = "ffi")]
             on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
         };
         Server::parse(&mut raw, ctx).unwrap_err();
     }
 
+    fn parse_with_max_headers<T: Http1Transaction>(
+        raw: &str,
+        req_method: Option<Method>,
+        max_headers: Option<usize>,
+    ) -> ParseResult<T::Incoming> {
+        let mut raw = BytesMut::from(raw);
+        let ctx = ParseContext {
+            cached_headers: &mut None,
+            req_method: &mut req_method.clone(),
+            h1_parser_config: Default::default(),
+            h1_max_headers: max_headers,
+            h1_header_read_timeout: None,
+            h1_header_read_timeout_fut: &mut None,
+            h1_header_read_timeout_running: &mut false,
+            timer: Time::Empty,
+            preserve_header_case: false,
+            #[cfg(feature = "ffi")]
+            preserve_header_order: false,
+            h09_responses: false,
+            #[cfg(feature = "ffi")]
+            on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
+        };
+        T::parse(&mut raw, ctx)
+    }
+
+    fn with_headers(start_line: &str, count: usize) -> String {
+        let mut raw = String::from(start_line);
+        for i in 0..count {
+            raw.push_str(&format!("x-header-{}: {}\r\n", i, i));
+        }
+        raw.push_str("\r\n");
+        raw
+    }
+
+    #[test]
+    fn test_parse_request_max_headers() {
+        let _ = pretty_env_logger::try_init();
+
+        let raw = with_headers("GET / HTTP/1.1\r\n", 100);
+        let msg = parse_with_max_headers::<Server>(&raw, None, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.head.headers.len(), 100);
+
+        let raw = with_headers("GET / HTTP/1.1\r\n", 101);
+        let err = parse_with_max_headers::<Server>(&raw, None, None).unwrap_err();
+        assert!(matches!(err, Parse::TooLarge));
+
+        let raw = with_headers("GET / HTTP/1.1\r\n", 5);
+        let err = parse_with_max_headers::<Server>(&raw, None, Some(4)).unwrap_err();
+        assert!(matches!(err, Parse::TooLarge));
+
+        let raw = with_headers("GET / HTTP/1.1\r\n", 500);
+        let msg = parse_with_max_headers::<Server>(&raw, None, Some(500))
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.head.headers.len(), 500);
+    }
+
+    #[test]
+    fn test_parse_response_max_headers() {
+        let _ = pretty_env_logger::try_init();
+
+        let raw = with_headers("HTTP/1.1 200 OK\r\n", 101);
+        let err = parse_with_max_headers::<Client>(&raw, Some(Method::GET), None).unwrap_err();
+        assert!(matches!(err, Parse::TooLarge));
+
+        let raw = with_headers("HTTP/1.1 200 OK\r\n", 5);
+        let err = parse_with_max_headers::<Client>(&raw, Some(Method::GET), Some(4)).unwrap_err();
+        assert!(matches!(err, Parse::TooLarge));
+
+        let raw = with_headers("HTTP/1.1 200 OK\r\n", 150);
+        let msg = parse_with_max_headers::<Client>(&raw, Some(Method::GET), Some(200))
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.head.headers.len(), 150);
+    }
+
     const H09_RESPONSE: &'static str = "Baguettes are super delicious, don't you agree?";
 
     #[test]
@@ -1655,6 +1862,7 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
@@ -1665,6 +1873,7 @@ mod tests {
             h09_responses: true,
             #[cfg(feature = "ffi")]
             on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw, H09_RESPONSE);
@@ -1681,6 +1890,7 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
@@ -1691,6 +1901,7 @@ mod tests {
             h09_responses: false,
             #[cfg(feature = "ffi")]
             on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
         };
         Client::parse(&mut raw, ctx).unwrap_err();
         assert_eq!(raw, H09_RESPONSE);
@@ -1711,6 +1922,7 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config,
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
@@ -1721,6 +1933,7 @@ mod tests {
             h09_responses: false,
             #[cfg(feature = "ffi")]
             on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw.len(), 0);
@@ -1738,6 +1951,7 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut Some(crate::Method::GET),
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
@@ -1748,6 +1962,7 @@ mod tests {
             h09_responses: false,
             #[cfg(feature = "ffi")]
             on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
         };
         Client::parse(&mut raw, ctx).unwrap_err();
     }
@@ -1760,6 +1975,7 @@ mod tests {
             cached_headers: &mut None,
             req_method: &mut None,
             h1_parser_config: Default::default(),
+            h1_max_headers: None,
             h1_header_read_timeout: None,
             h1_header_read_timeout_fut: &mut None,
             h1_header_read_timeout_running: &mut false,
@@ -1770,6 +1986,7 @@ mod tests {
             h09_responses: false,
             #[cfg(feature = "ffi")]
             on_informational: &mut None,
+            h1_expect_continue_fut: &mut None,
         };
         let parsed_message = Server::parse(&mut raw, ctx).unwrap().unwrap();
         let orig_headers = parsed_message
@@ -1803,6 +2020,7 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
@@ -1813,6 +2031,7 @@ mod tests {
                     h09_responses: false,
                     #[cfg(feature = "ffi")]
                     on_informational: &mut None,
+                    h1_expect_continue_fut: &mut None,
                 },
             )
             .expect("parse ok")
@@ -1827,6 +2046,7 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
@@ -1837,6 +2057,7 @@ mod tests {
                     h09_responses: false,
                     #[cfg(feature = "ffi")]
                     on_informational: &mut None,
+                    h1_expect_continue_fut: &mut None,
                 },
             )
             .expect_err(comment)
@@ -2060,6 +2281,7 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(Method::GET),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
@@ -2070,6 +2292,7 @@ mod tests {
                     h09_responses: false,
                     #[cfg(feature = "ffi")]
                     on_informational: &mut None,
+                    h1_expect_continue_fut: &mut None,
                 }
             )
             .expect("parse ok")
@@ -2084,6 +2307,7 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(m),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
@@ -2094,6 +2318,7 @@ mod tests {
                     h09_responses: false,
                     #[cfg(feature = "ffi")]
                     on_informational: &mut None,
+                    h1_expect_continue_fut: &mut None,
                 },
             )
             .expect("parse ok")
@@ -2108,6 +2333,7 @@ mod tests {
                     cached_headers: &mut None,
                     req_method: &mut Some(Method::GET),
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
@@ -2118,6 +2344,7 @@ mod tests {
                     h09_responses: false,
                     #[cfg(feature = "ffi")]
                     on_informational: &mut None,
+                    h1_expect_continue_fut: &mut None,
                 },
             )
             .expect_err("parse should err")
@@ -2421,6 +2648,8 @@ mod tests {
                 keep_alive: true,
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
             },
             &mut vec,
         )
@@ -2452,6 +2681,8 @@ mod tests {
                 keep_alive: true,
                 req_method: &mut None,
                 title_case_headers: false,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
             },
             &mut vec,
         )
@@ -2486,6 +2717,8 @@ mod tests {
                 keep_alive: true,
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
             },
             &mut vec,
         )
@@ -2510,6 +2743,8 @@ mod tests {
                 keep_alive: true,
                 req_method: &mut Some(Method::CONNECT),
                 title_case_headers: false,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
             },
             &mut vec,
         )
@@ -2518,6 +2753,28 @@ mod tests {
         assert!(encoder.is_last());
     }
 
+    #[test]
+    fn test_server_encode_informational() {
+        use http::header::HeaderValue;
+
+        let mut head = MessageHead::default();
+        head.subject = StatusCode::from_u16(103).unwrap();
+        head.headers
+            .insert("link", HeaderValue::from_static("</style.css>; rel=preload"));
+
+        let mut vec = Vec::new();
+        let is_continue = Server::encode_informational(head, &mut vec, true);
+        assert!(!is_continue);
+        let expected = "HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n";
+        assert_eq!(&vec, &expected.as_bytes());
+
+        let mut head = MessageHead::default();
+        head.subject = StatusCode::CONTINUE;
+        let mut vec = Vec::new();
+        assert!(Server::encode_informational(head, &mut vec, false));
+        assert_eq!(&vec, b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+
     #[test]
     fn test_server_response_encode_title_case() {
         use crate::proto::BodyLength;
@@ -2539,6 +2796,8 @@ mod tests {
                 keep_alive: true,
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
             },
             &mut vec,
         )
@@ -2573,6 +2832,8 @@ mod tests {
                 keep_alive: true,
                 req_method: &mut None,
                 title_case_headers: false,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
             },
             &mut vec,
         )
@@ -2607,6 +2868,8 @@ mod tests {
                 keep_alive: true,
                 req_method: &mut None,
                 title_case_headers: true,
+                #[cfg(feature = "server")]
+                allow_trailer_fields: false,
             },
             &mut vec,
         )
@@ -2627,6 +2890,7 @@ mod tests {
                 cached_headers: &mut None,
                 req_method: &mut Some(Method::GET),
                 h1_parser_config: Default::default(),
+                h1_max_headers: None,
                 h1_header_read_timeout: None,
                 h1_header_read_timeout_fut: &mut None,
                 h1_header_read_timeout_running: &mut false,
@@ -2637,6 +2901,7 @@ mod tests {
                 h09_responses: false,
                 #[cfg(feature = "ffi")]
                 on_informational: &mut None,
+                h1_expect_continue_fut: &mut None,
             },
         )
         .expect("parse ok")
@@ -2715,6 +2980,7 @@ mod tests {
                     cached_headers: &mut headers,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
@@ -2725,6 +2991,7 @@ mod tests {
                     h09_responses: false,
                     #[cfg(feature = "ffi")]
                     on_informational: &mut None,
+                    h1_expect_continue_fut: &mut None,
                 },
             )
             .unwrap()
@@ -2759,6 +3026,7 @@ mod tests {
                     cached_headers: &mut headers,
                     req_method: &mut None,
                     h1_parser_config: Default::default(),
+                    h1_max_headers: None,
                     h1_header_read_timeout: None,
                     h1_header_read_timeout_fut: &mut None,
                     h1_header_read_timeout_running: &mut false,
@@ -2769,6 +3037,7 @@ mod tests {
                     h09_responses: false,
                     #[cfg(feature = "ffi")]
                     on_informational: &mut None,
+                    h1_expect_continue_fut: &mut None,
                 },
             )
             .unwrap()
@@ -2811,6 +3080,8 @@ mod tests {
                     keep_alive: true,
                     req_method: &mut Some(Method::GET),
                     title_case_headers: false,
+                    #[cfg(feature = "server")]
+                    allow_trailer_fields: false,
                 },
                 &mut vec,
             )
@@ -2839,6 +3110,8 @@ mod tests {
                     keep_alive: true,
                     req_method: &mut Some(Method::GET),
                     title_case_headers: false,
+                    #[cfg(feature = "server")]
+                    allow_trailer_fields: false,
                 },
                 &mut vec,
             )
@@ -7,8 +7,7 @@ use futures_util::future::{self, Either, FutureExt as _, TryFutureExt as _};
 use futures_util::stream::StreamExt as _;
 use h2::client::{Builder, SendRequest};
 use h2::SendStream;
-use http::{Method, StatusCode};
-use tokio::io::{AsyncRead, AsyncWrite};
+use http::{Method, StatusCode, Version};
 use tracing::{debug, trace, warn};
 
 use super::{ping, H2Upgraded, PipeToSendStream, SendBuf};
@@ -18,8 +17,10 @@ use crate::client::dispatch::Callback;
 use crate::common::{exec::Exec, task, Future, Never, Pin, Poll};
 use crate::ext::Protocol;
 use crate::headers;
+use crate::observe::{ConnObserver, Observe};
 use crate::proto::h2::UpgradedSendStream;
 use crate::proto::Dispatched;
+use crate::rt::{Read, TokioIo, Write};
 use crate::upgrade::Upgraded;
 use crate::{Request, Response};
 use h2::client::ResponseFuture;
@@ -53,6 +54,8 @@ pub(crate) struct Config {
     pub(crate) keep_alive_while_idle: bool,
     pub(crate) max_concurrent_reset_streams: Option<usize>,
     pub(crate) max_send_buffer_size: usize,
+    pub(crate) max_response_body_size: Option<u64>,
+    pub(crate) observer: Observe,
 }
 
 impl Default for Config {
@@ -67,6 +70,8 @@ impl Default for Config {
             keep_alive_while_idle: false,
             max_concurrent_reset_streams: None,
             max_send_buffer_size: DEFAULT_MAX_SEND_BUF_SIZE,
+            max_response_body_size: None,
+            observer: Observe::default(),
         }
     }
 }
@@ -106,12 +111,12 @@ pub(crate) async fn handshake<T, B>(
     timer: Time,
 ) -> crate::Result<ClientTask<B>>
 where
-    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    T: Read + Write + Send + Unpin + 'static,
     B: Body,
     B::Data: Send + 'static,
 {
     let (h2_tx, mut conn) = new_builder(config)
-        .handshake::<_, SendBuf<B::Data>>(io)
+        .handshake::<_, SendBuf<B::Data>>(TokioIo::new(io))
         .await
         .map_err(crate::Error::new_h2)?;
 
@@ -165,6 +170,8 @@ where
         h2_tx,
         req_rx,
         fut_ctx: None,
+        max_body_size: config.max_response_body_size,
+        observer: config.observer.open(Version::HTTP_2),
     })
 }
 
@@ -213,6 +220,8 @@ where
     h2_tx: SendRequest<SendBuf<B::Data>>,
     req_rx: ClientRx<B>,
     fut_ctx: Option<FutCtx<B>>,
+    max_body_size: Option<u64>,
+    observer: ConnObserver,
 }
 
 impl<B> ClientTask<B>
@@ -232,9 +241,12 @@ where
 {
     fn poll_pipe(&mut self, f: FutCtx<B>, cx: &mut task::Context<'_>) {
         let ping = self.ping.clone();
+        let stream_id = u32::from(f.fut.stream_id());
         let send_stream = if !f.is_connect {
             if !f.eos {
-                let mut pipe = Box::pin(PipeToSendStream::new(f.body, f.body_tx)).map(|res| {
+                let pipe = PipeToSendStream::new(f.body, f.body_tx)
+                    .with_observer(self.observer.body(Some(stream_id), true));
+                let mut pipe = Box::pin(pipe).map(|res| {
                     if let Err(e) = res {
                         debug!("client request body error: {}", e);
                     }
@@ -266,10 +278,13 @@ where
             Some(f.body_tx)
         };
 
+        let max_body_size = self.max_body_size;
+        let observer = self.observer.clone();
         let fut = f.fut.map(move |result| match result {
             Ok(res) => {
                 // record that we got the response headers
                 ping.record_non_data();
+                observer.response_head(Some(stream_id), res.status());
 
                 let content_length = headers::content_length_parse_all(res.headers());
                 if let (Some(mut send_stream), StatusCode::OK) = (send_stream, res.status()) {
@@ -296,12 +311,32 @@ where
 
                     pending.fulfill(upgraded);
                     res.extensions_mut().insert(on_upgrade);
+                    observer.upgrade(Some(stream_id));
 
                     Ok(res)
                 } else {
+                    if let (Some(max), Some(len)) = (max_body_size, content_length) {
+                        if len > max {
+                            // dropping the response resets the stream
+                            debug!("response body is larger than the maximum of {} bytes", max);
+                            return Err((crate::Error::new_body_too_large(), None));
+                        }
+                    }
                     let res = res.map(|stream| {
                         let ping = ping.for_stream(&stream);
-                        IncomingBody::h2(stream, content_length.into(), ping)
+                        let body_observer = if stream.is_end_stream() {
+                            None
+                        } else {
+                            Some(observer.body(Some(stream_id), false))
+                        };
+                        let mut body = IncomingBody::h2(stream, content_length.into(), ping);
+                        if let Some(max) = max_body_size {
+                            body = body.with_max_size(max);
+                        }
+                        if let Some(body_observer) = body_observer {
+                            body = body.with_observer(body_observer);
+                        }
+                        body
                     });
                     Ok(res)
                 }
@@ -326,6 +361,22 @@ where
     type Output = crate::Result<Dispatched>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let ret = ready!(self.poll_inner(cx));
+        if let Err(ref e) = ret {
+            self.observer.error(e);
+        }
+        self.observer.close();
+        Poll::Ready(ret)
+    }
+}
+
+impl<B> ClientTask<B>
+where
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn poll_inner(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<Dispatched>> {
         loop {
             match ready!(self.h2_tx.poll_ready(cx)) {
                 Ok(()) => (),
@@ -333,6 +384,7 @@ where
                     self.ping.ensure_not_timed_out()?;
                     return if err.reason() == Some(::h2::Reason::NO_ERROR) {
                         trace!("connection gracefully shutdown");
+                        self.observer.go_away();
                         Poll::Ready(Ok(Dispatched::Shutdown))
                     } else {
                         Poll::Ready(Err(crate::Error::new_h2(err)))
@@ -386,6 +438,11 @@ where
                         req.extensions_mut().insert(protocol.into_inner());
                     }
 
+                    let head = if self.observer.is_enabled() {
+                        Some((req.method().clone(), req.uri().clone()))
+                    } else {
+                        None
+                    };
                     let (fut, body_tx) = match self.h2_tx.send_request(req, !is_connect && eos) {
                         Ok(ok) => ok,
                         Err(err) => {
@@ -394,6 +451,10 @@ where
                             continue;
                         }
                     };
+                    if let Some((method, uri)) = head {
+                        let stream_id = u32::from(fut.stream_id());
+                        self.observer.request_head(Some(stream_id), &method, &uri);
+                    }
 
                     let f = FutCtx {
                         is_connect,
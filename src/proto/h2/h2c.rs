@@ -0,0 +1,598 @@
+//! Cleartext HTTP/2 ("h2c") reached through an HTTP/1.1 `Upgrade`.
+//!
+//! RFC 7540, Section 3.2: the client sends an HTTP/1.1 request with
+//! `Upgrade: h2c` and an `HTTP2-Settings` header. If the server agrees, it
+//! replies `101 Switching Protocols`, both sides switch to HTTP/2, and the
+//! response to the original request is sent on stream 1.
+//!
+//! The `h2` crate has no way to start a connection with stream 1 already
+//! open, so the server turns the original request into a HEADERS frame for
+//! stream 1, and feeds it to `h2` right after the client's SETTINGS frame.
+//! This only adds frames to what the client sent, the HPACK state of both
+//! sides is untouched since the header block uses no indexing.
+
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use http::header::{HeaderValue, CONNECTION, HOST, UPGRADE};
+use http::{HeaderMap, Method, Request, Response, StatusCode, Version};
+use pin_project_lite::pin_project;
+use tracing::{debug, trace};
+
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::{task, Future, Pin, Poll};
+use crate::rt::{Read, ReadBuf};
+use crate::service::HttpService;
+use crate::service::Service;
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_HEADER_LEN: usize = 9;
+
+const TYPE_HEADERS: u8 = 0x1;
+const TYPE_SETTINGS: u8 = 0x4;
+const TYPE_CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+
+/// The stream used for the response to the request that asked for the
+/// upgrade.
+const UPGRADE_STREAM_ID: u32 = 1;
+
+/// The smallest `SETTINGS_MAX_FRAME_SIZE` a peer may advertise, so frames of
+/// this size are always accepted.
+const MIN_MAX_FRAME_SIZE: usize = 16_384;
+
+fn put_frame_header(dst: &mut BytesMut, len: usize, kind: u8, flags: u8, stream_id: u32) {
+    debug_assert!(len < 1 << 24);
+    dst.put_uint(len as u64, 3);
+    dst.put_u8(kind);
+    dst.put_u8(flags);
+    dst.put_u32(stream_id);
+}
+
+fn is_token68(value: &[u8]) -> bool {
+    let data = match value.iter().position(|&b| b == b'=') {
+        Some(idx) if value[idx..].iter().all(|&b| b == b'=') => &value[..idx],
+        Some(_) => return false,
+        None => value,
+    };
+    !data.is_empty()
+        && data
+            .iter()
+            .all(|&b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Returns whether `req` asks to upgrade the connection to h2c, and the
+/// upgrade can be done.
+///
+/// Requests with a body are served over HTTP/1 instead, since the body
+/// would have to be read before switching protocols.
+pub(crate) fn is_upgrade_request(req: &Request<IncomingBody>) -> bool {
+    if req.version() != Version::HTTP_11 || !req.body().is_end_stream() {
+        return false;
+    }
+
+    let headers = req.headers();
+    let upgrade = headers
+        .get_all(UPGRADE)
+        .iter()
+        .flat_map(header_tokens)
+        .any(|token| token.eq_ignore_ascii_case("h2c"));
+    if !upgrade {
+        return false;
+    }
+
+    let connection = headers
+        .get_all(CONNECTION)
+        .iter()
+        .flat_map(header_tokens)
+        .collect::<Vec<_>>();
+    if !connection.iter().any(|t| t.eq_ignore_ascii_case("upgrade"))
+        || !connection
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case("http2-settings"))
+    {
+        return false;
+    }
+
+    // Exactly one `HTTP2-Settings` header must be sent.
+    let mut settings = headers.get_all("http2-settings").iter();
+    match (settings.next(), settings.next()) {
+        (Some(value), None) => is_valid_settings(value.as_bytes()),
+        _ => false,
+    }
+}
+
+fn header_tokens(value: &HeaderValue) -> impl Iterator<Item = &str> {
+    value
+        .to_str()
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Checks that the `HTTP2-Settings` header is base64url encoded, and
+/// decodes to a whole number of 6 byte settings.
+///
+/// The settings themselves aren't applied, the client repeats them in
+/// the SETTINGS frame it must send once on HTTP/2.
+fn is_valid_settings(value: &[u8]) -> bool {
+    if !is_token68(value) {
+        return false;
+    }
+    let chars = value.iter().filter(|&&b| b != b'=').count();
+    chars % 4 != 1 && (chars * 3 / 4) % 6 == 0
+}
+
+/// Encodes the request that asked for the upgrade as the HEADERS frame
+/// opening stream 1, followed by CONTINUATION frames if it doesn't fit.
+///
+/// The header block only uses literals that aren't added to the HPACK
+/// dynamic table, so the decoder state of the peer is left untouched.
+pub(crate) fn encode_request(req: &Request<()>) -> Bytes {
+    let mut block = BytesMut::new();
+
+    let authority = req
+        .uri()
+        .authority()
+        .map(|authority| authority.as_str().as_bytes())
+        .or_else(|| req.headers().get(HOST).map(HeaderValue::as_bytes));
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .filter(|path| !path.is_empty())
+        .unwrap_or("/");
+
+    encode_literal(&mut block, b":method", req.method().as_str().as_bytes());
+    encode_literal(&mut block, b":scheme", b"http");
+    if let Some(authority) = authority {
+        encode_literal(&mut block, b":authority", authority);
+    }
+    if req.method() != Method::CONNECT {
+        encode_literal(&mut block, b":path", path.as_bytes());
+    }
+
+    let mut headers = req.headers().clone();
+    strip_upgrade_headers(&mut headers);
+    for (name, value) in &headers {
+        encode_literal(&mut block, name.as_str().as_bytes(), value.as_bytes());
+    }
+
+    let mut dst = BytesMut::with_capacity(block.len() + FRAME_HEADER_LEN);
+    let mut kind = TYPE_HEADERS;
+    let mut flags = FLAG_END_STREAM;
+    loop {
+        let len = block.len().min(MIN_MAX_FRAME_SIZE);
+        let last = len == block.len();
+        if last {
+            flags |= FLAG_END_HEADERS;
+        }
+        put_frame_header(&mut dst, len, kind, flags, UPGRADE_STREAM_ID);
+        dst.extend_from_slice(&block.split_to(len));
+        if last {
+            return dst.freeze();
+        }
+        kind = TYPE_CONTINUATION;
+        flags = 0;
+    }
+}
+
+/// Removes the headers that only applied to the HTTP/1 connection.
+fn strip_upgrade_headers(headers: &mut HeaderMap) {
+    let named = headers
+        .get_all(CONNECTION)
+        .iter()
+        .flat_map(header_tokens)
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    for name in named {
+        headers.remove(name.as_str());
+    }
+    headers.remove(CONNECTION);
+    headers.remove(UPGRADE);
+    headers.remove("http2-settings");
+    headers.remove(HOST);
+    super::strip_connection_headers(headers, true);
+}
+
+// HPACK "Literal Header Field without Indexing -- New Name"
+fn encode_literal(dst: &mut BytesMut, name: &[u8], value: &[u8]) {
+    dst.put_u8(0);
+    encode_str(dst, name);
+    encode_str(dst, value);
+}
+
+fn encode_str(dst: &mut BytesMut, s: &[u8]) {
+    // no Huffman encoding, 7 bit prefix length
+    encode_int(dst, s.len(), 7, 0);
+    dst.extend_from_slice(s);
+}
+
+fn encode_int(dst: &mut BytesMut, mut value: usize, prefix_bits: u32, first: u8) {
+    let max = (1 << prefix_bits) - 1;
+    if value < max {
+        dst.put_u8(first | value as u8);
+        return;
+    }
+    dst.put_u8(first | max as u8);
+    value -= max;
+    while value >= 128 {
+        dst.put_u8((value % 128) as u8 | 0x80);
+        value /= 128;
+    }
+    dst.put_u8(value as u8);
+}
+
+/// Reads the client connection preface sent after the `101` response.
+///
+/// Resolves with the IO and all bytes read, with the stream 1 `headers`
+/// inserted right after the client's first SETTINGS frame. The client
+/// can't send anything on the new connection before that frame, so the
+/// synthetic stream is always the first one `h2` sees.
+pub(crate) struct ReadPreface<I> {
+    io: Option<I>,
+    buf: BytesMut,
+    headers: Bytes,
+}
+
+impl<I> ReadPreface<I> {
+    pub(crate) fn new(io: I, read_buf: Bytes, headers: Bytes) -> Self {
+        ReadPreface {
+            io: Some(io),
+            buf: BytesMut::from(&read_buf[..]),
+            headers,
+        }
+    }
+
+    // Returns the length of the preface and SETTINGS frame, if they have
+    // been fully read.
+    fn preface_len(&self) -> io::Result<Option<usize>> {
+        let buf = &self.buf[..];
+        let len = buf.len().min(PREFACE.len());
+        if buf[..len] != PREFACE[..len] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid HTTP/2 connection preface after h2c upgrade",
+            ));
+        }
+        let head_end = PREFACE.len() + FRAME_HEADER_LEN;
+        if buf.len() < head_end {
+            return Ok(None);
+        }
+        let mut head = &buf[PREFACE.len()..head_end];
+        let len = head.get_uint(3) as usize;
+        if head.get_u8() != TYPE_SETTINGS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "HTTP/2 connection preface must start with SETTINGS",
+            ));
+        }
+        if buf.len() < head_end + len {
+            return Ok(None);
+        }
+        Ok(Some(head_end + len))
+    }
+}
+
+impl<I> Future for ReadPreface<I>
+where
+    I: Read + Unpin,
+{
+    type Output = io::Result<(I, Bytes)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        let end = loop {
+            if let Some(end) = this.preface_len()? {
+                break end;
+            }
+
+            let mut chunk = [0; 4096];
+            let mut buf = ReadBuf::new(&mut chunk);
+            let io = this.io.as_mut().expect("polled after complete");
+            ready!(Pin::new(io).poll_read(cx, buf.unfilled()))?;
+            if buf.filled().is_empty() {
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            this.buf.extend_from_slice(buf.filled());
+        };
+
+        trace!("h2c connection preface received");
+        let rest = this.buf.split_off(end);
+        this.buf.extend_from_slice(&this.headers);
+        this.buf.extend_from_slice(&rest);
+        let io = this.io.take().expect("polled after complete");
+        Poll::Ready(Ok((io, this.buf.split().freeze())))
+    }
+}
+
+/// Wraps the service of an HTTP/1 connection, answering h2c upgrade
+/// requests with `101 Switching Protocols` instead of calling the
+/// service.
+///
+/// The request is kept, to be served on stream 1 once the connection is
+/// on HTTP/2.
+pub(crate) struct UpgradeService<S> {
+    inner: S,
+    enabled: bool,
+    upgrade: Option<Request<()>>,
+}
+
+impl<S> UpgradeService<S> {
+    pub(crate) fn new(inner: S, enabled: bool) -> Self {
+        UpgradeService {
+            inner,
+            enabled,
+            upgrade: None,
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (S, Option<Request<()>>) {
+        (self.inner, self.upgrade)
+    }
+}
+
+impl<S, B> Service<Request<IncomingBody>> for UpgradeService<S>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    B: Body,
+{
+    type Response = Response<UpgradeBody<B>>;
+    type Error = S::Error;
+    type Future = UpgradeFuture<S::Future, B>;
+
+    fn call(&mut self, req: Request<IncomingBody>) -> Self::Future {
+        if self.enabled && self.upgrade.is_none() && is_upgrade_request(&req) {
+            debug!("upgrading connection to h2c");
+            let (parts, _) = req.into_parts();
+            self.upgrade = Some(Request::from_parts(parts, ()));
+
+            let mut res = Response::new(UpgradeBody { inner: None });
+            *res.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+            res.headers_mut()
+                .insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+            res.headers_mut()
+                .insert(UPGRADE, HeaderValue::from_static("h2c"));
+            return UpgradeFuture {
+                inner: None,
+                switching: Some(res),
+            };
+        }
+
+        UpgradeFuture {
+            inner: Some(self.inner.call(req)),
+            switching: None,
+        }
+    }
+}
+
+pin_project! {
+    #[allow(missing_debug_implementations)]
+    pub(crate) struct UpgradeFuture<F, B> {
+        #[pin]
+        inner: Option<F>,
+        switching: Option<Response<UpgradeBody<B>>>,
+    }
+}
+
+impl<F, B, E> Future for UpgradeFuture<F, B>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<UpgradeBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let me = self.project();
+        match me.inner.as_pin_mut() {
+            Some(fut) => fut
+                .poll(cx)
+                .map_ok(|res| res.map(|body| UpgradeBody { inner: Some(body) })),
+            None => Poll::Ready(Ok(me.switching.take().expect("polled after complete"))),
+        }
+    }
+}
+
+pin_project! {
+    /// The body of a response from the wrapped service, or the empty
+    /// body of a `101` response.
+    #[allow(missing_debug_implementations)]
+    pub(crate) struct UpgradeBody<B> {
+        #[pin]
+        inner: Option<B>,
+    }
+}
+
+impl<B: Body> Body for UpgradeBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<crate::body::Frame<Self::Data>, Self::Error>>> {
+        match self.project().inner.as_pin_mut() {
+            Some(body) => body.poll_frame(cx),
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.as_ref().map_or(true, Body::is_end_stream)
+    }
+
+    fn size_hint(&self) -> crate::body::SizeHint {
+        self.inner
+            .as_ref()
+            .map_or_else(|| crate::body::SizeHint::with_exact(0), Body::size_hint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rt::TokioIo;
+
+    #[test]
+    fn token68() {
+        assert!(is_token68(b"AAMAAABkAARAAAAAAAIAAAAA"));
+        assert!(is_token68(b"AAMAAABk-_=="));
+        assert!(!is_token68(b""));
+        assert!(!is_token68(b"=="));
+        assert!(!is_token68(b"AA=A"));
+        assert!(!is_token68(b"AA+/"));
+    }
+
+    fn upgrade_request() -> http::request::Builder {
+        Request::builder()
+            .uri("/upgrade?q=1")
+            .header("host", "example.local")
+            .header("connection", "Upgrade, HTTP2-Settings")
+            .header("upgrade", "h2c")
+            .header("http2-settings", "AAMAAABkAARAAAAAAAIAAAAA")
+    }
+
+    #[test]
+    fn detects_upgrade_request() {
+        let req = upgrade_request()
+            .body(crate::body::Incoming::empty())
+            .unwrap();
+        assert!(is_upgrade_request(&req));
+
+        let req = upgrade_request()
+            .header("http2-settings", "AAMAAABkAARAAAAAAAIAAAAA")
+            .body(crate::body::Incoming::empty())
+            .unwrap();
+        assert!(!is_upgrade_request(&req), "duplicate HTTP2-Settings");
+
+        let req = upgrade_request()
+            .header("connection", "keep-alive")
+            .body(crate::body::Incoming::empty())
+            .unwrap();
+        assert!(is_upgrade_request(&req), "Connection tokens across headers");
+
+        let mut req = upgrade_request()
+            .body(crate::body::Incoming::empty())
+            .unwrap();
+        req.headers_mut()
+            .insert("connection", http::HeaderValue::from_static("Upgrade"));
+        assert!(
+            !is_upgrade_request(&req),
+            "HTTP2-Settings not in Connection"
+        );
+
+        let mut req = upgrade_request()
+            .body(crate::body::Incoming::empty())
+            .unwrap();
+        req.headers_mut().insert(
+            "http2-settings",
+            http::HeaderValue::from_static("AAMAAABkAA"),
+        );
+        assert!(!is_upgrade_request(&req), "partial setting");
+    }
+
+    #[test]
+    fn encode_int_prefix() {
+        let mut dst = BytesMut::new();
+        encode_int(&mut dst, 10, 5, 0);
+        assert_eq!(dst, &[10][..]);
+
+        // RFC 7541, C.1.2
+        let mut dst = BytesMut::new();
+        encode_int(&mut dst, 1337, 5, 0);
+        assert_eq!(dst, &[31, 154, 10][..]);
+
+        let mut dst = BytesMut::new();
+        encode_int(&mut dst, 127, 7, 0x80);
+        assert_eq!(dst, &[0xff, 0][..]);
+    }
+
+    #[test]
+    fn encode_request_headers_frame() {
+        let req = upgrade_request().header("accept", "*/*").body(()).unwrap();
+        let frame = encode_request(&req);
+
+        let mut block = BytesMut::new();
+        encode_literal(&mut block, b":method", b"GET");
+        encode_literal(&mut block, b":scheme", b"http");
+        encode_literal(&mut block, b":authority", b"example.local");
+        encode_literal(&mut block, b":path", b"/upgrade?q=1");
+        encode_literal(&mut block, b"accept", b"*/*");
+
+        let mut expected = BytesMut::new();
+        put_frame_header(
+            &mut expected,
+            block.len(),
+            TYPE_HEADERS,
+            FLAG_END_STREAM | FLAG_END_HEADERS,
+            1,
+        );
+        expected.extend_from_slice(&block);
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn encode_request_continuation() {
+        let req = upgrade_request()
+            .header("x-big", "a".repeat(MIN_MAX_FRAME_SIZE))
+            .body(())
+            .unwrap();
+        let mut frame = encode_request(&req);
+
+        let len = frame.get_uint(3) as usize;
+        assert_eq!(len, MIN_MAX_FRAME_SIZE);
+        assert_eq!(frame.get_u8(), TYPE_HEADERS);
+        assert_eq!(frame.get_u8(), FLAG_END_STREAM);
+        assert_eq!(frame.get_u32(), 1);
+        frame.advance(len);
+
+        let len = frame.get_uint(3) as usize;
+        assert_eq!(frame.get_u8(), TYPE_CONTINUATION);
+        assert_eq!(frame.get_u8(), FLAG_END_HEADERS);
+        assert_eq!(frame.get_u32(), 1);
+        assert_eq!(frame.len(), len);
+    }
+
+    #[tokio::test]
+    async fn read_preface_inserts_headers() {
+        let mut settings = BytesMut::new();
+        put_frame_header(&mut settings, 6, TYPE_SETTINGS, 0, 0);
+        settings.extend_from_slice(&[0, 4, 0, 0, 0xff, 0xff]);
+
+        let mut rest = settings[4..].to_vec();
+        rest.extend_from_slice(b"more");
+
+        let io = tokio_test::io::Builder::new()
+            .read(&PREFACE[10..])
+            .read(&settings[..4])
+            .read(&rest)
+            .build();
+        let read_buf = Bytes::from_static(&PREFACE[..10]);
+        let headers = Bytes::from_static(b"HEADERS");
+        let (_io, buf) = ReadPreface::new(TokioIo::new(io), read_buf, headers)
+            .await
+            .unwrap();
+
+        let mut expected = PREFACE.to_vec();
+        expected.extend_from_slice(&settings);
+        expected.extend_from_slice(b"HEADERS");
+        // bytes read after the SETTINGS frame follow the headers
+        expected.extend_from_slice(b"more");
+        assert_eq!(buf, expected);
+    }
+
+    #[tokio::test]
+    async fn read_preface_rejects_garbage() {
+        let io = tokio_test::io::Builder::new()
+            .read(b"GET / HTTP/1.1\r\n")
+            .build();
+        let err = ReadPreface::new(TokioIo::new(io), Bytes::new(), Bytes::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -7,13 +7,17 @@ use std::error::Error as StdError;
 use std::io::{self, Cursor, IoSlice};
 use std::mem;
 use std::task::Context;
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tracing::{debug, trace, warn};
 
 use crate::body::Body;
+use crate::common::rate::DataRate;
 use crate::common::{task, Future, Pin, Poll};
+use crate::observe::BodyObserver;
 use crate::proto::h2::ping::Recorder;
+use crate::rt::{Read, ReadBufCursor, Write};
 
+#[cfg(all(feature = "http1", feature = "server"))]
+pub(crate) mod h2c;
 pub(crate) mod ping;
 
 cfg_client! {
@@ -85,34 +89,70 @@ fn strip_connection_headers(headers: &mut HeaderMap, is_request: bool) {
 // body adapters used by both Client and Server
 
 pin_project! {
-    struct PipeToSendStream<S>
+    struct PipeToSendStream<S, D>
     where
         S: Body,
     {
-        body_tx: SendStream<SendBuf<S::Data>>,
+        body_tx: SendStream<SendBuf<D>>,
         data_done: bool,
+        into_buf: fn(S::Data) -> SendBuf<D>,
+        data_rate: Option<DataRate>,
+        observer: BodyObserver,
         #[pin]
         stream: S,
     }
 }
 
-impl<S> PipeToSendStream<S>
+impl<S> PipeToSendStream<S, S::Data>
 where
     S: Body,
 {
-    fn new(stream: S, tx: SendStream<SendBuf<S::Data>>) -> PipeToSendStream<S> {
+    fn new(stream: S, tx: SendStream<SendBuf<S::Data>>) -> PipeToSendStream<S, S::Data> {
+        PipeToSendStream::with_buf(stream, tx, SendBuf::Buf)
+    }
+}
+
+impl<S, D> PipeToSendStream<S, D>
+where
+    S: Body,
+{
+    /// Pipes a body whose chunks must be converted to the buffer type of
+    /// the stream.
+    fn with_buf(
+        stream: S,
+        tx: SendStream<SendBuf<D>>,
+        into_buf: fn(S::Data) -> SendBuf<D>,
+    ) -> PipeToSendStream<S, D> {
         PipeToSendStream {
             body_tx: tx,
             data_done: false,
+            into_buf,
+            data_rate: None,
+            observer: BodyObserver::default(),
             stream,
         }
     }
+
+    /// Report the progress of the body to an observer.
+    fn with_observer(mut self, observer: BodyObserver) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Enforce a minimum send rate, counting the time spent waiting on the
+    /// peer for stream capacity.
+    #[cfg(feature = "server")]
+    fn with_data_rate(mut self, rate: Option<DataRate>) -> Self {
+        self.data_rate = rate;
+        self
+    }
 }
 
-impl<S> Future for PipeToSendStream<S>
+impl<S, D> Future for PipeToSendStream<S, D>
 where
     S: Body,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    D: Buf,
 {
     type Output = crate::Result<()>;
 
@@ -125,6 +165,14 @@ where
             me.body_tx.reserve_capacity(1);
 
             if me.body_tx.capacity() == 0 {
+                if let Some(ref rate) = me.data_rate {
+                    rate.resume();
+                    if rate.poll_expired(cx).is_ready() {
+                        debug!("response data rate too low, resetting stream");
+                        me.body_tx.send_reset(Reason::CANCEL);
+                        return Poll::Ready(Err(crate::Error::new_min_data_rate()));
+                    }
+                }
                 loop {
                     match ready!(me.body_tx.poll_capacity(cx)) {
                         Some(Ok(0)) => {}
@@ -153,6 +201,11 @@ where
                 ))));
             }
 
+            // Waiting on the body doesn't count against the send rate.
+            if let Some(ref rate) = me.data_rate {
+                rate.pause();
+            }
+
             match ready!(me.stream.as_mut().poll_frame(cx)) {
                 Some(Ok(frame)) => {
                     if frame.is_data() {
@@ -163,13 +216,18 @@ where
                             chunk.remaining(),
                             is_eos,
                         );
+                        if let Some(ref rate) = me.data_rate {
+                            rate.record(chunk.remaining());
+                        }
+                        me.observer.data(chunk.remaining());
 
-                        let buf = SendBuf::Buf(chunk);
+                        let buf = (me.into_buf)(chunk);
                         me.body_tx
                             .send_data(buf, is_eos)
                             .map_err(crate::Error::new_body_write)?;
 
                         if is_eos {
+                            me.observer.end();
                             return Poll::Ready(Ok(()));
                         }
                     } else if frame.is_trailers() {
@@ -178,6 +236,7 @@ where
                         me.body_tx
                             .send_trailers(frame.into_trailers().unwrap_or_else(|_| unreachable!()))
                             .map_err(crate::Error::new_body_write)?;
+                        me.observer.end();
                         return Poll::Ready(Ok(()));
                     } else {
                         trace!("discarding unknown frame");
@@ -189,7 +248,9 @@ where
                     // no more frames means we're done here
                     // but at this point, we haven't sent an EOS DATA, or
                     // any trailers, so send an empty EOS DATA.
-                    return Poll::Ready(me.body_tx.send_eos_frame());
+                    me.body_tx.send_eos_frame()?;
+                    me.observer.end();
+                    return Poll::Ready(Ok(()));
                 }
             }
         }
@@ -225,6 +286,8 @@ impl<B: Buf> SendStreamExt for SendStream<SendBuf<B>> {
 enum SendBuf<B> {
     Buf(B),
     Cursor(Cursor<Box<[u8]>>),
+    #[cfg(feature = "server")]
+    Bytes(Bytes),
     None,
 }
 
@@ -234,6 +297,8 @@ impl<B: Buf> Buf for SendBuf<B> {
         match *self {
             Self::Buf(ref b) => b.remaining(),
             Self::Cursor(ref c) => Buf::remaining(c),
+            #[cfg(feature = "server")]
+            Self::Bytes(ref b) => b.remaining(),
             Self::None => 0,
         }
     }
@@ -243,6 +308,8 @@ impl<B: Buf> Buf for SendBuf<B> {
         match *self {
             Self::Buf(ref b) => b.chunk(),
             Self::Cursor(ref c) => c.chunk(),
+            #[cfg(feature = "server")]
+            Self::Bytes(ref b) => b.chunk(),
             Self::None => &[],
         }
     }
@@ -252,6 +319,8 @@ impl<B: Buf> Buf for SendBuf<B> {
         match *self {
             Self::Buf(ref mut b) => b.advance(cnt),
             Self::Cursor(ref mut c) => c.advance(cnt),
+            #[cfg(feature = "server")]
+            Self::Bytes(ref mut b) => b.advance(cnt),
             Self::None => {}
         }
     }
@@ -260,6 +329,8 @@ impl<B: Buf> Buf for SendBuf<B> {
         match *self {
             Self::Buf(ref b) => b.chunks_vectored(dst),
             Self::Cursor(ref c) => c.chunks_vectored(dst),
+            #[cfg(feature = "server")]
+            Self::Bytes(ref b) => b.chunks_vectored(dst),
             Self::None => 0,
         }
     }
@@ -275,14 +346,14 @@ where
     buf: Bytes,
 }
 
-impl<B> AsyncRead for H2Upgraded<B>
+impl<B> Read for H2Upgraded<B>
 where
     B: Buf,
 {
     fn poll_read(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
-        read_buf: &mut ReadBuf<'_>,
+        mut read_buf: ReadBufCursor<'_>,
     ) -> Poll<Result<(), io::Error>> {
         if self.buf.is_empty() {
             self.buf = loop {
@@ -315,7 +386,7 @@ where
     }
 }
 
-impl<B> AsyncWrite for H2Upgraded<B>
+impl<B> Write for H2Upgraded<B>
 where
     B: Buf,
 {
@@ -63,10 +63,10 @@ pub(super) fn channel(ping_pong: PingPong, config: Config, __timer: Time) -> (Re
         while_idle: config.keep_alive_while_idle,
         sleep: __timer.sleep(interval),
         state: KeepAliveState::Init,
-        timer: __timer,
+        timer: __timer.clone(),
     });
 
-    let last_read_at = keep_alive.as_ref().map(|_| Instant::now());
+    let last_read_at = keep_alive.as_ref().map(|_| __timer.now());
 
     let shared = Arc::new(Mutex::new(Shared {
         bytes,
@@ -75,6 +75,7 @@ pub(super) fn channel(ping_pong: PingPong, config: Config, __timer: Time) -> (Re
         ping_pong,
         ping_sent_at: None,
         next_bdp_at,
+        timer: __timer,
     }));
 
     (
@@ -130,6 +131,10 @@ struct Shared {
     last_read_at: Option<Instant>,
 
     is_keep_alive_timed_out: bool,
+
+    /// The clock of `last_read_at`, which keep-alive deadlines are computed
+    /// from.
+    timer: Time,
 }
 
 struct Bdp {
@@ -344,7 +349,7 @@ impl Shared {
 
     fn update_last_read_at(&mut self) {
         if self.last_read_at.is_some() {
-            self.last_read_at = Some(Instant::now());
+            self.last_read_at = Some(self.timer.now());
         }
     }
 
@@ -464,7 +469,7 @@ impl KeepAlive {
                 trace!("keep-alive interval ({:?}) reached", self.interval);
                 shared.send_ping();
                 self.state = KeepAliveState::PingSent;
-                let timeout = Instant::now() + self.timeout;
+                let timeout = self.timer.now() + self.timeout;
                 self.timer.reset(&mut self.sleep, timeout);
             }
             KeepAliveState::Init | KeepAliveState::PingSent => (),
@@ -1,26 +1,34 @@
+use std::collections::HashMap;
 use std::error::Error as StdError;
+use std::fmt;
 use std::marker::Unpin;
-
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
 use std::time::Duration;
 
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use h2::server::{Connection, Handshake, SendResponse};
 use h2::{Reason, RecvStream};
-use http::{Method, Request};
+use http::{Method, Request, Version};
 use pin_project_lite::pin_project;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::oneshot;
 use tracing::{debug, trace, warn};
 
 use super::{ping, PipeToSendStream, SendBuf};
-use crate::body::{Body, Incoming as IncomingBody};
+use crate::body::{Body, Frame, Incoming as IncomingBody, SizeHint};
 use crate::rt::bounds::Http2ConnExec;
+use crate::rt::{Read, TokioIo, Write};
+use crate::common::rate::{DataRate, MinDataRate};
 use crate::common::time::Time;
 use crate::common::{date, task, Future, Pin, Poll};
-use crate::ext::Protocol;
+use crate::ext::{InformationalSender, Protocol};
 use crate::headers;
+use crate::observe::{ConnObserver, Observe};
 use crate::proto::h2::ping::Recorder;
 use crate::proto::h2::{H2Upgraded, UpgradedSendStream};
 use crate::proto::Dispatched;
+use crate::server::conn::http2::Pusher;
 use crate::service::HttpService;
 
 use crate::upgrade::{OnUpgrade, Pending, Upgraded};
@@ -51,6 +59,10 @@ pub(crate) struct Config {
     pub(crate) keep_alive_timeout: Duration,
     pub(crate) max_send_buffer_size: usize,
     pub(crate) max_header_list_size: u32,
+    pub(crate) max_request_body_size: Option<u64>,
+    pub(crate) min_receive_rate: Option<MinDataRate>,
+    pub(crate) min_send_rate: Option<MinDataRate>,
+    pub(crate) observer: Observe,
 }
 
 impl Default for Config {
@@ -66,6 +78,10 @@ impl Default for Config {
             keep_alive_timeout: Duration::from_secs(20),
             max_send_buffer_size: DEFAULT_MAX_SEND_BUF_SIZE,
             max_header_list_size: DEFAULT_SETTINGS_MAX_HEADER_LIST_SIZE,
+            max_request_body_size: None,
+            min_receive_rate: None,
+            min_send_rate: None,
+            observer: Observe::default(),
         }
     }
 }
@@ -78,6 +94,7 @@ pin_project! {
     {
         exec: E,
         timer: Time,
+        observer: ConnObserver,
         service: S,
         state: State<T, B>,
     }
@@ -89,7 +106,8 @@ where
 {
     Handshaking {
         ping_config: ping::Config,
-        hs: Handshake<T, SendBuf<B::Data>>,
+        limits: StreamLimits,
+        hs: Handshake<TokioIo<T>, SendBuf<B::Data>>,
     },
     Serving(Serving<T, B>),
     Closed,
@@ -100,13 +118,25 @@ where
     B: Body,
 {
     ping: Option<(ping::Recorder, ping::Ponger)>,
-    conn: Connection<T, SendBuf<B::Data>>,
+    conn: Connection<TokioIo<T>, SendBuf<B::Data>>,
     closing: Option<crate::Error>,
+    limits: StreamLimits,
+    observer: ConnObserver,
+    pushes: Arc<PushQueue>,
+}
+
+/// The limits enforced on each stream.
+#[derive(Clone)]
+struct StreamLimits {
+    max_body_size: Option<u64>,
+    receive_rate: Option<MinDataRate>,
+    send_rate: Option<MinDataRate>,
+    timer: Time,
 }
 
 impl<T, S, B, E> Server<T, S, B, E>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: Read + Write + Unpin,
     S: HttpService<IncomingBody, ResBody = B>,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
     B: Body + 'static,
@@ -132,7 +162,7 @@ where
         if config.enable_connect_protocol {
             builder.enable_connect_protocol();
         }
-        let handshake = builder.handshake(io);
+        let handshake = builder.handshake(TokioIo::new(io));
 
         let bdp = if config.adaptive_window {
             Some(config.initial_stream_window_size)
@@ -149,11 +179,20 @@ where
             keep_alive_while_idle: true,
         };
 
+        let limits = StreamLimits {
+            max_body_size: config.max_request_body_size,
+            receive_rate: config.min_receive_rate,
+            send_rate: config.min_send_rate,
+            timer: timer.clone(),
+        };
+
         Server {
             exec,
             timer,
+            observer: config.observer.open(Version::HTTP_2),
             state: State::Handshaking {
                 ping_config,
+                limits,
                 hs: handshake,
             },
             service,
@@ -169,6 +208,7 @@ where
             State::Serving(ref mut srv) => {
                 if srv.closing.is_none() {
                     srv.conn.graceful_shutdown();
+                    srv.observer.go_away();
                 }
                 return;
             }
@@ -182,7 +222,7 @@ where
 
 impl<T, S, B, E> Future for Server<T, S, B, E>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: Read + Write + Unpin,
     S: HttpService<IncomingBody, ResBody = B>,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
     B: Body + 'static,
@@ -191,12 +231,31 @@ where
     type Output = crate::Result<Dispatched>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        let me = &mut *self;
+        let ret = ready!(self.poll_inner(cx));
+        if let Err(ref e) = ret {
+            self.observer.error(e);
+        }
+        self.observer.close();
+        Poll::Ready(ret)
+    }
+}
+
+impl<T, S, B, E> Server<T, S, B, E>
+where
+    T: Read + Write + Unpin,
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: Body + 'static,
+    E: Http2ConnExec<S::Future, B>,
+{
+    fn poll_inner(&mut self, cx: &mut task::Context<'_>) -> Poll<crate::Result<Dispatched>> {
+        let me = self;
         loop {
             let next = match me.state {
                 State::Handshaking {
                     ref mut hs,
                     ref ping_config,
+                    ref limits,
                 } => {
                     let mut conn = ready!(Pin::new(hs).poll(cx).map_err(crate::Error::new_h2))?;
                     let ping = if ping_config.is_enabled() {
@@ -209,6 +268,9 @@ where
                         ping,
                         conn,
                         closing: None,
+                        limits: limits.clone(),
+                        observer: me.observer.clone(),
+                        pushes: Arc::default(),
                     })
                 }
                 State::Serving(ref mut srv) => {
@@ -228,7 +290,7 @@ where
 
 impl<T, B> Serving<T, B>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: Read + Write + Unpin,
     B: Body + 'static,
 {
     fn poll_server<S, E>(
@@ -249,6 +311,9 @@ where
                 match ready!(self.conn.poll_accept(cx)) {
                     Some(Ok((req, mut respond))) => {
                         trace!("incoming request");
+                        let stream_id = u32::from(respond.stream_id());
+                        self.observer
+                            .request_head(Some(stream_id), req.method(), req.uri());
                         let content_length = headers::content_length_parse_all(req.headers());
                         let ping = self
                             .ping
@@ -259,16 +324,47 @@ where
                         // Record the headers received
                         ping.record_non_data();
 
+                        if let (Some(max), Some(len)) = (self.limits.max_body_size, content_length)
+                        {
+                            if len > max {
+                                debug!("request body is larger than the maximum of {} bytes", max);
+                                respond.send_reset(h2::Reason::CANCEL);
+                                continue;
+                            }
+                        }
+
                         let is_connect = req.method() == Method::CONNECT;
                         let (mut parts, stream) = req.into_parts();
+                        let mut pushes = None;
+                        let mut watch = BodyWatch::default();
                         let (mut req, connect_parts) = if !is_connect {
-                            (
-                                Request::from_parts(
-                                    parts,
-                                    IncomingBody::h2(stream, content_length.into(), ping),
-                                ),
-                                None,
-                            )
+                            self.pushes.open(stream_id);
+                            parts.extensions.insert(Pusher::new(
+                                self.pushes.clone(),
+                                stream_id,
+                                &parts.uri,
+                            ));
+                            pushes = Some((self.pushes.clone(), stream_id));
+                            let observer = if stream.is_end_stream() {
+                                None
+                            } else {
+                                Some(self.observer.body(Some(stream_id), true))
+                            };
+                            let mut body = IncomingBody::h2(stream, content_length.into(), ping);
+                            if let Some(observer) = observer {
+                                body = body.with_observer(observer);
+                            }
+                            if let Some(max) = self.limits.max_body_size {
+                                let reset = ResetSignal::default();
+                                body = body.with_max_size(max).with_reset_signal(reset.clone());
+                                watch.reset = Some(reset);
+                            }
+                            if let Some(rate) = self.limits.receive_rate {
+                                let rate = DataRate::new(rate, self.limits.timer.clone());
+                                body = body.with_data_rate(rate.clone());
+                                watch.rate = Some(rate);
+                            }
+                            (Request::from_parts(parts, body), None)
                         } else {
                             if content_length.map_or(false, |len| len != 0) {
                                 warn!("h2 connect request with non-zero body not supported");
@@ -291,8 +387,24 @@ where
                         if let Some(protocol) = req.extensions_mut().remove::<h2::ext::Protocol>() {
                             req.extensions_mut().insert(Protocol::from_inner(protocol));
                         }
-
-                        let fut = H2Stream::new(service.call(req), connect_parts, respond);
+                        // h2 can't send informational responses on a stream,
+                        // so services are told when they try.
+                        req.extensions_mut()
+                            .insert(InformationalSender::unsupported());
+
+                        let send_rate = self
+                            .limits
+                            .send_rate
+                            .map(|rate| DataRate::new(rate, self.limits.timer.clone()));
+                        let fut = H2Stream::new(
+                            service.call(req),
+                            connect_parts,
+                            pushes,
+                            respond,
+                            watch,
+                            send_rate,
+                            self.observer.clone(),
+                        );
                         exec.execute_h2stream(fut);
                     }
                     Some(Err(e)) => {
@@ -331,6 +443,7 @@ where
                 Poll::Ready(ping::Ponged::KeepAliveTimedOut) => {
                     debug!("keep-alive timed out, closing connection");
                     self.conn.abrupt_shutdown(h2::Reason::NO_ERROR);
+                    self.observer.go_away();
                 }
                 Poll::Pending => {}
             }
@@ -345,6 +458,11 @@ pin_project! {
         B: Body,
     {
         reply: SendResponse<SendBuf<B::Data>>,
+        pushes: Pushes<B::Data>,
+        done: bool,
+        watch: BodyWatch,
+        send_rate: Option<DataRate>,
+        observer: ConnObserver,
         #[pin]
         state: H2StreamState<F, B>,
     }
@@ -363,17 +481,88 @@ pin_project! {
         },
         Body {
             #[pin]
-            pipe: PipeToSendStream<B>,
+            pipe: PipeToSendStream<B, B::Data>,
         },
     }
 }
 
+/// The limits of the request body that reset the stream when broken.
+#[derive(Default)]
+struct BodyWatch {
+    rate: Option<DataRate>,
+    reset: Option<ResetSignal>,
+}
+
 struct ConnectParts {
     pending: Pending,
     ping: Recorder,
     recv_stream: RecvStream,
 }
 
+/// Set by a request body that went over its maximum size, so the stream
+/// is reset by the task owning it.
+#[derive(Clone, Default)]
+pub(crate) struct ResetSignal {
+    inner: Arc<Mutex<ResetState>>,
+}
+
+#[derive(Default)]
+struct ResetState {
+    reset: bool,
+    waker: Option<Waker>,
+}
+
+/// A response pushed by the service with a `Pusher`.
+pub(crate) struct Push {
+    pub(crate) req: Request<()>,
+    pub(crate) res: Response<PushBody>,
+    pub(crate) tx: oneshot::Sender<crate::Result<()>>,
+}
+
+pub(crate) type PushBody =
+    Pin<Box<dyn Body<Data = Bytes, Error = Box<dyn StdError + Send + Sync>> + Send>>;
+
+/// The pushes waiting to be promised on the streams of a connection.
+///
+/// Streams are registered when their request is received, so a connection
+/// shares one queue between all its streams.
+#[derive(Default)]
+pub(crate) struct PushQueue {
+    streams: Mutex<HashMap<u32, PushSlot>>,
+}
+
+#[derive(Default)]
+struct PushSlot {
+    pushes: Vec<Push>,
+    waker: Option<Waker>,
+}
+
+/// The pushes of a stream, sent while the stream is still open.
+struct Pushes<D> {
+    queue: Option<(Arc<PushQueue>, u32)>,
+    pipes: Vec<PushPipe<D>>,
+}
+
+struct PushPipe<D> {
+    pipe: PipeToSendStream<PushBody, D>,
+    tx: oneshot::Sender<crate::Result<()>>,
+}
+
+pin_project! {
+    struct IntoPushBody<B> {
+        #[pin]
+        body: B,
+    }
+}
+
+pub(crate) fn push_body<B>(body: B) -> PushBody
+where
+    B: Body + Send + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    Box::pin(IntoPushBody { body })
+}
+
 impl<F, B> H2Stream<F, B>
 where
     B: Body,
@@ -381,10 +570,22 @@ where
     fn new(
         fut: F,
         connect_parts: Option<ConnectParts>,
+        pushes: Option<(Arc<PushQueue>, u32)>,
         respond: SendResponse<SendBuf<B::Data>>,
+        watch: BodyWatch,
+        send_rate: Option<DataRate>,
+        observer: ConnObserver,
     ) -> H2Stream<F, B> {
         H2Stream {
             reply: respond,
+            pushes: Pushes {
+                queue: pushes,
+                pipes: Vec::new(),
+            },
+            done: false,
+            watch,
+            send_rate,
+            observer,
             state: H2StreamState::Service { fut, connect_parts },
         }
     }
@@ -392,8 +593,13 @@ where
 
 macro_rules! reply {
     ($me:expr, $res:expr, $eos:expr) => {{
+        let status = $res.status();
         match $me.reply.send_response($res, $eos) {
-            Ok(tx) => tx,
+            Ok(tx) => {
+                let stream_id = u32::from($me.reply.stream_id());
+                $me.observer.response_head(Some(stream_id), status);
+                tx
+            }
             Err(e) => {
                 debug!("send response error: {}", e);
                 $me.reply.send_reset(Reason::INTERNAL_ERROR);
@@ -413,13 +619,34 @@ where
 {
     fn poll2(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<crate::Result<()>> {
         let mut me = self.project();
+
+        if let Some(ref rate) = me.watch.rate {
+            if rate.poll_expired(cx).is_ready() {
+                debug!("request body data rate too low, resetting stream");
+                me.reply.send_reset(Reason::CANCEL);
+                return Poll::Ready(Err(crate::Error::new_min_data_rate()));
+            }
+        }
+
+        if let Some(ref reset) = me.watch.reset {
+            if reset.poll_reset(cx).is_ready() {
+                debug!("request body is too large, resetting stream");
+                me.reply.send_reset(Reason::CANCEL);
+                return Poll::Ready(Err(crate::Error::new_body_too_large()));
+            }
+        }
+
         loop {
             let next = match me.state.as_mut().project() {
                 H2StreamStateProj::Service {
                     fut: h,
                     connect_parts,
                 } => {
-                    let res = match h.poll(cx) {
+                    let res = h.poll(cx);
+                    // Pushes must be promised before the response ends the
+                    // stream.
+                    me.pushes.poll_accept(me.reply, cx);
+                    let res = match res {
                         Poll::Ready(Ok(r)) => r,
                         Poll::Pending => {
                             // Response is not yet ready, so we want to check if the client has sent a
@@ -468,6 +695,7 @@ where
                                 },
                                 Bytes::new(),
                             ));
+                            me.observer.upgrade(Some(u32::from(me.reply.stream_id())));
                             return Poll::Ready(Ok(()));
                         }
                     }
@@ -479,8 +707,11 @@ where
                         }
 
                         let body_tx = reply!(me, res, false);
+                        let observer = me.observer.body(Some(u32::from(me.reply.stream_id())), false);
                         H2StreamState::Body {
-                            pipe: PipeToSendStream::new(body, body_tx),
+                            pipe: PipeToSendStream::new(body, body_tx)
+                                .with_data_rate(me.send_rate.take())
+                                .with_observer(observer),
                         }
                     } else {
                         reply!(me, res, true);
@@ -488,6 +719,7 @@ where
                     }
                 }
                 H2StreamStateProj::Body { pipe } => {
+                    me.pushes.poll_accept(me.reply, cx);
                     return pipe.poll(cx);
                 }
             };
@@ -506,11 +738,216 @@ where
 {
     type Output = ();
 
-    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        self.poll2(cx).map(|res| {
-            if let Err(e) = res {
-                debug!("stream error: {}", e);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        if !self.done {
+            if let Poll::Ready(res) = self.as_mut().poll2(cx) {
+                if let Err(e) = res {
+                    debug!("stream error: {}", e);
+                }
+                let me = self.as_mut().project();
+                *me.done = true;
+                // the stream is closed, later pushes are canceled
+                me.pushes.close();
             }
-        })
+        }
+
+        // pushed responses may still be sending after the stream is done
+        let me = self.project();
+        ready!(me.pushes.poll_pipes(cx));
+        if *me.done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl ResetSignal {
+    pub(crate) fn reset(&self) {
+        let mut state = self.inner.lock().unwrap();
+        state.reset = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_reset(&self, cx: &mut task::Context<'_>) -> Poll<()> {
+        let mut state = self.inner.lock().unwrap();
+        if state.reset {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl fmt::Debug for PushQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushQueue").finish()
+    }
+}
+
+impl PushQueue {
+    fn open(&self, stream_id: u32) {
+        self.streams
+            .lock()
+            .unwrap()
+            .insert(stream_id, PushSlot::default());
+    }
+
+    pub(crate) fn push(&self, stream_id: u32, push: Push) {
+        let mut streams = self.streams.lock().unwrap();
+        // if the stream is gone, the push is dropped and its sender canceled
+        if let Some(slot) = streams.get_mut(&stream_id) {
+            slot.pushes.push(push);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    fn poll_recv(&self, stream_id: u32, cx: &mut task::Context<'_>) -> Vec<Push> {
+        let mut streams = self.streams.lock().unwrap();
+        match streams.get_mut(&stream_id) {
+            Some(slot) => {
+                slot.waker = Some(cx.waker().clone());
+                mem::take(&mut slot.pushes)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn close(&self, stream_id: u32) {
+        self.streams.lock().unwrap().remove(&stream_id);
+    }
+}
+
+impl<D> Pushes<D> {
+    fn close(&mut self) {
+        if let Some((queue, stream_id)) = self.queue.take() {
+            queue.close(stream_id);
+        }
+    }
+}
+
+impl<D> Drop for Pushes<D> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<D: Buf> Pushes<D> {
+    fn poll_accept(&mut self, reply: &mut SendResponse<SendBuf<D>>, cx: &mut task::Context<'_>) {
+        let pushes = match self.queue {
+            Some((ref queue, stream_id)) => queue.poll_recv(stream_id, cx),
+            None => return,
+        };
+        for push in pushes {
+            self.send(reply, push);
+        }
+    }
+
+    fn send(&mut self, reply: &mut SendResponse<SendBuf<D>>, push: Push) {
+        let Push { req, res, tx } = push;
+        trace!("push promise: {} {}", req.method(), req.uri());
+        let mut pushed = match reply.push_request(req) {
+            Ok(pushed) => pushed,
+            Err(e) => {
+                debug!("push promise error: {}", e);
+                let _ = tx.send(Err(crate::Error::new_h2(e)));
+                return;
+            }
+        };
+
+        let (head, body) = res.into_parts();
+        let mut res = ::http::Response::from_parts(head, ());
+        super::strip_connection_headers(res.headers_mut(), false);
+        res.headers_mut()
+            .entry(::http::header::DATE)
+            .or_insert_with(date::update_and_header_value);
+
+        if body.is_end_stream() {
+            let sent = pushed
+                .send_response(res, true)
+                .map(|_| ())
+                .map_err(crate::Error::new_h2);
+            let _ = tx.send(sent);
+            return;
+        }
+
+        if let Some(len) = body.size_hint().exact() {
+            headers::set_content_length_if_missing(res.headers_mut(), len);
+        }
+        match pushed.send_response(res, false) {
+            Ok(body_tx) => {
+                let pipe = PipeToSendStream::with_buf(body, body_tx, SendBuf::Bytes);
+                self.pipes.push(PushPipe { pipe, tx });
+            }
+            Err(e) => {
+                debug!("send pushed response error: {}", e);
+                let _ = tx.send(Err(crate::Error::new_h2(e)));
+            }
+        }
+    }
+
+    fn poll_pipes(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        let mut i = 0;
+        while i < self.pipes.len() {
+            if let Poll::Ready(res) = Pin::new(&mut self.pipes[i].pipe).poll(cx) {
+                if let Err(ref e) = res {
+                    debug!("pushed stream error: {}", e);
+                }
+                let push = self.pipes.swap_remove(i);
+                let _ = push.tx.send(res);
+            } else {
+                i += 1;
+            }
+        }
+
+        if self.pipes.is_empty() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<B> Body for IntoPushBody<B>
+where
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Data = Bytes;
+    type Error = Box<dyn StdError + Send + Sync>;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut body = self.project().body;
+        loop {
+            let frame = match ready!(body.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                None => return Poll::Ready(None),
+            };
+            let frame = match frame.into_data() {
+                Ok(mut data) => Frame::data(data.copy_to_bytes(data.remaining())),
+                Err(frame) => match frame.into_trailers() {
+                    Ok(trailers) => Frame::trailers(trailers),
+                    Err(_) => continue,
+                },
+            };
+            return Poll::Ready(Some(Ok(frame)));
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
     }
 }
@@ -1,13 +1,23 @@
 //! Runtime components
 //!
-//! By default, hyper includes the [tokio](https://tokio.rs) runtime.
-//!
-//! If the `runtime` feature is disabled, the types in this module can be used
-//! to plug in other runtimes.
+//! If the `runtime` feature is enabled, [`TokioExecutor`] and [`TokioTimer`]
+//! can be used to run hyper on the [tokio](https://tokio.rs) runtime. Otherwise,
+//! the traits in this module can be used to plug in other runtimes.
 
 pub mod bounds;
+mod io;
+mod tokio;
+
+pub use self::io::{Read, ReadBuf, ReadBufCursor, Write};
+pub use self::tokio::TokioIo;
+cfg_feature! {
+    #![feature = "runtime"]
+
+    pub use self::tokio::{TokioExecutor, TokioTimer};
+}
 
 use std::{
+    any::TypeId,
     future::Future,
     pin::Pin,
     time::{Duration, Instant},
@@ -49,10 +59,67 @@ pub trait Timer {
     fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Sleep>>;
 
     /// Reset a future to resolve at `new_deadline` instead.
+    ///
+    /// The default implementation allocates a new `Sleep`. Implementations
+    /// can override this to reuse the existing one, see
+    /// [`downcast_mut_pin`](trait.Sleep.html#method.downcast_mut_pin).
     fn reset(&self, sleep: &mut Pin<Box<dyn Sleep>>, new_deadline: Instant) {
         *sleep = self.sleep_until(new_deadline);
     }
+
+    /// Return the current instant of this timer's clock.
+    ///
+    /// Deadlines passed to `sleep_until` and `reset` are computed from this
+    /// clock, and so are the time checks hyper does without sleeping, such
+    /// as keep-alive and data rate checks.
+    ///
+    /// The default implementation returns `Instant::now()`.
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
 }
 
 /// A future returned by a `Timer`.
-pub trait Sleep: Send + Sync + Future<Output = ()> {}
+pub trait Sleep: Send + Sync + Future<Output = ()> {
+    #[doc(hidden)]
+    /// This method is private and can not be implemented by downstream crate
+    fn __type_id(&self, _: private::Sealed) -> TypeId
+    where
+        Self: 'static,
+    {
+        TypeId::of::<Self>()
+    }
+}
+
+impl dyn Sleep {
+    /// Check whether the type is the same as `T`.
+    pub fn is<T>(&self) -> bool
+    where
+        T: Sleep + 'static,
+    {
+        self.__type_id(private::Sealed {}) == TypeId::of::<T>()
+    }
+
+    /// Downcast a pinned `&mut Sleep` object to its original type.
+    pub fn downcast_mut_pin<T>(self: Pin<&mut Self>) -> Option<Pin<&mut T>>
+    where
+        T: Sleep + 'static,
+    {
+        if self.is::<T>() {
+            // SAFETY: the type was just checked, and the pointer stays pinned.
+            unsafe {
+                let inner = Pin::into_inner_unchecked(self);
+                Some(Pin::new_unchecked(
+                    &mut *(&mut *inner as *mut dyn Sleep as *mut T),
+                ))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+mod private {
+    #![allow(missing_debug_implementations)]
+    pub struct Sealed {}
+}
@@ -0,0 +1,291 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// A wrapper that implements hyper's IO traits for a type that implements
+    /// Tokio's IO traits, and the other way around.
+    ///
+    /// This makes it possible to serve a `tokio::net::TcpStream`, or to use
+    /// an [`Upgraded`](crate::upgrade::Upgraded) connection with the
+    /// `tokio::io::AsyncReadExt` and `AsyncWriteExt` helpers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "server", feature = "http1"))]
+    /// # async fn run(stream: tokio::net::TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+    /// use hyper::rt::TokioIo;
+    /// # use hyper::{body::Incoming, Request, Response};
+    /// # async fn handle(_: Request<Incoming>) -> Result<Response<String>, hyper::Error> {
+    /// #     Ok(Response::new(String::new()))
+    /// # }
+    ///
+    /// hyper::server::conn::http1::Builder::new()
+    ///     .serve_connection(TokioIo::new(stream), hyper::service::service_fn(handle))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    #[derive(Debug)]
+    pub struct TokioIo<T> {
+        #[pin]
+        inner: T,
+    }
+}
+
+impl<T> TokioIo<T> {
+    /// Wrap a type implementing Tokio's or hyper's IO traits.
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Borrow the inner type.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Mutably borrow the inner type.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consume this wrapper and get the inner type.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> super::Read for TokioIo<T>
+where
+    T: tokio::io::AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: super::ReadBufCursor<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        let n = unsafe {
+            let mut tbuf = tokio::io::ReadBuf::uninit(buf.as_mut());
+            match tokio::io::AsyncRead::poll_read(self.project().inner, cx, &mut tbuf) {
+                Poll::Ready(Ok(())) => tbuf.filled().len(),
+                other => return other,
+            }
+        };
+
+        unsafe {
+            buf.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> super::Write for TokioIo<T>
+where
+    T: tokio::io::AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        tokio::io::AsyncWrite::poll_write(self.project().inner, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        tokio::io::AsyncWrite::poll_flush(self.project().inner, cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        tokio::io::AsyncWrite::poll_shutdown(self.project().inner, cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        tokio::io::AsyncWrite::is_write_vectored(&self.inner)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        tokio::io::AsyncWrite::poll_write_vectored(self.project().inner, cx, bufs)
+    }
+}
+
+impl<T> tokio::io::AsyncRead for TokioIo<T>
+where
+    T: super::Read,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        tbuf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        let filled = tbuf.filled().len();
+        let initialized = tbuf.initialized().len();
+        let (init, new_filled) = unsafe {
+            let mut buf = super::ReadBuf::uninit(tbuf.inner_mut());
+            buf.set_init(initialized);
+            buf.set_filled(filled);
+
+            match super::Read::poll_read(self.project().inner, cx, buf.unfilled()) {
+                Poll::Ready(Ok(())) => (buf.init_len(), buf.len()),
+                other => return other,
+            }
+        };
+
+        unsafe {
+            tbuf.assume_init(init - filled);
+            tbuf.set_filled(new_filled);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> tokio::io::AsyncWrite for TokioIo<T>
+where
+    T: super::Write,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        super::Write::poll_write(self.project().inner, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        super::Write::poll_flush(self.project().inner, cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        super::Write::poll_shutdown(self.project().inner, cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        super::Write::is_write_vectored(&self.inner)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        super::Write::poll_write_vectored(self.project().inner, cx, bufs)
+    }
+}
+
+cfg_feature! {
+    #![feature = "runtime"]
+
+    /// An [`Executor`](super::Executor) that spawns futures onto the current
+    /// tokio runtime.
+    ///
+    /// This can be passed to the HTTP/2 builders, which need to spawn tasks
+    /// for each stream.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct TokioExecutor;
+
+    /// A [`Timer`](super::Timer) backed by the current tokio runtime's timer.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct TokioTimer;
+}
+
+#[cfg(feature = "runtime")]
+impl<F> super::Executor<F> for TokioExecutor
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        tokio::task::spawn(fut);
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl super::Timer for TokioTimer {
+    fn sleep(&self, duration: std::time::Duration) -> Pin<Box<dyn super::Sleep>> {
+        Box::pin(TokioSleep {
+            inner: tokio::time::sleep(duration),
+        })
+    }
+
+    fn sleep_until(&self, deadline: std::time::Instant) -> Pin<Box<dyn super::Sleep>> {
+        Box::pin(TokioSleep {
+            inner: tokio::time::sleep_until(deadline.into()),
+        })
+    }
+
+    fn reset(&self, sleep: &mut Pin<Box<dyn super::Sleep>>, new_deadline: std::time::Instant) {
+        if let Some(sleep) = sleep.as_mut().downcast_mut_pin::<TokioSleep>() {
+            sleep.reset(new_deadline);
+        } else {
+            *sleep = self.sleep_until(new_deadline);
+        }
+    }
+
+    // Follows tokio's clock, which can be paused and advanced in tests.
+    fn now(&self) -> std::time::Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+#[cfg(feature = "runtime")]
+pin_project! {
+    // Wraps tokio::time::Sleep so it can implement hyper's Sleep trait.
+    #[derive(Debug)]
+    struct TokioSleep {
+        #[pin]
+        inner: tokio::time::Sleep,
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl TokioSleep {
+    fn reset(self: Pin<&mut Self>, deadline: std::time::Instant) {
+        self.project().inner.reset(deadline.into());
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl std::future::Future for TokioSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().inner.poll(cx)
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl super::Sleep for TokioSleep {}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::TokioTimer;
+    use crate::rt::Timer;
+
+    #[tokio::test(start_paused = true)]
+    async fn timer_reset_reuses_sleep() {
+        let timer = TokioTimer;
+        let mut sleep = timer.sleep(Duration::from_secs(60));
+        let before = &*sleep as *const _ as *const u8;
+
+        timer.reset(&mut sleep, Instant::now() + Duration::from_millis(10));
+        assert_eq!(&*sleep as *const _ as *const u8, before);
+
+        tokio::time::timeout(Duration::from_secs(1), sleep)
+            .await
+            .expect("reset sleep should fire at the new deadline");
+    }
+}
@@ -0,0 +1,470 @@
+//! HTTP/1 or HTTP/2 Server Connections
+//!
+//! The connection sniffs the first bytes sent by the client. If they are the
+//! HTTP/2 connection preface, the connection is served as HTTP/2 with "prior
+//! knowledge". Otherwise, it is served as HTTP/1.
+//!
+//! With [`Builder::h2c_upgrade`], an HTTP/1 connection can also switch to
+//! HTTP/2 when a client asks for it with `Upgrade: h2c`. Other upgrades,
+//! such as WebSockets, need [`Connection::with_upgrades`].
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use bytes::Bytes;
+use tracing::trace;
+
+use super::{http1, http2};
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::io::Rewind;
+use crate::common::{task, Future, Pin, Poll, Unpin};
+use crate::observe::Observer;
+use crate::rt::bounds::Http2ConnExec;
+use crate::rt::{Read, ReadBuf, Timer, Write};
+use crate::service::HttpService;
+use crate::upgrade::Pending;
+
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// A future binding a connection with a Service, serving either HTTP/1 or
+/// HTTP/2.
+///
+/// Polling this future will drive HTTP forward.
+#[must_use = "futures do nothing unless polled"]
+pub struct Connection<T, S, E>
+where
+    S: HttpService<IncomingBody>,
+{
+    state: State<T, S, E>,
+    builder: Builder<E>,
+    shutdown: bool,
+}
+
+enum State<T, S, E>
+where
+    S: HttpService<IncomingBody>,
+{
+    ReadVersion {
+        read_version: ReadVersion<T>,
+        service: Option<S>,
+    },
+    H1(Box<http1::H2cConnection<Rewind<T>, S, E>>),
+    H2(Box<http2::Connection<Rewind<T>, S, E>>),
+}
+
+/// A configuration builder for server connections that may be either
+/// HTTP/1 or HTTP/2.
+///
+/// The HTTP/1 and HTTP/2 options are configured with the builders returned
+/// by [`http1()`](Builder::http1) and [`http2()`](Builder::http2).
+#[derive(Clone, Debug)]
+pub struct Builder<E> {
+    http1: http1::Builder,
+    http2: http2::Builder<E>,
+    h2c_upgrade: bool,
+}
+
+// ===== impl Connection =====
+
+// An HTTP/1 upgrade other than h2c, with the IO and the bytes read past the
+// request.
+type Upgrade<I> = (Pending, Rewind<I>, Bytes);
+
+// The service and executor are never pinned, they are only moved into the
+// HTTP/1 or HTTP/2 connection once the version is known.
+impl<I: Unpin, S, E> Unpin for Connection<I, S, E> where S: HttpService<IncomingBody> {}
+
+impl<I, S, E> fmt::Debug for Connection<I, S, E>
+where
+    S: HttpService<IncomingBody>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = match self.state {
+            State::ReadVersion { .. } => "unknown",
+            State::H1(ref conn) if conn.version() == http::Version::HTTP_11 => "HTTP/1",
+            State::H1(..) | State::H2(..) => "HTTP/2",
+        };
+        f.debug_struct("Connection")
+            .field("version", &version)
+            .finish()
+    }
+}
+
+impl<I, B, S, E> Connection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http2ConnExec<S::Future, B>,
+{
+    /// Start a graceful shutdown process for this connection.
+    ///
+    /// This `Connection` should continue to be polled until shutdown
+    /// can finish.
+    ///
+    /// If nothing was received yet, the connection is closed, as an idle
+    /// HTTP/1 connection would be. If the connection is being upgraded to
+    /// HTTP/2, the request that asked for the upgrade is still answered.
+    ///
+    /// # Note
+    ///
+    /// This should only be called while the `Connection` future is still
+    /// pending. If called after `Connection::poll` has resolved, this does
+    /// nothing.
+    pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
+        self.shutdown = true;
+        match self.state {
+            State::ReadVersion { .. } => {}
+            State::H1(ref mut conn) => Pin::new(&mut **conn).graceful_shutdown(),
+            State::H2(ref mut conn) => Pin::new(&mut **conn).graceful_shutdown(),
+        }
+    }
+
+    /// Enable this connection to support higher-level HTTP upgrades, other
+    /// than h2c.
+    ///
+    /// See [the `upgrade` module](crate::upgrade) for more.
+    pub fn with_upgrades(self) -> upgrades::UpgradeableConnection<I, S, E>
+    where
+        I: Send,
+    {
+        upgrades::UpgradeableConnection { inner: Some(self) }
+    }
+}
+
+impl<I, B, S, E> Future for Connection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http2ConnExec<S::Future, B>,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        match ready!(self.poll_upgrade(cx))? {
+            Some((pending, _, _)) => {
+                // With no `Send` bound on `I`, we can't hand the IO to the
+                // service. In case a user was trying to use
+                // `Body::on_upgrade` with this API, send a special error
+                // letting them know about that.
+                pending.manual();
+                Poll::Ready(Ok(()))
+            }
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl<I, B, S, E> Connection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http2ConnExec<S::Future, B>,
+{
+    /// Drives the connection, returning an HTTP/1 upgrade other than h2c
+    /// for the caller to handle.
+    fn poll_upgrade(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<crate::Result<Option<Upgrade<I>>>> {
+        let this = self;
+        loop {
+            let mut state = match this.state {
+                State::ReadVersion {
+                    ref mut read_version,
+                    ref mut service,
+                } => {
+                    if this.shutdown && read_version.filled == 0 {
+                        trace!("auto connection shut down before receiving anything");
+                        read_version.io = None;
+                        return Poll::Ready(Ok(None));
+                    }
+                    let (version, io, buf) = match ready!(Pin::new(read_version).poll(cx)) {
+                        Ok(read) => read,
+                        Err(e) => return Poll::Ready(Err(crate::Error::new_io(e))),
+                    };
+                    let io = Rewind::new_buffered(io, buf);
+                    let service = service.take().expect("polled after complete");
+                    match version {
+                        Version::H1 => {
+                            trace!("auto connection detected HTTP/1");
+                            State::H1(Box::new(this.builder.http1.serve_h2c(
+                                io,
+                                service,
+                                this.builder.http2.clone(),
+                                this.builder.h2c_upgrade,
+                            )))
+                        }
+                        Version::H2 => {
+                            trace!("auto connection detected HTTP/2 prior knowledge");
+                            State::H2(Box::new(this.builder.http2.serve_connection(io, service)))
+                        }
+                    }
+                }
+                State::H1(ref mut conn) => return conn.poll_upgrade(cx),
+                State::H2(ref mut conn) => return Pin::new(conn).poll(cx).map_ok(|()| None),
+            };
+            if this.shutdown {
+                match state {
+                    State::H1(ref mut conn) => Pin::new(&mut **conn).graceful_shutdown(),
+                    State::H2(ref mut conn) => Pin::new(&mut **conn).graceful_shutdown(),
+                    // `ReadVersion` is only the initial state.
+                    State::ReadVersion { .. } => {}
+                }
+            }
+            this.state = state;
+        }
+    }
+}
+
+// ===== impl Builder =====
+
+impl<E> Builder<E> {
+    /// Create a new connection builder.
+    ///
+    /// This starts with the default options, and an executor which is a type
+    /// that implements [`Http2ConnExec`] trait, used if the connection is
+    /// HTTP/2.
+    ///
+    /// [`Http2ConnExec`]: crate::rt::bounds::Http2ConnExec
+    pub fn new(exec: E) -> Self {
+        Self {
+            http1: http1::Builder::new(),
+            http2: http2::Builder::new(exec),
+            h2c_upgrade: false,
+        }
+    }
+
+    /// Returns the builder used to configure HTTP/1 connections.
+    pub fn http1(&mut self) -> &mut http1::Builder {
+        &mut self.http1
+    }
+
+    /// Returns the builder used to configure HTTP/2 connections.
+    pub fn http2(&mut self) -> &mut http2::Builder<E> {
+        &mut self.http2
+    }
+
+    /// Set whether HTTP/1 connections switch to HTTP/2 when asked to with
+    /// `Upgrade: h2c`.
+    ///
+    /// The upgrade is done as by
+    /// [`http1::Builder::serve_connection_with_h2c`].
+    ///
+    /// Default is `false`.
+    pub fn h2c_upgrade(&mut self, enabled: bool) -> &mut Self {
+        self.h2c_upgrade = enabled;
+        self
+    }
+
+    /// Set the timer used in background tasks, for both HTTP/1 and HTTP/2.
+    pub fn timer<M>(&mut self, timer: M) -> &mut Self
+    where
+        M: Timer + Clone + Send + Sync + 'static,
+    {
+        self.http1.timer(timer.clone());
+        self.http2.timer(timer);
+        self
+    }
+
+    /// Set an observer for the events of connections, for both HTTP/1 and
+    /// HTTP/2.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Self
+    where
+        O: Observer + Clone + 'static,
+    {
+        self.http1.observer(observer.clone());
+        self.http2.observer(observer);
+        self
+    }
+
+    /// Bind a connection together with a [`Service`](crate::service::Service).
+    ///
+    /// This returns a Future that must be polled in order for HTTP to be
+    /// driven on the connection.
+    pub fn serve_connection<S, I, Bd>(&self, io: I, service: S) -> Connection<I, S, E>
+    where
+        S: HttpService<IncomingBody, ResBody = Bd>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        Bd: Body + 'static,
+        Bd::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I: Read + Write + Unpin,
+        E: Http2ConnExec<S::Future, Bd>,
+    {
+        Connection {
+            state: State::ReadVersion {
+                read_version: ReadVersion::new(Some(io)),
+                service: Some(service),
+            },
+            builder: self.clone(),
+            shutdown: false,
+        }
+    }
+}
+
+pub(crate) mod upgrades {
+    use crate::upgrade::Upgraded;
+
+    use super::*;
+
+    // A future binding a connection with a Service with Upgrade support.
+    //
+    // This type is unnameable outside the crate.
+    #[must_use = "futures do nothing unless polled"]
+    #[allow(missing_debug_implementations)]
+    pub struct UpgradeableConnection<T, S, E>
+    where
+        S: HttpService<IncomingBody>,
+    {
+        pub(super) inner: Option<Connection<T, S, E>>,
+    }
+
+    impl<I, B, S, E> UpgradeableConnection<I, S, E>
+    where
+        S: HttpService<IncomingBody, ResBody = B>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I: Read + Write + Unpin,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        E: Http2ConnExec<S::Future, B>,
+    {
+        /// Start a graceful shutdown process for this connection.
+        ///
+        /// This `Connection` should continue to be polled until shutdown
+        /// can finish.
+        pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
+            Pin::new(self.inner.as_mut().unwrap()).graceful_shutdown()
+        }
+    }
+
+    impl<I, B, S, E> Future for UpgradeableConnection<I, S, E>
+    where
+        S: HttpService<IncomingBody, ResBody = B>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I: Read + Write + Unpin + Send + 'static,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        E: Http2ConnExec<S::Future, B>,
+    {
+        type Output = crate::Result<()>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+            match ready!(self.inner.as_mut().unwrap().poll_upgrade(cx))? {
+                Some((pending, io, buf)) => {
+                    self.inner = None;
+                    pending.fulfill(Upgraded::new(io, buf));
+                    Poll::Ready(Ok(()))
+                }
+                None => Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+// ===== impl ReadVersion =====
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Version {
+    H1,
+    H2,
+}
+
+/// Reads from the IO until it is known whether the client sent the HTTP/2
+/// connection preface.
+struct ReadVersion<I> {
+    io: Option<I>,
+    buf: [u8; 24],
+    filled: usize,
+}
+
+impl<I> ReadVersion<I> {
+    fn new(io: Option<I>) -> Self {
+        ReadVersion {
+            io,
+            buf: [0; 24],
+            filled: 0,
+        }
+    }
+}
+
+impl<I> Future for ReadVersion<I>
+where
+    I: Read + Unpin,
+{
+    type Output = std::io::Result<(Version, I, Bytes)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        let version = loop {
+            if this.buf[..this.filled] != H2_PREFACE[..this.filled] {
+                break Version::H1;
+            }
+            if this.filled == H2_PREFACE.len() {
+                break Version::H2;
+            }
+
+            let io = this.io.as_mut().expect("polled after complete");
+            let mut buf = ReadBuf::new(&mut this.buf[this.filled..]);
+            ready!(Pin::new(io).poll_read(cx, buf.unfilled()))?;
+            let read = buf.filled().len();
+            if read == 0 {
+                // EOF, let HTTP/1 decide how to handle what was sent so far
+                break Version::H1;
+            }
+            this.filled += read;
+        };
+
+        let io = this.io.take().expect("polled after complete");
+        let buf = Bytes::copy_from_slice(&this.buf[..this.filled]);
+        Poll::Ready(Ok((version, io, buf)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::io::Builder as Mock;
+
+    use super::{ReadVersion, Version, H2_PREFACE};
+    use crate::rt::TokioIo;
+
+    fn read_version<I>(io: I) -> ReadVersion<TokioIo<I>> {
+        ReadVersion::new(Some(TokioIo::new(io)))
+    }
+
+    #[tokio::test]
+    async fn read_version_h1() {
+        let io = Mock::new().read(b"GET / HTTP/1.1\r\n").build();
+        let (version, _io, buf) = read_version(io).await.unwrap();
+        assert_eq!(version, Version::H1);
+        // stops reading as soon as the bytes can't be a preface
+        assert_eq!(buf, "GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn read_version_h2() {
+        let io = Mock::new()
+            .read(&H2_PREFACE[..5])
+            .read(&H2_PREFACE[5..])
+            .build();
+        let (version, _io, buf) = read_version(io).await.unwrap();
+        assert_eq!(version, Version::H2);
+        assert_eq!(buf, H2_PREFACE);
+    }
+
+    #[tokio::test]
+    async fn read_version_eof() {
+        let io = Mock::new().read(b"PRI * HTTP").build();
+        let (version, _io, buf) = read_version(io).await.unwrap();
+        assert_eq!(version, Version::H1);
+        assert_eq!(buf, "PRI * HTTP");
+    }
+}
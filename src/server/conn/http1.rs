@@ -6,13 +6,29 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use bytes::Bytes;
-use tokio::io::{AsyncRead, AsyncWrite};
+use http::{Request, StatusCode, Version};
 
 use crate::body::{Body, Incoming as IncomingBody};
 use crate::common::{task, Future, Pin, Poll, Unpin};
+use crate::common::rate::MinDataRate;
+use crate::observe::{Observe, Observer};
 use crate::{common::time::Time, rt::Timer};
 use crate::proto;
+use crate::rt::{Read, Write};
 use crate::service::HttpService;
+#[cfg(feature = "http2")]
+use tracing::trace;
+
+#[cfg(feature = "http2")]
+use super::http2;
+#[cfg(feature = "http2")]
+use crate::common::io::Rewind;
+#[cfg(feature = "http2")]
+use crate::proto::h2::h2c;
+#[cfg(feature = "http2")]
+use crate::rt::bounds::Http2ConnExec;
+#[cfg(feature = "http2")]
+use crate::upgrade::Pending;
 
 type Http1Dispatcher<T, B, S> =
     proto::h1::Dispatcher<proto::h1::dispatch::Server<S, IncomingBody>, B, T, proto::ServerTransaction>;
@@ -31,17 +47,53 @@ pin_project_lite::pin_project! {
     }
 }
 
+/// A future binding an http1 connection with a Service, that switches to
+/// HTTP/2 when the client asks for it with `Upgrade: h2c`.
+///
+/// Polling this future will drive HTTP forward.
+#[cfg(feature = "http2")]
+#[must_use = "futures do nothing unless polled"]
+pub struct H2cConnection<T, S, E>
+where
+    S: HttpService<IncomingBody>,
+{
+    state: H2cState<T, S, E>,
+    http2: http2::Builder<E>,
+    shutdown: bool,
+}
+
+#[cfg(feature = "http2")]
+enum H2cState<T, S, E>
+where
+    S: HttpService<IncomingBody>,
+{
+    H1(Box<Connection<T, h2c::UpgradeService<S>>>),
+    Preface {
+        read_preface: h2c::ReadPreface<T>,
+        service: Option<S>,
+    },
+    H2(Box<http2::Connection<Rewind<T>, S, E>>),
+    // The HTTP/1 connection ended with an upgrade other than h2c.
+    Upgraded,
+}
 
 /// A configuration builder for HTTP/1 server connections.
 #[derive(Clone, Debug)]
 pub struct Builder {
     timer: Time,
+    observer: Observe,
     h1_half_close: bool,
     h1_keep_alive: bool,
     h1_title_case_headers: bool,
     h1_preserve_header_case: bool,
     h1_header_read_timeout: Option<Duration>,
+    h1_idle_timeout: Option<Duration>,
+    h1_min_receive_rate: Option<MinDataRate>,
+    h1_min_send_rate: Option<MinDataRate>,
+    h1_expect_continue: Option<proto::h1::ExpectContinue>,
     h1_writev: Option<bool>,
+    h1_max_headers: Option<usize>,
+    h1_max_body_size: Option<u64>,
     max_buf_size: Option<usize>,
     pipeline_flush: bool,
 }
@@ -83,7 +135,7 @@ impl<I, B, S> Connection<I, S>
 where
     S: HttpService<IncomingBody, ResBody = B>,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
-    I: AsyncRead + AsyncWrite + Unpin,
+    I: Read + Write + Unpin,
     B: Body + 'static,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
@@ -166,6 +218,16 @@ where
     {
         upgrades::UpgradeableConnection { inner: Some(self) }
     }
+
+    /// Poll the connection, leaving a pending upgrade for the caller to
+    /// handle.
+    #[cfg(feature = "http2")]
+    pub(crate) fn poll_dispatched(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<crate::Result<proto::Dispatched>> {
+        Pin::new(&mut self.conn).poll(cx)
+    }
 }
 
 
@@ -173,7 +235,7 @@ impl<I, B, S> Future for Connection<I, S>
 where
     S: HttpService<IncomingBody, ResBody = B>,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
-    I: AsyncRead + AsyncWrite + Unpin + 'static,
+    I: Read + Write + Unpin + 'static,
     B: Body + 'static,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
@@ -199,6 +261,186 @@ where
     }
 }
 
+// ===== impl H2cConnection =====
+
+// An HTTP/1 upgrade other than h2c, with the IO and the bytes read past the
+// request.
+#[cfg(feature = "http2")]
+type Upgrade<I> = (Pending, I, Bytes);
+
+// The service and executor are never pinned, they are only moved into the
+// HTTP/2 connection once the upgrade is done.
+#[cfg(feature = "http2")]
+impl<I: Unpin, S, E> Unpin for H2cConnection<I, S, E> where S: HttpService<IncomingBody> {}
+
+#[cfg(feature = "http2")]
+impl<I, S, E> fmt::Debug for H2cConnection<I, S, E>
+where
+    S: HttpService<IncomingBody>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("H2cConnection")
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<I, S, E> H2cConnection<I, S, E>
+where
+    S: HttpService<IncomingBody>,
+{
+    pub(crate) fn version(&self) -> Version {
+        match self.state {
+            H2cState::H1(..) | H2cState::Upgraded => Version::HTTP_11,
+            H2cState::Preface { .. } | H2cState::H2(..) => Version::HTTP_2,
+        }
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<I, B, S, E> H2cConnection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http2ConnExec<S::Future, B>,
+{
+    /// Start a graceful shutdown process for this connection.
+    ///
+    /// This `H2cConnection` should continue to be polled until shutdown
+    /// can finish.
+    ///
+    /// If the connection is being upgraded to HTTP/2, the request that
+    /// asked for the upgrade is still answered.
+    ///
+    /// # Note
+    ///
+    /// This should only be called while the `H2cConnection` future is still
+    /// pending. If called after `H2cConnection::poll` has resolved, this does
+    /// nothing.
+    pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
+        self.shutdown = true;
+        match self.state {
+            H2cState::H1(ref mut conn) => Pin::new(&mut **conn).graceful_shutdown(),
+            H2cState::H2(ref mut conn) => Pin::new(&mut **conn).graceful_shutdown(),
+            H2cState::Preface { .. } | H2cState::Upgraded => {}
+        }
+    }
+
+    /// Enable this connection to support higher-level HTTP upgrades, other
+    /// than h2c.
+    ///
+    /// See [the `upgrade` module](crate::upgrade) for more.
+    pub fn with_upgrades(self) -> upgrades::UpgradeableH2cConnection<I, S, E>
+    where
+        I: Send,
+    {
+        upgrades::UpgradeableH2cConnection { inner: Some(self) }
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<I, B, S, E> H2cConnection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http2ConnExec<S::Future, B>,
+{
+    /// Drives the connection, returning an HTTP/1 upgrade other than h2c
+    /// for the caller to handle.
+    pub(crate) fn poll_upgrade(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<crate::Result<Option<Upgrade<I>>>> {
+        loop {
+            let mut state = match self.state {
+                H2cState::H1(ref mut conn) => match ready!(conn.poll_dispatched(cx))? {
+                    proto::Dispatched::Shutdown => return Poll::Ready(Ok(None)),
+                    proto::Dispatched::Upgrade(pending) => {
+                        let conn = match std::mem::replace(&mut self.state, H2cState::Upgraded) {
+                            H2cState::H1(conn) => conn,
+                            _ => unreachable!(),
+                        };
+                        let parts = conn.into_parts();
+                        let (service, upgrade) = parts.service.into_parts();
+                        let req = match upgrade {
+                            Some(req) => req,
+                            None => {
+                                // Not an h2c upgrade, the caller decides
+                                // whether the service gets the IO.
+                                return Poll::Ready(Ok(Some((pending, parts.io, parts.read_buf))));
+                            }
+                        };
+                        trace!("upgrading connection to h2c");
+                        H2cState::Preface {
+                            read_preface: h2c::ReadPreface::new(
+                                parts.io,
+                                parts.read_buf,
+                                h2c::encode_request(&req),
+                            ),
+                            service: Some(service),
+                        }
+                    }
+                },
+                H2cState::Preface {
+                    ref mut read_preface,
+                    ref mut service,
+                } => {
+                    let (io, buf) = match ready!(Pin::new(read_preface).poll(cx)) {
+                        Ok(read) => read,
+                        Err(e) => return Poll::Ready(Err(crate::Error::new_io(e))),
+                    };
+                    let io = Rewind::new_buffered(io, buf);
+                    let service = service.take().expect("polled after complete");
+                    H2cState::H2(Box::new(self.http2.serve_connection(io, service)))
+                }
+                H2cState::H2(ref mut conn) => return Pin::new(conn).poll(cx).map_ok(|()| None),
+                H2cState::Upgraded => panic!("polled after complete"),
+            };
+            if self.shutdown {
+                match state {
+                    H2cState::H2(ref mut conn) => Pin::new(&mut **conn).graceful_shutdown(),
+                    H2cState::H1(..) | H2cState::Preface { .. } | H2cState::Upgraded => {}
+                }
+            }
+            self.state = state;
+        }
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<I, B, S, E> Future for H2cConnection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: Http2ConnExec<S::Future, B>,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        match ready!(self.poll_upgrade(cx))? {
+            Some((pending, _, _)) => {
+                // With no `Send` bound on `I`, we can't hand the IO to the
+                // service. In case a user was trying to use
+                // `Body::on_upgrade` with this API, send a special error
+                // letting them know about that.
+                pending.manual();
+                Poll::Ready(Ok(()))
+            }
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
 // ===== impl Builder =====
 
 impl Builder {
@@ -206,12 +448,19 @@ impl Builder {
     pub fn new() -> Self {
         Self {
             timer: Time::Empty,
+            observer: Observe::default(),
             h1_half_close: false,
             h1_keep_alive: true,
             h1_title_case_headers: false,
             h1_preserve_header_case: false,
             h1_header_read_timeout: None,
+            h1_idle_timeout: None,
+            h1_min_receive_rate: None,
+            h1_min_send_rate: None,
+            h1_expect_continue: None,
             h1_writev: None,
+            h1_max_headers: None,
+            h1_max_body_size: None,
             max_buf_size: None,
             pipeline_flush: false,
         }
@@ -262,6 +511,37 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of headers.
+    ///
+    /// When a request is received, the parser will reserve a buffer to store headers for optimal
+    /// performance.
+    ///
+    /// If server receives more headers than the buffer size, it responds to the client with
+    /// "431 Request Header Fields Too Large".
+    ///
+    /// Headers are stored on the stack while this value is at most 100. Setting
+    /// a larger value moves the storage to the heap, which means an allocation
+    /// for each request parsed.
+    ///
+    /// Default is 100.
+    pub fn max_headers(&mut self, val: usize) -> &mut Self {
+        self.h1_max_headers = Some(val);
+        self
+    }
+
+    /// Set the maximum size of a request body, in bytes.
+    ///
+    /// A request declaring a larger `Content-Length` is rejected with a
+    /// `413 Payload Too Large` response before the service is called. If a
+    /// body without a declared length grows past the limit, reading it
+    /// returns an error and the connection is closed.
+    ///
+    /// Default is None.
+    pub fn max_request_body_size(&mut self, max: u64) -> &mut Self {
+        self.h1_max_body_size = Some(max);
+        self
+    }
+
     /// Set a timeout for reading client request headers. If a client does not
     /// transmit the entire header within this time, the connection is closed.
     ///
@@ -271,6 +551,80 @@ impl Builder {
         self
     }
 
+    /// Set a timeout for a kept-alive connection waiting for its next request.
+    ///
+    /// If no bytes of a new request arrive within this time after the
+    /// previous response, the connection is closed with an error for which
+    /// [`Error::is_timeout`](crate::Error::is_timeout) returns true. Once a
+    /// request starts arriving, the header read timeout applies instead.
+    ///
+    /// Requires a [`timer`](Builder::timer) to be set.
+    ///
+    /// Default is None.
+    pub fn idle_timeout(&mut self, idle_timeout: Duration) -> &mut Self {
+        self.h1_idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Set the minimum rate at which request bodies must be received.
+    ///
+    /// Once `grace_period` has passed, a request body must keep arriving at
+    /// an average of at least `bytes_per_second`, or the connection is closed
+    /// with an error. Only time spent waiting on the client counts, not time
+    /// the service spends before polling the body for more data.
+    ///
+    /// Requires a [`timer`](Builder::timer) to be set.
+    ///
+    /// Default is None.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` is 0.
+    pub fn min_receive_rate(&mut self, bytes_per_second: u64, grace_period: Duration) -> &mut Self {
+        self.h1_min_receive_rate = Some(MinDataRate::new(bytes_per_second, grace_period));
+        self
+    }
+
+    /// Set the minimum rate at which responses must be sent.
+    ///
+    /// Once `grace_period` has passed, the client must keep reading a
+    /// response at an average of at least `bytes_per_second`, or the
+    /// connection is closed with an error. Only time spent waiting on the
+    /// client to read counts, not time spent waiting on the response body.
+    ///
+    /// Requires a [`timer`](Builder::timer) to be set.
+    ///
+    /// Default is None.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` is 0.
+    pub fn min_send_rate(&mut self, bytes_per_second: u64, grace_period: Duration) -> &mut Self {
+        self.h1_min_send_rate = Some(MinDataRate::new(bytes_per_second, grace_period));
+        self
+    }
+
+    /// Set a policy for requests with an `Expect: 100-continue` header.
+    ///
+    /// The policy is called with the request head before any of the body
+    /// is read. Returning `Ok(())` accepts the expectation, and `100
+    /// Continue` is sent once the service starts reading the body.
+    ///
+    /// Returning `Err(status)` rejects it, for example with `417
+    /// Expectation Failed` or `413 Payload Too Large`. The request isn't
+    /// given to the service, the response is sent with an empty body, and
+    /// the connection is closed since the request body was never read. An
+    /// informational status is replaced with `417 Expectation Failed`.
+    ///
+    /// Default is to accept all expectations.
+    pub fn expect_continue<F>(&mut self, policy: F) -> &mut Self
+    where
+        F: Fn(&Request<()>) -> Result<(), StatusCode> + Send + Sync + 'static,
+    {
+        self.h1_expect_continue = Some(proto::h1::ExpectContinue::new(policy));
+        self
+    }
+
     /// Set whether HTTP/1 connections should try to use vectored writes,
     /// or always flatten into a single buffer.
     ///
@@ -323,6 +677,18 @@ impl Builder {
         self
     }
 
+    /// Set an observer for the events of connections, such as requests
+    /// being received and bodies ending.
+    ///
+    /// See the [`observe`](crate::observe) module for the events reported.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Self
+    where
+        O: Observer + 'static,
+    {
+        self.observer = Observe::new(observer);
+        self
+    }
+
     /// Bind a connection together with a [`Service`](crate::service::Service).
     ///
     /// This returns a Future that must be polled in order for HTTP to be
@@ -334,10 +700,10 @@ impl Builder {
     /// # use hyper::{body::Incoming, Request, Response};
     /// # use hyper::service::Service;
     /// # use hyper::server::conn::http1::Builder;
-    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use hyper::rt::{Read, Write};
     /// # async fn run<I, S>(some_io: I, some_service: S)
     /// # where
-    /// #     I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    /// #     I: Read + Write + Unpin + Send + 'static,
     /// #     S: Service<hyper::Request<Incoming>, Response=hyper::Response<Incoming>> + Send + 'static,
     /// #     S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
     /// #     S::Future: Send,
@@ -357,10 +723,11 @@ impl Builder {
         S::Error: Into<Box<dyn StdError + Send + Sync>>,
         S::ResBody: 'static,
         <S::ResBody as Body>::Error: Into<Box<dyn StdError + Send + Sync>>,
-        I: AsyncRead + AsyncWrite + Unpin,
+        I: Read + Write + Unpin,
     {
         let mut conn = proto::Conn::new(io);
         conn.set_timer(self.timer.clone());
+        conn.set_observer(self.observer.open(Version::HTTP_11));
         if !self.h1_keep_alive {
             conn.disable_keep_alive();
         }
@@ -373,9 +740,27 @@ impl Builder {
         if self.h1_preserve_header_case {
             conn.set_preserve_header_case();
         }
+        if let Some(max_headers) = self.h1_max_headers {
+            conn.set_http1_max_headers(max_headers);
+        }
+        if let Some(max) = self.h1_max_body_size {
+            conn.set_http1_max_body_size(max);
+        }
         if let Some(header_read_timeout) = self.h1_header_read_timeout {
             conn.set_http1_header_read_timeout(header_read_timeout);
         }
+        if let Some(idle_timeout) = self.h1_idle_timeout {
+            conn.set_http1_idle_timeout(idle_timeout);
+        }
+        if let Some(rate) = self.h1_min_receive_rate {
+            conn.set_http1_min_receive_rate(rate);
+        }
+        if let Some(rate) = self.h1_min_send_rate {
+            conn.set_http1_min_send_rate(rate);
+        }
+        if let Some(ref policy) = self.h1_expect_continue {
+            conn.set_http1_expect_continue(policy.clone());
+        }
         if let Some(writev) = self.h1_writev {
             if writev {
                 conn.set_write_strategy_queue();
@@ -393,9 +778,64 @@ impl Builder {
             conn: proto,
         }
     }
+
+    /// Bind a connection together with a [`Service`](crate::service::Service),
+    /// switching to HTTP/2 when the client asks for it with `Upgrade: h2c`.
+    ///
+    /// The upgrade is only done for requests without a body, that send
+    /// exactly one `HTTP2-Settings` header, as described in [RFC 7540]. The
+    /// request is answered with `101 Switching Protocols`, and then served
+    /// as HTTP/2 stream 1 with the `Service`, using the options of `http2`.
+    /// Any other request is served as by
+    /// [`serve_connection`](Builder::serve_connection).
+    ///
+    /// [RFC 7540]: https://httpwg.org/specs/rfc7540.html#discover-http
+    #[cfg(feature = "http2")]
+    pub fn serve_connection_with_h2c<I, S, E>(
+        &self,
+        io: I,
+        service: S,
+        http2: http2::Builder<E>,
+    ) -> H2cConnection<I, S, E>
+    where
+        S: HttpService<IncomingBody>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        S::ResBody: 'static,
+        <S::ResBody as Body>::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I: Read + Write + Unpin,
+        E: Http2ConnExec<S::Future, S::ResBody>,
+    {
+        self.serve_h2c(io, service, http2, true)
+    }
+
+    // With `enabled` unset, upgrades to h2c are refused but the connection
+    // can still be driven as an `H2cConnection`.
+    #[cfg(feature = "http2")]
+    pub(crate) fn serve_h2c<I, S, E>(
+        &self,
+        io: I,
+        service: S,
+        http2: http2::Builder<E>,
+        enabled: bool,
+    ) -> H2cConnection<I, S, E>
+    where
+        S: HttpService<IncomingBody>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        S::ResBody: 'static,
+        <S::ResBody as Body>::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I: Read + Write + Unpin,
+        E: Http2ConnExec<S::Future, S::ResBody>,
+    {
+        let service = h2c::UpgradeService::new(service, enabled);
+        H2cConnection {
+            state: H2cState::H1(Box::new(self.serve_connection(io, service))),
+            http2,
+            shutdown: false,
+        }
+    }
 }
 
-mod upgrades {
+pub(crate) mod upgrades {
     use crate::upgrade::Upgraded;
 
     use super::*;
@@ -416,7 +856,7 @@ mod upgrades {
     where
         S: HttpService<IncomingBody, ResBody = B>,
         S::Error: Into<Box<dyn StdError + Send + Sync>>,
-        I: AsyncRead + AsyncWrite + Unpin,
+        I: Read + Write + Unpin,
         B: Body + 'static,
         B::Error: Into<Box<dyn StdError + Send + Sync>>,
     {
@@ -433,7 +873,7 @@ mod upgrades {
     where
         S: HttpService<IncomingBody, ResBody = B>,
         S::Error: Into<Box<dyn StdError + Send + Sync>>,
-        I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        I: Read + Write + Unpin + Send + 'static,
         B: Body + 'static,
         B::Error: Into<Box<dyn StdError + Send + Sync>>,
     {
@@ -451,4 +891,61 @@ mod upgrades {
             }
         }
     }
+
+    // A future binding an h2c connection with a Service with Upgrade
+    // support.
+    //
+    // This type is unnameable outside the crate.
+    #[cfg(feature = "http2")]
+    #[must_use = "futures do nothing unless polled"]
+    #[allow(missing_debug_implementations)]
+    pub struct UpgradeableH2cConnection<T, S, E>
+    where
+        S: HttpService<IncomingBody>,
+    {
+        pub(super) inner: Option<H2cConnection<T, S, E>>,
+    }
+
+    #[cfg(feature = "http2")]
+    impl<I, B, S, E> UpgradeableH2cConnection<I, S, E>
+    where
+        S: HttpService<IncomingBody, ResBody = B>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I: Read + Write + Unpin,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        E: Http2ConnExec<S::Future, B>,
+    {
+        /// Start a graceful shutdown process for this connection.
+        ///
+        /// This `Connection` should continue to be polled until shutdown
+        /// can finish.
+        pub fn graceful_shutdown(mut self: Pin<&mut Self>) {
+            Pin::new(self.inner.as_mut().unwrap()).graceful_shutdown()
+        }
+    }
+
+    #[cfg(feature = "http2")]
+    impl<I, B, S, E> Future for UpgradeableH2cConnection<I, S, E>
+    where
+        S: HttpService<IncomingBody, ResBody = B>,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        I: Read + Write + Unpin + Send + 'static,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        E: Http2ConnExec<S::Future, B>,
+    {
+        type Output = crate::Result<()>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+            match ready!(self.inner.as_mut().unwrap().poll_upgrade(cx))? {
+                Some((pending, io, buf)) => {
+                    self.inner = None;
+                    pending.fulfill(Upgraded::new(io, buf));
+                    Poll::Ready(Ok(()))
+                }
+                None => Poll::Ready(Ok(())),
+            }
+        }
+    }
 }
@@ -5,13 +5,19 @@ use std::fmt;
 use std::sync::Arc;
 use std::time::Duration;
 
+use http::uri::{Authority, Scheme};
+use http::{Request, Response, Uri};
 use pin_project_lite::pin_project;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::oneshot;
 
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::rate::MinDataRate;
 use crate::common::{task, Future, Pin, Poll, Unpin};
+use crate::observe::{Observe, Observer};
 use crate::proto;
+use crate::proto::h2::server::{push_body, Push, PushQueue};
 use crate::rt::bounds::Http2ConnExec;
+use crate::rt::{Read, Write};
 use crate::service::HttpService;
 use crate::{common::time::Time, rt::Timer};
 
@@ -28,6 +34,20 @@ pin_project! {
     }
 }
 
+/// A handle to push responses to the client.
+///
+/// A `Pusher` is inserted into the extensions of each request received on
+/// an HTTP/2 connection, except `CONNECT` requests. It sends
+/// `PUSH_PROMISE` frames on the stream of that request, as described in
+/// [RFC 7540](https://httpwg.org/specs/rfc7540.html#PushResources).
+#[derive(Clone, Debug)]
+pub struct Pusher {
+    queue: Arc<PushQueue>,
+    stream_id: u32,
+    scheme: Option<Scheme>,
+    authority: Option<Authority>,
+}
+
 /// A configuration builder for HTTP/2 server connections.
 #[derive(Clone, Debug)]
 pub struct Builder<E> {
@@ -51,7 +71,7 @@ impl<I, B, S, E> Connection<I, S, E>
 where
     S: HttpService<IncomingBody, ResBody = B>,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
-    I: AsyncRead + AsyncWrite + Unpin,
+    I: Read + Write + Unpin,
     B: Body + 'static,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
     E: Http2ConnExec<S::Future, B>,
@@ -75,7 +95,7 @@ impl<I, B, S, E> Future for Connection<I, S, E>
 where
     S: HttpService<IncomingBody, ResBody = B>,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
-    I: AsyncRead + AsyncWrite + Unpin + 'static,
+    I: Read + Write + Unpin + 'static,
     B: Body + 'static,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
     E: Http2ConnExec<S::Future, B>,
@@ -94,6 +114,69 @@ where
     }
 }
 
+// ===== impl Pusher =====
+
+impl Pusher {
+    pub(crate) fn new(queue: Arc<PushQueue>, stream_id: u32, uri: &Uri) -> Pusher {
+        Pusher {
+            queue,
+            stream_id,
+            scheme: uri.scheme().cloned(),
+            authority: uri.authority().cloned(),
+        }
+    }
+
+    /// Promises `req` to the client, and sends `res` as its response.
+    ///
+    /// If the URI of `req` has no authority, the scheme and authority of
+    /// the original request are used. The method of `req` must be `GET` or
+    /// `HEAD`, and it can't have a body.
+    ///
+    /// Pushes are promised before the response to the original request is
+    /// sent, and must be started before that response ends. A pushed
+    /// response waits until the client's `SETTINGS_MAX_CONCURRENT_STREAMS`
+    /// allows another stream.
+    ///
+    /// The returned future resolves once the pushed response has been sent.
+    /// It fails if the client disabled push with `SETTINGS_ENABLE_PUSH`,
+    /// refused or reset the pushed stream, or if the original stream has
+    /// already ended. Dropping the future doesn't cancel the push.
+    pub fn push<B>(
+        &self,
+        mut req: Request<()>,
+        res: Response<B>,
+    ) -> impl Future<Output = crate::Result<()>> + Send
+    where
+        B: Body + Send + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        if req.uri().authority().is_none() {
+            let mut parts = req.uri().clone().into_parts();
+            parts.scheme = self.scheme.clone();
+            parts.authority = self.authority.clone();
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *req.uri_mut() = uri;
+            }
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let push = Push {
+            req,
+            res: res.map(push_body),
+            tx,
+        };
+        // if the stream is gone, the push is dropped and `rx` is canceled
+        self.queue.push(self.stream_id, push);
+
+        async move {
+            match rx.await {
+                Ok(res) => res,
+                Err(_) => Err(crate::Error::new_canceled()),
+            }
+        }
+    }
+}
+
 // ===== impl Builder =====
 
 impl<E> Builder<E> {
@@ -242,6 +325,57 @@ impl<E> Builder<E> {
         self
     }
 
+    /// Set the maximum size of a request body, in bytes.
+    ///
+    /// A request declaring a larger `Content-Length` has its stream reset
+    /// with `CANCEL` before the service is called. If a body without a
+    /// declared length grows past the limit, reading it returns an error.
+    ///
+    /// Default is None.
+    pub fn max_request_body_size(&mut self, max: u64) -> &mut Self {
+        self.h2_builder.max_request_body_size = Some(max);
+        self
+    }
+
+    /// Set the minimum rate at which request bodies must be received.
+    ///
+    /// Once `grace_period` has passed, a request body must keep arriving at
+    /// an average of at least `bytes_per_second`, or the stream is reset
+    /// with `CANCEL`. Only time spent waiting on the client counts, not time
+    /// the service spends before polling the body for more data.
+    ///
+    /// Requires a [`timer`](Builder::timer) to be set.
+    ///
+    /// Default is None.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` is 0.
+    pub fn min_receive_rate(&mut self, bytes_per_second: u64, grace_period: Duration) -> &mut Self {
+        self.h2_builder.min_receive_rate = Some(MinDataRate::new(bytes_per_second, grace_period));
+        self
+    }
+
+    /// Set the minimum rate at which response bodies must be sent.
+    ///
+    /// Once `grace_period` has passed, the client must keep granting flow
+    /// control capacity for a response body at an average of at least
+    /// `bytes_per_second`, or the stream is reset with `CANCEL`. Only time
+    /// spent waiting on the client counts, not time spent waiting on the
+    /// response body.
+    ///
+    /// Requires a [`timer`](Builder::timer) to be set.
+    ///
+    /// Default is None.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` is 0.
+    pub fn min_send_rate(&mut self, bytes_per_second: u64, grace_period: Duration) -> &mut Self {
+        self.h2_builder.min_send_rate = Some(MinDataRate::new(bytes_per_second, grace_period));
+        self
+    }
+
     /// Set the timer used in background tasks.
     pub fn timer<M>(&mut self, timer: M) -> &mut Self
     where
@@ -251,6 +385,18 @@ impl<E> Builder<E> {
         self
     }
 
+    /// Set an observer for the events of connections, such as requests
+    /// being received and bodies ending.
+    ///
+    /// See the [`observe`](crate::observe) module for the events reported.
+    pub fn observer<O>(&mut self, observer: O) -> &mut Self
+    where
+        O: Observer + 'static,
+    {
+        self.h2_builder.observer = Observe::new(observer);
+        self
+    }
+
     /// Bind a connection together with a [`Service`](crate::service::Service).
     ///
     /// This returns a Future that must be polled in order for HTTP to be
@@ -261,7 +407,7 @@ impl<E> Builder<E> {
         S::Error: Into<Box<dyn StdError + Send + Sync>>,
         Bd: Body + 'static,
         Bd::Error: Into<Box<dyn StdError + Send + Sync>>,
-        I: AsyncRead + AsyncWrite + Unpin,
+        I: Read + Write + Unpin,
         E: Http2ConnExec<S::Future, Bd>,
     {
         let proto = proto::h2::Server::new(
@@ -6,7 +6,8 @@
 //! customize those things externally.
 //!
 //! This module is split by HTTP version. Both work similarly, but do have
-//! specific options on each builder.
+//! specific options on each builder. The [`auto`] module serves a connection
+//! as either version, detecting HTTP/2 by its connection preface.
 //!
 //! ## Example
 //!
@@ -18,6 +19,7 @@
 //! use http::{Request, Response, StatusCode};
 //! use http_body_util::Full;
 //! use hyper::{server::conn::http1, service::service_fn, body, body::Bytes};
+//! use hyper::rt::TokioIo;
 //! use std::{net::SocketAddr, convert::Infallible};
 //! use tokio::net::TcpListener;
 //!
@@ -31,7 +33,7 @@
 //!         tokio::task::spawn(async move {
 //!             if let Err(http_err) = http1::Builder::new()
 //!                     .keep_alive(true)
-//!                     .serve_connection(tcp_stream, service_fn(hello))
+//!                     .serve_connection(TokioIo::new(tcp_stream), service_fn(hello))
 //!                     .await {
 //!                 eprintln!("Error while serving HTTP connection: {}", http_err);
 //!             }
@@ -45,6 +47,8 @@
 //! # }
 //! ```
 
+#[cfg(all(feature = "http1", feature = "http2"))]
+pub mod auto;
 #[cfg(feature = "http1")]
 pub mod http1;
 #[cfg(feature = "http2")]
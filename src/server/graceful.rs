@@ -0,0 +1,491 @@
+//! Graceful shutdown of many connections.
+//!
+//! A [`GracefulShutdown`] watches server connections. Starting a shutdown
+//! asks every watched connection to finish the requests it is serving and
+//! close, and then waits for all of them to drain. With a deadline, any
+//! connection still open when it expires is closed forcibly.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! # #[cfg(all(feature = "http1", feature = "runtime"))]
+//! # mod rt {
+//! use std::convert::Infallible;
+//! use std::time::Duration;
+//!
+//! use http::{Request, Response};
+//! use http_body_util::Full;
+//! use hyper::body::{self, Bytes};
+//! use hyper::rt::{TokioIo, TokioTimer};
+//! use hyper::server::conn::http1;
+//! use hyper::server::graceful::GracefulShutdown;
+//! use hyper::service::service_fn;
+//! use tokio::net::TcpListener;
+//!
+//! # async fn run(
+//! #     listener: TcpListener,
+//! #     signal: impl std::future::Future<Output = ()>,
+//! # ) -> std::io::Result<()> {
+//! let mut graceful = GracefulShutdown::new();
+//! graceful.timer(TokioTimer);
+//!
+//! tokio::pin!(signal);
+//! loop {
+//!     tokio::select! {
+//!         conn = listener.accept() => {
+//!             let (stream, _) = conn?;
+//!             let conn = http1::Builder::new()
+//!                 .serve_connection(TokioIo::new(stream), service_fn(hello));
+//!             tokio::spawn(graceful.watch(conn));
+//!         }
+//!         _ = &mut signal => break,
+//!     }
+//! }
+//!
+//! // give open connections 30 seconds to finish
+//! graceful.shutdown_timeout(Duration::from_secs(30)).await;
+//! # Ok(())
+//! # }
+//!
+//! async fn hello(_req: Request<body::Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+//!     Ok(Response::new(Full::new(Bytes::from("Hello World!"))))
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::time::Duration;
+
+use pin_project_lite::pin_project;
+use tracing::{debug, trace};
+
+use crate::body::{Body, Incoming as IncomingBody};
+use crate::common::time::Time;
+use crate::common::{task, Future, Pin, Poll};
+use crate::rt::{Read, Sleep, Timer, Write};
+use crate::service::HttpService;
+
+/// A handle to gracefully shut down many connections at once.
+///
+/// Connections are registered with [`watch`](GracefulShutdown::watch).
+/// Clones of the handle share the same set of connections.
+#[derive(Clone)]
+pub struct GracefulShutdown {
+    shared: Arc<Shared>,
+    timer: Time,
+}
+
+/// A connection that can be shut down gracefully.
+///
+/// This trait is sealed, it is implemented by the connection futures of
+/// the [`conn`](super::conn) module.
+pub trait GracefulConnection: Future<Output = crate::Result<()>> + sealed::Sealed {
+    /// Start a graceful shutdown process for this connection.
+    fn graceful_shutdown(self: Pin<&mut Self>);
+}
+
+pin_project! {
+    /// A connection watched by a [`GracefulShutdown`].
+    ///
+    /// This future resolves with the output of the connection. If the
+    /// connection is closed forcibly because a shutdown deadline expired, it
+    /// resolves with an error for which
+    /// [`Error::is_canceled`](crate::Error::is_canceled) returns true.
+    #[must_use = "futures do nothing unless polled"]
+    pub struct Watched<C> {
+        #[pin]
+        conn: C,
+        shared: Arc<Shared>,
+        id: usize,
+        draining: bool,
+        done: bool,
+    }
+
+    impl<C> PinnedDrop for Watched<C> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if !*this.done {
+                this.shared.remove(*this.id);
+            }
+        }
+    }
+}
+
+/// A future that resolves once the watched connections have drained.
+///
+/// Returned by [`GracefulShutdown::shutdown`] and
+/// [`GracefulShutdown::shutdown_timeout`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Shutdown {
+    shared: Arc<Shared>,
+    deadline: Option<Pin<Box<dyn Sleep>>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+}
+
+struct State {
+    signal: Signal,
+    next_id: usize,
+    // The live connections, with the waker of their last poll.
+    conns: HashMap<usize, Option<Waker>>,
+    drain_wakers: Vec<Waker>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Signal {
+    Running,
+    Draining,
+    Closing,
+}
+
+// ===== impl GracefulShutdown =====
+
+impl GracefulShutdown {
+    /// Create a new handle with no connections.
+    pub fn new() -> GracefulShutdown {
+        GracefulShutdown {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    signal: Signal::Running,
+                    next_id: 0,
+                    conns: HashMap::new(),
+                    drain_wakers: Vec::new(),
+                }),
+            }),
+            timer: Time::Empty,
+        }
+    }
+
+    /// Set the timer used for shutdown deadlines.
+    pub fn timer<M>(&mut self, timer: M) -> &mut Self
+    where
+        M: Timer + Send + Sync + 'static,
+    {
+        self.timer = Time::Timer(Arc::new(timer));
+        self
+    }
+
+    /// Watch a connection, so that it is shut down along with the others.
+    ///
+    /// The returned future must be polled instead of the connection. A
+    /// connection watched after a shutdown has started begins shutting down
+    /// right away.
+    pub fn watch<C: GracefulConnection>(&self, conn: C) -> Watched<C> {
+        let id = self.shared.insert();
+        Watched {
+            conn,
+            shared: self.shared.clone(),
+            id,
+            draining: false,
+            done: false,
+        }
+    }
+
+    /// Returns the number of watched connections that are still open.
+    pub fn count(&self) -> usize {
+        self.shared.lock().conns.len()
+    }
+
+    /// Start a graceful shutdown of all watched connections.
+    ///
+    /// The returned future resolves once every connection has closed.
+    pub fn shutdown(&self) -> Shutdown {
+        self.shared.signal(Signal::Draining);
+        Shutdown {
+            shared: self.shared.clone(),
+            deadline: None,
+        }
+    }
+
+    /// Start a graceful shutdown of all watched connections, with a deadline.
+    ///
+    /// The returned future resolves once every connection has closed, or
+    /// when `timeout` expires. Connections still open at that point are
+    /// closed forcibly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no [`timer`](GracefulShutdown::timer) has been set.
+    pub fn shutdown_timeout(&self, timeout: Duration) -> Shutdown {
+        let deadline = self.timer.sleep(timeout);
+        Shutdown {
+            deadline: Some(deadline),
+            ..self.shutdown()
+        }
+    }
+}
+
+impl Default for GracefulShutdown {
+    fn default() -> GracefulShutdown {
+        GracefulShutdown::new()
+    }
+}
+
+impl fmt::Debug for GracefulShutdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("GracefulShutdown")
+            .field("signal", &state.signal)
+            .field("count", &state.conns.len())
+            .finish()
+    }
+}
+
+// ===== impl Watched =====
+
+impl<C: GracefulConnection> Future for Watched<C> {
+    type Output = crate::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let mut me = self.project();
+
+        if !*me.done {
+            match me.shared.poll_signal(*me.id, cx) {
+                Signal::Running => {}
+                Signal::Draining => {
+                    if !*me.draining {
+                        *me.draining = true;
+                        me.conn.as_mut().graceful_shutdown();
+                    }
+                }
+                Signal::Closing => {
+                    debug!("shutdown deadline expired, closing connection");
+                    *me.done = true;
+                    me.shared.remove(*me.id);
+                    return Poll::Ready(Err(crate::Error::new_canceled()));
+                }
+            }
+        }
+
+        let res = ready!(me.conn.poll(cx));
+        if !*me.done {
+            *me.done = true;
+            me.shared.remove(*me.id);
+        }
+        Poll::Ready(res)
+    }
+}
+
+impl<C> fmt::Debug for Watched<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watched").finish()
+    }
+}
+
+// ===== impl Shutdown =====
+
+impl Future for Shutdown {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        if self.shared.poll_drained(cx).is_ready() {
+            return Poll::Ready(());
+        }
+
+        if let Some(ref mut deadline) = self.deadline {
+            ready!(Future::poll(deadline.as_mut(), cx));
+            debug!(
+                "shutdown deadline expired with {} connections open",
+                self.shared.lock().conns.len()
+            );
+            self.shared.signal(Signal::Closing);
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }
+}
+
+impl fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shutdown").finish()
+    }
+}
+
+// ===== impl Shared =====
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn insert(&self) -> usize {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id = state.next_id.wrapping_add(1);
+        state.conns.insert(id, None);
+        id
+    }
+
+    fn remove(&self, id: usize) {
+        let mut state = self.lock();
+        state.conns.remove(&id);
+        if state.conns.is_empty() {
+            for waker in state.drain_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+
+    fn signal(&self, signal: Signal) {
+        let mut state = self.lock();
+        if state.signal == signal || state.signal == Signal::Closing {
+            return;
+        }
+        trace!("graceful shutdown signal: {:?}", signal);
+        state.signal = signal;
+        if signal == Signal::Closing {
+            // Stragglers stop counting right away, they close once polled.
+            for waker in state.conns.drain().filter_map(|(_, waker)| waker) {
+                waker.wake();
+            }
+            for waker in state.drain_wakers.drain(..) {
+                waker.wake();
+            }
+        } else {
+            for waker in state.conns.values_mut().filter_map(Option::take) {
+                waker.wake();
+            }
+        }
+    }
+
+    fn poll_signal(&self, id: usize, cx: &mut task::Context<'_>) -> Signal {
+        let mut state = self.lock();
+        if state.signal != Signal::Closing {
+            if let Some(waker) = state.conns.get_mut(&id) {
+                match *waker {
+                    Some(ref w) if w.will_wake(cx.waker()) => {}
+                    _ => *waker = Some(cx.waker().clone()),
+                }
+            }
+        }
+        state.signal
+    }
+
+    fn poll_drained(&self, cx: &mut task::Context<'_>) -> Poll<()> {
+        let mut state = self.lock();
+        if state.conns.is_empty() {
+            return Poll::Ready(());
+        }
+        if !state.drain_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.drain_wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+// ===== impl GracefulConnection =====
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+#[cfg(feature = "http1")]
+impl<I, B, S> sealed::Sealed for super::conn::http1::Connection<I, S>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+}
+
+#[cfg(feature = "http1")]
+impl<I, B, S> GracefulConnection for super::conn::http1::Connection<I, S>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        super::conn::http1::Connection::graceful_shutdown(self)
+    }
+}
+
+#[cfg(feature = "http1")]
+impl<I, B, S> sealed::Sealed for super::conn::http1::upgrades::UpgradeableConnection<I, S>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + Send + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+}
+
+#[cfg(feature = "http1")]
+impl<I, B, S> GracefulConnection for super::conn::http1::upgrades::UpgradeableConnection<I, S>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + Send + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        super::conn::http1::upgrades::UpgradeableConnection::graceful_shutdown(self)
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<I, B, S, E> sealed::Sealed for super::conn::http2::Connection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: crate::rt::bounds::Http2ConnExec<S::Future, B>,
+{
+}
+
+#[cfg(feature = "http2")]
+impl<I, B, S, E> GracefulConnection for super::conn::http2::Connection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: crate::rt::bounds::Http2ConnExec<S::Future, B>,
+{
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        super::conn::http2::Connection::graceful_shutdown(self)
+    }
+}
+
+#[cfg(all(feature = "http1", feature = "http2"))]
+impl<I, B, S, E> sealed::Sealed for super::conn::auto::Connection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: crate::rt::bounds::Http2ConnExec<S::Future, B>,
+{
+}
+
+#[cfg(all(feature = "http1", feature = "http2"))]
+impl<I, B, S, E> GracefulConnection for super::conn::auto::Connection<I, S, E>
+where
+    S: HttpService<IncomingBody, ResBody = B>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: crate::rt::bounds::Http2ConnExec<S::Future, B>,
+{
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        super::conn::auto::Connection::graceful_shutdown(self)
+    }
+}
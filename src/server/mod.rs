@@ -8,3 +8,5 @@
 //! it with the types in the [`conn`](conn) module.
 pub mod conn;
 
+#[cfg(any(feature = "http1", feature = "http2"))]
+pub mod graceful;
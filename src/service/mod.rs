@@ -20,8 +20,13 @@
 //! The helper [`service_fn`](service_fn) should be sufficient for most cases, but
 //! if you need to implement `Service` for a type manually, you can follow the example
 //! in `service_struct_impl.rs`.
+//!
+//! To dispatch requests to different functions by method and path, use
+//! [`ServiceFns`](ServiceFns).
 
 mod http;
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "server"))]
+mod router;
 mod service;
 mod util;
 
@@ -33,5 +38,6 @@ pub use self::http::HttpService;
 ))]
 pub use self::service::Service;
 
+#[cfg(all(any(feature = "http1", feature = "http2"), feature = "server"))]
+pub use self::router::{Params, ServiceFns};
 pub use self::util::service_fn;
-pub use self::util::ServiceFns;
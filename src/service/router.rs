@@ -0,0 +1,617 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use http::header::{HeaderValue, ALLOW};
+use http::{Method, StatusCode};
+
+use crate::service::service::Service;
+use crate::{Request, Response};
+
+type BoxFuture<ResBody, E> = Pin<Box<dyn Future<Output = Result<Response<ResBody>, E>> + Send>>;
+
+type Handler<ReqBody, ResBody, E> =
+    Arc<dyn Fn(Request<ReqBody>) -> BoxFuture<ResBody, E> + Send + Sync>;
+
+type Fallback<ResBody> = Arc<dyn Fn() -> Response<ResBody> + Send + Sync>;
+
+/// A `Service` that routes requests to handler functions by method and path.
+///
+/// Paths are matched segment by segment, and may contain:
+///
+/// - literal segments, such as `/users/me`, which must match exactly.
+/// - parameter segments, such as `/users/{id}`, which match any non-empty
+///   segment and capture it under the given name.
+/// - a trailing `*`, such as `/static/*`, which matches the prefix and any
+///   remaining path. The remainder is captured under the name `*`.
+///
+/// Captured values are inserted into the request extensions as [`Params`].
+///
+/// When several paths match a request, the most specific one is used:
+/// exact paths are tried before ones with parameters, and prefixes are tried
+/// last, longest first. If a path matches but no handler was added for the
+/// request method, a `405 Method Not Allowed` response is returned, with an
+/// `Allow` header listing the methods that are routed. If no path matches,
+/// a `404 Not Found` response is returned. Both responses can be customized
+/// with [`not_found`](ServiceFns::not_found) and
+/// [`method_not_allowed`](ServiceFns::method_not_allowed).
+///
+/// # Example
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use bytes::Bytes;
+/// use http_body_util::Full;
+/// use hyper::service::{Params, ServiceFns};
+/// use hyper::{body, Method, Request, Response};
+///
+/// let mut router = ServiceFns::new();
+/// router
+///     .add(Method::GET, "/", |_req: Request<body::Incoming>| async {
+///         Ok::<_, Infallible>(Response::new(Full::<Bytes>::from("Hello World")))
+///     })
+///     .add(Method::GET, "/users/{id}", |req: Request<body::Incoming>| async move {
+///         let params = req.extensions().get::<Params>().unwrap();
+///         let body = format!("user {}", params.get("id").unwrap());
+///         Ok(Response::new(Full::from(body)))
+///     });
+/// ```
+pub struct ServiceFns<ReqBody, ResBody, E> {
+    routes: Vec<Route<ReqBody, ResBody, E>>,
+    not_found: Option<Fallback<ResBody>>,
+    method_not_allowed: Option<Fallback<ResBody>>,
+}
+
+struct Route<ReqBody, ResBody, E> {
+    pattern: Pattern,
+    methods: Vec<(Option<Method>, Handler<ReqBody, ResBody, E>)>,
+}
+
+/// Path parameters captured by [`ServiceFns`].
+///
+/// These are inserted as a request extension before the handler is called.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Params {
+    params: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    source: String,
+    segments: Vec<Segment>,
+    prefix: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Static(String),
+    Param(String),
+}
+
+// ===== impl ServiceFns =====
+
+impl<ReqBody, ResBody, E> ServiceFns<ReqBody, ResBody, E> {
+    /// Creates a new router without any routes.
+    pub fn new() -> Self {
+        ServiceFns {
+            routes: Vec::new(),
+            not_found: None,
+            method_not_allowed: None,
+        }
+    }
+
+    /// Routes requests with this method and path to a handler function.
+    ///
+    /// Adding a handler for a method and path that was already added
+    /// replaces the previous handler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path doesn't start with a `/`, contains an empty
+    /// `{}` parameter, or has a `*` anywhere but the last segment.
+    pub fn add<H, Fut>(&mut self, method: Method, path: &str, handler: H) -> &mut Self
+    where
+        H: Fn(Request<ReqBody>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, E>> + Send + 'static,
+    {
+        self.insert(Some(method), path, handler)
+    }
+
+    /// Routes requests with any method and this path to a handler function.
+    ///
+    /// Handlers added for a specific method with [`add`](ServiceFns::add)
+    /// take precedence over this one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is invalid, as with [`add`](ServiceFns::add).
+    pub fn add_any<H, Fut>(&mut self, path: &str, handler: H) -> &mut Self
+    where
+        H: Fn(Request<ReqBody>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, E>> + Send + 'static,
+    {
+        self.insert(None, path, handler)
+    }
+
+    /// Sets the response returned when no path matches the request.
+    ///
+    /// Default is an empty `404 Not Found` response.
+    pub fn not_found<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn() -> Response<ResBody> + Send + Sync + 'static,
+    {
+        self.not_found = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the response returned when a path matches the request, but
+    /// no handler was added for the request method.
+    ///
+    /// The `Allow` header is always set on this response.
+    ///
+    /// Default is an empty `405 Method Not Allowed` response.
+    pub fn method_not_allowed<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn() -> Response<ResBody> + Send + Sync + 'static,
+    {
+        self.method_not_allowed = Some(Arc::new(f));
+        self
+    }
+
+    fn insert<H, Fut>(&mut self, method: Option<Method>, path: &str, handler: H) -> &mut Self
+    where
+        H: Fn(Request<ReqBody>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, E>> + Send + 'static,
+    {
+        let handler: Handler<ReqBody, ResBody, E> =
+            Arc::new(move |req| Box::pin(handler(req)) as BoxFuture<ResBody, E>);
+
+        if let Some(route) = self.routes.iter_mut().find(|r| r.pattern.source == path) {
+            match route.methods.iter_mut().find(|(m, _)| *m == method) {
+                Some(existing) => existing.1 = handler,
+                None => route.methods.push((method, handler)),
+            }
+            return self;
+        }
+
+        self.routes.push(Route {
+            pattern: Pattern::parse(path),
+            methods: vec![(method, handler)],
+        });
+        // stable, so equally specific routes keep the order they were added
+        self.routes.sort_by_key(|r| r.pattern.specificity());
+        self
+    }
+}
+
+impl<ReqBody, ResBody, E> Service<Request<ReqBody>> for ServiceFns<ReqBody, ResBody, E>
+where
+    ResBody: Default + Send + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>> + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = E;
+    type Future = BoxFuture<ResBody, E>;
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let mut allowed = Vec::new();
+
+        for route in &self.routes {
+            let params = match route.pattern.matches(req.uri().path()) {
+                Some(params) => params,
+                None => continue,
+            };
+
+            if let Some(handler) = route.handler(req.method()) {
+                req.extensions_mut().insert(params);
+                return handler(req);
+            }
+
+            for (method, _) in &route.methods {
+                if let Some(method) = method {
+                    if !allowed.contains(method) {
+                        allowed.push(method.clone());
+                    }
+                }
+            }
+        }
+
+        let res = if allowed.is_empty() {
+            fallback(&self.not_found, StatusCode::NOT_FOUND)
+        } else {
+            let mut res = fallback(&self.method_not_allowed, StatusCode::METHOD_NOT_ALLOWED);
+            let allow = allowed
+                .iter()
+                .map(Method::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+            if let Ok(value) = HeaderValue::from_str(&allow) {
+                res.headers_mut().insert(ALLOW, value);
+            }
+            res
+        };
+
+        Box::pin(future::ready(Ok(res)))
+    }
+}
+
+fn fallback<ResBody: Default>(
+    f: &Option<Fallback<ResBody>>,
+    status: StatusCode,
+) -> Response<ResBody> {
+    match f {
+        Some(f) => f(),
+        None => {
+            let mut res = Response::new(ResBody::default());
+            *res.status_mut() = status;
+            res
+        }
+    }
+}
+
+impl<ReqBody, ResBody, E> Default for ServiceFns<ReqBody, ResBody, E> {
+    fn default() -> Self {
+        ServiceFns::new()
+    }
+}
+
+impl<ReqBody, ResBody, E> Clone for ServiceFns<ReqBody, ResBody, E> {
+    fn clone(&self) -> Self {
+        ServiceFns {
+            routes: self
+                .routes
+                .iter()
+                .map(|r| Route {
+                    pattern: r.pattern.clone(),
+                    methods: r.methods.clone(),
+                })
+                .collect(),
+            not_found: self.not_found.clone(),
+            method_not_allowed: self.method_not_allowed.clone(),
+        }
+    }
+}
+
+impl<ReqBody, ResBody, E> fmt::Debug for ServiceFns<ReqBody, ResBody, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Methods<'a, ReqBody, ResBody, E>(&'a Route<ReqBody, ResBody, E>);
+
+        impl<ReqBody, ResBody, E> fmt::Debug for Methods<'_, ReqBody, ResBody, E> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_list()
+                    .entries(self.0.methods.iter().map(|(m, _)| match m {
+                        Some(m) => m.as_str(),
+                        None => "*",
+                    }))
+                    .finish()
+            }
+        }
+
+        f.debug_map()
+            .entries(self.routes.iter().map(|r| (&r.pattern.source, Methods(r))))
+            .finish()
+    }
+}
+
+// ===== impl Route =====
+
+impl<ReqBody, ResBody, E> Route<ReqBody, ResBody, E> {
+    fn handler(&self, method: &Method) -> Option<&Handler<ReqBody, ResBody, E>> {
+        self.methods
+            .iter()
+            .find(|(m, _)| m.as_ref() == Some(method))
+            .or_else(|| self.methods.iter().find(|(m, _)| m.is_none()))
+            .map(|(_, handler)| handler)
+    }
+}
+
+// ===== impl Params =====
+
+impl Params {
+    /// Returns the value captured for a parameter name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns an iterator over the captured names and values, in the
+    /// order they appear in the path.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of captured parameters.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Returns `true` if no parameters were captured.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+// ===== impl Pattern =====
+
+impl Pattern {
+    fn parse(path: &str) -> Pattern {
+        let rest = match path.strip_prefix('/') {
+            Some(rest) => rest,
+            None => panic!("route path must start with '/': {:?}", path),
+        };
+
+        let mut segments = Vec::new();
+        let mut prefix = false;
+        let mut parts = rest.split('/').peekable();
+        while let Some(part) = parts.next() {
+            if part == "*" {
+                if parts.peek().is_some() {
+                    panic!("route wildcard must be the last segment: {:?}", path);
+                }
+                prefix = true;
+            } else if part.starts_with('{') && part.ends_with('}') {
+                let name = &part[1..part.len() - 1];
+                if name.is_empty() {
+                    panic!("route parameter must have a name: {:?}", path);
+                }
+                segments.push(Segment::Param(name.to_owned()));
+            } else {
+                segments.push(Segment::Static(part.to_owned()));
+            }
+        }
+
+        Pattern {
+            source: path.to_owned(),
+            segments,
+            prefix,
+        }
+    }
+
+    /// Sort key where lower is more specific.
+    fn specificity(&self) -> (bool, usize, std::cmp::Reverse<usize>) {
+        let params = self
+            .segments
+            .iter()
+            .filter(|s| matches!(s, Segment::Param(_)))
+            .count();
+        (self.prefix, params, std::cmp::Reverse(self.segments.len()))
+    }
+
+    fn matches(&self, path: &str) -> Option<Params> {
+        let mut params = Params::default();
+        let mut rest = Some(path.strip_prefix('/')?);
+
+        for segment in &self.segments {
+            let part = rest?;
+            let (part, next) = match part.find('/') {
+                Some(i) => (&part[..i], Some(&part[i + 1..])),
+                None => (part, None),
+            };
+
+            match segment {
+                Segment::Static(lit) => {
+                    if part != lit {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    if part.is_empty() {
+                        return None;
+                    }
+                    params.params.push((name.clone(), part.to_owned()));
+                }
+            }
+
+            rest = next;
+        }
+
+        if self.prefix {
+            params
+                .params
+                .push(("*".to_owned(), rest.unwrap_or("").to_owned()));
+            Some(params)
+        } else if rest.is_none() {
+            Some(params)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use http::header::ALLOW;
+    use http::{Method, Request, Response, StatusCode};
+
+    use super::{Params, Pattern, ServiceFns};
+    use crate::service::service::Service;
+
+    fn params(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+        Pattern::parse(pattern).matches(path).map(|p| {
+            p.iter()
+                .map(|(n, v)| (n.to_owned(), v.to_owned()))
+                .collect()
+        })
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn pattern_exact() {
+        assert_eq!(params("/", "/"), pairs(&[]));
+        assert_eq!(params("/", "/a"), None);
+        assert_eq!(params("/users", "/users"), pairs(&[]));
+        assert_eq!(params("/users", "/users/"), None);
+        assert_eq!(params("/users", "/user"), None);
+        assert_eq!(params("/users/me", "/users"), None);
+    }
+
+    #[test]
+    fn pattern_params() {
+        assert_eq!(params("/users/{id}", "/users/42"), pairs(&[("id", "42")]));
+        assert_eq!(
+            params("/users/{id}/posts/{post}", "/users/42/posts/7"),
+            pairs(&[("id", "42"), ("post", "7")])
+        );
+        assert_eq!(params("/users/{id}", "/users/"), None);
+        assert_eq!(params("/users/{id}", "/users/42/posts"), None);
+    }
+
+    #[test]
+    fn pattern_prefix() {
+        assert_eq!(params("/static/*", "/static"), pairs(&[("*", "")]));
+        assert_eq!(params("/static/*", "/static/"), pairs(&[("*", "")]));
+        assert_eq!(
+            params("/static/*", "/static/css/site.css"),
+            pairs(&[("*", "css/site.css")])
+        );
+        assert_eq!(params("/static/*", "/staticfiles"), None);
+        assert_eq!(
+            params("/*", "/anything/at/all"),
+            pairs(&[("*", "anything/at/all")])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn pattern_wildcard_not_last() {
+        Pattern::parse("/static/*/nope");
+    }
+
+    #[test]
+    #[should_panic]
+    fn pattern_missing_slash() {
+        Pattern::parse("users");
+    }
+
+    fn router() -> ServiceFns<&'static str, String, Infallible> {
+        fn reply(
+            body: &'static str,
+        ) -> impl Fn(Request<&'static str>) -> std::future::Ready<Result<Response<String>, Infallible>>
+        {
+            move |req| {
+                let params = req.extensions().get::<Params>().expect("params");
+                let params = params
+                    .iter()
+                    .map(|(n, v)| format!("{}={}", n, v))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                std::future::ready(Ok(Response::new(format!("{} {}", body, params))))
+            }
+        }
+
+        let mut router = ServiceFns::new();
+        router
+            .add(Method::GET, "/users/{id}", reply("get user"))
+            .add(Method::GET, "/users/me", reply("get me"))
+            .add(Method::DELETE, "/users/{id}", reply("delete user"))
+            .add(Method::POST, "/users", reply("create user"))
+            .add_any("/static/*", reply("static"))
+            .add(Method::POST, "/static/upload", reply("upload"));
+        router
+    }
+
+    async fn call(
+        router: &mut ServiceFns<&'static str, String, Infallible>,
+        method: Method,
+        path: &str,
+    ) -> Response<String> {
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body("")
+            .unwrap();
+        router.call(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn routes_by_method_and_path() {
+        let mut router = router();
+
+        let res = call(&mut router, Method::GET, "/users/42").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.body(), "get user id=42");
+
+        let res = call(&mut router, Method::DELETE, "/users/42").await;
+        assert_eq!(res.body(), "delete user id=42");
+
+        let res = call(&mut router, Method::POST, "/users").await;
+        assert_eq!(res.body(), "create user ");
+    }
+
+    #[tokio::test]
+    async fn most_specific_route_wins() {
+        let mut router = router();
+
+        let res = call(&mut router, Method::GET, "/users/me").await;
+        assert_eq!(res.body(), "get me ");
+
+        // a less specific route still handles methods the exact one doesn't
+        let res = call(&mut router, Method::DELETE, "/users/me").await;
+        assert_eq!(res.body(), "delete user id=me");
+
+        let res = call(&mut router, Method::POST, "/static/upload").await;
+        assert_eq!(res.body(), "upload ");
+
+        let res = call(&mut router, Method::GET, "/static/upload").await;
+        assert_eq!(res.body(), "static *=upload");
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let mut router = router();
+
+        let res = call(&mut router, Method::GET, "/nope").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "");
+
+        router.not_found(|| Response::new("custom not found".to_owned()));
+        let res = call(&mut router, Method::GET, "/nope").await;
+        assert_eq!(res.body(), "custom not found");
+    }
+
+    #[tokio::test]
+    async fn method_not_allowed() {
+        let mut router = router();
+
+        let res = call(&mut router, Method::PUT, "/users/42").await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers()[ALLOW], "GET, DELETE");
+
+        router.method_not_allowed(|| {
+            Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .body("custom not allowed".to_owned())
+                .unwrap()
+        });
+        let res = call(&mut router, Method::PUT, "/users/me").await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.body(), "custom not allowed");
+        assert_eq!(res.headers()[ALLOW], "GET, DELETE");
+    }
+
+    #[tokio::test]
+    async fn replaces_existing_handler() {
+        let mut router = router();
+        router.add(Method::POST, "/users", |_req| async {
+            Ok(Response::new("replaced".to_owned()))
+        });
+
+        let res = call(&mut router, Method::POST, "/users").await;
+        assert_eq!(res.body(), "replaced");
+    }
+}
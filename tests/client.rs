@@ -16,6 +16,7 @@ use http::uri::PathAndQuery;
 use http_body_util::{BodyExt, StreamBody};
 use hyper::body::Frame;
 use hyper::header::HeaderValue;
+use hyper::rt::TokioIo;
 use hyper::{Method, Request, StatusCode, Uri, Version};
 
 use bytes::Bytes;
@@ -36,8 +37,8 @@ where
     b.collect().await.map(|c| c.to_bytes())
 }
 
-fn tcp_connect(addr: &SocketAddr) -> impl Future<Output = std::io::Result<TcpStream>> {
-    TcpStream::connect(*addr)
+async fn tcp_connect(addr: &SocketAddr) -> std::io::Result<TokioIo<TcpStream>> {
+    TcpStream::connect(*addr).await.map(TokioIo::new)
 }
 
 struct HttpInfo {
@@ -312,7 +313,7 @@ macro_rules! test {
                 req.headers_mut().append("Host", HeaderValue::from_str(&host).unwrap());
             }
 
-            let (mut sender, conn) = builder.handshake(stream).await?;
+            let (mut sender, conn) = builder.handshake(TokioIo::new(stream)).await?;
 
             tokio::task::spawn(async move {
                 if let Err(err) = conn.await {
@@ -1330,6 +1331,7 @@ mod conn {
     use std::io::{self, Read, Write};
     use std::net::{SocketAddr, TcpListener};
     use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
     use std::task::{Context, Poll};
     use std::thread;
     use std::time::Duration;
@@ -1337,13 +1339,14 @@ mod conn {
     use bytes::{Buf, Bytes};
     use futures_channel::{mpsc, oneshot};
     use futures_util::future::{self, poll_fn, FutureExt, TryFutureExt};
-    use http_body_util::{BodyExt, Empty, StreamBody};
-    use hyper::rt::Timer;
-    use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, ReadBuf};
+    use http_body_util::{BodyExt, Empty, Full, StreamBody};
+    use hyper::rt::{Timer, TokioIo};
+    use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWriteExt as _};
     use tokio::net::{TcpListener as TkTcpListener, TcpStream};
 
     use hyper::body::{Body, Frame};
     use hyper::client::conn;
+    use hyper::observe::{Event, EventKind};
     use hyper::upgrade::OnUpgrade;
     use hyper::{Method, Request, Response, StatusCode};
 
@@ -1773,11 +1776,14 @@ mod conn {
         }
 
         let parts = conn.into_parts();
-        let mut io = parts.io;
+        let mut io = TokioIo::new(parts.io);
         let buf = parts.read_buf;
 
         assert_eq!(buf, b"foobar=ready"[..]);
-        assert!(!io.shutdown_called, "upgrade shouldn't shutdown AsyncWrite");
+        assert!(
+            !io.inner().shutdown_called,
+            "upgrade shouldn't shutdown AsyncWrite"
+        );
         rt.block_on(poll_fn(|ctx| {
             let ready = client.poll_ready(ctx);
             assert_matches!(ready, Poll::Ready(Err(_)));
@@ -1861,11 +1867,14 @@ mod conn {
         }
 
         let parts = conn.into_parts();
-        let mut io = parts.io;
+        let mut io = TokioIo::new(parts.io);
         let buf = parts.read_buf;
 
         assert_eq!(buf, b"foobar=ready"[..]);
-        assert!(!io.shutdown_called, "tunnel shouldn't shutdown AsyncWrite");
+        assert!(
+            !io.inner().shutdown_called,
+            "tunnel shouldn't shutdown AsyncWrite"
+        );
 
         rt.block_on(poll_fn(|ctx| {
             let ready = client.poll_ready(ctx);
@@ -1895,6 +1904,7 @@ mod conn {
                 tokio::select! {
                     res = listener.accept() => {
                         let (stream, _) = res.unwrap();
+                        let stream = TokioIo::new(stream);
 
                         let service = service_fn(|_:Request<hyper::body::Incoming>| future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new())));
 
@@ -2077,7 +2087,7 @@ mod conn {
 
         // Spawn an HTTP2 server that reads the whole body and responds
         tokio::spawn(async move {
-            let sock = listener.accept().await.unwrap().0;
+            let sock = TokioIo::new(listener.accept().await.unwrap().0);
             hyper::server::conn::http2::Builder::new(TokioExecutor)
                 .timer(TokioTimer)
                 .serve_connection(
@@ -2166,7 +2176,7 @@ mod conn {
         let res = client.send_request(req).await.expect("send_request");
         assert_eq!(res.status(), StatusCode::OK);
 
-        let mut upgraded = hyper::upgrade::on(res).await.unwrap();
+        let mut upgraded = TokioIo::new(hyper::upgrade::on(res).await.unwrap());
 
         let mut vec = vec![];
         upgraded.read_to_end(&mut vec).await.unwrap();
@@ -2264,6 +2274,253 @@ mod conn {
         );
     }
 
+    #[tokio::test]
+    async fn http1_expect_continue_waits_for_100() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let head = read_head(&mut sock).await;
+            assert!(head.contains("expect: 100-continue\r\n"), "{:?}", head);
+
+            // nothing more until the server says to continue
+            let mut buf = [0; 5];
+            let early = tokio::time::timeout(Duration::from_millis(100), sock.read(&mut buf)).await;
+            assert!(early.is_err(), "body sent before 100 Continue");
+
+            sock.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .await
+                .unwrap();
+            sock.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+            sock.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .timer(TokioTimer)
+                .expect_continue_timeout(Duration::from_secs(10))
+                .handshake(io)
+                .await
+                .expect("http handshake");
+
+            tokio::spawn(async move {
+                let _ = conn.await;
+            });
+
+            let req = Request::post("/upload")
+                .header("expect", "100-continue")
+                .header("content-length", "5")
+                .body(Full::new(Bytes::from_static(b"hello")))
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_timeout_sends_body() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let head = read_head(&mut sock).await;
+            assert!(head.contains("expect: 100-continue\r\n"), "{:?}", head);
+
+            // never sends 100 Continue, the client gives up waiting
+            let mut buf = [0; 5];
+            sock.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+            sock.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .timer(TokioTimer)
+                .expect_continue_timeout(Duration::from_millis(50))
+                .handshake(io)
+                .await
+                .expect("http handshake");
+
+            tokio::spawn(async move {
+                let _ = conn.await;
+            });
+
+            let req = Request::post("/upload")
+                .header("expect", "100-continue")
+                .header("content-length", "5")
+                .body(Full::new(Bytes::from_static(b"hello")))
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::OK);
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_timeout_requires_timer() {
+        let (_listener, addr) = setup_tk_test_server().await;
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let err = conn::http1::Builder::new()
+            .expect_continue_timeout(Duration::from_millis(50))
+            .handshake::<_, Empty<Bytes>>(io)
+            .await
+            .expect_err("handshake without a timer");
+        assert!(err.is_user(), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn http1_expect_continue_rejected_skips_body() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            let head = read_head(&mut sock).await;
+            assert!(head.contains("expect: 100-continue\r\n"), "{:?}", head);
+
+            sock.write_all(b"HTTP/1.1 417 Expectation Failed\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+
+            // the client closes without sending the body
+            let mut rest = Vec::new();
+            sock.read_to_end(&mut rest).await.unwrap();
+            assert!(rest.is_empty(), "body sent: {:?}", rest);
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .timer(TokioTimer)
+                .expect_continue_timeout(Duration::from_secs(10))
+                .handshake(io)
+                .await
+                .expect("http handshake");
+
+            let conn = tokio::spawn(conn);
+
+            let req = Request::post("/upload")
+                .header("expect", "100-continue")
+                .header("content-length", "5")
+                .body(Full::new(Bytes::from_static(b"hello")))
+                .unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            assert_eq!(res.status(), StatusCode::EXPECTATION_FAILED);
+            drop(res);
+
+            conn.await.unwrap().expect("client conn");
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_max_response_body_size_rejects_content_length() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            read_head(&mut sock).await;
+            sock.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\n")
+                .await
+                .unwrap();
+        };
+
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .max_response_body_size(10)
+                .handshake(io)
+                .await
+                .expect("http handshake");
+
+            let conn = tokio::spawn(conn);
+
+            let req = Request::get("/").body(Empty::<Bytes>::new()).unwrap();
+            let err = client.send_request(req).await.expect_err("send_request");
+            assert_eq!(
+                err.to_string(),
+                "message body is larger than the configured maximum"
+            );
+
+            conn.await.unwrap().expect("client conn");
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn http1_observer_reports_exchange() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        let server = async move {
+            let mut sock = listener.accept().await.unwrap().0;
+            read_head(&mut sock).await;
+            sock.write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 3\r\n\r\nabc")
+                .await
+                .unwrap();
+        };
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let observed = events.clone();
+        let client = async move {
+            let io = tcp_connect(&addr).await.expect("tcp connect");
+            let (mut client, conn) = conn::http1::Builder::new()
+                .observer(move |event: &Event<'_>| {
+                    let mut events = observed.lock().unwrap();
+                    events.push((event.kind(), event.status(), event.bytes()));
+                })
+                .handshake(io)
+                .await
+                .expect("http handshake");
+
+            let conn = tokio::spawn(conn);
+
+            let req = Request::get("/a").body(Empty::<Bytes>::new()).unwrap();
+            let res = client.send_request(req).await.expect("send_request");
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, "abc");
+
+            drop(client);
+            conn.await.unwrap().expect("client conn");
+        };
+
+        future::join(server, client).await;
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (EventKind::ConnectionOpen, None, None),
+                (EventKind::RequestHead, None, None),
+                (EventKind::ResponseHead, Some(StatusCode::NOT_FOUND), None),
+                (EventKind::ResponseBodyFirstByte, None, None),
+                (EventKind::ResponseBodyEnd, None, Some(3)),
+                (EventKind::KeepAlive, None, None),
+                (EventKind::ConnectionClose, None, None),
+            ]
+        );
+    }
+
+    async fn read_head<T: AsyncRead + Unpin>(sock: &mut T) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            sock.read_exact(&mut byte).await.expect("read head");
+            head.push(byte[0]);
+        }
+        String::from_utf8(head).unwrap()
+    }
+
     async fn drain_til_eof<T: AsyncRead + Unpin>(mut sock: T) -> io::Result<()> {
         let mut buf = [0u8; 1024];
         loop {
@@ -2276,11 +2533,11 @@ mod conn {
     }
 
     struct DebugStream {
-        tcp: TcpStream,
+        tcp: TokioIo<TcpStream>,
         shutdown_called: bool,
     }
 
-    impl AsyncWrite for DebugStream {
+    impl hyper::rt::Write for DebugStream {
         fn poll_shutdown(
             mut self: Pin<&mut Self>,
             cx: &mut Context<'_>,
@@ -2305,17 +2562,245 @@ mod conn {
         }
     }
 
-    impl AsyncRead for DebugStream {
+    impl hyper::rt::Read for DebugStream {
         fn poll_read(
             mut self: Pin<&mut Self>,
             cx: &mut Context<'_>,
-            buf: &mut ReadBuf<'_>,
+            buf: hyper::rt::ReadBufCursor<'_>,
         ) -> Poll<io::Result<()>> {
             Pin::new(&mut self.tcp).poll_read(cx, buf)
         }
     }
 }
 
+mod pool {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures_core::Future;
+    use http_body_util::{BodyExt, Empty, Full};
+    use hyper::client::pool::{Client, Connect};
+    use hyper::rt::{Timer, TokioIo};
+    use hyper::service::service_fn;
+    use hyper::{Request, Response, Uri, Version};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::support::{TokioExecutor, TokioTimer};
+
+    #[derive(Clone)]
+    struct Connector {
+        connects: Arc<AtomicUsize>,
+    }
+
+    impl Connector {
+        fn new() -> Connector {
+            Connector {
+                connects: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn connects(&self) -> usize {
+            self.connects.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Connect for Connector {
+        type Io = TokioIo<TcpStream>;
+        type Error = std::io::Error;
+        type Future = Pin<Box<dyn Future<Output = std::io::Result<Self::Io>> + Send>>;
+
+        fn connect(&self, dst: &Uri) -> Self::Future {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            let addr = dst.authority().unwrap().to_string();
+            Box::pin(async move { TcpStream::connect(addr).await.map(TokioIo::new) })
+        }
+    }
+
+    async fn serve(http2: bool, delay: Duration) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (sock, _) = listener.accept().await.unwrap();
+                let sock = TokioIo::new(sock);
+                let service = service_fn(move |req: Request<hyper::body::Incoming>| async move {
+                    TokioTimer.sleep(delay).await;
+                    let body = req.uri().path().to_owned();
+                    Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(body))))
+                });
+                tokio::spawn(async move {
+                    let _ = if http2 {
+                        hyper::server::conn::http2::Builder::new(TokioExecutor)
+                            .serve_connection(sock, service)
+                            .await
+                    } else {
+                        hyper::server::conn::http1::Builder::new()
+                            .serve_connection(sock, service)
+                            .await
+                    };
+                });
+            }
+        });
+        addr
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> Request<Empty<Bytes>> {
+        Request::get(format!("http://{}{}", addr, path))
+            .body(Empty::new())
+            .unwrap()
+    }
+
+    async fn body(res: Response<hyper::body::Incoming>) -> Bytes {
+        res.into_body().collect().await.unwrap().to_bytes()
+    }
+
+    #[tokio::test]
+    async fn reuses_idle_http1_connection() {
+        let addr = serve(false, Duration::ZERO).await;
+        let connector = Connector::new();
+        let client = Client::builder(TokioExecutor).build(connector.clone());
+
+        for path in &["/a", "/b", "/c"] {
+            let res = client.request(get(addr, path)).await.unwrap();
+            assert_eq!(res.version(), Version::HTTP_11);
+            assert_eq!(body(res).await, path.as_bytes());
+            // let the connection go back to the pool
+            TokioTimer.sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(connector.connects(), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_http1_requests_open_connections() {
+        let addr = serve(false, Duration::from_millis(50)).await;
+        let connector = Connector::new();
+        let client = Client::builder(TokioExecutor).build(connector.clone());
+
+        let (a, b) = futures_util::future::join(
+            client.request(get(addr, "/a")),
+            client.request(get(addr, "/b")),
+        )
+        .await;
+        assert_eq!(body(a.unwrap()).await, "/a");
+        assert_eq!(body(b.unwrap()).await, "/b");
+
+        assert_eq!(connector.connects(), 2);
+    }
+
+    #[tokio::test]
+    async fn max_connections_per_host() {
+        let addr = serve(false, Duration::from_millis(50)).await;
+        let connector = Connector::new();
+        let client = Client::builder(TokioExecutor)
+            .pool_max_connections_per_host(1)
+            .build(connector.clone());
+
+        let reqs = (0..3).map(|i| {
+            let client = client.clone();
+            async move {
+                let res = client.request(get(addr, &format!("/{}", i))).await?;
+                Ok::<_, hyper::Error>(body(res).await)
+            }
+        });
+        let bodies = futures_util::future::try_join_all(reqs).await.unwrap();
+        assert_eq!(bodies, vec!["/0", "/1", "/2"]);
+
+        assert_eq!(connector.connects(), 1);
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_closes_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connector = Connector::new();
+        let client = Client::builder(TokioExecutor)
+            .timer(TokioTimer)
+            .pool_idle_timeout(Some(Duration::from_millis(50)))
+            .build(connector.clone());
+
+        let res = tokio::spawn(client.request(get(addr, "/")));
+
+        let (mut sock, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 1024];
+        let n = sock.read(&mut buf).await.unwrap();
+        assert!(buf[..n].ends_with(b"\r\n\r\n"));
+        tokio::io::AsyncWriteExt::write_all(
+            &mut sock,
+            b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let res = res.await.unwrap().unwrap();
+        assert_eq!(res.status(), 200);
+        drop(res);
+
+        // the idle connection is closed once the timeout passed
+        let n = tokio::time::timeout(Duration::from_secs(1), sock.read(&mut buf))
+            .await
+            .expect("idle connection closed")
+            .unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn http2_only_multiplexes() {
+        let addr = serve(true, Duration::from_millis(50)).await;
+        let connector = Connector::new();
+        let client = Client::builder(TokioExecutor)
+            .http2_only(true)
+            .build(connector.clone());
+
+        let (a, b) = futures_util::future::join(
+            client.request(get(addr, "/a")),
+            client.request(get(addr, "/b")),
+        )
+        .await;
+        let a = a.unwrap();
+        assert_eq!(a.version(), Version::HTTP_2);
+        assert_eq!(body(a).await, "/a");
+        assert_eq!(body(b.unwrap()).await, "/b");
+
+        let res = client.request(get(addr, "/c")).await.unwrap();
+        assert_eq!(body(res).await, "/c");
+
+        assert_eq!(connector.connects(), 1);
+    }
+
+    #[tokio::test]
+    async fn requires_absolute_uri() {
+        let client = Client::builder(TokioExecutor).build(Connector::new());
+        let req = Request::get("/relative")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let err = client.request(req).await.unwrap_err();
+        assert!(err.is_user(), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn connect_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let client = Client::builder(TokioExecutor)
+            .pool_max_connections_per_host(1)
+            .build(Connector::new());
+        let err = client.request(get(addr, "/")).await.unwrap_err();
+        assert!(err.is_connect(), "{:?}", err);
+
+        // the failed connection doesn't count towards the limit
+        let err = client.request(get(addr, "/")).await.unwrap_err();
+        assert!(err.is_connect(), "{:?}", err);
+    }
+}
+
 trait FutureHyperExt: TryFuture {
     fn expect(self, msg: &'static str) -> Pin<Box<dyn Future<Output = Self::Ok>>>;
 }
@@ -21,14 +21,15 @@ use h2::client::SendRequest;
 use h2::{RecvStream, SendStream};
 use http::header::{HeaderName, HeaderValue};
 use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full, StreamBody};
-use hyper::rt::Timer;
+use hyper::rt::{Timer, TokioIo};
 use support::{TokioExecutor, TokioTimer};
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener as TkTcpListener, TcpListener, TcpStream as TkTcpStream};
 
 use hyper::body::{Body, Incoming as IncomingBody};
+use hyper::observe::{Event, EventKind};
 use hyper::server::conn::{http1, http2};
+use hyper::server::graceful::GracefulShutdown;
 use hyper::service::{service_fn, Service};
 use hyper::{Method, Request, Response, StatusCode, Uri, Version};
 
@@ -958,6 +959,7 @@ async fn expect_continue_waits_for_body_poll() {
     });
 
     let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
 
     http1::Builder::new()
         .serve_connection(
@@ -979,6 +981,283 @@ async fn expect_continue_waits_for_body_poll() {
     child.join().expect("client thread");
 }
 
+#[tokio::test]
+async fn informational_responses_before_final() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write");
+
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        let expected = "\
+            HTTP/1.1 103 Early Hints\r\n\
+            link: </style.css>; rel=preload\r\n\
+            \r\n\
+            HTTP/1.1 103 Early Hints\r\n\
+            link: </script.js>; rel=preload\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+        ";
+        assert_eq!(&resp[..expected.len()], expected);
+    });
+
+    let late = Arc::new(Mutex::new(None));
+    let late2 = late.clone();
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(
+            socket,
+            service_fn(move |req: Request<IncomingBody>| {
+                let info = req
+                    .extensions()
+                    .get::<hyper::ext::InformationalSender>()
+                    .expect("informational extension")
+                    .clone();
+                *late2.lock().unwrap() = Some(info.clone());
+                async move {
+                    let hint = |link: &'static str| {
+                        Response::builder()
+                            .status(103)
+                            .header("link", link)
+                            .body(())
+                            .unwrap()
+                    };
+                    info.send(hint("</style.css>; rel=preload")).unwrap();
+                    let err = info.send(Response::new(())).unwrap_err();
+                    assert!(err.is_user(), "{:?}", err);
+
+                    // the first hint is written while the service is pending
+                    TokioTimer.sleep(Duration::from_millis(10)).await;
+                    info.send(hint("</script.js>; rel=preload")).unwrap();
+                    Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                }
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+
+    // too late once the final response is sent
+    let late = late.lock().unwrap().take().unwrap();
+    let err = late.send(Response::new(())).unwrap_err();
+    assert!(err.is_user(), "{:?}", err);
+    let err = late
+        .send(Response::builder().status(103).body(()).unwrap())
+        .unwrap_err();
+    assert!(err.is_closed(), "{:?}", err);
+}
+
+#[tokio::test]
+async fn informational_100_continue_replaces_automatic() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 5\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write");
+
+        let expected = "HTTP/1.1 100 Continue\r\nx-ready: yes\r\n\r\n";
+        let mut buf = vec![0; expected.len()];
+        tcp.read_exact(&mut buf).expect("read 100");
+        assert_eq!(s(&buf), expected);
+
+        tcp.write_all(b"hello").expect("write body");
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                let res = Response::builder()
+                    .status(StatusCode::CONTINUE)
+                    .header("x-ready", "yes")
+                    .body(())
+                    .unwrap();
+                req.extensions()
+                    .get::<hyper::ext::InformationalSender>()
+                    .unwrap()
+                    .send(res)
+                    .unwrap();
+                let body = req.into_body().collect().await?.to_bytes();
+                assert_eq!(body, "hello");
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn informational_unsupported_for_http10() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GET / HTTP/1.0\r\n\r\n").expect("write");
+
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        assert!(resp.starts_with("HTTP/1.0 200 OK\r\n"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    http1::Builder::new()
+        .serve_connection(
+            TokioIo::new(socket),
+            service_fn(|req: Request<IncomingBody>| async move {
+                let res = Response::builder().status(103).body(()).unwrap();
+                let err = req
+                    .extensions()
+                    .get::<hyper::ext::InformationalSender>()
+                    .expect("informational extension")
+                    .send(res)
+                    .unwrap_err();
+                assert_eq!(
+                    err.to_string(),
+                    "informational responses are not supported by the connection"
+                );
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn expect_continue_policy_rejects() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 100\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write");
+
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        assert!(
+            resp.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "{:?}",
+            resp
+        );
+        assert!(resp.contains("connection: close\r\n"), "{:?}", resp);
+        assert!(resp.contains("content-length: 0\r\n"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .expect_continue(|req| {
+            let len = req.headers()["content-length"].to_str().unwrap();
+            if len.parse::<u64>().unwrap() > 10 {
+                Err(StatusCode::PAYLOAD_TOO_LARGE)
+            } else {
+                Ok(())
+            }
+        })
+        .serve_connection(
+            socket,
+            service_fn(|_| async move {
+                Err::<Response<Empty<Bytes>>, _>("service shouldn't be called")
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[tokio::test]
+async fn expect_continue_policy_accepts() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST /upload HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 5\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write");
+
+        let expected = "HTTP/1.1 100 Continue\r\n\r\n";
+        let mut buf = vec![0; expected.len()];
+        tcp.read_exact(&mut buf).expect("read 100");
+        assert_eq!(s(&buf), expected);
+
+        tcp.write_all(b"hello").expect("write body");
+        let mut resp = String::new();
+        tcp.read_to_string(&mut resp).expect("read");
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", resp);
+    });
+
+    let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .expect_continue(|req| {
+            assert_eq!(req.method(), Method::POST);
+            assert_eq!(req.uri(), "/upload");
+            Ok(())
+        })
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                // The policy gets the head back to the request.
+                assert_eq!(req.uri(), "/upload");
+                assert_eq!(req.headers()["expect"], "100-continue");
+                let body = req.into_body().collect().await?.to_bytes();
+                assert_eq!(body, "hello");
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
 #[test]
 fn pipeline_disabled() {
     let server = serve();
@@ -1131,6 +1410,7 @@ async fn disable_keep_alive_mid_request() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     let srv = http1::Builder::new().serve_connection(socket, HelloWorld);
     future::try_select(srv, rx1)
         .then(|r| match r {
@@ -1177,6 +1457,7 @@ async fn disable_keep_alive_post_request() {
     let dropped = Dropped::new();
     let dropped2 = dropped.clone();
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     let transport = DebugStream {
         stream: socket,
         _debug: dropped2,
@@ -1206,6 +1487,7 @@ async fn empty_parse_eof_does_not_return_error() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     http1::Builder::new()
         .serve_connection(socket, HelloWorld)
         .await
@@ -1222,6 +1504,7 @@ async fn nonempty_parse_eof_returns_error() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     http1::Builder::new()
         .serve_connection(socket, HelloWorld)
         .await
@@ -1245,6 +1528,7 @@ async fn http1_allow_half_close() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     http1::Builder::new()
         .half_close(true)
         .serve_connection(
@@ -1272,6 +1556,7 @@ async fn disconnect_after_reading_request_before_responding() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     http1::Builder::new()
         .half_close(false)
         .serve_connection(
@@ -1303,6 +1588,7 @@ async fn returning_1xx_response_is_error() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     http1::Builder::new()
         .serve_connection(
             socket,
@@ -1367,6 +1653,7 @@ async fn header_read_timeout_slow_writes() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     let conn = http1::Builder::new()
         .timer(TokioTimer)
         .header_read_timeout(Duration::from_secs(5))
@@ -1442,6 +1729,7 @@ async fn header_read_timeout_slow_writes_multiple_requests() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     let conn = http1::Builder::new()
         .timer(TokioTimer)
         .header_read_timeout(Duration::from_secs(5))
@@ -1459,470 +1747,881 @@ async fn header_read_timeout_slow_writes_multiple_requests() {
 }
 
 #[tokio::test]
-async fn upgrades() {
+async fn idle_timeout_closes_kept_alive_connection() {
     let (listener, addr) = setup_tcp_listener();
-    let (tx, rx) = oneshot::channel();
 
     thread::spawn(move || {
         let mut tcp = connect(&addr);
+        // the idle timeout doesn't apply before the first request
+        thread::sleep(Duration::from_millis(700));
         tcp.write_all(
             b"\
             GET / HTTP/1.1\r\n\
-            Upgrade: foobar\r\n\
-            Connection: upgrade\r\n\
+            Host: example.domain\r\n\
             \r\n\
-            eagerly optimistic\
         ",
         )
         .expect("write 1");
         let mut buf = [0; 256];
-        tcp.read(&mut buf).expect("read 1");
-
-        let expected = "HTTP/1.1 101 Switching Protocols\r\n";
-        assert_eq!(s(&buf[..expected.len()]), expected);
-        let _ = tx.send(());
+        let n = tcp.read(&mut buf).expect("read 1");
+        assert!(s(&buf[..n]).starts_with("HTTP/1.1 200 OK\r\n"));
 
         let n = tcp.read(&mut buf).expect("read 2");
-        assert_eq!(s(&buf[..n]), "foo=bar");
-        tcp.write_all(b"bar=foo").expect("write 2");
+        assert_eq!(n, 0, "connection should be closed after idling");
     });
 
     let (socket, _) = listener.accept().await.unwrap();
-    let conn = http1::Builder::new().serve_connection(
-        socket,
-        service_fn(|_| {
-            let res = Response::builder()
-                .status(101)
-                .header("upgrade", "foobar")
-                .body(Empty::<Bytes>::new())
-                .unwrap();
-            future::ready(Ok::<_, hyper::Error>(res))
-        }),
-    );
-
-    let parts = conn.without_shutdown().await.unwrap();
-    assert_eq!(parts.read_buf, "eagerly optimistic");
-
-    // wait so that we don't write until other side saw 101 response
-    rx.await.unwrap();
-
-    let mut io = parts.io;
-    io.write_all(b"foo=bar").await.unwrap();
-    let mut vec = vec![];
-    io.read_to_end(&mut vec).await.unwrap();
-    assert_eq!(vec, b"bar=foo");
+    let socket = TokioIo::new(socket);
+    let conn = http1::Builder::new()
+        .timer(TokioTimer)
+        .idle_timeout(Duration::from_millis(500))
+        .serve_connection(
+            socket,
+            service_fn(|_| {
+                let res = Response::builder()
+                    .status(200)
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                future::ready(Ok::<_, hyper::Error>(res))
+            }),
+        );
+    let err = conn.await.expect_err("idle timeout");
+    assert!(err.is_timeout(), "{:?}", err);
 }
 
 #[tokio::test]
-async fn http_connect() {
+async fn min_receive_rate_closes_stalled_request_body() {
     let (listener, addr) = setup_tcp_listener();
-    let (tx, rx) = oneshot::channel();
 
     thread::spawn(move || {
         let mut tcp = connect(&addr);
         tcp.write_all(
             b"\
-            CONNECT localhost:80 HTTP/1.1\r\n\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Length: 100\r\n\
             \r\n\
-            eagerly optimistic\
+            hello\
         ",
         )
         .expect("write 1");
+        // stall, the rest of the body never arrives
         let mut buf = [0; 256];
-        tcp.read(&mut buf).expect("read 1");
-
-        let expected = "HTTP/1.1 200 OK\r\n";
-        assert_eq!(s(&buf[..expected.len()]), expected);
-        let _ = tx.send(());
-
-        let n = tcp.read(&mut buf).expect("read 2");
-        assert_eq!(s(&buf[..n]), "foo=bar");
-        tcp.write_all(b"bar=foo").expect("write 2");
+        let n = tcp.read(&mut buf).expect("read 1");
+        assert_eq!(n, 0, "connection should be closed");
     });
 
     let (socket, _) = listener.accept().await.unwrap();
-    let conn = http1::Builder::new().serve_connection(
-        socket,
-        service_fn(|_| {
-            let res = Response::builder()
-                .status(200)
-                .body(Empty::<Bytes>::new())
-                .unwrap();
-            future::ready(Ok::<_, hyper::Error>(res))
-        }),
-    );
-
-    let parts = conn.without_shutdown().await.unwrap();
-    assert_eq!(parts.read_buf, "eagerly optimistic");
-
-    // wait so that we don't write until other side saw 101 response
-    rx.await.unwrap();
-
-    let mut io = parts.io;
-    io.write_all(b"foo=bar").await.unwrap();
-    let mut vec = vec![];
-    io.read_to_end(&mut vec).await.unwrap();
-    assert_eq!(vec, b"bar=foo");
+    let socket = TokioIo::new(socket);
+    let conn = http1::Builder::new()
+        .timer(TokioTimer)
+        .min_receive_rate(1000, Duration::from_millis(200))
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                let err = req.into_body().collect().await.expect_err("body error");
+                assert!(err.is_timeout(), "{:?}", err);
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        );
+    let err = conn.await.expect_err("min receive rate");
+    assert!(err.is_timeout(), "{:?}", err);
 }
 
 #[tokio::test]
-async fn upgrades_new() {
-    use tokio::io::{AsyncReadExt, AsyncWriteExt};
-
+async fn h2_min_receive_rate_resets_stalled_stream() {
     let (listener, addr) = setup_tcp_listener();
-    let (read_101_tx, read_101_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let svc = service_fn(|req: Request<IncomingBody>| async move {
+            req.into_body().collect().await?;
+            Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+        });
+        http2::Builder::new(TokioExecutor)
+            .timer(TokioTimer)
+            .min_receive_rate(1000, Duration::from_millis(200))
+            .serve_connection(socket, svc)
+            .await
+            .unwrap();
+    });
 
-    thread::spawn(move || {
-        let mut tcp = connect(&addr);
-        tcp.write_all(
-            b"\
-            GET / HTTP/1.1\r\n\
-            Upgrade: foobar\r\n\
-            Connection: upgrade\r\n\
-            \r\n\
-            eagerly optimistic\
-        ",
-        )
-        .expect("write 1");
-        let mut buf = [0; 256];
-        tcp.read(&mut buf).expect("read 1");
+    let conn = connect_async(addr).await;
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
 
-        let response = s(&buf);
-        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
-        assert!(!has_header(&response, "content-length"));
-        let _ = read_101_tx.send(());
+    let mut h2 = h2.ready().await.unwrap();
+    let req = Request::post("http://localhost/").body(()).unwrap();
+    // the body stream is left open, but no data is ever sent
+    let (res, _body_tx) = h2.send_request(req, false).unwrap();
+    let err = res.await.expect_err("stream should be reset");
+    assert_eq!(err.reason(), Some(h2::Reason::CANCEL));
+}
 
-        let n = tcp.read(&mut buf).expect("read 2");
-        assert_eq!(s(&buf[..n]), "foo=bar");
-        tcp.write_all(b"bar=foo").expect("write 2");
-    });
+// Yields until the connection is waiting on a sleep of `timer`.
+#[cfg(feature = "test-util")]
+async fn wait_for_sleep(timer: &hyper::mock::MockTimer) {
+    while timer.pending_sleeps() == 0 {
+        tokio::task::yield_now().await;
+    }
+}
 
-    let (upgrades_tx, upgrades_rx) = mpsc::channel();
-    let svc = service_fn(move |req: Request<IncomingBody>| {
-        let on_upgrade = hyper::upgrade::on(req);
-        let _ = upgrades_tx.send(on_upgrade);
-        future::ok::<_, hyper::Error>(
-            Response::builder()
-                .status(101)
-                .header("upgrade", "foobar")
-                .body(Empty::<Bytes>::new())
-                .unwrap(),
-        )
-    });
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn http1_min_send_rate_closes_connection() {
+    use hyper::mock::{duplex, MockTimer};
 
-    let (socket, _) = listener.accept().await.unwrap();
-    http1::Builder::new()
-        .serve_connection(socket, svc)
-        .with_upgrades()
+    let (client, server) = duplex(64);
+    let timer = MockTimer::new();
+    let conn = http1::Builder::new()
+        .timer(timer.clone())
+        .min_send_rate(1000, Duration::from_secs(1))
+        .serve_connection(
+            server,
+            service_fn(|_| async {
+                let body = Full::new(Bytes::from(vec![b'x'; 16 * 1024]));
+                Ok::<_, hyper::Error>(Response::new(body))
+            }),
+        );
+    let conn = tokio::spawn(conn);
+
+    // the client sends a request, but never reads the response
+    let mut client = TokioIo::new(client);
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n")
         .await
         .unwrap();
 
-    let on_upgrade = upgrades_rx.recv().unwrap();
-
-    // wait so that we don't write until other side saw 101 response
-    read_101_rx.await.unwrap();
-
-    let upgraded = on_upgrade.await.expect("on_upgrade");
-    let parts = upgraded.downcast::<TkTcpStream>().unwrap();
-    assert_eq!(parts.read_buf, "eagerly optimistic");
+    wait_for_sleep(&timer).await;
+    timer.advance(Duration::from_secs(2));
 
-    let mut io = parts.io;
-    io.write_all(b"foo=bar").await.unwrap();
-    let mut vec = vec![];
-    io.read_to_end(&mut vec).await.unwrap();
-    assert_eq!(s(&vec), "bar=foo");
+    let err = conn
+        .await
+        .unwrap()
+        .expect_err("send rate should be too low");
+    assert!(err.is_timeout(), "{:?}", err);
+    assert_eq!(
+        err.to_string(),
+        "data transfer rate below the configured minimum: operation timed out"
+    );
 }
 
+#[cfg(feature = "test-util")]
 #[tokio::test]
-async fn upgrades_ignored() {
-    let (listener, addr) = setup_tcp_listener();
+async fn h2_min_send_rate_resets_stream() {
+    use hyper::mock::{duplex, MockTimer};
+
+    let (client, server) = duplex(64 * 1024);
+    let timer = MockTimer::new();
+    let conn = http2::Builder::new(TokioExecutor)
+        .timer(timer.clone())
+        .min_send_rate(1000, Duration::from_secs(1))
+        .serve_connection(
+            server,
+            service_fn(|_| async {
+                let body = Full::new(Bytes::from(vec![b'x'; 16 * 1024]));
+                Ok::<_, hyper::Error>(Response::new(body))
+            }),
+        );
+    tokio::spawn(conn);
 
+    // the client grants no capacity for the response body
+    let (mut h2, connection) = h2::client::Builder::new()
+        .initial_window_size(0)
+        .handshake::<_, Bytes>(TokioIo::new(client))
+        .await
+        .unwrap();
     tokio::spawn(async move {
-        let svc = service_fn(move |req: Request<IncomingBody>| {
-            assert_eq!(req.headers()["upgrade"], "yolo");
-            future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
-        });
-
-        loop {
-            let (socket, _) = listener.accept().await.unwrap();
-            tokio::task::spawn(async move {
-                http1::Builder::new()
-                    .serve_connection(socket, svc)
-                    .with_upgrades()
-                    .await
-                    .expect("server task");
-            });
-        }
+        connection.await.unwrap();
     });
 
-    let client = TestClient::new();
-    let url = format!("http://{}/", addr);
-
-    let make_req = || {
-        hyper::Request::builder()
-            .uri(&*url)
-            .header("upgrade", "yolo")
-            .header("connection", "upgrade")
-            .body(Empty::<Bytes>::new())
-            .expect("make_req")
-    };
+    let req = Request::get("http://localhost/").body(()).unwrap();
+    let (res, _) = h2.send_request(req, true).unwrap();
+    let res = res.await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
 
-    let res1 = client.request(make_req()).await.expect("req 1");
-    assert_eq!(res1.status(), 200);
-    drop(res1);
+    wait_for_sleep(&timer).await;
+    timer.advance(Duration::from_secs(2));
 
-    let res2 = client.request(make_req()).await.expect("req 2");
-    assert_eq!(res2.status(), 200);
+    let err = res.into_body().data().await.unwrap().unwrap_err();
+    assert_eq!(err.reason(), Some(h2::Reason::CANCEL));
 }
 
 #[tokio::test]
-async fn http_connect_new() {
+async fn max_request_body_size_rejects_content_length() {
     let (listener, addr) = setup_tcp_listener();
-    let (read_200_tx, read_200_rx) = oneshot::channel();
 
     thread::spawn(move || {
         let mut tcp = connect(&addr);
         tcp.write_all(
             b"\
-            CONNECT localhost HTTP/1.1\r\n\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Length: 100\r\n\
             \r\n\
-            eagerly optimistic\
         ",
         )
         .expect("write 1");
         let mut buf = [0; 256];
-        tcp.read(&mut buf).expect("read 1");
+        let n = tcp.read(&mut buf).expect("read 1");
+        assert!(s(&buf[..n]).starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    });
 
-        let expected = "HTTP/1.1 200 OK\r\n";
-        assert_eq!(s(&buf[..expected.len()]), expected);
-        let _ = read_200_tx.send(());
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let err = http1::Builder::new()
+        .max_request_body_size(10)
+        .serve_connection(
+            socket,
+            service_fn(
+                |_| -> future::Ready<Result<Response<Empty<Bytes>>, hyper::Error>> {
+                    panic!("service should not be called")
+                },
+            ),
+        )
+        .await
+        .expect_err("body too large");
+    assert_eq!(
+        err.to_string(),
+        "message body is larger than the configured maximum"
+    );
+}
 
-        let n = tcp.read(&mut buf).expect("read 2");
-        assert_eq!(s(&buf[..n]), "foo=bar");
-        tcp.write_all(b"bar=foo").expect("write 2");
-    });
+#[tokio::test]
+async fn max_request_body_size_errors_chunked_body() {
+    let (listener, addr) = setup_tcp_listener();
 
-    let (upgrades_tx, upgrades_rx) = mpsc::channel();
-    let svc = service_fn(move |req: Request<IncomingBody>| {
-        let on_upgrade = hyper::upgrade::on(req);
-        let _ = upgrades_tx.send(on_upgrade);
-        future::ok::<_, hyper::Error>(
-            Response::builder()
-                .status(200)
-                .body(Empty::<Bytes>::new())
-                .unwrap(),
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            8\r\n\
+            12345678\r\n\
+            8\r\n\
+            12345678\r\n\
+            0\r\n\
+            \r\n\
+        ",
         )
+        .expect("write 1");
+        let mut buf = [0; 256];
+        let _ = tcp.read(&mut buf);
     });
 
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     http1::Builder::new()
-        .serve_connection(socket, svc)
-        .with_upgrades()
+        .max_request_body_size(10)
+        .serve_connection(
+            socket,
+            service_fn(move |req: Request<IncomingBody>| {
+                let tx = tx.take().expect("one request");
+                async move {
+                    let res = req.into_body().collect().await;
+                    tx.send(res.map(|_| ())).unwrap();
+                    Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+                }
+            }),
+        )
         .await
-        .unwrap();
+        .expect_err("body too large");
 
-    let on_upgrade = upgrades_rx.recv().unwrap();
+    let err = rx.await.unwrap().expect_err("body error");
+    assert_eq!(
+        err.to_string(),
+        "error reading a body from connection: message body is larger than the configured maximum"
+    );
+}
 
-    // wait so that we don't write until other side saw 200
-    read_200_rx.await.unwrap();
+#[tokio::test]
+async fn h2_max_request_body_size_resets_stream() {
+    let (listener, addr) = setup_tcp_listener();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let svc = service_fn(
+            |_| -> future::Ready<Result<Response<Empty<Bytes>>, hyper::Error>> {
+                panic!("service should not be called")
+            },
+        );
+        http2::Builder::new(TokioExecutor)
+            .max_request_body_size(10)
+            .serve_connection(socket, svc)
+            .await
+            .unwrap();
+    });
 
-    let upgraded = on_upgrade.await.expect("on_upgrade");
-    let parts = upgraded.downcast::<TkTcpStream>().unwrap();
-    assert_eq!(parts.read_buf, "eagerly optimistic");
+    let conn = connect_async(addr).await;
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
 
-    let mut io = parts.io;
-    io.write_all(b"foo=bar").await.unwrap();
-    let mut vec = vec![];
-    io.read_to_end(&mut vec).await.unwrap();
-    assert_eq!(s(&vec), "bar=foo");
+    let mut h2 = h2.ready().await.unwrap();
+    let req = Request::post("http://localhost/")
+        .header("content-length", "100")
+        .body(())
+        .unwrap();
+    let (res, _body_tx) = h2.send_request(req, false).unwrap();
+    let err = res.await.expect_err("stream should be reset");
+    assert_eq!(err.reason(), Some(h2::Reason::CANCEL));
 }
 
 #[tokio::test]
-async fn h2_connect() {
+async fn h2_max_request_body_size_resets_streamed_body() {
     let (listener, addr) = setup_tcp_listener();
-    let conn = connect_async(addr).await;
+    let (errors_tx, errors_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let errors_tx = Arc::new(Mutex::new(Some(errors_tx)));
+        let svc = service_fn(move |req: Request<IncomingBody>| {
+            let errors_tx = errors_tx.clone();
+            async move {
+                let mut body = req.into_body();
+                let first = body.frame().await.unwrap().unwrap();
+                assert_eq!(first.into_data().unwrap(), "hello");
+                let err = body.frame().await.unwrap().unwrap_err();
+                // the error is latched
+                let again = body.frame().await.unwrap().unwrap_err();
+                let errors = (err.to_string(), again.to_string());
+                errors_tx
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .unwrap()
+                    .send(errors)
+                    .unwrap();
+                future::pending::<Result<Response<Empty<Bytes>>, hyper::Error>>().await
+            }
+        });
+        http2::Builder::new(TokioExecutor)
+            .max_request_body_size(10)
+            .serve_connection(socket, svc)
+            .await
+            .unwrap();
+    });
 
+    let conn = connect_async(addr).await;
     let (h2, connection) = h2::client::handshake(conn).await.unwrap();
     tokio::spawn(async move {
         connection.await.unwrap();
     });
-    let mut h2 = h2.ready().await.unwrap();
 
-    async fn connect_and_recv_bread(
-        h2: &mut SendRequest<Bytes>,
-    ) -> (RecvStream, SendStream<Bytes>) {
-        let request = Request::connect("localhost").body(()).unwrap();
-        let (response, send_stream) = h2.send_request(request, false).unwrap();
-        let response = response.await.unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+    let mut h2 = h2.ready().await.unwrap();
+    let req = Request::post("http://localhost/").body(()).unwrap();
+    let (res, mut body_tx) = h2.send_request(req, false).unwrap();
+    body_tx.send_data(Bytes::from("hello"), false).unwrap();
+    body_tx.send_data(Bytes::from("world!"), false).unwrap();
+    let err = res.await.expect_err("stream should be reset");
+    assert_eq!(err.reason(), Some(h2::Reason::CANCEL));
+
+    let (err, again) = errors_rx.await.unwrap();
+    assert_eq!(err, "message body is larger than the configured maximum");
+    assert_eq!(err, again);
+}
 
-        let mut body = response.into_body();
-        let bytes = body.data().await.unwrap().unwrap();
-        assert_eq!(&bytes[..], b"Bread?");
-        let _ = body.flow_control().release_capacity(bytes.len());
+#[tokio::test]
+async fn graceful_shutdown_drains_watched_connections() {
+    let (listener, addr) = setup_tcp_listener();
+    let graceful = GracefulShutdown::new();
+
+    let mut clients = Vec::new();
+    let mut conns = Vec::new();
+    for _ in 0..2 {
+        clients.push(connect_async(addr).await);
+        let (socket, _) = listener.accept().await.unwrap();
+        let conn = http1::Builder::new().serve_connection(TokioIo::new(socket), HelloWorld);
+        conns.push(tokio::spawn(graceful.watch(conn)));
+    }
+    for client in &mut clients {
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = [0; 256];
+        let n = client.read(&mut buf).await.unwrap();
+        assert!(s(&buf[..n]).starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+    assert_eq!(graceful.count(), 2);
 
-        (body, send_stream)
+    graceful.shutdown().await;
+    assert_eq!(graceful.count(), 0);
+    for conn in conns {
+        conn.await.unwrap().expect("graceful shutdown");
     }
+}
 
-    tokio::spawn(async move {
-        let (mut recv_stream, mut send_stream) = connect_and_recv_bread(&mut h2).await;
+#[tokio::test]
+async fn graceful_shutdown_timeout_closes_stalled_connections() {
+    let (listener, addr) = setup_tcp_listener();
+    let mut graceful = GracefulShutdown::new();
+    graceful.timer(TokioTimer);
 
-        send_stream.send_data("Baguette!".into(), true).unwrap();
+    let mut client = connect_async(addr).await;
+    client
+        .write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Length: 10\r\n\
+            \r\n\
+        ",
+        )
+        .await
+        .unwrap();
 
-        assert!(recv_stream.data().await.unwrap().unwrap().is_empty());
-    });
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    let (socket, _) = listener.accept().await.unwrap();
+    let conn = http1::Builder::new().serve_connection(
+        TokioIo::new(socket),
+        service_fn(move |req: Request<IncomingBody>| {
+            tx.take().expect("one request").send(()).unwrap();
+            async move {
+                req.into_body().collect().await?;
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }
+        }),
+    );
+    let conn = tokio::spawn(graceful.watch(conn));
 
-    let svc = service_fn(move |req: Request<IncomingBody>| {
-        let on_upgrade = hyper::upgrade::on(req);
+    // the request body never arrives
+    rx.await.unwrap();
+    graceful.shutdown_timeout(Duration::from_millis(100)).await;
+    assert_eq!(graceful.count(), 0);
 
-        tokio::spawn(async move {
-            let mut upgraded = on_upgrade.await.expect("on_upgrade");
-            upgraded.write_all(b"Bread?").await.unwrap();
+    let err = conn.await.unwrap().expect_err("force closed");
+    assert!(err.is_canceled(), "{:?}", err);
+}
 
-            let mut vec = vec![];
-            upgraded.read_to_end(&mut vec).await.unwrap();
-            assert_eq!(s(&vec), "Baguette!");
+#[tokio::test]
+async fn observer_reports_http1_events() {
+    let (listener, addr) = setup_tcp_listener();
 
-            upgraded.shutdown().await.unwrap();
-        });
-
-        future::ok::<_, hyper::Error>(
-            Response::builder()
-                .status(200)
-                .body(Empty::<Bytes>::new())
-                .unwrap(),
-        )
+    let client = tokio::spawn(async move {
+        let mut tcp = connect_async(addr).await;
+        for _ in 0..2 {
+            tcp.write_all(
+                b"\
+                POST /upload HTTP/1.1\r\n\
+                Host: example.domain\r\n\
+                Content-Length: 5\r\n\
+                \r\n\
+                hello\
+            ",
+            )
+            .await
+            .unwrap();
+            let mut res = Vec::new();
+            while !res.ends_with(b"world") {
+                let mut buf = [0; 256];
+                let n = tcp.read(&mut buf).await.unwrap();
+                assert_ne!(n, 0, "unexpected eof");
+                res.extend_from_slice(&buf[..n]);
+            }
+        }
     });
 
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observed = events.clone();
     let (socket, _) = listener.accept().await.unwrap();
-    http2::Builder::new(TokioExecutor)
-        .serve_connection(socket, svc)
-        //.with_upgrades()
+    http1::Builder::new()
+        .observer(move |event: &Event<'_>| {
+            observed.lock().unwrap().push((event.kind(), event.bytes()));
+            if event.kind() == EventKind::RequestHead {
+                assert_eq!(event.method(), Some(&Method::POST));
+                assert_eq!(event.uri().unwrap().path(), "/upload");
+            }
+        })
+        .serve_connection(
+            TokioIo::new(socket),
+            service_fn(|req: Request<IncomingBody>| async move {
+                req.into_body().collect().await?;
+                Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("world"))))
+            }),
+        )
         .await
         .unwrap();
+    client.await.unwrap();
+
+    let exchange = [
+        (EventKind::RequestHead, None),
+        (EventKind::RequestBodyFirstByte, None),
+        (EventKind::RequestBodyEnd, Some(5)),
+        (EventKind::ResponseHead, None),
+        (EventKind::ResponseBodyFirstByte, None),
+        (EventKind::ResponseBodyEnd, Some(5)),
+        (EventKind::KeepAlive, None),
+    ];
+    let mut expected = vec![(EventKind::ConnectionOpen, None)];
+    expected.extend_from_slice(&exchange);
+    expected.extend_from_slice(&exchange);
+    expected.push((EventKind::ConnectionClose, None));
+    assert_eq!(*events.lock().unwrap(), expected);
 }
 
 #[tokio::test]
-async fn h2_connect_multiplex() {
-    use futures_util::stream::FuturesUnordered;
-    use futures_util::StreamExt;
-
+async fn observer_reports_http2_stream_events() {
     let (listener, addr) = setup_tcp_listener();
-    let conn = connect_async(addr).await;
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observed = events.clone();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        http2::Builder::new(TokioExecutor)
+            .observer(move |event: &Event<'_>| {
+                assert_eq!(event.version(), Version::HTTP_2);
+                observed
+                    .lock()
+                    .unwrap()
+                    .push((event.kind(), event.stream_id(), event.bytes()));
+            })
+            .serve_connection(
+                TokioIo::new(socket),
+                service_fn(|req: Request<IncomingBody>| async move {
+                    req.into_body().collect().await?;
+                    Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from("world"))))
+                }),
+            )
+            .await
+            .unwrap();
+    });
 
+    let conn = connect_async(addr).await;
     let (h2, connection) = h2::client::handshake(conn).await.unwrap();
-    tokio::spawn(async move {
+    let connection = tokio::spawn(async move {
         connection.await.unwrap();
     });
+
     let mut h2 = h2.ready().await.unwrap();
+    let req = Request::post("http://localhost/").body(()).unwrap();
+    let (res, mut body_tx) = h2.send_request(req, false).unwrap();
+    body_tx.send_data(Bytes::from("hello"), true).unwrap();
+    let mut body = res.await.unwrap().into_body();
+    while let Some(chunk) = body.data().await {
+        chunk.unwrap();
+    }
+    drop((h2, body_tx, body));
+    connection.await.unwrap();
+    server.await.unwrap();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (EventKind::ConnectionOpen, None, None),
+            (EventKind::RequestHead, Some(1), None),
+            (EventKind::RequestBodyFirstByte, Some(1), None),
+            (EventKind::RequestBodyEnd, Some(1), Some(5)),
+            (EventKind::ResponseHead, Some(1), None),
+            (EventKind::ResponseBodyFirstByte, Some(1), None),
+            (EventKind::ResponseBodyEnd, Some(1), Some(5)),
+            (EventKind::ConnectionClose, None, None),
+        ]
+    );
+}
 
-    tokio::spawn(async move {
-        let mut streams = vec![];
-        for i in 0..80 {
-            let request = Request::connect(format!("localhost_{}", i % 4))
-                .body(())
-                .unwrap();
-            let (response, send_stream) = h2.send_request(request, false).unwrap();
-            streams.push((i, response, send_stream));
-        }
+#[tokio::test]
+async fn upgrades() {
+    let (listener, addr) = setup_tcp_listener();
+    let (tx, rx) = oneshot::channel();
 
-        let futures = streams
-            .into_iter()
-            .map(|(i, response, mut send_stream)| async move {
-                if i % 4 == 0 {
-                    return;
-                }
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Upgrade: foobar\r\n\
+            Connection: upgrade\r\n\
+            \r\n\
+            eagerly optimistic\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
 
-                let response = response.await.unwrap();
-                assert_eq!(response.status(), StatusCode::OK);
+        let expected = "HTTP/1.1 101 Switching Protocols\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+        let _ = tx.send(());
 
-                if i % 4 == 1 {
-                    return;
-                }
+        let n = tcp.read(&mut buf).expect("read 2");
+        assert_eq!(s(&buf[..n]), "foo=bar");
+        tcp.write_all(b"bar=foo").expect("write 2");
+    });
 
-                let mut body = response.into_body();
-                let bytes = body.data().await.unwrap().unwrap();
-                assert_eq!(&bytes[..], b"Bread?");
-                let _ = body.flow_control().release_capacity(bytes.len());
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let conn = http1::Builder::new().serve_connection(
+        socket,
+        service_fn(|_| {
+            let res = Response::builder()
+                .status(101)
+                .header("upgrade", "foobar")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            future::ready(Ok::<_, hyper::Error>(res))
+        }),
+    );
 
-                if i % 4 == 2 {
-                    return;
-                }
+    let parts = conn.without_shutdown().await.unwrap();
+    assert_eq!(parts.read_buf, "eagerly optimistic");
 
-                send_stream.send_data("Baguette!".into(), true).unwrap();
+    // wait so that we don't write until other side saw 101 response
+    rx.await.unwrap();
 
-                assert!(body.data().await.unwrap().unwrap().is_empty());
-            })
-            .collect::<FuturesUnordered<_>>();
+    let mut io = parts.io.into_inner();
+    io.write_all(b"foo=bar").await.unwrap();
+    let mut vec = vec![];
+    io.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(vec, b"bar=foo");
+}
 
-        futures.for_each(future::ready).await;
+#[tokio::test]
+async fn http_connect() {
+    let (listener, addr) = setup_tcp_listener();
+    let (tx, rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            CONNECT localhost:80 HTTP/1.1\r\n\
+            \r\n\
+            eagerly optimistic\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 200 OK\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+        let _ = tx.send(());
+
+        let n = tcp.read(&mut buf).expect("read 2");
+        assert_eq!(s(&buf[..n]), "foo=bar");
+        tcp.write_all(b"bar=foo").expect("write 2");
     });
 
-    let svc = service_fn(move |req: Request<IncomingBody>| {
-        let authority = req.uri().authority().unwrap().to_string();
-        let on_upgrade = hyper::upgrade::on(req);
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let conn = http1::Builder::new().serve_connection(
+        socket,
+        service_fn(|_| {
+            let res = Response::builder()
+                .status(200)
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            future::ready(Ok::<_, hyper::Error>(res))
+        }),
+    );
 
-        tokio::spawn(async move {
-            let upgrade_res = on_upgrade.await;
-            if authority == "localhost_0" {
-                assert!(upgrade_res.expect_err("upgrade cancelled").is_canceled());
-                return;
-            }
-            let mut upgraded = upgrade_res.expect("upgrade successful");
+    let parts = conn.without_shutdown().await.unwrap();
+    assert_eq!(parts.read_buf, "eagerly optimistic");
 
-            upgraded.write_all(b"Bread?").await.unwrap();
+    // wait so that we don't write until other side saw 101 response
+    rx.await.unwrap();
 
-            let mut vec = vec![];
-            let read_res = upgraded.read_to_end(&mut vec).await;
+    let mut io = parts.io.into_inner();
+    io.write_all(b"foo=bar").await.unwrap();
+    let mut vec = vec![];
+    io.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(vec, b"bar=foo");
+}
 
-            if authority == "localhost_1" || authority == "localhost_2" {
-                let err = read_res.expect_err("read failed");
-                assert_eq!(err.kind(), io::ErrorKind::Other);
-                assert_eq!(
-                    err.get_ref()
-                        .unwrap()
-                        .downcast_ref::<h2::Error>()
-                        .unwrap()
-                        .reason(),
-                    Some(h2::Reason::CANCEL),
-                );
-                return;
-            }
+#[tokio::test]
+async fn upgrades_new() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-            read_res.unwrap();
-            assert_eq!(s(&vec), "Baguette!");
+    let (listener, addr) = setup_tcp_listener();
+    let (read_101_tx, read_101_rx) = oneshot::channel();
 
-            upgraded.shutdown().await.unwrap();
-        });
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Upgrade: foobar\r\n\
+            Connection: upgrade\r\n\
+            \r\n\
+            eagerly optimistic\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let response = s(&buf);
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(!has_header(&response, "content-length"));
+        let _ = read_101_tx.send(());
+
+        let n = tcp.read(&mut buf).expect("read 2");
+        assert_eq!(s(&buf[..n]), "foo=bar");
+        tcp.write_all(b"bar=foo").expect("write 2");
+    });
 
+    let (upgrades_tx, upgrades_rx) = mpsc::channel();
+    let svc = service_fn(move |req: Request<IncomingBody>| {
+        let on_upgrade = hyper::upgrade::on(req);
+        let _ = upgrades_tx.send(on_upgrade);
         future::ok::<_, hyper::Error>(
             Response::builder()
-                .status(200)
+                .status(101)
+                .header("upgrade", "foobar")
                 .body(Empty::<Bytes>::new())
                 .unwrap(),
         )
     });
 
     let (socket, _) = listener.accept().await.unwrap();
-    http2::Builder::new(TokioExecutor)
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
         .serve_connection(socket, svc)
-        //.with_upgrades()
+        .with_upgrades()
         .await
         .unwrap();
+
+    let on_upgrade = upgrades_rx.recv().unwrap();
+
+    // wait so that we don't write until other side saw 101 response
+    read_101_rx.await.unwrap();
+
+    let upgraded = on_upgrade.await.expect("on_upgrade");
+    let parts = upgraded.downcast::<TokioIo<TkTcpStream>>().unwrap();
+    assert_eq!(parts.read_buf, "eagerly optimistic");
+
+    let mut io = parts.io.into_inner();
+    io.write_all(b"foo=bar").await.unwrap();
+    let mut vec = vec![];
+    io.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(s(&vec), "bar=foo");
 }
 
 #[tokio::test]
-async fn h2_connect_large_body() {
+async fn upgrades_ignored() {
     let (listener, addr) = setup_tcp_listener();
-    let conn = connect_async(addr).await;
 
-    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
     tokio::spawn(async move {
-        connection.await.unwrap();
-    });
-    let mut h2 = h2.ready().await.unwrap();
-
-    const NO_BREAD: &str = "All work and no bread makes nox a dull boy.\n";
+        let svc = service_fn(move |req: Request<IncomingBody>| {
+            assert_eq!(req.headers()["upgrade"], "yolo");
+            future::ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+        });
+
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let socket = TokioIo::new(socket);
+            tokio::task::spawn(async move {
+                http1::Builder::new()
+                    .serve_connection(socket, svc)
+                    .with_upgrades()
+                    .await
+                    .expect("server task");
+            });
+        }
+    });
+
+    let client = TestClient::new();
+    let url = format!("http://{}/", addr);
+
+    let make_req = || {
+        hyper::Request::builder()
+            .uri(&*url)
+            .header("upgrade", "yolo")
+            .header("connection", "upgrade")
+            .body(Empty::<Bytes>::new())
+            .expect("make_req")
+    };
+
+    let res1 = client.request(make_req()).await.expect("req 1");
+    assert_eq!(res1.status(), 200);
+    drop(res1);
+
+    let res2 = client.request(make_req()).await.expect("req 2");
+    assert_eq!(res2.status(), 200);
+}
+
+#[tokio::test]
+async fn http_connect_new() {
+    let (listener, addr) = setup_tcp_listener();
+    let (read_200_tx, read_200_rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            CONNECT localhost HTTP/1.1\r\n\
+            \r\n\
+            eagerly optimistic\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 200 OK\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+        let _ = read_200_tx.send(());
+
+        let n = tcp.read(&mut buf).expect("read 2");
+        assert_eq!(s(&buf[..n]), "foo=bar");
+        tcp.write_all(b"bar=foo").expect("write 2");
+    });
+
+    let (upgrades_tx, upgrades_rx) = mpsc::channel();
+    let svc = service_fn(move |req: Request<IncomingBody>| {
+        let on_upgrade = hyper::upgrade::on(req);
+        let _ = upgrades_tx.send(on_upgrade);
+        future::ok::<_, hyper::Error>(
+            Response::builder()
+                .status(200)
+                .body(Empty::<Bytes>::new())
+                .unwrap(),
+        )
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(socket, svc)
+        .with_upgrades()
+        .await
+        .unwrap();
+
+    let on_upgrade = upgrades_rx.recv().unwrap();
+
+    // wait so that we don't write until other side saw 200
+    read_200_rx.await.unwrap();
+
+    let upgraded = on_upgrade.await.expect("on_upgrade");
+    let parts = upgraded.downcast::<TokioIo<TkTcpStream>>().unwrap();
+    assert_eq!(parts.read_buf, "eagerly optimistic");
+
+    let mut io = parts.io.into_inner();
+    io.write_all(b"foo=bar").await.unwrap();
+    let mut vec = vec![];
+    io.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(s(&vec), "bar=foo");
+}
+
+#[tokio::test]
+async fn h2_connect() {
+    let (listener, addr) = setup_tcp_listener();
+    let conn = connect_async(addr).await;
+
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+    let mut h2 = h2.ready().await.unwrap();
 
     async fn connect_and_recv_bread(
         h2: &mut SendRequest<Bytes>,
@@ -1943,10 +2642,7 @@ async fn h2_connect_large_body() {
     tokio::spawn(async move {
         let (mut recv_stream, mut send_stream) = connect_and_recv_bread(&mut h2).await;
 
-        let large_body = Bytes::from(NO_BREAD.repeat(9000));
-
-        send_stream.send_data(large_body.clone(), false).unwrap();
-        send_stream.send_data(large_body, true).unwrap();
+        send_stream.send_data("Baguette!".into(), true).unwrap();
 
         assert!(recv_stream.data().await.unwrap().unwrap().is_empty());
     });
@@ -1955,14 +2651,122 @@ async fn h2_connect_large_body() {
         let on_upgrade = hyper::upgrade::on(req);
 
         tokio::spawn(async move {
-            let mut upgraded = on_upgrade.await.expect("on_upgrade");
+            let mut upgraded = TokioIo::new(on_upgrade.await.expect("on_upgrade"));
             upgraded.write_all(b"Bread?").await.unwrap();
 
             let mut vec = vec![];
-            if upgraded.read_to_end(&mut vec).await.is_err() {
+            upgraded.read_to_end(&mut vec).await.unwrap();
+            assert_eq!(s(&vec), "Baguette!");
+
+            upgraded.shutdown().await.unwrap();
+        });
+
+        future::ok::<_, hyper::Error>(
+            Response::builder()
+                .status(200)
+                .body(Empty::<Bytes>::new())
+                .unwrap(),
+        )
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http2::Builder::new(TokioExecutor)
+        .serve_connection(socket, svc)
+        //.with_upgrades()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn h2_connect_multiplex() {
+    use futures_util::stream::FuturesUnordered;
+    use futures_util::StreamExt;
+
+    let (listener, addr) = setup_tcp_listener();
+    let conn = connect_async(addr).await;
+
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+    let mut h2 = h2.ready().await.unwrap();
+
+    tokio::spawn(async move {
+        let mut streams = vec![];
+        for i in 0..80 {
+            let request = Request::connect(format!("localhost_{}", i % 4))
+                .body(())
+                .unwrap();
+            let (response, send_stream) = h2.send_request(request, false).unwrap();
+            streams.push((i, response, send_stream));
+        }
+
+        let futures = streams
+            .into_iter()
+            .map(|(i, response, mut send_stream)| async move {
+                if i % 4 == 0 {
+                    return;
+                }
+
+                let response = response.await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+
+                if i % 4 == 1 {
+                    return;
+                }
+
+                let mut body = response.into_body();
+                let bytes = body.data().await.unwrap().unwrap();
+                assert_eq!(&bytes[..], b"Bread?");
+                let _ = body.flow_control().release_capacity(bytes.len());
+
+                if i % 4 == 2 {
+                    return;
+                }
+
+                send_stream.send_data("Baguette!".into(), true).unwrap();
+
+                assert!(body.data().await.unwrap().unwrap().is_empty());
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        futures.for_each(future::ready).await;
+    });
+
+    let svc = service_fn(move |req: Request<IncomingBody>| {
+        let authority = req.uri().authority().unwrap().to_string();
+        let on_upgrade = hyper::upgrade::on(req);
+
+        tokio::spawn(async move {
+            let upgrade_res = on_upgrade.await;
+            if authority == "localhost_0" {
+                assert!(upgrade_res.expect_err("upgrade cancelled").is_canceled());
                 return;
             }
-            assert_eq!(vec.len(), NO_BREAD.len() * 9000 * 2);
+            let mut upgraded = TokioIo::new(upgrade_res.expect("upgrade successful"));
+
+            upgraded.write_all(b"Bread?").await.unwrap();
+
+            let mut vec = vec![];
+            let read_res = upgraded.read_to_end(&mut vec).await;
+
+            if authority == "localhost_1" || authority == "localhost_2" {
+                let err = read_res.expect_err("read failed");
+                assert_eq!(err.kind(), io::ErrorKind::Other);
+                assert_eq!(
+                    err.get_ref()
+                        .unwrap()
+                        .downcast_ref::<h2::Error>()
+                        .unwrap()
+                        .reason(),
+                    Some(h2::Reason::CANCEL),
+                );
+                return;
+            }
+
+            read_res.unwrap();
+            assert_eq!(s(&vec), "Baguette!");
 
             upgraded.shutdown().await.unwrap();
         });
@@ -1976,164 +2780,1011 @@ async fn h2_connect_large_body() {
     });
 
     let (socket, _) = listener.accept().await.unwrap();
-    http2::Builder::new(TokioExecutor)
-        .serve_connection(socket, svc)
-        //.with_upgrades()
+    let socket = TokioIo::new(socket);
+    http2::Builder::new(TokioExecutor)
+        .serve_connection(socket, svc)
+        //.with_upgrades()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn h2_connect_large_body() {
+    let (listener, addr) = setup_tcp_listener();
+    let conn = connect_async(addr).await;
+
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+    let mut h2 = h2.ready().await.unwrap();
+
+    const NO_BREAD: &str = "All work and no bread makes nox a dull boy.\n";
+
+    async fn connect_and_recv_bread(
+        h2: &mut SendRequest<Bytes>,
+    ) -> (RecvStream, SendStream<Bytes>) {
+        let request = Request::connect("localhost").body(()).unwrap();
+        let (response, send_stream) = h2.send_request(request, false).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut body = response.into_body();
+        let bytes = body.data().await.unwrap().unwrap();
+        assert_eq!(&bytes[..], b"Bread?");
+        let _ = body.flow_control().release_capacity(bytes.len());
+
+        (body, send_stream)
+    }
+
+    tokio::spawn(async move {
+        let (mut recv_stream, mut send_stream) = connect_and_recv_bread(&mut h2).await;
+
+        let large_body = Bytes::from(NO_BREAD.repeat(9000));
+
+        send_stream.send_data(large_body.clone(), false).unwrap();
+        send_stream.send_data(large_body, true).unwrap();
+
+        assert!(recv_stream.data().await.unwrap().unwrap().is_empty());
+    });
+
+    let svc = service_fn(move |req: Request<IncomingBody>| {
+        let on_upgrade = hyper::upgrade::on(req);
+
+        tokio::spawn(async move {
+            let mut upgraded = TokioIo::new(on_upgrade.await.expect("on_upgrade"));
+            upgraded.write_all(b"Bread?").await.unwrap();
+
+            let mut vec = vec![];
+            if upgraded.read_to_end(&mut vec).await.is_err() {
+                return;
+            }
+            assert_eq!(vec.len(), NO_BREAD.len() * 9000 * 2);
+
+            upgraded.shutdown().await.unwrap();
+        });
+
+        future::ok::<_, hyper::Error>(
+            Response::builder()
+                .status(200)
+                .body(Empty::<Bytes>::new())
+                .unwrap(),
+        )
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http2::Builder::new(TokioExecutor)
+        .serve_connection(socket, svc)
+        //.with_upgrades()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn h2_connect_empty_frames() {
+    let (listener, addr) = setup_tcp_listener();
+    let conn = connect_async(addr).await;
+
+    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+    let mut h2 = h2.ready().await.unwrap();
+
+    async fn connect_and_recv_bread(
+        h2: &mut SendRequest<Bytes>,
+    ) -> (RecvStream, SendStream<Bytes>) {
+        let request = Request::connect("localhost").body(()).unwrap();
+        let (response, send_stream) = h2.send_request(request, false).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut body = response.into_body();
+        let bytes = body.data().await.unwrap().unwrap();
+        assert_eq!(&bytes[..], b"Bread?");
+        let _ = body.flow_control().release_capacity(bytes.len());
+
+        (body, send_stream)
+    }
+
+    tokio::spawn(async move {
+        let (mut recv_stream, mut send_stream) = connect_and_recv_bread(&mut h2).await;
+
+        send_stream.send_data("".into(), false).unwrap();
+        send_stream.send_data("".into(), false).unwrap();
+        send_stream.send_data("".into(), false).unwrap();
+        send_stream.send_data("Baguette!".into(), false).unwrap();
+        send_stream.send_data("".into(), true).unwrap();
+
+        assert!(recv_stream.data().await.unwrap().unwrap().is_empty());
+    });
+
+    let svc = service_fn(move |req: Request<IncomingBody>| {
+        let on_upgrade = hyper::upgrade::on(req);
+
+        tokio::spawn(async move {
+            let mut upgraded = TokioIo::new(on_upgrade.await.expect("on_upgrade"));
+            upgraded.write_all(b"Bread?").await.unwrap();
+
+            let mut vec = vec![];
+            upgraded.read_to_end(&mut vec).await.unwrap();
+            assert_eq!(s(&vec), "Baguette!");
+
+            upgraded.shutdown().await.unwrap();
+        });
+
+        future::ok::<_, hyper::Error>(
+            Response::builder()
+                .status(200)
+                .body(Empty::<Bytes>::new())
+                .unwrap(),
+        )
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http2::Builder::new(TokioExecutor)
+        .serve_connection(socket, svc)
+        //.with_upgrades()
+        .await
+        .unwrap();
+}
+
+async fn serve_h2_push<B, F>(listener: TkTcpListener, push_body: F)
+where
+    B: Body + Send + 'static,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    F: Fn() -> B + Clone + Send + 'static,
+{
+    let svc = service_fn(move |req: Request<IncomingBody>| {
+        let push_body = push_body.clone();
+        async move {
+            let pusher = req
+                .extensions()
+                .get::<http2::Pusher>()
+                .expect("pusher extension")
+                .clone();
+            let push_req = Request::get("/style.css").body(()).unwrap();
+            let pushed = pusher.push(push_req, Response::new(push_body())).await;
+            let body = match pushed {
+                Ok(()) => "pushed".to_string(),
+                Err(e) => format!("{}", e),
+            };
+            Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(body))))
+        }
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http2::Builder::new(TokioExecutor)
+        .serve_connection(socket, svc)
+        .await
+        .unwrap();
+}
+
+async fn recv_h2_body(mut body: RecvStream) -> Bytes {
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.unwrap();
+        let _ = body.flow_control().release_capacity(chunk.len());
+        buf.extend_from_slice(&chunk);
+    }
+    buf.into()
+}
+
+#[tokio::test]
+async fn h2_push_promise() {
+    let (listener, addr) = setup_tcp_listener();
+    tokio::spawn(serve_h2_push(listener, || {
+        Full::new(Bytes::from_static(b"body {}"))
+    }));
+
+    let conn = connect_async(addr).await;
+    // pushed streams count against the concurrency limit of the client
+    let (mut h2, connection) = h2::client::Builder::new()
+        .max_concurrent_streams(10)
+        .handshake::<_, Bytes>(conn)
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+
+    let req = Request::get("http://localhost/").body(()).unwrap();
+    let (mut res, _) = h2.send_request(req, true).unwrap();
+    let mut promises = res.push_promises();
+    let promise = promises.push_promise().await.unwrap().unwrap();
+    let (pushed_req, pushed_res) = promise.into_parts();
+    assert_eq!(pushed_req.method(), Method::GET);
+    assert_eq!(pushed_req.uri(), "http://localhost/style.css");
+
+    let pushed_res = pushed_res.await.unwrap();
+    assert_eq!(pushed_res.status(), StatusCode::OK);
+    assert_eq!(pushed_res.headers()["content-length"], "7");
+    assert_eq!(recv_h2_body(pushed_res.into_body()).await, "body {}");
+
+    let res = res.await.unwrap();
+    assert_eq!(recv_h2_body(res.into_body()).await, "pushed");
+}
+
+#[tokio::test]
+async fn h2_push_disabled_by_client() {
+    let (listener, addr) = setup_tcp_listener();
+    tokio::spawn(serve_h2_push(listener, Empty::<Bytes>::new));
+
+    let conn = connect_async(addr).await;
+    let (mut h2, connection) = h2::client::Builder::new()
+        .enable_push(false)
+        .handshake::<_, Bytes>(conn)
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+
+    let req = Request::get("http://localhost/").body(()).unwrap();
+    let (res, _) = h2.send_request(req, true).unwrap();
+    let res = res.await.unwrap();
+    let body = recv_h2_body(res.into_body()).await;
+    assert!(body.starts_with(b"http2 error"), "{:?}", body);
+}
+
+#[tokio::test]
+async fn h2_push_refused() {
+    let (listener, addr) = setup_tcp_listener();
+    tokio::spawn(serve_h2_push(listener, || {
+        // never finishes, so the push is still sending when refused
+        StreamBody::new(futures_util::stream::pending::<
+            Result<hyper::body::Frame<Bytes>, hyper::Error>,
+        >())
+    }));
+
+    let conn = connect_async(addr).await;
+    let (mut h2, connection) = h2::client::Builder::new()
+        .max_concurrent_streams(10)
+        .handshake::<_, Bytes>(conn)
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+
+    let req = Request::get("http://localhost/").body(()).unwrap();
+    let (mut res, _) = h2.send_request(req, true).unwrap();
+    let mut promises = res.push_promises();
+    let promise = promises.push_promise().await.unwrap().unwrap();
+    // dropping the promise resets the pushed stream
+    drop(promise);
+
+    let res = res.await.unwrap();
+    let body = recv_h2_body(res.into_body()).await;
+    assert!(body.starts_with(b"error writing a body"), "{:?}", body);
+}
+
+#[tokio::test]
+async fn h2_push_after_stream_ended() {
+    let (listener, addr) = setup_tcp_listener();
+    let (pusher_tx, mut pusher_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let svc = service_fn(move |req: Request<IncomingBody>| {
+            let pusher = req.extensions().get::<http2::Pusher>().cloned();
+            pusher_tx.send(pusher.expect("pusher extension")).unwrap();
+            async { Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new())) }
+        });
+        http2::Builder::new(TokioExecutor)
+            .serve_connection(socket, svc)
+            .await
+            .unwrap();
+    });
+
+    let conn = connect_async(addr).await;
+    let (mut h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+
+    let req = Request::get("http://localhost/").body(()).unwrap();
+    let (res, _) = h2.send_request(req, true).unwrap();
+    let res = res.await.unwrap();
+    assert!(recv_h2_body(res.into_body()).await.is_empty());
+
+    let pusher = pusher_rx.recv().await.unwrap();
+    let push_req = Request::get("/style.css").body(()).unwrap();
+    let err = pusher
+        .push(push_req, Response::new(Empty::<Bytes>::new()))
+        .await
+        .unwrap_err();
+    assert!(err.is_canceled(), "{:?}", err);
+}
+
+#[tokio::test]
+async fn h2_informational_unsupported() {
+    let (listener, addr) = setup_tcp_listener();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let svc = service_fn(|req: Request<IncomingBody>| async move {
+            let res = Response::builder().status(103).body(()).unwrap();
+            let err = req
+                .extensions()
+                .get::<hyper::ext::InformationalSender>()
+                .expect("informational extension")
+                .send(res)
+                .unwrap_err();
+            assert!(err.is_user(), "{:?}", err);
+            assert_eq!(
+                err.to_string(),
+                "informational responses are not supported by the connection"
+            );
+            Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+        });
+        http2::Builder::new(TokioExecutor)
+            .serve_connection(socket, svc)
+            .await
+            .unwrap();
+    });
+
+    let conn = connect_async(addr).await;
+    let (mut h2, connection) = h2::client::handshake(conn).await.unwrap();
+    tokio::spawn(async move {
+        connection.await.unwrap();
+    });
+
+    let req = Request::get("http://localhost/").body(()).unwrap();
+    let (res, _) = h2.send_request(req, true).unwrap();
+    assert_eq!(res.await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn parse_errors_send_4xx_response() {
+    let (listener, addr) = setup_tcp_listener();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"GE T / HTTP/1.1\r\n\r\n").unwrap();
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).unwrap();
+
+        let expected = "HTTP/1.1 400 ";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("HTTP parse error");
+}
+
+#[tokio::test]
+async fn illegal_request_length_returns_400_response() {
+    let (listener, addr) = setup_tcp_listener();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"POST / HTTP/1.1\r\nContent-Length: foo\r\n\r\n")
+            .unwrap();
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).unwrap();
+
+        let expected = "HTTP/1.1 400 ";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("illegal Content-Length should error");
+}
+
+#[cfg(feature = "http1")]
+#[test]
+#[should_panic]
+fn max_buf_size_panic_too_small() {
+    const MAX: usize = 8191;
+    http1::Builder::new().max_buf_size(MAX);
+}
+
+#[cfg(feature = "http1")]
+#[test]
+fn max_buf_size_no_panic() {
+    const MAX: usize = 8193;
+    http1::Builder::new().max_buf_size(MAX);
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn max_buf_size() {
+    let (listener, addr) = setup_tcp_listener();
+
+    const MAX: usize = 16_000;
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(b"POST /").expect("write 1");
+        tcp.write_all(&[b'a'; MAX]).expect("write 2");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 431 ";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .max_buf_size(MAX)
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("should TooLarge error");
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn max_headers_exceeded() {
+    let (listener, addr) = setup_tcp_listener();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        let mut req = String::from("GET / HTTP/1.1\r\n");
+        for i in 0..11 {
+            req.push_str(&format!("x-header-{}: {}\r\n", i, i));
+        }
+        req.push_str("\r\n");
+        tcp.write_all(req.as_bytes()).expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 431 ";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .max_headers(10)
+        .serve_connection(socket, HelloWorld)
+        .await
+        .expect_err("should TooLarge error");
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn max_headers_above_default() {
+    let (listener, addr) = setup_tcp_listener();
+
+    thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        let mut req = String::from("GET / HTTP/1.1\r\nConnection: close\r\n");
+        for i in 0..150 {
+            req.push_str(&format!("x-header-{}: {}\r\n", i, i));
+        }
+        req.push_str("\r\n");
+        tcp.write_all(req.as_bytes()).expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 200 ";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .max_headers(200)
+        .serve_connection(socket, HelloWorld)
+        .await
+        .unwrap();
+}
+
+#[cfg(feature = "http1")]
+async fn serve_response_with_trailers(te_trailers: bool) -> Vec<u8> {
+    use hyper::body::Frame;
+
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        let te = if te_trailers { "TE: trailers\r\n" } else { "" };
+        tcp.write_all(
+            format!(
+                "GET / HTTP/1.1\r\nHost: example.domain\r\n{}Connection: close\r\n\r\n",
+                te
+            )
+            .as_bytes(),
+        )
+        .expect("write 1");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read 1");
+        buf
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(
+            socket,
+            service_fn(|_| {
+                let mut trailers = http::HeaderMap::new();
+                trailers.insert("chunky-trailer", HeaderValue::from_static("header data"));
+                trailers.insert("not-declared", HeaderValue::from_static("oops"));
+                let frames = vec![
+                    Ok::<_, hyper::Error>(Frame::data(Bytes::from_static(b"hello"))),
+                    Ok(Frame::trailers(trailers)),
+                ];
+                let body = StreamBody::new(futures_util::stream::iter(frames));
+                let res = Response::builder()
+                    .header("trailer", "chunky-trailer")
+                    .body(body)
+                    .expect("response");
+                future::ok::<_, hyper::Error>(res)
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread")
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn http1_response_trailers() {
+    let buf = serve_response_with_trailers(true).await;
+
+    let expected = b"5\r\nhello\r\n0\r\nchunky-trailer: header data\r\n\r\n";
+    assert!(
+        buf.ends_with(expected),
+        "response should end with trailers: {:?}",
+        s(&buf)
+    );
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn http1_response_trailers_not_accepted() {
+    let buf = serve_response_with_trailers(false).await;
+
+    let expected = b"5\r\nhello\r\n0\r\n\r\n";
+    assert!(
+        buf.ends_with(expected),
+        "response should not include trailers: {:?}",
+        s(&buf)
+    );
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn http1_request_trailers() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Trailer: chunky-trailer\r\n\
+            Connection: close\r\n\
+            \r\n\
+            5\r\n\
+            hello\r\n\
+            0\r\n\
+            chunky-trailer: header data\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = [0; 256];
+        tcp.read(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 200 ";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(
+            socket,
+            service_fn(|req: Request<IncomingBody>| async move {
+                let collected = req.into_body().collect().await?;
+                assert_eq!(
+                    collected.trailers().expect("trailers")["chunky-trailer"],
+                    "header data"
+                );
+                assert_eq!(collected.to_bytes(), "hello");
+                Ok::<_, hyper::Error>(Response::new(Empty::<Bytes>::new()))
+            }),
+        )
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[cfg(feature = "http1")]
+#[tokio::test]
+async fn service_fns_router() {
+    use hyper::service::{Params, ServiceFns};
+
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET /users/42 HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+            PUT /users/42 HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+            GET /nope HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read 1");
+        let res = s(&buf);
+
+        let first = res.find("HTTP/1.1 200 OK\r\n").expect("200 response");
+        let second = res.find("HTTP/1.1 405 ").expect("405 response");
+        let third = res.find("HTTP/1.1 404 ").expect("404 response");
+        assert!(
+            first < second && second < third,
+            "responses in order: {:?}",
+            res
+        );
+        assert!(res.contains("user 42"), "routed body: {:?}", res);
+        assert!(res.contains("allow: GET\r\n"), "allow header: {:?}", res);
+    });
+
+    let mut router = ServiceFns::new();
+    router.add(
+        Method::GET,
+        "/users/{id}",
+        |req: Request<IncomingBody>| async move {
+            let params = req.extensions().get::<Params>().expect("params");
+            let body = format!("user {}", params.get("id").expect("id"));
+            Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from(body))))
+        },
+    );
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection(socket, router)
+        .await
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
+}
+
+#[cfg(all(feature = "http1", feature = "http2"))]
+#[tokio::test]
+async fn auto_serves_http1() {
+    use hyper::server::conn::auto;
+
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut tcp = connect(&addr);
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read 1");
+
+        let expected = "HTTP/1.1 200 OK\r\n";
+        assert_eq!(s(&buf[..expected.len()]), expected);
+        assert!(buf.ends_with(HELLO.as_bytes()));
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    auto::Builder::new(TokioExecutor)
+        .serve_connection(socket, HelloWorld)
         .await
-        .unwrap();
+        .expect("serve_connection");
+
+    child.join().expect("client thread");
 }
 
+#[cfg(all(feature = "http1", feature = "http2"))]
 #[tokio::test]
-async fn h2_connect_empty_frames() {
+async fn auto_serves_http2_prior_knowledge() {
+    use hyper::server::conn::auto;
+
     let (listener, addr) = setup_tcp_listener();
-    let conn = connect_async(addr).await;
 
-    let (h2, connection) = h2::client::handshake(conn).await.unwrap();
     tokio::spawn(async move {
-        connection.await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+        let socket = TokioIo::new(socket);
+        let mut builder = auto::Builder::new(TokioExecutor);
+        builder.http2().max_concurrent_streams(10);
+        builder
+            .serve_connection(socket, HelloWorld)
+            .await
+            .expect("serve_connection");
     });
-    let mut h2 = h2.ready().await.unwrap();
 
-    async fn connect_and_recv_bread(
-        h2: &mut SendRequest<Bytes>,
-    ) -> (RecvStream, SendStream<Bytes>) {
-        let request = Request::connect("localhost").body(()).unwrap();
-        let (response, send_stream) = h2.send_request(request, false).unwrap();
-        let response = response.await.unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+    let tcp = connect_async(addr).await;
+    let (mut client, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
+        .handshake(TokioIo::new(tcp))
+        .await
+        .expect("http handshake");
 
-        let mut body = response.into_body();
-        let bytes = body.data().await.unwrap().unwrap();
-        assert_eq!(&bytes[..], b"Bread?");
-        let _ = body.flow_control().release_capacity(bytes.len());
+    tokio::spawn(async move {
+        conn.await.expect("client conn");
+    });
 
-        (body, send_stream)
-    }
+    let res = client
+        .send_request(Request::new(Empty::<Bytes>::new()))
+        .await
+        .expect("client.send_request");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.version(), Version::HTTP_2);
 
-    tokio::spawn(async move {
-        let (mut recv_stream, mut send_stream) = connect_and_recv_bread(&mut h2).await;
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, HELLO);
+}
 
-        send_stream.send_data("".into(), false).unwrap();
-        send_stream.send_data("".into(), false).unwrap();
-        send_stream.send_data("".into(), false).unwrap();
-        send_stream.send_data("Baguette!".into(), false).unwrap();
-        send_stream.send_data("".into(), true).unwrap();
+#[cfg(all(feature = "http1", feature = "http2"))]
+#[tokio::test]
+async fn auto_graceful_shutdown_before_version_detected() {
+    use hyper::server::conn::auto;
 
-        assert!(recv_stream.data().await.unwrap().unwrap().is_empty());
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
+        let mut req = connect(&addr);
+        let mut buf = vec![];
+        req.read_to_end(&mut buf).unwrap();
+        assert!(buf.is_empty(), "{:?}", s(&buf));
     });
 
-    let svc = service_fn(move |req: Request<IncomingBody>| {
-        let on_upgrade = hyper::upgrade::on(req);
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    let mut conn = auto::Builder::new(TokioExecutor).serve_connection(socket, HelloWorld);
+    Pin::new(&mut conn).graceful_shutdown();
+    // nothing was received, so the connection closes without waiting for
+    // the client
+    conn.await.expect("serve_connection");
 
-        tokio::spawn(async move {
-            let mut upgraded = on_upgrade.await.expect("on_upgrade");
-            upgraded.write_all(b"Bread?").await.unwrap();
+    child.join().unwrap();
+}
 
-            let mut vec = vec![];
-            upgraded.read_to_end(&mut vec).await.unwrap();
-            assert_eq!(s(&vec), "Baguette!");
+#[cfg(all(feature = "http1", feature = "http2"))]
+#[tokio::test]
+async fn auto_graceful_shutdown_http1_mid_request() {
+    use hyper::server::conn::auto;
 
-            upgraded.shutdown().await.unwrap();
-        });
+    let (listener, addr) = setup_tcp_listener();
+    let (tx1, rx1) = oneshot::channel();
+    let (tx2, rx2) = mpsc::channel();
 
-        future::ok::<_, hyper::Error>(
-            Response::builder()
-                .status(200)
-                .body(Empty::<Bytes>::new())
-                .unwrap(),
-        )
+    let child = thread::spawn(move || {
+        let mut req = connect(&addr);
+        req.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        tx1.send(()).unwrap();
+        rx2.recv().unwrap();
+        req.write_all(b"Host: localhost\r\n\r\n").unwrap();
+        let mut buf = vec![];
+        req.read_to_end(&mut buf).unwrap();
+        assert!(buf.ends_with(HELLO.as_bytes()), "{:?}", s(&buf));
     });
 
     let (socket, _) = listener.accept().await.unwrap();
-    http2::Builder::new(TokioExecutor)
-        .serve_connection(socket, svc)
-        //.with_upgrades()
+    let socket = TokioIo::new(socket);
+    let mut conn = auto::Builder::new(TokioExecutor).serve_connection(socket, HelloWorld);
+    rx1.await.unwrap();
+    // let the connection read the start of the request
+    tokio::time::timeout(Duration::from_millis(100), &mut conn)
         .await
-        .unwrap();
+        .expect_err("request is incomplete");
+    Pin::new(&mut conn).graceful_shutdown();
+    tx2.send(()).unwrap();
+    conn.await.unwrap();
+
+    child.join().unwrap();
 }
 
+#[cfg(all(feature = "http1", feature = "http2"))]
 #[tokio::test]
-async fn parse_errors_send_4xx_response() {
+async fn auto_h2c_upgrade() {
+    use hyper::server::conn::auto;
+
     let (listener, addr) = setup_tcp_listener();
+    let child = thread::spawn(move || h2c_upgrade_client(&addr));
 
-    thread::spawn(move || {
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    auto::Builder::new(TokioExecutor)
+        .h2c_upgrade(true)
+        .serve_connection(socket, service_fn(h2c_echo_version))
+        .await
+        .expect("serve_connection");
+
+    let (upgraded, next) = child.join().expect("client thread");
+    assert_eq!(s(&upgraded), "HTTP/2.0 http://example.domain/upgrade?q=1");
+    assert_eq!(s(&next), "HTTP/2.0 http://example.domain/next");
+}
+
+#[cfg(feature = "http2")]
+#[tokio::test]
+async fn http1_h2c_upgrade() {
+    let (listener, addr) = setup_tcp_listener();
+    let child = thread::spawn(move || h2c_upgrade_client(&addr));
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
+    http1::Builder::new()
+        .serve_connection_with_h2c(
+            socket,
+            service_fn(h2c_echo_version),
+            http2::Builder::new(TokioExecutor),
+        )
+        .await
+        .expect("serve_connection_with_h2c");
+
+    let (upgraded, next) = child.join().expect("client thread");
+    assert_eq!(s(&upgraded), "HTTP/2.0 http://example.domain/upgrade?q=1");
+    assert_eq!(s(&next), "HTTP/2.0 http://example.domain/next");
+}
+
+#[cfg(feature = "http2")]
+#[tokio::test]
+async fn http1_h2c_without_upgrade() {
+    let (listener, addr) = setup_tcp_listener();
+
+    let child = thread::spawn(move || {
         let mut tcp = connect(&addr);
-        tcp.write_all(b"GE T / HTTP/1.1\r\n\r\n").unwrap();
-        let mut buf = [0; 256];
-        tcp.read(&mut buf).unwrap();
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read 1");
 
-        let expected = "HTTP/1.1 400 ";
+        let expected = "HTTP/1.1 200 OK\r\n";
         assert_eq!(s(&buf[..expected.len()]), expected);
+        assert!(buf.ends_with(b"HTTP/1.1 http://example.domain/"));
     });
 
     let (socket, _) = listener.accept().await.unwrap();
+    let socket = TokioIo::new(socket);
     http1::Builder::new()
-        .serve_connection(socket, HelloWorld)
+        .serve_connection_with_h2c(
+            socket,
+            service_fn(h2c_echo_version),
+            http2::Builder::new(TokioExecutor),
+        )
         .await
-        .expect_err("HTTP parse error");
+        .expect("serve_connection_with_h2c");
+
+    child.join().expect("client thread");
 }
 
+#[cfg(all(feature = "http1", feature = "http2"))]
 #[tokio::test]
-async fn illegal_request_length_returns_400_response() {
+async fn auto_h2c_upgrade_disabled() {
+    use hyper::server::conn::auto;
+
     let (listener, addr) = setup_tcp_listener();
 
-    thread::spawn(move || {
+    let child = thread::spawn(move || {
         let mut tcp = connect(&addr);
-        tcp.write_all(b"POST / HTTP/1.1\r\nContent-Length: foo\r\n\r\n")
-            .unwrap();
-        let mut buf = [0; 256];
-        tcp.read(&mut buf).unwrap();
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: Upgrade, HTTP2-Settings, close\r\n\
+            Upgrade: h2c\r\n\
+            HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA\r\n\
+            \r\n\
+        ",
+        )
+        .expect("write 1");
+        let mut buf = Vec::new();
+        tcp.read_to_end(&mut buf).expect("read 1");
 
-        let expected = "HTTP/1.1 400 ";
+        let expected = "HTTP/1.1 200 OK\r\n";
         assert_eq!(s(&buf[..expected.len()]), expected);
+        assert!(buf.ends_with(HELLO.as_bytes()));
     });
 
     let (socket, _) = listener.accept().await.unwrap();
-    http1::Builder::new()
+    let socket = TokioIo::new(socket);
+    auto::Builder::new(TokioExecutor)
         .serve_connection(socket, HelloWorld)
         .await
-        .expect_err("illegal Content-Length should error");
-}
-
-#[cfg(feature = "http1")]
-#[test]
-#[should_panic]
-fn max_buf_size_panic_too_small() {
-    const MAX: usize = 8191;
-    http1::Builder::new().max_buf_size(MAX);
-}
+        .expect("serve_connection");
 
-#[cfg(feature = "http1")]
-#[test]
-fn max_buf_size_no_panic() {
-    const MAX: usize = 8193;
-    http1::Builder::new().max_buf_size(MAX);
+    child.join().expect("client thread");
 }
 
-#[cfg(feature = "http1")]
+#[cfg(all(feature = "http1", feature = "http2"))]
 #[tokio::test]
-async fn max_buf_size() {
-    let (listener, addr) = setup_tcp_listener();
+async fn auto_upgrades_other_than_h2c() {
+    use hyper::server::conn::auto;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-    const MAX: usize = 16_000;
+    let (listener, addr) = setup_tcp_listener();
 
-    thread::spawn(move || {
+    let child = thread::spawn(move || {
         let mut tcp = connect(&addr);
-        tcp.write_all(b"POST /").expect("write 1");
-        tcp.write_all(&[b'a'; MAX]).expect("write 2");
+        tcp.write_all(
+            b"\
+            GET / HTTP/1.1\r\n\
+            Upgrade: foobar\r\n\
+            Connection: upgrade\r\n\
+            \r\n\
+            eagerly optimistic\
+        ",
+        )
+        .expect("write 1");
         let mut buf = [0; 256];
-        tcp.read(&mut buf).expect("read 1");
+        let n = tcp.read(&mut buf).expect("read 1");
+        assert!(s(&buf[..n]).starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
 
-        let expected = "HTTP/1.1 431 ";
-        assert_eq!(s(&buf[..expected.len()]), expected);
+        let n = tcp.read(&mut buf).expect("read 2");
+        assert_eq!(s(&buf[..n]), "foo=bar");
+        tcp.write_all(b"bar=foo").expect("write 2");
+    });
+
+    let (upgrades_tx, upgrades_rx) = mpsc::channel();
+    let svc = service_fn(move |req: Request<IncomingBody>| {
+        let _ = upgrades_tx.send(hyper::upgrade::on(req));
+        future::ok::<_, hyper::Error>(
+            Response::builder()
+                .status(101)
+                .header("upgrade", "foobar")
+                .body(Empty::<Bytes>::new())
+                .unwrap(),
+        )
     });
 
     let (socket, _) = listener.accept().await.unwrap();
-    http1::Builder::new()
-        .max_buf_size(MAX)
-        .serve_connection(socket, HelloWorld)
+    let socket = TokioIo::new(socket);
+    auto::Builder::new(TokioExecutor)
+        .h2c_upgrade(true)
+        .serve_connection(socket, svc)
+        .with_upgrades()
         .await
-        .expect_err("should TooLarge error");
+        .expect("serve_connection");
+
+    let upgraded = upgrades_rx.recv().unwrap().await.expect("on_upgrade");
+    let mut io = TokioIo::new(upgraded);
+    let mut buf = [0; 18];
+    io.read_exact(&mut buf).await.unwrap();
+    assert_eq!(s(&buf), "eagerly optimistic");
+    io.write_all(b"foo=bar").await.unwrap();
+    let mut vec = vec![];
+    io.read_to_end(&mut vec).await.unwrap();
+    assert_eq!(s(&vec), "bar=foo");
+
+    child.join().expect("client thread");
 }
 
 #[test]
@@ -2359,6 +4010,7 @@ async fn http2_keep_alive_detects_unresponsive_client() {
     });
 
     let (socket, _) = listener.accept().await.expect("accept");
+    let socket = TokioIo::new(socket);
 
     let err = http2::Builder::new(TokioExecutor)
         .timer(TokioTimer)
@@ -2377,6 +4029,7 @@ async fn http2_keep_alive_with_responsive_client() {
 
     tokio::spawn(async move {
         let (socket, _) = listener.accept().await.expect("accept");
+        let socket = TokioIo::new(socket);
 
         http2::Builder::new(TokioExecutor)
             .timer(TokioTimer)
@@ -2389,7 +4042,7 @@ async fn http2_keep_alive_with_responsive_client() {
 
     let tcp = connect_async(addr).await;
     let (mut client, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
-        .handshake(tcp)
+        .handshake(TokioIo::new(tcp))
         .await
         .expect("http handshake");
 
@@ -2440,6 +4093,7 @@ async fn http2_keep_alive_count_server_pings() {
 
     tokio::spawn(async move {
         let (socket, _) = listener.accept().await.expect("accept");
+        let socket = TokioIo::new(socket);
 
         http2::Builder::new(TokioExecutor)
             .timer(TokioTimer)
@@ -2823,6 +4477,7 @@ impl ServeOptions {
                         tokio::select! {
                             res = listener.accept() => {
                                 let (stream, _) = res.unwrap();
+                                let stream = TokioIo::new(stream);
 
                                 tokio::task::spawn(async move {
                                     let msg_tx = msg_tx.clone();
@@ -2864,6 +4519,119 @@ impl ServeOptions {
     }
 }
 
+#[cfg(feature = "http2")]
+async fn h2c_echo_version(
+    req: Request<IncomingBody>,
+) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    assert!(req.headers().get("upgrade").is_none());
+    assert!(req.headers().get("http2-settings").is_none());
+    let uri = match req.uri().authority() {
+        Some(_) => req.uri().to_string(),
+        None => format!(
+            "http://{}{}",
+            req.headers()["host"].to_str().unwrap(),
+            req.uri()
+        ),
+    };
+    let body = format!("{:?} {}", req.version(), uri);
+    Ok(Response::new(Full::new(Bytes::from(body))))
+}
+
+// Upgrades a connection to h2c with a request for `/upgrade?q=1`, then
+// sends a request for `/next` on stream 3, and returns both response bodies.
+//
+// There is no h2c client, so the frames are written and parsed by hand.
+#[cfg(feature = "http2")]
+fn h2c_upgrade_client(addr: &SocketAddr) -> (Vec<u8>, Vec<u8>) {
+    const DATA: u8 = 0x0;
+    const HEADERS: u8 = 0x1;
+    const SETTINGS: u8 = 0x4;
+    const END_STREAM: u8 = 0x1;
+    const END_HEADERS: u8 = 0x4;
+
+    fn write_frame(tcp: &mut TcpStream, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+        let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        frame.extend_from_slice(&[kind, flags]);
+        frame.extend_from_slice(&stream_id.to_be_bytes());
+        frame.extend_from_slice(payload);
+        tcp.write_all(&frame).expect("write frame");
+    }
+
+    fn read_frame(tcp: &mut TcpStream) -> (u8, u8, u32, Vec<u8>) {
+        let mut head = [0; 9];
+        tcp.read_exact(&mut head).expect("read frame head");
+        let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+        let stream_id = u32::from_be_bytes([head[5], head[6], head[7], head[8]]);
+        let mut payload = vec![0; len];
+        tcp.read_exact(&mut payload).expect("read frame payload");
+        (head[3], head[4], stream_id, payload)
+    }
+
+    fn read_response(tcp: &mut TcpStream, stream_id: u32) -> Vec<u8> {
+        let mut body = Vec::new();
+        loop {
+            let (kind, flags, id, payload) = read_frame(tcp);
+            if id != stream_id {
+                continue;
+            }
+            match kind {
+                // `:status: 200` is entry 8 of the HPACK static table
+                HEADERS => assert_eq!(payload[0], 0x88, "status 200"),
+                DATA => body.extend_from_slice(&payload),
+                _ => {}
+            }
+            if kind != SETTINGS && flags & END_STREAM != 0 {
+                return body;
+            }
+        }
+    }
+
+    let mut tcp = connect(addr);
+    tcp.write_all(
+        b"\
+        GET /upgrade?q=1 HTTP/1.1\r\n\
+        Host: example.domain\r\n\
+        Connection: Upgrade, HTTP2-Settings\r\n\
+        Upgrade: h2c\r\n\
+        HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA\r\n\
+        \r\n\
+    ",
+    )
+    .expect("write upgrade request");
+
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        tcp.read_exact(&mut byte).expect("read 101 response");
+        head.push(byte[0]);
+    }
+    let head = s(&head).to_ascii_lowercase();
+    assert!(
+        head.starts_with("http/1.1 101 switching protocols\r\n"),
+        "{:?}",
+        head
+    );
+    assert!(has_header(&head, "upgrade: h2c"), "{:?}", head);
+
+    tcp.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+        .expect("write preface");
+    write_frame(&mut tcp, SETTINGS, 0, 0, &[]);
+    let upgraded = read_response(&mut tcp, 1);
+
+    // Literal header fields without indexing, with an indexed name, so no
+    // HPACK state is shared with the server.
+    let mut block = vec![0x82, 0x86]; // :method GET, :scheme http
+    for &(index, value) in &[(1u8, "example.domain"), (4, "/next")] {
+        block.push(index); // :authority, :path
+        block.push(value.len() as u8);
+        block.extend_from_slice(value.as_bytes());
+    }
+    write_frame(&mut tcp, HEADERS, END_STREAM | END_HEADERS, 3, &block);
+    let next = read_response(&mut tcp, 3);
+
+    (upgraded, next)
+}
+
 fn s(buf: &[u8]) -> &str {
     std::str::from_utf8(buf).unwrap()
 }
@@ -2928,7 +4696,7 @@ impl<T: Write, D> Write for DebugStream<T, D> {
     }
 }
 
-impl<T: AsyncWrite + Unpin, D> AsyncWrite for DebugStream<T, D> {
+impl<T: hyper::rt::Write + Unpin, D> hyper::rt::Write for DebugStream<T, D> {
     fn poll_write(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -2949,11 +4717,11 @@ impl<T: AsyncWrite + Unpin, D> AsyncWrite for DebugStream<T, D> {
     }
 }
 
-impl<T: AsyncRead + Unpin, D: Unpin> AsyncRead for DebugStream<T, D> {
+impl<T: hyper::rt::Read + Unpin, D: Unpin> hyper::rt::Read for DebugStream<T, D> {
     fn poll_read(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
+        buf: hyper::rt::ReadBufCursor<'_>,
     ) -> Poll<io::Result<()>> {
         Pin::new(&mut self.stream).poll_read(cx, buf)
     }
@@ -3013,6 +4781,7 @@ impl TestClient {
         let stream = TkTcpStream::connect(format!("{}:{}", host, port))
             .await
             .unwrap();
+        let stream = TokioIo::new(stream);
 
         if self.http2_only {
             let (mut sender, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
@@ -8,6 +8,7 @@ use std::sync::{
 
 use bytes::Bytes;
 use http_body_util::{BodyExt, Full};
+use hyper::rt::TokioIo;
 use hyper::server;
 use tokio::net::{TcpListener, TcpStream};
 
@@ -20,8 +21,7 @@ pub use futures_util::{
 pub use hyper::{HeaderMap, StatusCode};
 pub use std::net::SocketAddr;
 
-mod tokiort;
-pub use tokiort::{TokioExecutor, TokioTimer};
+pub use hyper::rt::{TokioExecutor, TokioTimer};
 
 #[allow(unused_macros)]
 macro_rules! t {
@@ -357,6 +357,7 @@ async fn async_test(cfg: __TestConfig) {
 
         loop {
             let (stream, _) = listener.accept().await.expect("server error");
+            let stream = TokioIo::new(stream);
 
             // Move a clone into the service_fn
             let serve_handles = serve_handles.clone();
@@ -424,7 +425,7 @@ async fn async_test(cfg: __TestConfig) {
         let cbody = cres.body;
 
         async move {
-            let stream = TcpStream::connect(addr).await.unwrap();
+            let stream = TokioIo::new(TcpStream::connect(addr).await.unwrap());
 
             let res = if http2_only {
                 let (mut sender, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor)
@@ -508,6 +509,7 @@ async fn naive_proxy(cfg: ProxyConfig) -> (SocketAddr, impl Future<Output = ()>)
 
             loop {
                 let (stream, _) = listener.accept().await.unwrap();
+                let stream = TokioIo::new(stream);
 
                 let service = service_fn(move |mut req| {
                     async move {
@@ -523,6 +525,7 @@ async fn naive_proxy(cfg: ProxyConfig) -> (SocketAddr, impl Future<Output = ()>)
                         let stream = TcpStream::connect(format!("{}:{}", uri, port))
                             .await
                             .unwrap();
+                        let stream = TokioIo::new(stream);
 
                         let resp = if http2_only {
                             let (mut sender, conn) =